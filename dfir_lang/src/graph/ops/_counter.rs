@@ -7,6 +7,7 @@ use super::{
 /// > Arguments: A `tag` string and a `Duration` for how long to wait between printing.
 ///
 /// Counts the number of items passing through and prints to stdout whenever the stream trigger activates.
+/// The waits follow the clock of the running instance, so they can be simulated.
 ///
 /// ```dfir
 /// source_stream(dfir_rs::util::iter_batches_stream(0..=100_000, 1))
@@ -76,7 +77,7 @@ pub const _COUNTER: OperatorConstraints = OperatorConstraints {
             #df_ident.request_task(async move {
                 loop {
                     println!("_counter({}): {}", #tag_ident, #read_ident.get());
-                    #root::tokio::time::sleep(#duration_ident).await;
+                    #root::scheduled::clock::sleep(#duration_ident).await;
                 }
             });
         };
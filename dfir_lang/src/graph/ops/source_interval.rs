@@ -13,6 +13,10 @@ use super::{
 /// Emits units `()` on a repeated interval. The first tick completes immediately. Missed ticks will
 /// be scheduled as soon as possible.
 ///
+/// Time is read from the instance's [`Clock`](https://hydro.run/rustdoc/dfir_rs/scheduled/clock/trait.Clock),
+/// which is the system clock unless replaced with `Dfir::set_clock`, e.g. with a `VirtualClock`
+/// to advance time instantly in tests.
+///
 /// Note that this requires the dfir instance be run within a [Tokio `Runtime`](https://docs.rs/tokio/1/tokio/runtime/struct.Runtime.html).
 /// The easiest way to do this is with a [`#[dfir_rs::main]`](https://hydro.run/rustdoc/dfir_rs/attr.main)
 /// annotation on `async fn main() { ... }` as in the example below.
@@ -69,7 +73,7 @@ pub const SOURCE_INTERVAL: OperatorConstraints = OperatorConstraints {
         let mut write_prologue = quote_spanned! {op_span=>
             let #ident_intervalstream =
                 #root::tokio_stream::StreamExt::map(
-                    #root::tokio_stream::wrappers::IntervalStream::new(#root::tokio::time::interval(#arguments)),
+                    #root::scheduled::clock::interval(#arguments),
                     |_| {  }
                 );
         };
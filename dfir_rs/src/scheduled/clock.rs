@@ -0,0 +1,358 @@
+//! Pluggable time sources for timer-based operators.
+//!
+//! Operators such as `source_interval` read time through a [`Clock`] instead of calling into
+//! Tokio directly. By default every [`Dfir`](super::graph::Dfir) instance uses the
+//! [`SystemClock`], which is backed by the Tokio timer. Tests and simulations can install a
+//! [`VirtualClock`] with [`Dfir::set_clock`](super::graph::Dfir::set_clock) instead, which
+//! only moves forward when explicitly advanced, so timers fire instantly and deterministically.
+//!
+//! The clock of the running instance is available from [`Context::clock`](super::context::Context::clock),
+//! and through the free functions [`now`], [`sleep`], [`interval`] and [`interval_delayed`]
+//! which are used by generated code.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::Stream;
+use tokio::time::Instant;
+use web_time::SystemTime;
+
+/// A source of time, along with the ability to wait for a point in time.
+pub trait Clock {
+    /// Returns the current monotonic time.
+    fn now(&self) -> Instant;
+
+    /// Returns the current wall-clock time.
+    fn system_time(&self) -> SystemTime;
+
+    /// Returns a future which completes once [`Self::now`] reaches `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()>>>;
+}
+
+/// The default [`Clock`], which reads the real time and sleeps using the Tokio timer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(tokio::time::sleep_until(deadline))
+    }
+}
+
+/// A [`Clock`] which only moves forward when [advanced](Self::advance).
+///
+/// Clones share the same time, so a single virtual clock can drive many `Dfir` instances.
+/// The wall-clock time starts at the [`UNIX_EPOCH`](std::time::UNIX_EPOCH), so that runs are
+/// reproducible.
+#[derive(Clone)]
+pub struct VirtualClock {
+    state: Rc<RefCell<VirtualClockState>>,
+}
+
+struct VirtualClockState {
+    origin: Instant,
+    elapsed: Duration,
+    next_timer_id: u64,
+    /// Pending timers, ordered by deadline and then by registration order.
+    timers: BTreeMap<(Instant, u64), Option<Waker>>,
+}
+
+impl VirtualClock {
+    /// Creates a new virtual clock, starting at zero elapsed time.
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(VirtualClockState {
+                origin: Instant::now(),
+                elapsed: Duration::ZERO,
+                next_timer_id: 0,
+                timers: BTreeMap::new(),
+            })),
+        }
+    }
+
+    /// Returns the time elapsed since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        self.state.borrow().elapsed
+    }
+
+    /// Returns the deadline of the earliest pending timer, if any.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let state = self.state.borrow();
+        state.timers.keys().next().map(|&(deadline, _)| deadline)
+    }
+
+    /// Moves time forward by `duration`, waking all timers that are now due.
+    pub fn advance(&self, duration: Duration) {
+        let wakers = {
+            let mut state = self.state.borrow_mut();
+            state.elapsed += duration;
+            let now = state.origin + state.elapsed;
+
+            let still_pending = state.timers.split_off(&(now, u64::MAX));
+            std::mem::replace(&mut state.timers, still_pending)
+        };
+
+        // Wake outside of the borrow, in case a waker polls the timer inline.
+        for waker in wakers.into_values().flatten() {
+            waker.wake();
+        }
+    }
+
+    /// Moves time forward to the deadline of the earliest pending timer (if it is in the
+    /// future) and wakes it, along with any other timers that are due.
+    ///
+    /// Returns `false` if there are no pending timers.
+    pub fn advance_to_next_deadline(&self) -> bool {
+        let Some(deadline) = self.next_deadline() else {
+            return false;
+        };
+        self.advance(deadline.saturating_duration_since(self.now()));
+        true
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        let state = self.state.borrow();
+        state.origin + state.elapsed
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + self.elapsed()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(VirtualSleep {
+            clock: self.clone(),
+            deadline,
+            timer_id: None,
+        })
+    }
+}
+
+/// Future returned by [`VirtualClock::sleep_until`].
+struct VirtualSleep {
+    clock: VirtualClock,
+    deadline: Instant,
+    /// Set once the timer has been registered with the clock.
+    timer_id: Option<u64>,
+}
+
+impl Future for VirtualSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.clock.now() >= self.deadline {
+            self.deregister();
+            return Poll::Ready(());
+        }
+
+        let this = &mut *self;
+        let mut state = this.clock.state.borrow_mut();
+        let timer_id = *this.timer_id.get_or_insert_with(|| {
+            state.next_timer_id += 1;
+            state.next_timer_id
+        });
+        state
+            .timers
+            .insert((this.deadline, timer_id), Some(cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl VirtualSleep {
+    fn deregister(&mut self) {
+        if let Some(timer_id) = self.timer_id.take() {
+            self.clock
+                .state
+                .borrow_mut()
+                .timers
+                .remove(&(self.deadline, timer_id));
+        }
+    }
+}
+
+impl Drop for VirtualSleep {
+    fn drop(&mut self) {
+        self.deregister();
+    }
+}
+
+thread_local! {
+    /// The clock of the `Dfir` instance currently running on this thread.
+    static CURRENT_CLOCK: RefCell<Option<Rc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Returns the clock of the `Dfir` instance currently running on this thread, or the
+/// [`SystemClock`] if there is none.
+pub fn current() -> Rc<dyn Clock> {
+    CURRENT_CLOCK
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(|| Rc::new(SystemClock))
+}
+
+/// Returns the current time according to [`current`].
+pub fn now() -> Instant {
+    CURRENT_CLOCK.with(|current| match &*current.borrow() {
+        Some(clock) => clock.now(),
+        None => Instant::now(),
+    })
+}
+
+/// Returns a future which completes once `duration` has passed according to [`current`].
+pub fn sleep(duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+    let clock = current();
+    clock.sleep_until(clock.now() + duration)
+}
+
+/// Wraps `task` so that `clock` is the [`current`] clock whenever the task is polled.
+pub(crate) fn bind(
+    clock: Rc<dyn Clock>,
+    mut task: Pin<Box<dyn Future<Output = ()>>>,
+) -> Pin<Box<dyn Future<Output = ()>>> {
+    Box::pin(std::future::poll_fn(move |cx| {
+        let _clock = enter(clock.clone());
+        task.as_mut().poll(cx)
+    }))
+}
+
+/// Installs `clock` as the [`current`] clock until the returned guard is dropped.
+pub(crate) fn enter(clock: Rc<dyn Clock>) -> CurrentClockGuard {
+    let prev = CURRENT_CLOCK.with(|current| current.borrow_mut().replace(clock));
+    CurrentClockGuard { prev }
+}
+
+/// Restores the previous [`current`] clock when dropped.
+pub(crate) struct CurrentClockGuard {
+    prev: Option<Rc<dyn Clock>>,
+}
+
+impl Drop for CurrentClockGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT_CLOCK.with(|current| *current.borrow_mut() = prev);
+    }
+}
+
+/// Creates an [`Interval`] which yields every `period`, starting immediately.
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    interval_delayed(Duration::ZERO, period)
+}
+
+/// Creates an [`Interval`] which yields every `period`, starting after `delay`.
+///
+/// Panics if `period` is zero.
+pub fn interval_delayed(delay: Duration, period: Duration) -> Interval {
+    assert!(period > Duration::ZERO, "`period` must be non-zero.");
+    Interval {
+        delay,
+        period,
+        clock: None,
+        next: None,
+        sleep: None,
+    }
+}
+
+/// A stream which yields the scheduled [`Instant`] of each tick of a fixed-period timer.
+///
+/// The clock is picked up from the running `Dfir` instance the first time the stream is
+/// polled, so the interval can be created before a clock is installed. The first tick is
+/// scheduled relative to that first poll. Like Tokio's default
+/// [`MissedTickBehavior::Burst`](tokio::time::MissedTickBehavior::Burst), missed ticks are
+/// yielded as soon as possible.
+pub struct Interval {
+    delay: Duration,
+    period: Duration,
+    clock: Option<Rc<dyn Clock>>,
+    next: Option<Instant>,
+    sleep: Option<Pin<Box<dyn Future<Output = ()>>>>,
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let clock = this.clock.get_or_insert_with(current);
+        let deadline = *this.next.get_or_insert_with(|| clock.now() + this.delay);
+
+        if clock.now() < deadline {
+            let sleep = this
+                .sleep
+                .get_or_insert_with(|| clock.sleep_until(deadline));
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        this.sleep = None;
+        this.next = Some(deadline + this.period);
+        Poll::Ready(Some(deadline))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+    use futures::task::noop_waker_ref;
+
+    use super::*;
+
+    #[test]
+    fn virtual_interval_fires_on_advance() {
+        let clock = VirtualClock::new();
+        let _guard = enter(Rc::new(clock.clone()));
+        let start = clock.now();
+
+        let mut interval = interval_delayed(Duration::from_secs(1), Duration::from_secs(10));
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        assert_eq!(Poll::Pending, interval.poll_next_unpin(&mut cx));
+        assert_eq!(Some(start + Duration::from_secs(1)), clock.next_deadline());
+
+        assert!(clock.advance_to_next_deadline());
+        assert_eq!(
+            Poll::Ready(Some(start + Duration::from_secs(1))),
+            interval.poll_next_unpin(&mut cx)
+        );
+        assert_eq!(Poll::Pending, interval.poll_next_unpin(&mut cx));
+
+        // Missed ticks are delivered in a burst.
+        clock.advance(Duration::from_secs(25));
+        assert_eq!(
+            Poll::Ready(Some(start + Duration::from_secs(11))),
+            interval.poll_next_unpin(&mut cx)
+        );
+        assert_eq!(
+            Poll::Ready(Some(start + Duration::from_secs(21))),
+            interval.poll_next_unpin(&mut cx)
+        );
+        assert_eq!(Poll::Pending, interval.poll_next_unpin(&mut cx));
+
+        drop(interval);
+        assert_eq!(None, clock.next_deadline());
+        assert!(!clock.advance_to_next_deadline());
+        assert_eq!(Duration::from_secs(26), clock.elapsed());
+    }
+}
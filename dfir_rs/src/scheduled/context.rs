@@ -9,11 +9,13 @@ use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::DerefMut;
 use std::pin::Pin;
+use std::rc::Rc;
 
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use tokio::task::JoinHandle;
 use web_time::SystemTime;
 
+use super::clock::{self, Clock, SystemClock};
 use super::graph::StateLifespan;
 use super::state::StateHandle;
 use super::{LoopId, LoopTag, StateId, StateTag, SubgraphId, SubgraphTag};
@@ -63,6 +65,8 @@ pub struct Context {
     pub(super) current_stratum: usize,
 
     pub(super) current_tick_start: SystemTime,
+    /// Source of time for timer-based operators and [`Self::current_tick_start`].
+    pub(super) clock: Rc<dyn Clock>,
     pub(super) is_first_run_this_tick: bool,
     pub(super) loop_iter_count: usize,
 
@@ -96,6 +100,11 @@ impl Context {
         self.current_tick_start
     }
 
+    /// Gets the [`Clock`] used by this instance.
+    pub fn clock(&self) -> &Rc<dyn Clock> {
+        &self.clock
+    }
+
     /// Gets whether this is the first time this subgraph is being scheduled for this tick
     pub fn is_first_run_this_tick(&self) -> bool {
         self.is_first_run_this_tick
@@ -275,12 +284,23 @@ impl Context {
     }
 
     /// Launches all tasks requested with [`Self::request_task`] on the internal Tokio executor.
+    ///
+    /// Like operators, the tasks read time from the [`Clock`] of this instance.
     pub fn spawn_tasks(&mut self) {
-        for task in self.tasks_to_spawn.drain(..) {
+        for task in self.take_tasks() {
             self.task_join_handles.push(tokio::task::spawn_local(task));
         }
     }
 
+    /// Takes the tasks requested with [`Self::request_task`] that have not been launched yet,
+    /// so that the caller can poll them itself instead of [`Self::spawn_tasks`].
+    pub fn take_tasks(&mut self) -> Vec<Pin<Box<dyn Future<Output = ()> + 'static>>> {
+        self.tasks_to_spawn
+            .drain(..)
+            .map(|task| clock::bind(self.clock.clone(), task))
+            .collect()
+    }
+
     /// Aborts all tasks spawned with [`Self::spawn_tasks`].
     pub fn abort_tasks(&mut self) {
         for task in self.task_join_handles.drain(..) {
@@ -323,6 +343,7 @@ impl Default for Context {
             current_tick: TickInstant::default(),
 
             current_tick_start: SystemTime::now(),
+            clock: Rc::new(SystemClock),
             is_first_run_this_tick: false,
             loop_iter_count: 0,
 
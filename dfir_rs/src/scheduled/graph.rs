@@ -6,6 +6,8 @@ use std::cell::Cell;
 use std::cmp::Ordering;
 use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::rc::Rc;
 
 #[cfg(feature = "meta")]
 use dfir_lang::diagnostic::{Diagnostic, SerdeSpan};
@@ -13,8 +15,8 @@ use dfir_lang::diagnostic::{Diagnostic, SerdeSpan};
 use dfir_lang::graph::DfirGraph;
 use ref_cast::RefCast;
 use smallvec::SmallVec;
-use web_time::SystemTime;
 
+use super::clock::{self, Clock};
 use super::context::Context;
 use super::handoff::handoff_list::PortList;
 use super::handoff::{Handoff, HandoffMeta, TeeingHandoff};
@@ -197,6 +199,16 @@ impl<'a> Dfir<'a> {
         self.context.current_tick
     }
 
+    /// Replaces the [`Clock`] used by timer-based operators, such as `source_interval`, and
+    /// for [`Context::current_tick_start`]. Defaults to the
+    /// [`SystemClock`](clock::SystemClock).
+    ///
+    /// Must be called before the first tick is run, as timers pick up the clock when they are
+    /// first polled.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.context.clock = Rc::new(clock);
+    }
+
     /// Gets the current stratum nubmer.
     pub fn current_stratum(&self) -> usize {
         self.context.current_stratum
@@ -261,6 +273,9 @@ impl<'a> Dfir<'a> {
         // This drains the task buffer, so becomes a no-op after first call.
         self.context.spawn_tasks();
 
+        // Operators read time from the clock of the running instance.
+        let _clock = clock::enter(self.context.clock.clone());
+
         let mut work_done = false;
 
         'pop: while let Some(sg_id) =
@@ -448,7 +463,7 @@ impl<'a> Dfir<'a> {
             // Starting the tick, reset this to `false`.
             tracing::trace!("Starting tick, setting `can_start_tick = false`.");
             self.context.can_start_tick = false;
-            self.context.current_tick_start = SystemTime::now();
+            self.context.current_tick_start = self.context.clock.system_time();
 
             // Ensure external events are received before running the tick.
             if !self.context.events_received_tick {
@@ -989,6 +1004,11 @@ impl Dfir<'_> {
         self.context.request_task(future);
     }
 
+    /// Alias for [`Context::take_tasks`].
+    pub fn take_tasks(&mut self) -> Vec<Pin<Box<dyn Future<Output = ()> + 'static>>> {
+        self.context.take_tasks()
+    }
+
     /// Alias for [`Context::abort_tasks`].
     pub fn abort_tasks(&mut self) {
         self.context.abort_tasks()
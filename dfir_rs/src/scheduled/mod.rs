@@ -5,6 +5,7 @@
 
 use crate::util::slot_vec::Key;
 
+pub mod clock;
 pub mod context;
 pub mod graph;
 pub mod graph_ext;
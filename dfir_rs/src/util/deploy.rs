@@ -26,14 +26,27 @@ macro_rules! launch {
 pub use crate::launch;
 
 pub async fn launch_flow(mut flow: Dfir<'_>) {
-    let stop = tokio::sync::oneshot::channel();
+    let (stop_send, stop_recv) = tokio::sync::oneshot::channel();
     tokio::task::spawn_blocking(|| {
         let mut line = String::new();
-        std::io::stdin().read_line(&mut line).unwrap();
-        if line.starts_with("stop") {
-            stop.0.send(()).unwrap();
-        } else {
-            eprintln!("Unexpected stdin input: {:?}", line);
+        loop {
+            line.clear();
+            if std::io::stdin().read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+
+            if line.starts_with("stop") {
+                stop_send.send(()).unwrap();
+                break;
+            } else if let Some(update) = line.strip_prefix("membership: ") {
+                match serde_json::from_str(update.trim()) {
+                    Ok(update) => publish_membership_update(update),
+                    Err(e) => eprintln!("Invalid membership update {:?}: {}", update, e),
+                }
+            } else {
+                eprintln!("Unexpected stdin input: {:?}", line);
+                break;
+            }
         }
     });
 
@@ -41,7 +54,7 @@ pub async fn launch_flow(mut flow: Dfir<'_>) {
     let flow = local_set.run_until(flow.run_async());
 
     tokio::select! {
-        _ = stop.1 => {},
+        _ = stop_recv => {},
         _ = flow => {}
     }
 }
@@ -59,6 +59,16 @@
 //! destination hostname and the interface name on that host to which the message should be
 //! delivered.
 //!
+//! ### Fault Injection
+//! Every directed link between two hosts has a [`LinkPolicy`], which can drop, duplicate and
+//! delay (and thereby reorder) the messages sent over it. Delays are measured in network
+//! rounds, i.e. calls to [`Fleet::process_network`]. Hosts can also be partitioned from each
+//! other, and crashed and restarted; a restarted host gets a fresh process built from the
+//! factory that was given to [`Fleet::add_restartable_host`]. Faults can be applied
+//! immediately with [`Fleet::apply_fault`], or scheduled for a later round with
+//! [`Fleet::schedule_fault`]. All random decisions are drawn from an RNG seeded by
+//! [`Fleet::with_seed`], so a failing run can be replayed exactly.
+//!
 //! ## Progress of Time in the Simulation
 //! The single-threaded unit test can drive time forward on every host by invoking the `run_tick`
 //! method on the host. This ultimately runs a single tick on the process. The unit test is
@@ -68,11 +78,12 @@
 //! ## Examples
 //! Check the tests module for examples on how to use the simulation framework.
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::Infallible;
 use std::fmt::Debug;
 use std::future::ready;
 use std::pin::Pin;
+use std::rc::Rc;
 
 use futures::{Sink, SinkExt, StreamExt, sink};
 use serde::{Deserialize, Serialize};
@@ -136,23 +147,70 @@ pub struct Inbox {
 /// on their host.
 pub struct Outbox {
     receiver: Pin<Box<dyn Stream<Item = MessageWithAddress>>>,
+    /// Clones a message taken from this outbox, so that the network can duplicate it. `None`
+    /// if the messages are not `Clone`, in which case they are never duplicated.
+    clone_message: Option<CloneMessageFn>,
+}
+
+type CloneMessageFn = fn(&dyn Any) -> Box<dyn Any>;
+
+fn clone_message<T: Clone + 'static>(message: &dyn Any) -> Box<dyn Any> {
+    Box::new(message.downcast_ref::<T>().unwrap().clone())
 }
 
+/// Builds the process for a host, given a context to create its inboxes and outboxes.
+type ProcessFactory = Rc<dyn Fn(&mut ProcessBuilderContext) -> Dfir<'static>>;
+
 /// A host is a single Hydro/DFIR process running in the simulation. It has a unique hostname
 /// and can communicate with other hosts over the virtual network. It has a collection of inboxes
 /// and outboxes.
 pub struct Host {
     name: Hostname,
-    process: Dfir<'static>,
+    /// The running process, or `None` if the host has crashed.
+    process: Option<Dfir<'static>>,
+    factory: Option<ProcessFactory>,
     inputs: HashMap<InterfaceName, Inbox>,
     output: HashMap<InterfaceName, Outbox>,
 }
 
 impl Host {
     /// Run a single tick on the host's process. Returns true if any work was done by the
-    /// process. This effectively "advances" time on the process.
+    /// process. This effectively "advances" time on the process. A crashed host does no work.
     pub fn run_tick(&mut self) -> bool {
-        self.process.run_tick()
+        self.process
+            .as_mut()
+            .is_some_and(|process| process.run_tick())
+    }
+
+    /// Returns true if the host is running, i.e. it has not crashed (or has been restarted).
+    pub fn is_up(&self) -> bool {
+        self.process.is_some()
+    }
+
+    /// Stops the host's process, discarding all of its state and any messages it has not
+    /// yet sent or received.
+    fn crash(&mut self) {
+        self.process = None;
+        self.inputs.clear();
+        self.output.clear();
+    }
+
+    /// Replaces the host's process with a fresh one built by its factory.
+    fn restart(&mut self) {
+        let factory = self.factory.clone().unwrap_or_else(|| {
+            panic!(
+                "Host {} cannot be restarted because it was not added with a process factory",
+                self.name
+            )
+        });
+
+        self.inputs.clear();
+        self.output.clear();
+        let mut context = ProcessBuilderContext {
+            inboxes: &mut self.inputs,
+            outboxes: &mut self.output,
+        };
+        self.process = Some(factory(&mut context));
     }
 }
 
@@ -160,6 +218,7 @@ impl Host {
 pub struct HostBuilder {
     name: Hostname,
     process: Option<Dfir<'static>>,
+    factory: Option<ProcessFactory>,
     inboxes: HashMap<InterfaceName, Inbox>,
     outboxes: HashMap<InterfaceName, Outbox>,
 }
@@ -195,10 +254,29 @@ impl ProcessBuilderContext<'_> {
     }
 
     /// Creates a new outbox on the host with the given interface name. Returns a sink that can
-    /// be written to by the process using the dest_sink dfir operator.
+    /// be written to by the process using the dest_sink dfir operator. Messages sent through
+    /// this outbox are never duplicated by the network, see
+    /// [`ProcessBuilderContext::new_duplicable_outbox`].
     pub fn new_outbox<T: 'static>(
         &mut self,
         interface: InterfaceName,
+    ) -> impl use<T> + Sink<(T, Address), Error = Infallible> {
+        self.insert_outbox(interface, None)
+    }
+
+    /// Like [`ProcessBuilderContext::new_outbox`], but for messages that are `Clone`, so that
+    /// links with a [`LinkPolicy::duplicate_probability`] can duplicate them.
+    pub fn new_duplicable_outbox<T: Clone + 'static>(
+        &mut self,
+        interface: InterfaceName,
+    ) -> impl use<T> + Sink<(T, Address), Error = Infallible> {
+        self.insert_outbox(interface, Some(clone_message::<T>))
+    }
+
+    fn insert_outbox<T: 'static>(
+        &mut self,
+        interface: InterfaceName,
+        clone_message: Option<CloneMessageFn>,
     ) -> impl use<T> + Sink<(T, Address), Error = Infallible> {
         let (sender, receiver) = unbounded_channel::<(T, Address)>();
 
@@ -208,6 +286,7 @@ impl ProcessBuilderContext<'_> {
             interface,
             Outbox {
                 receiver: Box::pin(receiver),
+                clone_message,
             },
         );
 
@@ -221,6 +300,7 @@ impl HostBuilder {
         HostBuilder {
             name,
             process: None,
+            factory: None,
             inboxes: Default::default(),
             outboxes: Default::default(),
         }
@@ -240,6 +320,19 @@ impl HostBuilder {
         self
     }
 
+    /// Supplies a factory for the process that runs on this host. Unlike
+    /// [`HostBuilder::with_process`], the factory is kept around so that the process can be
+    /// rebuilt from scratch when the host is restarted after a crash.
+    pub fn with_process_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&mut ProcessBuilderContext) -> Dfir<'static> + 'static,
+    {
+        let factory: ProcessFactory = Rc::new(factory);
+        self = self.with_process(|context| factory(context));
+        self.factory = Some(factory);
+        self
+    }
+
     /// Builds the host with the supplied configuration.
     pub fn build(self) -> Host {
         if self.process.is_none() {
@@ -248,24 +341,165 @@ impl HostBuilder {
 
         Host {
             name: self.name,
-            process: self.process.unwrap(),
+            process: self.process,
+            factory: self.factory,
             inputs: self.inboxes,
             output: self.outboxes,
         }
     }
 }
 
+/// Describes how the network treats messages sent over a link from one host to another.
+///
+/// The default policy delivers every message exactly once, in the next network round.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkPolicy {
+    /// The probability that a message is dropped.
+    pub drop_probability: f64,
+    /// The probability that a (non-dropped) message is delivered twice. Only messages sent
+    /// through an outbox created with [`ProcessBuilderContext::new_duplicable_outbox`] can be
+    /// duplicated.
+    pub duplicate_probability: f64,
+    /// Every message is held back for a uniformly random number of network rounds between
+    /// zero and this bound. Since each message is delayed independently, this also reorders
+    /// messages on the link.
+    pub max_delay: u64,
+}
+
+impl LinkPolicy {
+    /// Sets the probability that a message is dropped.
+    pub fn with_drop_probability(mut self, drop_probability: f64) -> Self {
+        self.drop_probability = drop_probability;
+        self
+    }
+
+    /// Sets the probability that a message is delivered twice.
+    pub fn with_duplicate_probability(mut self, duplicate_probability: f64) -> Self {
+        self.duplicate_probability = duplicate_probability;
+        self
+    }
+
+    /// Sets the maximum number of network rounds that a message can be delayed by.
+    pub fn with_max_delay(mut self, max_delay: u64) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+}
+
+/// A fault that can be applied to the simulated network or its hosts, either immediately or
+/// at a scheduled network round.
+#[derive(Clone, Debug)]
+pub enum Fault {
+    /// Drops all messages between the two groups of hosts (in both directions), including
+    /// messages that are already in flight, until the partition is healed.
+    Partition(Vec<Hostname>, Vec<Hostname>),
+    /// Removes all partitions.
+    Heal,
+    /// Stops a host, discarding all of its state and queued messages, including messages in
+    /// flight to it.
+    Crash(Hostname),
+    /// Restarts a crashed host with a fresh process.
+    Restart(Hostname),
+    /// Changes the policy of the link from one host to another.
+    SetLinkPolicy {
+        /// The sending host.
+        from: Hostname,
+        /// The receiving host.
+        to: Hostname,
+        /// The new policy for the link.
+        policy: LinkPolicy,
+    },
+}
+
+/// The seeded pseudo-random number generator (SplitMix64) that makes the random decisions of
+/// simulations, so that a run can be replayed exactly from its seed. We avoid pulling in
+/// `rand` so that runs are reproducible across dependency upgrades.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    /// Creates a generator that produces the sequence determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        SimRng { state: seed }
+    }
+
+    /// Returns the next uniformly random 64-bit value.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `0..bound`. Panics if `bound` is zero.
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "Cannot sample from an empty range");
+        (self.next_u64() % (bound as u64)) as usize
+    }
+
+    /// Returns true with the given probability.
+    fn gen_bool(&mut self, probability: f64) -> bool {
+        if probability <= 0.0 {
+            false
+        } else if probability >= 1.0 {
+            true
+        } else {
+            // The top 53 bits give a uniformly distributed float in [0, 1).
+            ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+        }
+    }
+
+    /// Returns a uniformly random delay between zero and `max_delay`, inclusive.
+    fn gen_delay(&mut self, max_delay: u64) -> u64 {
+        match max_delay.checked_add(1) {
+            Some(bound) => self.next_u64() % bound,
+            None => self.next_u64(),
+        }
+    }
+}
+
+/// A message that has been picked up from an outbox but not yet delivered.
+struct InFlightMessage {
+    deliver_at: u64,
+    src: Address,
+    message: MessageWithAddress,
+}
+
 /// A fleet is a collection of hosts in the simulation. It is responsible for running the
 /// simulation and processing network messages.
 pub struct Fleet {
-    hosts: HashMap<String, Host>,
+    hosts: BTreeMap<String, Host>,
+    rng: SimRng,
+    /// The number of network rounds that have been processed so far.
+    round: u64,
+    default_link_policy: LinkPolicy,
+    link_policies: HashMap<(Hostname, Hostname), LinkPolicy>,
+    partitioned: HashSet<(Hostname, Hostname)>,
+    in_flight: Vec<InFlightMessage>,
+    scheduled_faults: BTreeMap<u64, Vec<Fault>>,
 }
 
 impl Fleet {
     /// Creates a new instance of Fleet.
     pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Creates a new instance of Fleet, whose network makes random decisions (for lossy, delayed
+    /// or duplicating links) using an RNG with the given seed.
+    pub fn with_seed(seed: u64) -> Self {
         Fleet {
-            hosts: HashMap::new(),
+            hosts: BTreeMap::new(),
+            rng: SimRng::new(seed),
+            round: 0,
+            default_link_policy: LinkPolicy::default(),
+            link_policies: HashMap::new(),
+            partitioned: HashSet::new(),
+            in_flight: Vec::new(),
+            scheduled_faults: BTreeMap::new(),
         }
     }
 
@@ -274,11 +508,26 @@ impl Fleet {
     where
         F: FnOnce(&mut ProcessBuilderContext) -> Dfir<'static>,
     {
-        let host = HostBuilder::new(name.clone())
-            .with_process(process_builder)
-            .build();
+        self.insert_host(HostBuilder::new(name).with_process(process_builder).build())
+    }
+
+    /// Adds a new host to the fleet whose process is built by `process_factory`. The factory is
+    /// called again to rebuild the process whenever the host is restarted after a crash.
+    pub fn add_restartable_host<F>(&mut self, name: String, process_factory: F) -> &Host
+    where
+        F: Fn(&mut ProcessBuilderContext) -> Dfir<'static> + 'static,
+    {
+        self.insert_host(
+            HostBuilder::new(name)
+                .with_process_factory(process_factory)
+                .build(),
+        )
+    }
+
+    fn insert_host(&mut self, host: Host) -> &Host {
+        let name = host.name.clone();
         assert!(
-            self.hosts.insert(host.name.clone(), host).is_none(),
+            self.hosts.insert(name.clone(), host).is_none(),
             "Host with name {} already exists",
             name
         );
@@ -295,10 +544,67 @@ impl Fleet {
         self.hosts.get_mut(name)
     }
 
+    /// The number of network rounds processed so far, which is the clock used to schedule
+    /// faults and delay messages.
+    pub fn current_round(&self) -> u64 {
+        self.round
+    }
+
+    /// Sets the policy used for all links that do not have a specific policy.
+    pub fn set_default_link_policy(&mut self, policy: LinkPolicy) {
+        self.default_link_policy = policy;
+    }
+
+    /// Sets the policy for the link from one host to another (but not the reverse direction).
+    pub fn set_link_policy(&mut self, from: Hostname, to: Hostname, policy: LinkPolicy) {
+        self.link_policies.insert((from, to), policy);
+    }
+
+    fn link_policy(&self, from: &str, to: &str) -> &LinkPolicy {
+        self.link_policies
+            .get(&(from.to_string(), to.to_string()))
+            .unwrap_or(&self.default_link_policy)
+    }
+
+    /// Applies a fault immediately.
+    pub fn apply_fault(&mut self, fault: Fault) {
+        trace!("Applying fault {:?} at round {}", fault, self.round);
+        match fault {
+            Fault::Partition(side_a, side_b) => {
+                for a in &side_a {
+                    for b in &side_b {
+                        self.partitioned.insert((a.clone(), b.clone()));
+                        self.partitioned.insert((b.clone(), a.clone()));
+                    }
+                }
+            }
+            Fault::Heal => self.partitioned.clear(),
+            Fault::Crash(name) => {
+                self.host_for_fault(&name).crash();
+                self.in_flight.retain(|m| m.message.1.host != name);
+            }
+            Fault::Restart(name) => self.host_for_fault(&name).restart(),
+            Fault::SetLinkPolicy { from, to, policy } => self.set_link_policy(from, to, policy),
+        }
+    }
+
+    fn host_for_fault(&mut self, name: &str) -> &mut Host {
+        self.hosts
+            .get_mut(name)
+            .unwrap_or_else(|| panic!("No host named {:?} to apply a fault to", name))
+    }
+
+    /// Schedules a fault to be applied at the start of the given network round (see
+    /// [`Fleet::current_round`]). Faults scheduled for a round that has already started are
+    /// applied at the start of the next round.
+    pub fn schedule_fault(&mut self, round: u64, fault: Fault) {
+        self.scheduled_faults.entry(round).or_default().push(fault);
+    }
+
     /// Advance time on all hosts by a single tick. Returns true if any work was done by any of the
     /// hosts. After ticking once on all the hosts, the method also processes network messages.
     ///
-    /// The order in which the ticks are processed is not guaranteed.
+    /// Hosts are ticked in order of their hostnames.
     pub async fn run_single_tick_all_hosts(&mut self) -> bool {
         let mut work_done: bool = false;
 
@@ -313,27 +619,94 @@ impl Fleet {
     }
 
     /// Process all network messages in the simulation. This method picks up all messages from all
-    /// outboxes on all hosts and delivers them to the corresponding inboxes on the destination.
+    /// outboxes on all hosts and passes them through the link policies, then delivers all the
+    /// messages that are due in this round to the corresponding inboxes on the destination.
     ///
-    /// The order in which the messages are processed is not guaranteed.
+    /// Any faults scheduled for this round are applied first. Messages are picked up in order of
+    /// the sending host and interface, so that runs with the same seed are reproducible.
     pub async fn process_network(&mut self) {
-        let mut all_messages: Vec<(Address, MessageWithAddress)> = Vec::new();
+        let due_rounds = self
+            .scheduled_faults
+            .range(..=self.round)
+            .map(|(round, _)| *round)
+            .collect::<Vec<_>>();
+        for round in due_rounds {
+            for fault in self.scheduled_faults.remove(&round).unwrap() {
+                self.apply_fault(fault);
+            }
+        }
+
+        let mut all_messages: Vec<(Address, MessageWithAddress, Option<CloneMessageFn>)> =
+            Vec::new();
 
         // Collect all messages from all outboxes on all hosts.
         for (name, host) in self.hosts.iter_mut() {
-            for (interface, output) in host.output.iter_mut() {
+            let mut outputs = host.output.iter_mut().collect::<Vec<_>>();
+            outputs.sort_by_key(|(interface, _)| *interface);
+            for (interface, output) in outputs {
                 let src_address = Address::new(name.clone(), interface.clone());
                 let all_messages_on_interface: Vec<_> =
                     collect_ready_async(&mut output.receiver).await;
                 for message_on_interface in all_messages_on_interface {
-                    all_messages.push((src_address.clone(), message_on_interface));
+                    all_messages.push((
+                        src_address.clone(),
+                        message_on_interface,
+                        output.clone_message,
+                    ));
                 }
             }
         }
 
-        // Deliver all messages to the corresponding inboxes on the destination hosts.
-        for (src_address, (msg, addr)) in all_messages {
-            if let Some(destination_host) = self.hosts.get(&addr.host) {
+        // Apply the link policies to decide whether, when, and how many times each message is
+        // delivered.
+        for (src_address, (msg, addr), clone_message) in all_messages {
+            let policy = self.link_policy(&src_address.host, &addr.host).clone();
+
+            if self.rng.gen_bool(policy.drop_probability) {
+                trace!("Dropping message {:?} sent to {:?}.", msg, addr);
+                continue;
+            }
+
+            if let Some(clone_message) = clone_message {
+                if self.rng.gen_bool(policy.duplicate_probability) {
+                    let deliver_at = self.round + self.rng.gen_delay(policy.max_delay);
+                    self.in_flight.push(InFlightMessage {
+                        deliver_at,
+                        src: src_address.clone(),
+                        message: (clone_message(msg.as_ref()), addr.clone()),
+                    });
+                }
+            }
+
+            let deliver_at = self.round + self.rng.gen_delay(policy.max_delay);
+            self.in_flight.push(InFlightMessage {
+                deliver_at,
+                src: src_address,
+                message: (msg, addr),
+            });
+        }
+
+        // Deliver all due messages to the corresponding inboxes on the destination hosts.
+        let (due, pending) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition::<Vec<_>, _>(|m| m.deliver_at <= self.round);
+        self.in_flight = pending;
+
+        for InFlightMessage {
+            src: src_address,
+            message: (msg, addr),
+            ..
+        } in due
+        {
+            if self
+                .partitioned
+                .contains(&(src_address.host.clone(), addr.host.clone()))
+            {
+                trace!(
+                    "Host {:?} is partitioned from {:?}. Dropping message {:?}.",
+                    src_address.host, addr.host, msg
+                );
+            } else if let Some(destination_host) = self.hosts.get(&addr.host) {
                 if let Some(input) = destination_host.inputs.get(&addr.interface) {
                     input.sender.send((msg, src_address.clone()));
                 } else {
@@ -349,13 +722,28 @@ impl Fleet {
                 );
             }
         }
+
+        self.round += 1;
     }
 
-    /// Tick all hosts until all hosts are quiescent (i.e. no new work is done by any host). Ticking
-    /// is done in "rounds". At each round, all hosts are ticked once and then network messages are
+    /// Tick all hosts until all hosts are quiescent (i.e. no new work is done by any host), no
+    /// delayed messages are left in flight and no faults are left scheduled. Ticking is done in
+    /// "rounds". At each round, all hosts are ticked once and then network messages are
     /// processed. The process continues until no work is done by any host in a round.
+    ///
+    /// While the fleet is idle but faults are still scheduled, the rounds up to the next
+    /// scheduled fault are skipped.
     pub async fn run_until_quiescent(&mut self) {
-        while self.run_single_tick_all_hosts().await {}
+        loop {
+            if self.run_single_tick_all_hosts().await || !self.in_flight.is_empty() {
+                continue;
+            }
+
+            match self.scheduled_faults.keys().next() {
+                Some(&next_fault) => self.round = self.round.max(next_fault),
+                None => break,
+            }
+        }
     }
 }
 
@@ -369,9 +757,11 @@ impl Default for Fleet {
 mod tests {
     use dfir_macro::{dfir_syntax, dfir_test};
     use futures::StreamExt;
+    use tokio::sync::mpsc::UnboundedSender;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
 
-    use crate::util::simulation::{Address, Fleet, Hostname};
-    use crate::util::unbounded_channel;
+    use crate::util::simulation::{Address, Fault, Fleet, Hostname, LinkPolicy};
+    use crate::util::{collect_ready_async, unbounded_channel};
 
     /// A simple test to demonstrate use of the simulation framework. Implements an echo server
     /// and client.
@@ -434,4 +824,201 @@ mod tests {
         let response = client_response_rx.next().await.unwrap();
         assert_eq!(response, "Hello, world!");
     }
+
+    /// Builds a fleet with a restartable echo server, which prefixes every response with the
+    /// number of messages it has echoed since it (re)started, and a client. Returns the fleet,
+    /// a channel to make the client send messages and a stream of the responses it receives.
+    fn echo_fleet(
+        seed: u64,
+    ) -> (
+        Fleet,
+        UnboundedSender<String>,
+        UnboundedReceiverStream<String>,
+    ) {
+        let mut fleet = Fleet::with_seed(seed);
+        let server_address = Address::new("server".to_string(), "echo".to_string());
+
+        fleet.add_restartable_host("server".to_string(), |ctx| {
+            let network_input = ctx.new_inbox::<String>("echo".to_string());
+            let network_output = ctx.new_outbox::<String>("echo".to_string());
+            dfir_syntax! {
+                source_stream(network_input)
+                    -> enumerate::<'static>()
+                    -> map(|(i, (msg, addr))| (format!("{}:{}", i, msg), addr))
+                    -> dest_sink(network_output);
+            }
+        });
+
+        let (trigger_tx, trigger_rx) = unbounded_channel::<String>();
+        let (response_tx, response_rx) = unbounded_channel::<String>();
+
+        fleet.add_host("client".to_string(), |ctx| {
+            let network_out = ctx.new_duplicable_outbox::<String>("echo".to_string());
+            let network_in = ctx.new_inbox::<String>("echo".to_string());
+
+            dfir_syntax! {
+                source_stream(trigger_rx)
+                    -> map(|msg| (msg, server_address.clone()))
+                    -> dest_sink(network_out);
+
+                source_stream(network_in)
+                    -> for_each(|(msg, _addr)| response_tx.send(msg).unwrap());
+            }
+        });
+
+        (fleet, trigger_tx, response_rx)
+    }
+
+    #[dfir_test]
+    async fn test_drop_all_messages() {
+        let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(0);
+        fleet.set_default_link_policy(LinkPolicy::default().with_drop_probability(1.0));
+
+        trigger_tx.send("hello".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            Vec::<String>::new()
+        );
+    }
+
+    #[dfir_test]
+    async fn test_duplicate_messages() {
+        let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(0);
+        fleet.set_link_policy(
+            "client".to_string(),
+            "server".to_string(),
+            LinkPolicy::default().with_duplicate_probability(1.0),
+        );
+
+        trigger_tx.send("hello".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            vec!["0:hello", "1:hello"]
+        );
+    }
+
+    #[dfir_test]
+    async fn test_delays_reorder_reproducibly() {
+        async fn run(seed: u64) -> Vec<String> {
+            let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(seed);
+            fleet.set_default_link_policy(LinkPolicy::default().with_max_delay(5));
+
+            for i in 0..20 {
+                trigger_tx.send(i.to_string()).unwrap();
+            }
+            fleet.run_until_quiescent().await;
+
+            collect_ready_async::<Vec<_>, _>(&mut response_rx)
+                .await
+                .into_iter()
+                .map(|response| response.split_once(':').unwrap().1.to_string())
+                .collect()
+        }
+
+        let received = run(7).await;
+        let mut sorted = received.clone();
+        sorted.sort_by_key(|msg| msg.parse::<usize>().unwrap());
+        assert_eq!(sorted, (0..20).map(|i| i.to_string()).collect::<Vec<_>>());
+        assert_ne!(received, sorted, "delays should reorder the messages");
+
+        assert_eq!(received, run(7).await);
+    }
+
+    #[dfir_test]
+    async fn test_scheduled_partition_and_heal() {
+        let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(0);
+        fleet.schedule_fault(
+            0,
+            Fault::Partition(vec!["client".to_string()], vec!["server".to_string()]),
+        );
+        fleet.schedule_fault(3, Fault::Heal);
+
+        // The fleet keeps running until the heal is applied, but the message sent during the
+        // partition is lost.
+        trigger_tx.send("during partition".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+        assert!(fleet.current_round() > 3);
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            Vec::<String>::new()
+        );
+
+        trigger_tx.send("after heal".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            vec!["0:after heal"]
+        );
+    }
+
+    #[dfir_test]
+    async fn test_crash_and_restart() {
+        let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(0);
+
+        trigger_tx.send("a".to_string()).unwrap();
+        trigger_tx.send("b".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            vec!["0:a", "1:b"]
+        );
+
+        fleet.apply_fault(Fault::Crash("server".to_string()));
+        assert!(!fleet.get_host("server").unwrap().is_up());
+
+        trigger_tx.send("c".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            Vec::<String>::new()
+        );
+
+        // The restarted server starts from a fresh process, so its count is reset.
+        fleet.apply_fault(Fault::Restart("server".to_string()));
+        trigger_tx.send("d".to_string()).unwrap();
+        fleet.run_until_quiescent().await;
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            vec!["0:d"]
+        );
+    }
+
+    #[dfir_test]
+    async fn test_scheduled_restart_applies_when_idle() {
+        let (mut fleet, _trigger_tx, _response_rx) = echo_fleet(0);
+        fleet.apply_fault(Fault::Crash("server".to_string()));
+        fleet.schedule_fault(10, Fault::Restart("server".to_string()));
+
+        fleet.run_until_quiescent().await;
+        assert!(fleet.get_host("server").unwrap().is_up());
+    }
+
+    #[dfir_test]
+    async fn test_crash_drops_messages_in_flight() {
+        let (mut fleet, trigger_tx, mut response_rx) = echo_fleet(0);
+        fleet.set_link_policy(
+            "client".to_string(),
+            "server".to_string(),
+            LinkPolicy::default().with_max_delay(100),
+        );
+
+        trigger_tx.send("lost".to_string()).unwrap();
+        fleet.run_single_tick_all_hosts().await;
+        assert!(!fleet.in_flight.is_empty());
+
+        // The message in flight to the server is lost with it, so the restarted server never
+        // receives it.
+        fleet.apply_fault(Fault::Crash("server".to_string()));
+        assert!(fleet.in_flight.is_empty());
+        fleet.apply_fault(Fault::Restart("server".to_string()));
+        fleet.run_until_quiescent().await;
+        assert_eq!(
+            collect_ready_async::<Vec<_>, _>(&mut response_rx).await,
+            Vec::<String>::new()
+        );
+    }
 }
@@ -1,10 +1,11 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use dfir_macro::dfir_test;
+use dfir_rs::scheduled::clock::{Clock, VirtualClock};
 use dfir_rs::{assert_graphvis_snapshots, dfir_syntax};
 use multiplatform_test::multiplatform_test;
-use web_time::Duration;
+use web_time::{Duration, SystemTime};
 
 #[multiplatform_test]
 pub fn test_context_ref() {
@@ -75,3 +76,58 @@ pub async fn test_defered_tick_and_no_io_with_run_async() {
         _ = rx.recv() => {},
     }
 }
+
+#[test]
+pub fn test_source_interval_virtual_clock() {
+    let clock = VirtualClock::new();
+    let ticks = Rc::new(RefCell::new(Vec::new()));
+
+    let mut df = {
+        let ticks = ticks.clone();
+        dfir_syntax! {
+            source_interval(Duration::from_secs(60))
+                -> for_each(|()| ticks.borrow_mut().push(context.current_tick_start()));
+        }
+    };
+    df.set_clock(clock.clone());
+
+    // The first tick completes immediately.
+    df.run_available();
+    assert_eq!(&[SystemTime::UNIX_EPOCH], &**ticks.borrow());
+
+    // No real time passes, so nothing happens until the clock is advanced.
+    df.run_available();
+    assert_eq!(1, ticks.borrow().len());
+
+    assert!(clock.advance_to_next_deadline());
+    df.run_available();
+    clock.advance(Duration::from_secs(120));
+    df.run_available();
+    assert_eq!(
+        &[
+            SystemTime::UNIX_EPOCH,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(60),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(180),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(180),
+        ],
+        &**ticks.borrow()
+    );
+}
+
+#[test]
+pub fn test_counter_virtual_clock() {
+    let clock = VirtualClock::new();
+    let mut df = dfir_syntax! {
+        source_iter(0..3) -> _counter("nums", Duration::from_secs(1));
+    };
+    df.set_clock(clock.clone());
+
+    // The printing task waits on the virtual clock rather than the Tokio timer.
+    let mut tasks = df.take_tasks();
+    let mut cx = std::task::Context::from_waker(futures::task::noop_waker_ref());
+    assert!(tasks[0].as_mut().poll(&mut cx).is_pending());
+    assert_eq!(
+        Some(clock.now() + Duration::from_secs(1)),
+        clock.next_deadline()
+    );
+}
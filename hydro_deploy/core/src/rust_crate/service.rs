@@ -6,7 +6,7 @@ use std::time::Duration;
 use anyhow::{Context, Result, bail};
 use async_trait::async_trait;
 use futures::Future;
-use hydro_deploy_integration::{InitConfig, ServerPort};
+use hydro_deploy_integration::{InitConfig, MembershipUpdate, ServerPort};
 use serde::Serialize;
 use tokio::sync::{RwLock, mpsc};
 
@@ -178,6 +178,18 @@ impl RustCrateService {
         self.launched_binary.as_ref().unwrap().exit_code()
     }
 
+    /// Notifies the running program that a cluster member has joined or left.
+    pub fn send_membership_update(&self, update: &MembershipUpdate) -> Result<()> {
+        let launched_binary = self
+            .launched_binary
+            .as_ref()
+            .context("Cannot update the membership of a service that is not running")?;
+        launched_binary
+            .stdin()
+            .send(format!("membership: {}\n", serde_json::to_string(update)?))?;
+        Ok(())
+    }
+
     fn build(
         &self,
     ) -> impl use<> + 'static + Future<Output = Result<&'static BuildOutput, BuildError>> {
@@ -4,6 +4,7 @@ use std::marker::PhantomData;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Mutex;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
@@ -18,9 +19,10 @@ use tokio::io;
 use tokio::net::{TcpListener, TcpStream};
 #[cfg(unix)]
 use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::task::JoinHandle;
 use tokio_stream::StreamExt;
-use tokio_stream::wrappers::TcpListenerStream;
+use tokio_stream::wrappers::{TcpListenerStream, UnboundedReceiverStream};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
 pub type InitConfig = (HashMap<String, ServerBindConfig>, Option<String>);
@@ -42,6 +44,33 @@ impl<T> DeployPorts<T> {
     }
 }
 
+/// A change to the membership of a cluster, sent by Hydro Deploy to every running service
+/// as a `membership: <json>` line on stdin.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MembershipUpdate {
+    pub cluster: usize,
+    pub member: u32,
+    pub joined: bool,
+}
+
+static MEMBERSHIP_SUBSCRIBERS: Mutex<Vec<UnboundedSender<MembershipUpdate>>> =
+    Mutex::new(Vec::new());
+
+/// Returns a stream of all the membership updates published after this call.
+pub fn subscribe_membership_updates() -> UnboundedReceiverStream<MembershipUpdate> {
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    MEMBERSHIP_SUBSCRIBERS.lock().unwrap().push(sender);
+    UnboundedReceiverStream::new(receiver)
+}
+
+/// Forwards a membership update to every current subscriber.
+pub fn publish_membership_update(update: MembershipUpdate) {
+    MEMBERSHIP_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|subscriber| subscriber.send(update.clone()).is_ok());
+}
+
 #[cfg(not(unix))]
 type UnixStream = std::convert::Infallible;
 
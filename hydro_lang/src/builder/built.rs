@@ -3,12 +3,14 @@ use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
 
 use dfir_lang::graph::{DfirGraph, eliminate_extra_unions_tees, partition_graph};
+use serde::{Deserialize, Serialize};
 
 use super::compiled::CompiledFlow;
 use super::deploy::{DeployFlow, DeployResult};
 use crate::deploy::{ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec};
-use crate::ir::{HydroLeaf, emit};
+use crate::ir::{HydroLeaf, emit, serde_dedup_tee};
 use crate::location::{Cluster, ExternalProcess, Process};
+use crate::nondet::{ChoicePoint, choice_points};
 use crate::staging_util::Invariant;
 
 pub struct BuiltFlow<'a> {
@@ -16,10 +18,26 @@ pub struct BuiltFlow<'a> {
     pub(super) process_id_name: Vec<(usize, String)>,
     pub(super) cluster_id_name: Vec<(usize, String)>,
     pub(super) external_id_name: Vec<(usize, String)>,
+    /// Recorded before any rewrites run, since optimizations such as `persist_pullup`
+    /// erase the batch boundaries that make up some choice points.
+    pub(super) choice_points: Vec<ChoicePoint>,
 
     pub(super) _phantom: Invariant<'a>,
 }
 
+/// Version of the format written by [`BuiltFlow::to_json`] and [`BuiltFlow::to_bincode`],
+/// incremented whenever the IR changes in an incompatible way.
+pub const SERIALIZED_FLOW_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SerializedFlow<I> {
+    version: u32,
+    ir: I,
+    process_id_name: Vec<(usize, String)>,
+    cluster_id_name: Vec<(usize, String)>,
+    external_id_name: Vec<(usize, String)>,
+}
+
 pub(crate) fn build_inner(ir: &mut Vec<HydroLeaf>) -> BTreeMap<usize, DfirGraph> {
     emit(ir)
         .into_iter()
@@ -38,6 +56,71 @@ impl<'a> BuiltFlow<'a> {
         &self.ir
     }
 
+    /// Lists the points in this flow where the runtime makes a non-deterministic choice,
+    /// such as the boundaries of a `tick_batch` or the interleaving of network messages.
+    pub fn choice_points(&self) -> Vec<ChoicePoint> {
+        self.choice_points.clone()
+    }
+
+    fn to_serialized(&self) -> SerializedFlow<&[HydroLeaf]> {
+        SerializedFlow {
+            version: SERIALIZED_FLOW_VERSION,
+            ir: &self.ir,
+            process_id_name: self.process_id_name.clone(),
+            cluster_id_name: self.cluster_id_name.clone(),
+            external_id_name: self.external_id_name.clone(),
+        }
+    }
+
+    fn from_serialized<E: serde::de::Error>(
+        serialized: SerializedFlow<Vec<HydroLeaf>>,
+    ) -> Result<Self, E> {
+        if serialized.version != SERIALIZED_FLOW_VERSION {
+            return Err(E::custom(format!(
+                "unsupported IR format version {} (expected {})",
+                serialized.version, SERIALIZED_FLOW_VERSION
+            )));
+        }
+
+        Ok(BuiltFlow {
+            choice_points: choice_points(&serialized.ir),
+            ir: serialized.ir,
+            process_id_name: serialized.process_id_name,
+            cluster_id_name: serialized.cluster_id_name,
+            external_id_name: serialized.external_id_name,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Serializes the IR of this flow, including its metadata and the locations it uses,
+    /// to JSON. The flow can be reconstructed with [`BuiltFlow::from_json`].
+    ///
+    /// Network instantiation is not persisted, so a loaded flow is deployed from scratch.
+    /// Because the original location handles are not available, a loaded flow is typically
+    /// deployed with [`BuiltFlow::with_remaining_processes`] (and friends).
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_dedup_tee(|| serde_json::to_string(&self.to_serialized()))
+    }
+
+    /// Reconstructs a flow that was serialized with [`BuiltFlow::to_json`].
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_dedup_tee(|| Self::from_serialized(serde_json::from_str(json)?))
+    }
+
+    /// Serializes the IR of this flow to a compact binary format, see [`BuiltFlow::to_json`].
+    pub fn to_bincode(&self) -> bincode::Result<Vec<u8>> {
+        // `bincode::serialize` makes a separate pass to compute the size, which would see every
+        // tee as already serialized, so we write in a single pass instead.
+        let mut bytes = Vec::new();
+        serde_dedup_tee(|| bincode::serialize_into(&mut bytes, &self.to_serialized()))?;
+        Ok(bytes)
+    }
+
+    /// Reconstructs a flow that was serialized with [`BuiltFlow::to_bincode`].
+    pub fn from_bincode(bytes: &[u8]) -> bincode::Result<Self> {
+        serde_dedup_tee(|| Self::from_serialized(bincode::deserialize(bytes)?))
+    }
+
     pub fn optimize_with(mut self, f: impl FnOnce(&mut [HydroLeaf])) -> Self {
         f(&mut self.ir);
         BuiltFlow {
@@ -45,12 +128,14 @@ impl<'a> BuiltFlow<'a> {
             process_id_name: std::mem::take(&mut self.process_id_name),
             cluster_id_name: std::mem::take(&mut self.cluster_id_name),
             external_id_name: std::mem::take(&mut self.external_id_name),
+            choice_points: std::mem::take(&mut self.choice_points),
             _phantom: PhantomData,
         }
     }
 
     pub fn with_default_optimize<D: Deploy<'a>>(self) -> DeployFlow<'a, D> {
         self.optimize_with(crate::rewrites::persist_pullup::persist_pullup)
+            .optimize_with(crate::rewrites::cse::cse)
             .into_deploy()
     }
 
@@ -90,6 +175,7 @@ impl<'a> BuiltFlow<'a> {
             cluster_id_name: std::mem::take(&mut self.cluster_id_name),
             externals,
             external_id_name: std::mem::take(&mut self.external_id_name),
+            choice_points: std::mem::take(&mut self.choice_points),
             _phantom: PhantomData,
         }
     }
@@ -154,3 +240,132 @@ impl<'a> BuiltFlow<'a> {
         self.into_deploy::<D>().deploy(env)
     }
 }
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    use stageleft::*;
+
+    use super::BuiltFlow;
+    use crate::FlowBuilder;
+    use crate::deploy::HydroDeploy;
+    use crate::ir::{HydroLeaf, HydroNode, traverse_dfir};
+    use crate::location::Location;
+
+    /// Builds a flow with a tee and a network, annotated with profiling metadata.
+    fn profiled_flow<'a>() -> BuiltFlow<'a> {
+        let flow = FlowBuilder::new();
+        let p1 = flow.process::<()>();
+        let p2 = flow.process::<()>();
+
+        let numbers = p1.source_iter(q!(0..10)).map(q!(|n| n * 2));
+        numbers
+            .clone()
+            .send_bincode(&p2)
+            .for_each(q!(|n| println!("{}", n)));
+        numbers.for_each(q!(|n| println!("local {}", n)));
+
+        flow.finalize()
+            .optimize_with(crate::rewrites::persist_pullup::persist_pullup)
+            .optimize_with(|ir| {
+                traverse_dfir(
+                    ir,
+                    |_, _| {},
+                    |node, next_stmt_id| {
+                        let metadata = node.metadata_mut();
+                        metadata.cardinality = Some(*next_stmt_id);
+                        metadata.cpu_usage = Some(*next_stmt_id as f64 / 10.0);
+                    },
+                )
+            })
+    }
+
+    fn ir_hash(ir: &[HydroLeaf]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        ir.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The metadata annotations in traversal order, and the tees that were found.
+    struct IrSummary {
+        annotations: Vec<(Option<usize>, Option<f64>)>,
+        tee_uses: usize,
+        distinct_tees: usize,
+    }
+
+    fn summarize(ir: &mut [HydroLeaf]) -> IrSummary {
+        let mut annotations = Vec::new();
+        let mut tee_uses = 0;
+        let mut tees = HashSet::new();
+        traverse_dfir(
+            ir,
+            |_, _| {},
+            |node, _| {
+                if let HydroNode::Tee { inner, .. } = node {
+                    tee_uses += 1;
+                    tees.insert(inner.as_ptr());
+                }
+                let metadata = node.metadata();
+                annotations.push((metadata.cardinality, metadata.cpu_usage));
+            },
+        );
+        IrSummary {
+            annotations,
+            tee_uses,
+            distinct_tees: tees.len(),
+        }
+    }
+
+    fn surface_syntax(flow: BuiltFlow<'_>) -> Vec<String> {
+        flow.into_deploy::<HydroDeploy>()
+            .preview_compile()
+            .all_dfir()
+            .values()
+            .map(|graph| graph.surface_syntax_string())
+            .collect()
+    }
+
+    #[test]
+    fn json_round_trip_preserves_ir() {
+        let mut original = profiled_flow();
+        let json = original.to_json().unwrap();
+        let mut loaded = BuiltFlow::from_json(&json).unwrap();
+
+        assert_eq!(ir_hash(&original.ir), ir_hash(&loaded.ir));
+        assert_eq!(original.process_id_name, loaded.process_id_name);
+
+        let original_summary = summarize(&mut original.ir);
+        let loaded_summary = summarize(&mut loaded.ir);
+        assert_eq!(original_summary.annotations, loaded_summary.annotations);
+        assert_eq!(loaded_summary.tee_uses, 2);
+        assert_eq!(loaded_summary.distinct_tees, 1);
+
+        assert_eq!(surface_syntax(original), surface_syntax(loaded));
+    }
+
+    #[test]
+    fn bincode_round_trip_preserves_ir() {
+        let original = profiled_flow();
+        let loaded = BuiltFlow::from_bincode(&original.to_bincode().unwrap()).unwrap();
+
+        assert_eq!(ir_hash(&original.ir), ir_hash(&loaded.ir));
+        assert_eq!(surface_syntax(original), surface_syntax(loaded));
+    }
+
+    #[test]
+    fn rejects_unknown_format_version() {
+        let json = profiled_flow()
+            .to_json()
+            .unwrap()
+            .replacen("\"version\":1", "\"version\":0", 1);
+        let error = BuiltFlow::from_json(&json).err().unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("unsupported IR format version 0")
+        );
+    }
+}
@@ -5,7 +5,7 @@ use std::marker::PhantomData;
 use std::pin::Pin;
 
 use bytes::Bytes;
-use futures::{Sink, Stream};
+use futures::{Sink, SinkExt, Stream, StreamExt};
 use proc_macro2::Span;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
@@ -13,13 +13,12 @@ use stageleft::QuotedWithContext;
 
 use super::built::build_inner;
 use super::compiled::CompiledFlow;
+use crate::codec::Codec;
 use crate::deploy::{
     ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec, Node, ProcessSpec, RegisterPort,
 };
 use crate::ir::HydroLeaf;
-use crate::location::external_process::{
-    ExternalBincodeSink, ExternalBincodeStream, ExternalBytesPort,
-};
+use crate::location::external_process::{ExternalBytesPort, ExternalSink, ExternalStream};
 use crate::location::{Cluster, ExternalProcess, Location, LocationId, Process};
 use crate::staging_util::Invariant;
 
@@ -45,6 +44,9 @@ where
     pub(super) clusters: HashMap<usize, D::Cluster>,
     pub(super) cluster_id_name: Vec<(usize, String)>,
 
+    /// Choice points of the unoptimized flow, reported to the deployment environment.
+    pub(super) choice_points: Vec<crate::nondet::ChoicePoint>,
+
     pub(super) _phantom: Invariant<'a, D>,
 }
 
@@ -206,6 +208,19 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
                     .push(syn::parse_quote! {
                         let #other_id_ident = #other_id_expr;
                     });
+
+                // each membership source gets its own stream of updates
+                let membership_ident = syn::Ident::new(
+                    &format!("__hydro_lang_cluster_membership_{}", c_id),
+                    Span::call_site(),
+                );
+                let membership_expr = D::cluster_membership(env, c_id).splice_untyped();
+                extra_stmts
+                    .entry(*other_location)
+                    .or_default()
+                    .push(syn::parse_quote! {
+                        let #membership_ident = || #membership_expr;
+                    });
             }
         }
         extra_stmts
@@ -215,6 +230,8 @@ impl<'a, D: Deploy<'a>> DeployFlow<'a, D> {
 impl<'a, D: Deploy<'a, CompileEnv = ()>> DeployFlow<'a, D> {
     #[must_use]
     pub fn deploy(mut self, env: &mut D::InstantiateEnv) -> DeployResult<'a, D> {
+        D::register_choice_points(env, std::mem::take(&mut self.choice_points));
+
         let mut seen_tees_instantiate: HashMap<_, _> = HashMap::new();
         let mut seen_tee_locations: HashMap<_, _> = HashMap::new();
         self.ir.get_mut().iter_mut().for_each(|leaf| {
@@ -368,6 +385,25 @@ impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
             .raw_port(port.port_id)
     }
 
+    pub fn raw_port_bincode<T: DeserializeOwned>(
+        &self,
+        port: &ExternalStream<T>,
+    ) -> D::ExternalRawPort {
+        self.raw_port_codec(port)
+    }
+
+    /// Like [`DeployResult::raw_port_bincode`], for an external output port whose messages
+    /// are encoded with a [`Codec`] other than bincode.
+    pub fn raw_port_codec<T: DeserializeOwned, C: Codec>(
+        &self,
+        port: &ExternalStream<T, C>,
+    ) -> D::ExternalRawPort {
+        self.externals
+            .get(&port.process_id)
+            .unwrap()
+            .raw_port(port.port_id)
+    }
+
     pub async fn connect_sink_bytes(
         &self,
         port: ExternalBytesPort,
@@ -381,7 +417,7 @@ impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
 
     pub async fn connect_sink_bincode<T: Serialize + DeserializeOwned + 'static>(
         &self,
-        port: ExternalBincodeSink<T>,
+        port: ExternalSink<T>,
     ) -> Pin<Box<dyn Sink<T, Error = Error>>> {
         self.externals
             .get(&port.process_id)
@@ -403,7 +439,7 @@ impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
 
     pub async fn connect_source_bincode<T: Serialize + DeserializeOwned + 'static>(
         &self,
-        port: ExternalBincodeStream<T>,
+        port: ExternalStream<T>,
     ) -> Pin<Box<dyn Stream<Item = T>>> {
         self.externals
             .get(&port.process_id)
@@ -411,4 +447,40 @@ impl<'a, D: Deploy<'a>> DeployResult<'a, D> {
             .as_bincode_source(port.port_id)
             .await
     }
+
+    /// Connects to an external input port whose messages are encoded with a [`Codec`]
+    /// other than bincode.
+    pub async fn connect_sink_codec<T: Serialize + DeserializeOwned + 'static, C: Codec>(
+        &self,
+        port: ExternalSink<T, C>,
+    ) -> Pin<Box<dyn Sink<T, Error = Error>>> {
+        let sink = self
+            .externals
+            .get(&port.process_id)
+            .unwrap()
+            .as_bytes_sink(port.port_id)
+            .await;
+
+        Box::pin(sink.with(|item: T| async move {
+            C::encode(&item)
+                .map(Bytes::from)
+                .map_err(|e| Error::other(format!("{:?}", e)))
+        }))
+    }
+
+    /// Connects to an external output port whose messages are encoded with a [`Codec`]
+    /// other than bincode.
+    pub async fn connect_source_codec<T: Serialize + DeserializeOwned + 'static, C: Codec>(
+        &self,
+        port: ExternalStream<T, C>,
+    ) -> Pin<Box<dyn Stream<Item = T>>> {
+        let source = self
+            .externals
+            .get(&port.process_id)
+            .unwrap()
+            .as_bytes_source(port.port_id)
+            .await;
+
+        Box::pin(source.map(|item| C::decode(&item).unwrap()))
+    }
 }
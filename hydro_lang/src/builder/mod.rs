@@ -2,6 +2,7 @@ use std::any::type_name;
 use std::cell::RefCell;
 use std::marker::PhantomData;
 use std::rc::Rc;
+use std::sync::Once;
 
 #[cfg(feature = "build")]
 use compiled::CompiledFlow;
@@ -100,6 +101,41 @@ impl Drop for FlowBuilder<'_> {
     }
 }
 
+/// Teaches [`quote_type`] the public paths of types that `std::any::type_name` reports
+/// through private modules, such as lattice accumulators and `BTreeMap` / `BTreeSet`.
+fn register_private_reexports() {
+    static REGISTERED: Once = Once::new();
+    REGISTERED.call_once(|| {
+        for module in [
+            "conflict",
+            "dom_pair",
+            "ord",
+            "pair",
+            "point",
+            "vec_union",
+            "with_bot",
+            "with_top",
+        ] {
+            add_private_reexport(vec!["lattices", module], vec!["lattices"]);
+            // Stageleft may already have rewritten `lattices` to point into some crate's
+            // `__deps`, so we map back to `lattices` and let that rewrite apply again.
+            add_private_reexport(
+                vec!["*", "__staged", "__deps", "lattices", module],
+                vec!["lattices"],
+            );
+        }
+
+        add_private_reexport(
+            vec!["std", "collections", "btree", "map"],
+            vec!["std", "collections", "btree_map"],
+        );
+        add_private_reexport(
+            vec!["std", "collections", "btree", "set"],
+            vec!["std", "collections", "btree_set"],
+        );
+    });
+}
+
 impl QuotedContext for FlowBuilder<'_> {
     fn create() -> Self {
         FlowBuilder::new()
@@ -112,6 +148,8 @@ impl<'a> FlowBuilder<'a> {
         reason = "call `new` explicitly, not `default`"
     )]
     pub fn new() -> FlowBuilder<'a> {
+        register_private_reexports();
+
         FlowBuilder {
             flow_state: Rc::new(RefCell::new(FlowStateInner {
                 leaves: Some(vec![]),
@@ -212,8 +250,10 @@ impl<'a> FlowBuilder<'a> {
     pub fn finalize(mut self) -> built::BuiltFlow<'a> {
         self.finalized = true;
 
+        let ir = self.flow_state.borrow_mut().leaves.take().unwrap();
         built::BuiltFlow {
-            ir: self.flow_state.borrow_mut().leaves.take().unwrap(),
+            choice_points: crate::nondet::choice_points(&ir),
+            ir,
             process_id_name: self.processes.replace(vec![]),
             cluster_id_name: self.clusters.replace(vec![]),
             external_id_name: self.externals.replace(vec![]),
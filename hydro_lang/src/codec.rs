@@ -0,0 +1,118 @@
+//! Wire formats for the messages sent between locations.
+//!
+//! Network channels created with [`Stream::send_bincode`](crate::Stream::send_bincode)
+//! always use [`Bincode`]. To pick a different format for a channel or an external port,
+//! pass a codec to [`Stream::send_with_codec`](crate::Stream::send_with_codec),
+//! [`Stream::send_external_with_codec`](crate::Stream::send_external_with_codec), or
+//! [`ExternalProcess::source_external_with_codec`](crate::ExternalProcess::source_external_with_codec).
+//! Raw bytes can still be exchanged with `send_bytes`, which does not use a codec at all.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// A format for encoding the messages sent over the network.
+///
+/// Codecs are stateless: the encoding functions are invoked through the type, so that the
+/// generated code for a channel only needs to know the name of the codec. Because the type
+/// is spliced into the generated program, a user-supplied codec must be a public type that
+/// is reachable from the crate containing the flow.
+///
+/// ```rust,ignore
+/// pub struct Cbor;
+///
+/// impl Codec for Cbor {
+///     type Error = ciborium::ser::Error<std::io::Error>;
+///     // ...
+/// }
+///
+/// stream.send_with_codec(&other, Cbor)
+/// ```
+pub trait Codec {
+    /// The error returned when encoding or decoding fails.
+    type Error: std::fmt::Debug;
+
+    /// Encodes a single message.
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    /// Decodes a single message that was produced by [`Codec::encode`].
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// The [`bincode`] 1.x format, which is compact and fast but is only practical to decode
+/// from Rust. This is the format used by `send_bincode`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bincode;
+
+impl Codec for Bincode {
+    type Error = bincode::Error;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        bincode::serialize(value)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// The [`postcard`] format, which uses variable-length integers and is usually smaller than
+/// [`Bincode`] for messages containing many small numbers.
+#[cfg(feature = "postcard")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postcard")))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Codec for Postcard {
+    type Error = postcard::Error;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        postcard::to_stdvec(value)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        postcard::from_bytes(bytes)
+    }
+}
+
+/// The JSON format, which is much larger than the binary formats but can be read by humans
+/// and produced by peers that are not written in Rust.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+impl Codec for Json {
+    type Error = serde_json::Error;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<C: Codec>() {
+        let value = (42u64, "hello".to_string(), vec![Some(1i32), None]);
+        let bytes = C::encode(&value).unwrap();
+        let decoded: (u64, String, Vec<Option<i32>>) = C::decode(&bytes).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn codecs_round_trip() {
+        round_trip::<Bincode>();
+        #[cfg(feature = "postcard")]
+        round_trip::<Postcard>();
+        round_trip::<Json>();
+    }
+
+    #[test]
+    fn json_is_readable() {
+        assert_eq!(Json::encode(&(1, "a")).unwrap(), br#"[1,"a"]"#);
+    }
+}
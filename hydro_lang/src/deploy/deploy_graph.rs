@@ -13,8 +13,8 @@ use hydro_deploy::custom_service::CustomClientPort;
 use hydro_deploy::rust_crate::RustCrateService;
 use hydro_deploy::rust_crate::ports::{DemuxSink, RustCrateSink, RustCrateSource, TaggedSource};
 use hydro_deploy::rust_crate::tracing_options::TracingOptions;
-use hydro_deploy::{CustomService, Deployment, Host, RustCrate, TracingResults};
-use hydro_deploy_integration::{ConnectedSink, ConnectedSource};
+use hydro_deploy::{CustomService, Deployment, Host, RustCrate, Service, TracingResults};
+use hydro_deploy_integration::{ConnectedSink, ConnectedSource, MembershipUpdate};
 use nameof::name_of;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
@@ -23,7 +23,10 @@ use tokio::sync::RwLock;
 
 use super::trybuild::{HYDRO_RUNTIME_FEATURES, create_graph_trybuild};
 use super::{ClusterSpec, Deploy, ExternalSpec, IntoProcessSpec, Node, ProcessSpec, RegisterPort};
+use crate::builder::deploy::DeployResult;
 use crate::deploy_runtime::*;
+use crate::location::cluster::MembershipStream;
+use crate::location::{Cluster, ClusterId, Location, LocationId};
 
 pub struct HydroDeploy {}
 
@@ -375,6 +378,100 @@ impl<'a> Deploy<'a> for HydroDeploy {
     fn cluster_self_id(_env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a {
         cluster_self_id(RuntimeData::new("__hydro_lang_trybuild_cli"))
     }
+
+    fn cluster_membership(
+        _env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, MembershipStream, ()> + Copy + 'a {
+        cluster_membership(RuntimeData::new("__hydro_lang_trybuild_cli"), of_cluster)
+    }
+}
+
+/// Runtime changes to the membership of a deployed cluster.
+///
+/// Hydro Deploy connects every port of a [`Deployment`] before it starts, so a running
+/// deployment cannot launch new cluster instances. The members of a cluster are fixed when
+/// it is deployed: they can be masked (reported as having left) and unmasked again, or
+/// removed for good, which also stops them.
+impl<'a> DeployResult<'a, HydroDeploy> {
+    /// Unmasks `member` of `cluster`, so that every location observes a
+    /// [`MembershipEvent::Joined`](crate::location::MembershipEvent) for it in
+    /// [`Location::source_cluster_membership`].
+    ///
+    /// This only changes which of the instances the cluster was deployed with are reported as
+    /// members; it does not launch a new instance. The member must already be deployed, and it
+    /// keeps running whether or not it is masked.
+    pub async fn unmask_cluster_member<C>(&self, cluster: &Cluster<'a, C>, member: ClusterId<C>) {
+        self.update_cluster_membership(cluster, member, true).await;
+    }
+
+    /// Masks `member` of `cluster`, so that every location observes a
+    /// [`MembershipEvent::Left`](crate::location::MembershipEvent) for it in
+    /// [`Location::source_cluster_membership`].
+    ///
+    /// This does not stop the member, which keeps running and can still send and receive
+    /// messages. See [`DeployResult::unmask_cluster_member`] to report it as joined again.
+    pub async fn mask_cluster_member<C>(&self, cluster: &Cluster<'a, C>, member: ClusterId<C>) {
+        self.update_cluster_membership(cluster, member, false).await;
+    }
+
+    /// Removes `member` from `cluster`: every location observes a
+    /// [`MembershipEvent::Left`](crate::location::MembershipEvent) for it, and then its
+    /// process is stopped. A removed member cannot be added back.
+    pub async fn remove_cluster_member<C>(&self, cluster: &Cluster<'a, C>, member: ClusterId<C>) {
+        self.update_cluster_membership(cluster, member, false).await;
+
+        self.get_cluster(cluster).members()[member.raw_id as usize]
+            .underlying()
+            .write()
+            .await
+            .stop()
+            .await
+            .unwrap();
+    }
+
+    async fn update_cluster_membership<C>(
+        &self,
+        cluster: &Cluster<'a, C>,
+        member: ClusterId<C>,
+        joined: bool,
+    ) {
+        let cluster_id = match cluster.id() {
+            LocationId::Cluster(id) => id,
+            _ => panic!("Cluster ID expected"),
+        };
+
+        assert!(
+            (member.raw_id as usize) < self.get_cluster(cluster).members().len(),
+            "{} is not one of the deployed members of the cluster",
+            member
+        );
+
+        let update = MembershipUpdate {
+            cluster: cluster_id,
+            member: member.raw_id,
+            joined,
+        };
+
+        let services = self
+            .get_all_processes()
+            .map(|(_, _, process)| process.underlying())
+            .chain(self.get_all_clusters().flat_map(|(_, _, cluster)| {
+                cluster
+                    .members()
+                    .into_iter()
+                    .map(|member| member.underlying())
+            }))
+            .collect::<Vec<_>>();
+
+        for service in services {
+            service
+                .read()
+                .await
+                .send_membership_update(&update)
+                .unwrap();
+        }
+    }
 }
 
 pub trait DeployCrateWrapper {
@@ -9,6 +9,9 @@ use serde::Serialize;
 use serde::de::DeserializeOwned;
 use stageleft::QuotedWithContext;
 
+use crate::location::cluster::MembershipStream;
+use crate::location::cluster::membership::StaticClusterMembership;
+
 #[cfg(stageleft_runtime)]
 #[cfg(feature = "deploy")]
 pub(crate) mod trybuild;
@@ -32,6 +35,16 @@ pub mod deploy_graph;
 #[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
 pub use deploy_graph::*;
 
+#[cfg(stageleft_runtime)]
+#[cfg(feature = "deploy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
+pub mod sim;
+
+#[cfg(stageleft_runtime)]
+#[cfg(feature = "deploy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deploy")))]
+pub use sim::{SimCounterexample, SimDeploy, Simulation};
+
 pub trait Deploy<'a> {
     type InstantiateEnv;
     type CompileEnv;
@@ -63,6 +76,14 @@ pub trait Deploy<'a> {
         panic!("No trivial external process")
     }
 
+    /// Called before the flow is instantiated with its non-deterministic choice points, so
+    /// that deployments which explore those choices (such as [`SimDeploy`]) can record them.
+    fn register_choice_points(
+        _env: &mut Self::InstantiateEnv,
+        _choice_points: Vec<crate::nondet::ChoicePoint>,
+    ) {
+    }
+
     fn allocate_process_port(process: &Self::Process) -> Self::Port;
     fn allocate_cluster_port(cluster: &Self::Cluster) -> Self::Port;
     fn allocate_external_port(external: &Self::ExternalProcess) -> Self::Port;
@@ -156,6 +177,17 @@ pub trait Deploy<'a> {
         of_cluster: usize,
     ) -> impl QuotedWithContext<'a, &'a [u32], ()> + Copy + 'a;
     fn cluster_self_id(env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a;
+
+    /// Evaluates to a new [`MembershipStream`] for the given cluster, which starts with the
+    /// members the cluster was deployed with and then follows any changes made at runtime.
+    ///
+    /// By default, the members the cluster was deployed with never change.
+    fn cluster_membership(
+        _env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, MembershipStream, ()> + Copy + 'a {
+        StaticClusterMembership { id: of_cluster }
+    }
 }
 
 pub trait ProcessSpec<'a, D>
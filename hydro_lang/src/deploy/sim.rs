@@ -0,0 +1,958 @@
+//! An in-process, deterministic simulation backend for Hydro flows.
+//!
+//! Instead of launching one binary per location and wiring them up with real sockets, the
+//! [`SimDeploy`] backend compiles every process and cluster of a flow into a single binary.
+//! Network channels are backed by in-memory queues, and a scheduler driven by a seeded RNG
+//! decides at each step whether to run a tick on some location or to deliver a pending
+//! message. Re-running a [`Simulation`] with the same seed replays exactly the same schedule.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::future::Future;
+use std::io::{Error, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+
+use bytes::Bytes;
+use dfir_lang::graph::DfirGraph;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use proc_macro2::Span;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use stageleft::{QuotedWithContext, RuntimeData};
+
+use super::trybuild::{TrybuildConfig, compile_dfir_expr, trybuild_crate_name, write_trybuild_bin};
+use super::{ClusterSpec, Deploy, ExternalSpec, Node, ProcessSpec, RegisterPort};
+use crate::builder::deploy::DeployResult;
+use crate::location::cluster::MembershipStream;
+use crate::location::{Cluster, ClusterId, ExternalProcess, Location, LocationId, MembershipEvent};
+use crate::nondet::ChoicePoint;
+use crate::sim_runtime::*;
+use crate::{FlowBuilder, Process};
+
+/// Upper bound on scheduling decisions, so that flows which never quiesce still terminate.
+pub const DEFAULT_SIM_MAX_STEPS: usize = 100_000;
+
+pub struct SimDeploy {}
+
+impl<'a> Deploy<'a> for SimDeploy {
+    type InstantiateEnv = Simulation;
+    type CompileEnv = ();
+    type Process = SimNode;
+    type Cluster = SimCluster;
+    type ExternalProcess = SimExternal;
+    type Meta = HashMap<usize, Vec<u32>>;
+    type GraphId = ();
+    type Port = String;
+    type ExternalRawPort = String;
+
+    fn register_choice_points(env: &mut Simulation, choice_points: Vec<ChoicePoint>) {
+        env.inner.borrow_mut().choice_points.extend(choice_points);
+    }
+
+    fn allocate_process_port(process: &Self::Process) -> Self::Port {
+        process.next_port()
+    }
+
+    fn allocate_cluster_port(cluster: &Self::Cluster) -> Self::Port {
+        cluster.next_port()
+    }
+
+    fn allocate_external_port(external: &Self::ExternalProcess) -> Self::Port {
+        external.next_port()
+    }
+
+    fn o2o_sink_source(
+        _env: &(),
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _p2: &Self::Process,
+        p2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        sim_o2o(RuntimeData::new("__hydro_lang_sim_ports"), p2_port.as_str())
+    }
+
+    fn o2o_connect(
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _p2: &Self::Process,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        // Channels are addressed by the receiving port, so there is nothing to connect.
+        Box::new(|| {})
+    }
+
+    fn o2m_sink_source(
+        _env: &(),
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        sim_o2m(RuntimeData::new("__hydro_lang_sim_ports"), c2_port.as_str())
+    }
+
+    fn o2m_connect(
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        _c2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        Box::new(|| {})
+    }
+
+    fn m2o_sink_source(
+        _env: &(),
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _p2: &Self::Process,
+        p2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        sim_m2o(RuntimeData::new("__hydro_lang_sim_ports"), p2_port.as_str())
+    }
+
+    fn m2o_connect(
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _p2: &Self::Process,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        Box::new(|| {})
+    }
+
+    fn m2m_sink_source(
+        _env: &(),
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        c2_port: &Self::Port,
+    ) -> (syn::Expr, syn::Expr) {
+        sim_m2m(RuntimeData::new("__hydro_lang_sim_ports"), c2_port.as_str())
+    }
+
+    fn m2m_connect(
+        _c1: &Self::Cluster,
+        _c1_port: &Self::Port,
+        _c2: &Self::Cluster,
+        _c2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        Box::new(|| {})
+    }
+
+    fn e2o_source(
+        _compile_env: &Self::CompileEnv,
+        _p1: &Self::ExternalProcess,
+        _p1_port: &Self::Port,
+        _p2: &Self::Process,
+        p2_port: &Self::Port,
+    ) -> syn::Expr {
+        sim_e2o(RuntimeData::new("__hydro_lang_sim_ports"), p2_port.as_str())
+    }
+
+    fn e2o_connect(
+        p1: &Self::ExternalProcess,
+        p1_port: &Self::Port,
+        _p2: &Self::Process,
+        p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        let p1 = p1.clone();
+        let p1_port = p1_port.clone();
+        let p2_port = p2_port.clone();
+
+        Box::new(move || {
+            p1.input_routes.borrow_mut().insert(p1_port, p2_port);
+        })
+    }
+
+    fn o2e_sink(
+        _compile_env: &Self::CompileEnv,
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _p2: &Self::ExternalProcess,
+        p2_port: &Self::Port,
+    ) -> syn::Expr {
+        sim_o2e(RuntimeData::new("__hydro_lang_sim_ports"), p2_port.as_str())
+    }
+
+    fn o2e_connect(
+        _p1: &Self::Process,
+        _p1_port: &Self::Port,
+        _p2: &Self::ExternalProcess,
+        _p2_port: &Self::Port,
+    ) -> Box<dyn FnOnce()> {
+        Box::new(|| {})
+    }
+
+    fn cluster_ids(
+        _env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, &'a [u32], ()> + Copy + 'a {
+        sim_cluster_members(RuntimeData::new("__hydro_lang_sim_ports"), of_cluster)
+    }
+
+    fn cluster_self_id(_env: &Self::CompileEnv) -> impl QuotedWithContext<'a, u32, ()> + Copy + 'a {
+        sim_cluster_self_id(RuntimeData::new("__hydro_lang_sim_ports"))
+    }
+
+    fn cluster_membership(
+        _env: &Self::CompileEnv,
+        of_cluster: usize,
+    ) -> impl QuotedWithContext<'a, MembershipStream, ()> + Copy + 'a {
+        sim_cluster_membership(RuntimeData::new("__hydro_lang_sim_ports"), of_cluster)
+    }
+}
+
+struct SimLocation {
+    members: Option<Vec<u32>>,
+    item: syn::Item,
+}
+
+struct SimulationInner {
+    seed: u64,
+    max_steps: usize,
+    max_drops: usize,
+    name_hint: Option<String>,
+    locations: BTreeMap<usize, SimLocation>,
+    inputs: BTreeMap<SimChannel, Vec<Bytes>>,
+    membership_updates: Vec<(usize, u32, MembershipEvent)>,
+    choice_points: Vec<ChoicePoint>,
+    binary: Option<PathBuf>,
+    output: Option<SimOutput>,
+}
+
+/// The instantiation environment for [`SimDeploy`], which collects the compiled locations of a
+/// flow and runs them together in a single simulation binary.
+///
+/// The simulation is compiled and executed lazily, the first time an external output is
+/// connected or [`Simulation::run`] is called. Any external inputs must be sent before then.
+#[derive(Clone)]
+pub struct Simulation {
+    inner: Rc<RefCell<SimulationInner>>,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Simulation {
+            inner: Rc::new(RefCell::new(SimulationInner {
+                seed: 0,
+                max_steps: DEFAULT_SIM_MAX_STEPS,
+                max_drops: 0,
+                name_hint: None,
+                locations: BTreeMap::new(),
+                inputs: BTreeMap::new(),
+                membership_updates: Vec::new(),
+                choice_points: Vec::new(),
+                binary: None,
+                output: None,
+            })),
+        }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.inner.borrow_mut().seed = seed;
+        self
+    }
+
+    pub fn with_max_steps(self, max_steps: usize) -> Self {
+        self.inner.borrow_mut().max_steps = max_steps;
+        self
+    }
+
+    /// Allows randomized schedules to drop up to `max_drops` messages sent between locations,
+    /// to test flows that must tolerate connection failures. Inputs from external processes
+    /// are always delivered, and the canonical schedule never drops messages.
+    pub fn with_message_loss(self, max_drops: usize) -> Self {
+        self.inner.borrow_mut().max_drops = max_drops;
+        self
+    }
+
+    /// Schedules a change to the membership of `cluster`, which is observed by every location
+    /// that follows the cluster's membership at a point in the schedule chosen by the
+    /// simulator. Updates to the same cluster are observed in the order they were made. Like
+    /// external inputs, updates must be made before the simulation runs.
+    pub fn update_cluster_membership<C>(
+        &self,
+        cluster: &Cluster<'_, C>,
+        member: ClusterId<C>,
+        event: MembershipEvent,
+    ) {
+        let cluster_id = match cluster.id() {
+            LocationId::Cluster(id) => id,
+            _ => panic!("Cluster ID expected"),
+        };
+
+        let mut inner = self.inner.borrow_mut();
+        assert!(
+            inner.output.is_none(),
+            "Cannot update the membership of a simulation that has already run"
+        );
+        inner
+            .membership_updates
+            .push((cluster_id, member.raw_id, event));
+    }
+
+    pub fn with_name_hint(self, name_hint: impl Into<String>) -> Self {
+        self.inner.borrow_mut().name_hint = Some(name_hint.into());
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.inner.borrow().seed
+    }
+
+    /// The non-deterministic choice points of the flows deployed to this simulation, which
+    /// are explored by [`Simulation::explore`].
+    pub fn choice_points(&self) -> Vec<ChoicePoint> {
+        self.inner.borrow().choice_points.clone()
+    }
+
+    fn add_location(
+        &self,
+        id: usize,
+        members: Option<Vec<u32>>,
+        graph: DfirGraph,
+        extra_stmts: Vec<syn::Stmt>,
+    ) {
+        let (crate_name, is_test) = trybuild_crate_name();
+        let dfir_expr = compile_dfir_expr(graph, &crate_name, is_test);
+        let fn_ident = syn::Ident::new(&format!("__hydro_sim_location_{}", id), Span::call_site());
+
+        let item: syn::Item = syn::parse_quote! {
+            #[allow(unused)]
+            fn #fn_ident<'a>(__hydro_lang_sim_ports: &'a hydro_lang::sim_runtime::SimPorts) -> hydro_lang::runtime_support::dfir_rs::scheduled::graph::Dfir<'a> {
+                #(#extra_stmts)*
+                #dfir_expr
+            }
+        };
+
+        let mut inner = self.inner.borrow_mut();
+        assert!(
+            inner.binary.is_none(),
+            "Cannot add locations to a simulation that has already been compiled"
+        );
+        inner.locations.insert(id, SimLocation { members, item });
+    }
+
+    fn send_input(&self, channel: SimChannel, data: Bytes) {
+        let mut inner = self.inner.borrow_mut();
+        assert!(
+            inner.output.is_none(),
+            "Cannot send inputs to a simulation that has already run"
+        );
+        inner.inputs.entry(channel).or_default().push(data);
+    }
+
+    fn compile(&self) -> PathBuf {
+        if let Some(binary) = &self.inner.borrow().binary {
+            return binary.clone();
+        }
+
+        let inner = self.inner.borrow();
+        let location_fns = inner.locations.values().map(|l| &l.item);
+        let location_ids = inner.locations.keys().copied();
+        let location_fn_idents = inner
+            .locations
+            .keys()
+            .map(|id| syn::Ident::new(&format!("__hydro_sim_location_{}", id), Span::call_site()));
+
+        let generated_code: syn::File = syn::parse_quote! {
+            #![allow(unused_imports, unused_crate_dependencies, missing_docs, non_snake_case)]
+            use hydro_lang::*;
+            use hydro_lang::runtime_support::dfir_rs as __root_dfir_rs;
+
+            #(#location_fns)*
+
+            #[hydro_lang::runtime_support::tokio::main(crate = "hydro_lang::runtime_support::tokio", flavor = "current_thread")]
+            async fn main() {
+                hydro_lang::runtime_support::sim::run_main(|fleet| {
+                    #(fleet.add_location(#location_ids, #location_fn_idents);)*
+                }).await;
+            }
+        };
+
+        let (bin_name, config) = write_trybuild_bin(generated_code, &inner.name_hint);
+        drop(inner);
+
+        let binary = build_sim_binary(&bin_name, &config);
+        self.inner.borrow_mut().binary = Some(binary.clone());
+        binary
+    }
+
+    fn run_config(&self, seed: u64, schedule: Option<Vec<SimEvent>>) -> SimOutput {
+        let binary = self.compile();
+
+        let config = {
+            let inner = self.inner.borrow();
+            SimConfig {
+                seed,
+                max_steps: inner.max_steps,
+                clusters: inner
+                    .locations
+                    .iter()
+                    .filter_map(|(id, l)| l.members.clone().map(|m| (*id, m)))
+                    .collect(),
+                inputs: inner
+                    .inputs
+                    .iter()
+                    .map(|(channel, data)| (channel.clone(), data.clone()))
+                    .collect(),
+                schedule,
+                max_drops: inner.max_drops,
+                membership_updates: inner.membership_updates.clone(),
+            }
+        };
+
+        let mut child = Command::new(&binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to launch simulation");
+
+        let mut stdin = child.stdin.take().unwrap();
+        writeln!(stdin, "{}", serde_json::to_string(&config).unwrap()).unwrap();
+        drop(stdin);
+
+        let result = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        assert!(
+            result.status.success(),
+            "Simulation with seed {} failed ({}), stdout:\n{}",
+            seed,
+            result.status,
+            stdout
+        );
+
+        let output_line = stdout
+            .lines()
+            .find_map(|line| line.strip_prefix(SIM_OUTPUT_PREFIX))
+            .expect("Simulation did not report its output");
+        serde_json::from_str(output_line).unwrap()
+    }
+
+    /// Compiles (if needed) and runs the simulation with the given seed, without caching the
+    /// result. This can be used to explore different schedules of the same flow.
+    pub fn run_with_seed(&self, seed: u64) -> SimOutput {
+        self.run_config(seed, None)
+    }
+
+    /// Replays the given scheduling decisions, then finishes the run with a canonical schedule
+    /// that delivers all in-flight messages before running a tick on every location. Events
+    /// that are not enabled when they are reached are skipped, so any subsequence of a trace
+    /// is a valid schedule.
+    pub fn run_schedule(&self, schedule: &[SimEvent]) -> SimOutput {
+        self.run_config(0, Some(schedule.to_vec()))
+    }
+
+    /// Explores the non-deterministic choices of the flow (batch boundaries, message
+    /// interleavings and, if enabled, message losses) by running the canonical schedule and
+    /// then one randomized schedule per seed, checking each output with `check`. If the flow
+    /// has no [choice points](Simulation::choice_points) and no membership changes, every
+    /// schedule produces the same outputs, so only the canonical schedule is checked.
+    ///
+    /// When a check fails, the schedule that led to the failure is minimized by repeatedly
+    /// removing decisions while the failure still reproduces, and the shortest schedule
+    /// found is returned as a [`SimCounterexample`] that can be replayed with
+    /// [`Simulation::run_schedule`].
+    pub fn explore(
+        &self,
+        seeds: impl IntoIterator<Item = u64>,
+        check: impl Fn(&SimOutput) -> Result<(), String>,
+    ) -> Result<(), SimCounterexample> {
+        if let Err(message) = check(&self.run_schedule(&[])) {
+            return Err(SimCounterexample {
+                seed: None,
+                schedule: vec![],
+                message,
+            });
+        }
+
+        let deterministic = {
+            let inner = self.inner.borrow();
+            inner.choice_points.is_empty() && inner.membership_updates.is_empty()
+        };
+        if deterministic {
+            return Ok(());
+        }
+
+        for seed in seeds {
+            let output = self.run_with_seed(seed);
+            if let Err(message) = check(&output) {
+                let schedule = minimize_schedule(output.trace, |schedule| {
+                    check(&self.run_schedule(schedule)).is_err()
+                });
+
+                let message = check(&self.run_schedule(&schedule))
+                    .err()
+                    .unwrap_or(message);
+
+                return Err(SimCounterexample {
+                    seed: Some(seed),
+                    schedule,
+                    message,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the simulation with the configured seed. The result is cached, so this is only
+    /// executed once per [`Simulation`].
+    pub fn run(&self) -> SimOutput {
+        if let Some(output) = &self.inner.borrow().output {
+            return output.clone();
+        }
+
+        let output = self.run_with_seed(self.seed());
+        self.inner.borrow_mut().output = Some(output.clone());
+        output
+    }
+
+    /// The sequence of scheduling decisions taken by the simulation.
+    pub fn trace(&self) -> Vec<SimEvent> {
+        self.run().trace
+    }
+
+    /// Whether the simulation ran until no more work was available, rather than hitting the
+    /// step limit.
+    pub fn is_quiescent(&self) -> bool {
+        self.run().quiescent
+    }
+
+    fn external_output(&self, port: &str) -> Vec<Bytes> {
+        self.run().outputs.get(port).cloned().unwrap_or_default()
+    }
+}
+
+/// A schedule under which a check passed to [`Simulation::explore`] fails.
+#[derive(Clone, Debug)]
+pub struct SimCounterexample {
+    /// The seed of the randomized run that first found the failure, or `None` if the
+    /// canonical schedule already fails.
+    pub seed: Option<u64>,
+    /// A minimized schedule that reproduces the failure with [`Simulation::run_schedule`].
+    pub schedule: Vec<SimEvent>,
+    /// The error reported by the check on the minimized schedule.
+    pub message: String,
+}
+
+impl Display for SimCounterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.seed {
+            Some(seed) => writeln!(
+                f,
+                "check failed: {} (found with seed {})",
+                self.message, seed
+            )?,
+            None => writeln!(
+                f,
+                "check failed on the canonical schedule: {}",
+                self.message
+            )?,
+        }
+
+        for (i, event) in self.schedule.iter().enumerate() {
+            writeln!(f, "  {}: {:?}", i, event)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for SimCounterexample {}
+
+/// Shrinks a failing schedule with delta debugging, removing chunks of decisions (halving the
+/// chunk size whenever no chunk can be removed) as long as `fails` still holds.
+fn minimize_schedule(
+    mut schedule: Vec<SimEvent>,
+    mut fails: impl FnMut(&[SimEvent]) -> bool,
+) -> Vec<SimEvent> {
+    let mut granularity = 2;
+    while !schedule.is_empty() {
+        let chunk_size = schedule.len().div_ceil(granularity);
+        let reduced = (0..schedule.len()).step_by(chunk_size).find_map(|start| {
+            let end = (start + chunk_size).min(schedule.len());
+            let candidate = [&schedule[..start], &schedule[end..]].concat();
+            fails(&candidate).then_some(candidate)
+        });
+
+        if let Some(candidate) = reduced {
+            schedule = candidate;
+            granularity = (granularity - 1).max(2);
+        } else if chunk_size == 1 {
+            break;
+        } else {
+            granularity = (granularity * 2).min(schedule.len());
+        }
+    }
+
+    schedule
+}
+
+fn build_sim_binary(bin_name: &str, config: &TrybuildConfig) -> PathBuf {
+    let mut command = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()));
+    command
+        .current_dir(&config.project_dir)
+        .arg("build")
+        .arg("--bin")
+        .arg(bin_name)
+        .arg("--target-dir")
+        .arg(&config.target_dir)
+        .arg("--no-default-features")
+        .arg("--config")
+        .arg("build.incremental = false")
+        .env("RUSTFLAGS", &config.cfgs)
+        .stdout(Stdio::null());
+
+    if let Some(features) = &config.features {
+        command.arg("--features").arg(features.join(","));
+    }
+
+    let status = command.status().expect("Failed to run cargo");
+    assert!(
+        status.success(),
+        "Failed to compile simulation {}",
+        bin_name
+    );
+
+    config
+        .target_dir
+        .join("debug")
+        .join(format!("{}{}", bin_name, std::env::consts::EXE_SUFFIX))
+}
+
+#[derive(Clone)]
+pub struct SimNode {
+    id: usize,
+    next_port: Rc<RefCell<usize>>,
+}
+
+impl Node for SimNode {
+    type Port = String;
+    type Meta = HashMap<usize, Vec<u32>>;
+    type InstantiateEnv = Simulation;
+
+    fn next_port(&self) -> String {
+        let next_port = *self.next_port.borrow();
+        *self.next_port.borrow_mut() += 1;
+
+        format!("location_{}_port_{}", self.id, next_port)
+    }
+
+    fn update_meta(&mut self, _meta: &Self::Meta) {}
+
+    fn instantiate(
+        &self,
+        env: &mut Self::InstantiateEnv,
+        _meta: &mut Self::Meta,
+        graph: DfirGraph,
+        extra_stmts: Vec<syn::Stmt>,
+    ) {
+        env.add_location(self.id, None, graph, extra_stmts);
+    }
+}
+
+impl ProcessSpec<'_, SimDeploy> for () {
+    fn build(self, id: usize, _name_hint: &str) -> SimNode {
+        SimNode {
+            id,
+            next_port: Rc::new(RefCell::new(0)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SimCluster {
+    id: usize,
+    count: usize,
+    next_port: Rc<RefCell<usize>>,
+}
+
+impl SimCluster {
+    pub fn members(&self) -> Vec<u32> {
+        (0..self.count as u32).collect()
+    }
+}
+
+impl Node for SimCluster {
+    type Port = String;
+    type Meta = HashMap<usize, Vec<u32>>;
+    type InstantiateEnv = Simulation;
+
+    fn next_port(&self) -> String {
+        let next_port = *self.next_port.borrow();
+        *self.next_port.borrow_mut() += 1;
+
+        format!("location_{}_port_{}", self.id, next_port)
+    }
+
+    fn update_meta(&mut self, _meta: &Self::Meta) {}
+
+    fn instantiate(
+        &self,
+        env: &mut Self::InstantiateEnv,
+        meta: &mut Self::Meta,
+        graph: DfirGraph,
+        extra_stmts: Vec<syn::Stmt>,
+    ) {
+        meta.insert(self.id, self.members());
+        env.add_location(self.id, Some(self.members()), graph, extra_stmts);
+    }
+}
+
+/// A cluster in a simulation is specified by its number of members.
+impl ClusterSpec<'_, SimDeploy> for usize {
+    fn build(self, id: usize, _name_hint: &str) -> SimCluster {
+        SimCluster {
+            id,
+            count: self,
+            next_port: Rc::new(RefCell::new(0)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SimExternal {
+    id: usize,
+    next_port: Rc<RefCell<usize>>,
+    simulation: Rc<RefCell<Option<Simulation>>>,
+    allocated_ports: Rc<RefCell<HashMap<usize, String>>>,
+    /// Maps ports on this external to the port on the process that they send to.
+    input_routes: Rc<RefCell<HashMap<String, String>>>,
+}
+
+impl SimExternal {
+    fn simulation(&self) -> Simulation {
+        self.simulation
+            .borrow()
+            .clone()
+            .expect("External process has not been deployed")
+    }
+}
+
+impl<'a> RegisterPort<'a, SimDeploy> for SimExternal {
+    fn register(&self, key: usize, port: <SimDeploy as Deploy>::Port) {
+        self.allocated_ports.borrow_mut().insert(key, port);
+    }
+
+    fn raw_port(&self, key: usize) -> <SimDeploy as Deploy>::ExternalRawPort {
+        self.allocated_ports.borrow().get(&key).unwrap().clone()
+    }
+
+    fn as_bytes_sink(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<Bytes, Error = Error>>>> + 'a {
+        let simulation = self.simulation();
+        let channel = SimChannel {
+            from_location: self.id,
+            from_member: None,
+            port: self
+                .input_routes
+                .borrow()
+                .get(&self.raw_port(key))
+                .unwrap()
+                .clone(),
+            to_member: None,
+        };
+
+        async move {
+            Box::pin(SimSink::new(move |data| {
+                simulation.send_input(channel.clone(), data)
+            })) as Pin<Box<dyn Sink<Bytes, Error = Error>>>
+        }
+    }
+
+    fn as_bincode_sink<T: Serialize + 'static>(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Sink<T, Error = Error>>>> + 'a {
+        let sink = self.as_bytes_sink(key);
+        async move {
+            Box::pin(
+                sink.await
+                    .with(|item| async move { Ok(bincode::serialize(&item).unwrap().into()) }),
+            ) as Pin<Box<dyn Sink<T, Error = Error>>>
+        }
+    }
+
+    fn as_bytes_source(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = Bytes>>>> + 'a {
+        let simulation = self.simulation();
+        let port = self.raw_port(key);
+        async move {
+            Box::pin(futures::stream::iter(simulation.external_output(&port)))
+                as Pin<Box<dyn Stream<Item = Bytes>>>
+        }
+    }
+
+    fn as_bincode_source<T: DeserializeOwned + 'static>(
+        &self,
+        key: usize,
+    ) -> impl Future<Output = Pin<Box<dyn Stream<Item = T>>>> + 'a {
+        let source = self.as_bytes_source(key);
+        async move {
+            Box::pin(
+                source
+                    .await
+                    .map(|item| bincode::deserialize(&item).unwrap()),
+            ) as Pin<Box<dyn Stream<Item = T>>>
+        }
+    }
+}
+
+impl Node for SimExternal {
+    type Port = String;
+    type Meta = HashMap<usize, Vec<u32>>;
+    type InstantiateEnv = Simulation;
+
+    fn next_port(&self) -> String {
+        let next_port = *self.next_port.borrow();
+        *self.next_port.borrow_mut() += 1;
+
+        format!("location_{}_port_{}", self.id, next_port)
+    }
+
+    fn update_meta(&mut self, _meta: &Self::Meta) {}
+
+    fn instantiate(
+        &self,
+        env: &mut Self::InstantiateEnv,
+        _meta: &mut Self::Meta,
+        _graph: DfirGraph,
+        _extra_stmts: Vec<syn::Stmt>,
+    ) {
+        *self.simulation.borrow_mut() = Some(env.clone());
+    }
+}
+
+impl ExternalSpec<'_, SimDeploy> for () {
+    fn build(self, id: usize, _name_hint: &str) -> SimExternal {
+        SimExternal {
+            id,
+            next_port: Rc::new(RefCell::new(0)),
+            simulation: Rc::new(RefCell::new(None)),
+            allocated_ports: Rc::new(RefCell::new(HashMap::new())),
+            input_routes: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+}
+
+/// Like [`crate::test_util::multi_location_test`], but runs the flow in a deterministic in-process
+/// [`Simulation`] with the given seed instead of deploying to localhost.
+pub async fn sim_multi_location_test<'a, T, C, B, O, R>(
+    seed: u64,
+    thunk: impl FnOnce(&FlowBuilder<'a>, &Process<'a, ()>) -> crate::Stream<T, Process<'a>, B, O, R>,
+    check: impl FnOnce(Pin<Box<dyn Stream<Item = T>>>) -> C,
+) where
+    T: Serialize + DeserializeOwned + 'static,
+    C: Future<Output = ()>,
+{
+    let mut simulation = Simulation::new().with_seed(seed);
+    let flow = FlowBuilder::new();
+    let process = flow.process::<()>();
+    let external = flow.external_process::<()>();
+    let out = thunk(&flow, &process);
+    let out_port = out.send_bincode_external(&external);
+    let nodes = flow
+        .with_remaining_processes(|| ())
+        .with_remaining_clusters(|| 4)
+        .with_external(&external, ())
+        .deploy(&mut simulation);
+
+    let external_out = nodes.connect_source_bincode(out_port).await;
+
+    check(external_out).await;
+}
+
+/// Like [`crate::test_util::stream_transform_test`], but runs the flow in a deterministic in-process
+/// [`Simulation`] with the given seed instead of deploying to localhost.
+pub async fn sim_stream_transform_test<'a, T, C, B, O, R>(
+    seed: u64,
+    thunk: impl FnOnce(&Process<'a>) -> crate::Stream<T, Process<'a>, B, O, R>,
+    check: impl FnOnce(Pin<Box<dyn Stream<Item = T>>>) -> C,
+) where
+    T: Serialize + DeserializeOwned + 'static,
+    C: Future<Output = ()>,
+{
+    let mut simulation = Simulation::new().with_seed(seed);
+    let flow = FlowBuilder::new();
+    let process = flow.process::<()>();
+    let external = flow.external_process::<()>();
+    let out = thunk(&process);
+    let out_port = out.send_bincode_external(&external);
+    let nodes = flow
+        .with_process(&process, ())
+        .with_external(&external, ())
+        .deploy(&mut simulation);
+
+    let external_out = nodes.connect_source_bincode(out_port).await;
+
+    check(external_out).await;
+}
+
+/// Builds a flow with `thunk`, which is given the external process to send outputs to, and
+/// deploys it to `simulation`, placing every process on its own simulated node and giving every
+/// cluster three members. Returns the deployed nodes along with whatever `thunk` returned,
+/// typically the ports to read outputs from.
+pub fn sim_deploy_test<'a, P>(
+    simulation: &mut Simulation,
+    thunk: impl FnOnce(&FlowBuilder<'a>, &ExternalProcess<'a, ()>) -> P,
+) -> (DeployResult<'a, SimDeploy>, P) {
+    let flow = FlowBuilder::new();
+    let external = flow.external_process::<()>();
+    let ports = thunk(&flow, &external);
+    let nodes = flow
+        .with_remaining_processes(|| ())
+        .with_remaining_clusters(|| 3)
+        .with_external(&external, ())
+        .deploy(simulation);
+
+    (nodes, ports)
+}
+
+/// Like [`sim_deploy_test`], for a flow with a single output stream: runs `simulation` until it
+/// quiesces and returns every element of the stream returned by `thunk`.
+pub async fn sim_collect_test<'a, T, L, B, O, R>(
+    simulation: &mut Simulation,
+    thunk: impl FnOnce(&FlowBuilder<'a>) -> crate::Stream<T, Process<'a, L>, B, O, R>,
+) -> Vec<T>
+where
+    T: Serialize + DeserializeOwned + 'static,
+{
+    let (nodes, port) = sim_deploy_test(simulation, |flow, external| {
+        thunk(flow).send_bincode_external(external)
+    });
+
+    nodes.connect_source_bincode(port).await.collect().await
+}
+
+/// Like [`sim_collect_test`], but checks the elements of the output stream under every schedule
+/// that [`Simulation::explore`] tries with the given seeds.
+pub fn sim_explore_test<'a, T, L, B, O, R>(
+    simulation: &mut Simulation,
+    seeds: impl IntoIterator<Item = u64>,
+    thunk: impl FnOnce(&FlowBuilder<'a>) -> crate::Stream<T, Process<'a, L>, B, O, R>,
+    check: impl Fn(Vec<T>) -> Result<(), String>,
+) -> Result<(), SimCounterexample>
+where
+    T: Serialize + DeserializeOwned + 'static,
+{
+    let (nodes, port) = sim_deploy_test(simulation, |flow, external| {
+        thunk(flow).send_bincode_external(external)
+    });
+
+    let port = nodes.raw_port_bincode(&port);
+    simulation.explore(seeds, |output| check(output.bincode(&port)))
+}
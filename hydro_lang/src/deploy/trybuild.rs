@@ -46,13 +46,31 @@ pub fn create_graph_trybuild(
     extra_stmts: Vec<syn::Stmt>,
     name_hint: &Option<String>,
 ) -> (String, TrybuildConfig) {
+    let (crate_name, is_test) = trybuild_crate_name();
+    let generated_code = compile_graph_trybuild(graph, extra_stmts, crate_name, is_test);
+    write_trybuild_bin(generated_code, name_hint)
+}
+
+/// Returns the (normalized) name of the crate being compiled, and whether we are in test mode.
+pub(crate) fn trybuild_crate_name() -> (String, bool) {
     let source_dir = cargo::manifest_dir().unwrap();
     let source_manifest = dependencies::get_manifest(&source_dir).unwrap();
-    let crate_name = &source_manifest.package.name.to_string().replace("-", "_");
+    let crate_name = source_manifest.package.name.to_string().replace("-", "_");
 
-    let is_test = IS_TEST.load(std::sync::atomic::Ordering::Relaxed);
+    (
+        crate_name,
+        IS_TEST.load(std::sync::atomic::Ordering::Relaxed),
+    )
+}
 
-    let generated_code = compile_graph_trybuild(graph, extra_stmts, crate_name.clone(), is_test);
+/// Writes the generated code into a new binary in the trybuild project, returning the
+/// name of the binary and the configuration needed to build it.
+pub(crate) fn write_trybuild_bin(
+    generated_code: syn::File,
+    name_hint: &Option<String>,
+) -> (String, TrybuildConfig) {
+    let source_dir = cargo::manifest_dir().unwrap();
+    let (crate_name, is_test) = trybuild_crate_name();
 
     let inlined_staged: syn::File = if is_test {
         let gen_staged = stageleft_tool::gen_staged_trybuild(
@@ -75,7 +93,7 @@ pub fn create_graph_trybuild(
             }
         }
     } else {
-        let crate_name_ident = syn::Ident::new(crate_name, proc_macro2::Span::call_site());
+        let crate_name_ident = syn::Ident::new(&crate_name, proc_macro2::Span::call_site());
         syn::parse_quote!(
             pub use #crate_name_ident::__staged;
         )
@@ -131,12 +149,12 @@ pub fn create_graph_trybuild(
     )
 }
 
-pub fn compile_graph_trybuild(
+/// Generates the expression that instantiates the `Dfir` for a partitioned graph.
+pub(crate) fn compile_dfir_expr(
     partitioned_graph: DfirGraph,
-    extra_stmts: Vec<syn::Stmt>,
-    crate_name: String,
+    crate_name: &str,
     is_test: bool,
-) -> syn::File {
+) -> syn::Expr {
     let mut diagnostics = Vec::new();
     let mut dfir_expr: syn::Expr = syn::parse2(partitioned_graph.as_code(
         &quote! { __root_dfir_rs },
@@ -148,11 +166,22 @@ pub fn compile_graph_trybuild(
 
     if is_test {
         UseTestModeStaged {
-            crate_name: crate_name.clone(),
+            crate_name: crate_name.to_string(),
         }
         .visit_expr_mut(&mut dfir_expr);
     }
 
+    dfir_expr
+}
+
+pub fn compile_graph_trybuild(
+    partitioned_graph: DfirGraph,
+    extra_stmts: Vec<syn::Stmt>,
+    crate_name: String,
+    is_test: bool,
+) -> syn::File {
+    let dfir_expr = compile_dfir_expr(partitioned_graph, &crate_name, is_test);
+
     let source_ast: syn::File = syn::parse_quote! {
         #![allow(unused_imports, unused_crate_dependencies, missing_docs, non_snake_case)]
         use hydro_lang::*;
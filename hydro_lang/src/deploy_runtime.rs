@@ -1,11 +1,15 @@
 use std::collections::HashMap;
 
+use futures::StreamExt;
 use hydro_deploy_integration::{
     ConnectedDemux, ConnectedDirect, ConnectedSink, ConnectedSource, ConnectedTagged, DeployPorts,
+    subscribe_membership_updates,
 };
 use serde::{Deserialize, Serialize};
 use stageleft::{QuotedWithContext, RuntimeData, q};
 
+use crate::location::cluster::{MembershipEvent, MembershipStream};
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct HydroMeta {
     pub clusters: HashMap<usize, Vec<u32>>,
@@ -34,6 +38,42 @@ pub fn cluster_self_id(
         .expect("Tried to read Cluster ID on a non-cluster node"))
 }
 
+pub fn cluster_membership(
+    cli: RuntimeData<&DeployPorts<HydroMeta>>,
+    of_cluster: usize,
+) -> impl QuotedWithContext<MembershipStream, ()> + Copy {
+    q!(crate::deploy_runtime::membership_stream(
+        cli.meta
+            .clusters
+            .get(&of_cluster)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[]),
+        of_cluster
+    ))
+}
+
+/// Starts with a [`MembershipEvent::Joined`] for each of the `initial` members, followed by
+/// the updates to the cluster that Hydro Deploy sends while the program is running.
+pub fn membership_stream(initial: &[u32], of_cluster: usize) -> MembershipStream {
+    let initial = initial
+        .iter()
+        .map(|id| (*id, MembershipEvent::Joined))
+        .collect::<Vec<_>>();
+
+    let updates = subscribe_membership_updates().filter_map(move |update| {
+        futures::future::ready((update.cluster == of_cluster).then(|| {
+            let event = if update.joined {
+                MembershipEvent::Joined
+            } else {
+                MembershipEvent::Left
+            };
+            (update.member, event)
+        }))
+    });
+
+    Box::pin(futures::stream::iter(initial).chain(updates))
+}
+
 pub fn deploy_o2o(
     env: RuntimeData<&DeployPorts<HydroMeta>>,
     p1_port: &str,
@@ -0,0 +1,603 @@
+//! Structural diffs between two versions of a Hydro IR or DFIR graph.
+//!
+//! Rewrites such as `persist_pullup` change the shape of the IR in ways that are hard to follow
+//! by comparing `Debug` output. [`diff_hydro_ir`] and [`diff_dfir_graphs`] match up the
+//! operators of two graphs and report which ones were added, removed, or rewired (kept, but fed
+//! by different inputs). The result can be printed as a summary, or rendered through any
+//! [`HydroGraphWrite`] implementation with the changes highlighted.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+#[cfg(feature = "build")]
+use dfir_lang::graph::{DfirGraph, GraphNode};
+
+use super::render::{
+    HydroDot, HydroEdgeType, HydroGraphStructure, HydroGraphWrite, HydroMermaid, HydroNodeType,
+    HydroWriteConfig, hydro_ir_graph_structure,
+};
+use crate::ir::HydroLeaf;
+
+/// How a node or edge changed between the two graphs of a [`HydroGraphDiff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffStatus {
+    /// Present in both graphs, with the same inputs.
+    Unchanged,
+    /// Only present in the new graph.
+    Added,
+    /// Only present in the old graph.
+    Removed,
+    /// Present in both graphs, but with different inputs.
+    Rewired,
+}
+
+impl DiffStatus {
+    /// The prefix used to mark this status in textual output, i.e. `"+ "` for added nodes.
+    pub fn marker(&self) -> &'static str {
+        match self {
+            DiffStatus::Unchanged => "",
+            DiffStatus::Added => "+ ",
+            DiffStatus::Removed => "- ",
+            DiffStatus::Rewired => "~ ",
+        }
+    }
+}
+
+/// The result of diffing two graphs, as the union of both graphs annotated with a
+/// [`DiffStatus`] for every node and edge.
+#[derive(Debug, Default)]
+pub struct HydroGraphDiff {
+    /// Every node and edge of the new graph, followed by those only in the old graph.
+    pub structure: HydroGraphStructure,
+    /// The status of each node in [`Self::structure`].
+    pub node_status: HashMap<usize, DiffStatus>,
+    /// The status of each edge in [`Self::structure`], by index into its `edges`.
+    pub edge_status: Vec<DiffStatus>,
+}
+
+impl HydroGraphDiff {
+    /// Returns `true` if the two graphs are structurally identical.
+    pub fn is_unchanged(&self) -> bool {
+        self.node_status
+            .values()
+            .chain(self.edge_status.iter())
+            .all(|status| *status == DiffStatus::Unchanged)
+    }
+
+    /// Returns the IDs and labels of the nodes with the given status, in ID order.
+    pub fn nodes_with_status(&self, status: DiffStatus) -> Vec<(usize, &str)> {
+        let mut nodes = self
+            .node_status
+            .iter()
+            .filter(|(_, s)| **s == status)
+            .map(|(&node_id, _)| (node_id, self.structure.nodes[&node_id].0.as_str()))
+            .collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|(node_id, _)| *node_id);
+        nodes
+    }
+
+    /// Labels of the operators only present in the new graph.
+    pub fn added(&self) -> Vec<&str> {
+        self.labels_with_status(DiffStatus::Added)
+    }
+
+    /// Labels of the operators only present in the old graph.
+    pub fn removed(&self) -> Vec<&str> {
+        self.labels_with_status(DiffStatus::Removed)
+    }
+
+    /// Labels of the operators present in both graphs, but with different inputs.
+    pub fn rewired(&self) -> Vec<&str> {
+        self.labels_with_status(DiffStatus::Rewired)
+    }
+
+    fn labels_with_status(&self, status: DiffStatus) -> Vec<&str> {
+        self.nodes_with_status(status)
+            .into_iter()
+            .map(|(_, label)| label)
+            .collect()
+    }
+
+    /// Generate a mermaid graph of the diff, with the changed nodes and edges highlighted.
+    pub fn to_mermaid(&self, config: &HydroWriteConfig) -> String {
+        let mut output = String::new();
+        self.write_graph(HydroMermaid::new(&mut output), config)
+            .unwrap();
+        output
+    }
+
+    /// Generate a DOT/Graphviz graph of the diff, with the changed nodes and edges highlighted.
+    pub fn to_dot(&self, config: &HydroWriteConfig) -> String {
+        let mut output = String::new();
+        self.write_graph(HydroDot::new(&mut output), config)
+            .unwrap();
+        output
+    }
+
+    /// Write the diff with any [`HydroGraphWrite`] implementation.
+    pub fn write_graph<W>(&self, graph_write: W, config: &HydroWriteConfig) -> Result<(), W::Err>
+    where
+        W: HydroGraphWrite,
+    {
+        let changed = |status: Option<&DiffStatus>| {
+            status
+                .copied()
+                .filter(|status| *status != DiffStatus::Unchanged)
+        };
+        self.structure.write_graph_with_status(
+            graph_write,
+            config,
+            |node_id| changed(self.node_status.get(&node_id)),
+            |edge_idx| changed(self.edge_status.get(edge_idx)),
+        )
+    }
+}
+
+impl Display for HydroGraphDiff {
+    /// Lists the changed nodes, one per line, prefixed with their [`DiffStatus::marker`].
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut changed = self
+            .node_status
+            .iter()
+            .filter(|(_, status)| **status != DiffStatus::Unchanged)
+            .collect::<Vec<_>>();
+        changed.sort_unstable_by_key(|(node_id, _)| **node_id);
+        for (node_id, status) in changed {
+            writeln!(f, "{}{}", status.marker(), self.structure.nodes[node_id].0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Diffs the Hydro IR before and after a rewrite.
+pub fn diff_hydro_ir(before: &[HydroLeaf], after: &[HydroLeaf]) -> HydroGraphDiff {
+    let config = HydroWriteConfig::default();
+    hydro_ir_graph_structure(before, &config).diff(&hydro_ir_graph_structure(after, &config))
+}
+
+/// Diffs two DFIR graphs, such as the output of compiling a flow before and after a rewrite.
+#[cfg(feature = "build")]
+#[cfg_attr(docsrs, doc(cfg(feature = "build")))]
+pub fn diff_dfir_graphs(before: &DfirGraph, after: &DfirGraph) -> HydroGraphDiff {
+    dfir_graph_structure(before).diff(&dfir_graph_structure(after))
+}
+
+/// Converts a DFIR graph into a [`HydroGraphStructure`], so that it can be diffed and rendered
+/// like the Hydro IR. Operators are labelled with their surface syntax, and edges with the
+/// ports they connect.
+#[cfg(feature = "build")]
+#[cfg_attr(docsrs, doc(cfg(feature = "build")))]
+pub fn dfir_graph_structure(graph: &DfirGraph) -> HydroGraphStructure {
+    let mut structure = HydroGraphStructure::new();
+    let mut node_ids = HashMap::new();
+
+    for (node_id, node) in graph.nodes() {
+        let node_type = match node {
+            GraphNode::Operator(_) if graph.node_degree_in(node_id) == 0 => HydroNodeType::Source,
+            GraphNode::Operator(_) if graph.node_degree_out(node_id) == 0 => HydroNodeType::Sink,
+            GraphNode::Operator(op) => match op.name_string().as_str() {
+                "tee" => HydroNodeType::Tee,
+                "join"
+                | "join_fused"
+                | "join_fused_lhs"
+                | "join_fused_rhs"
+                | "join_multiset"
+                | "cross_join"
+                | "cross_join_multiset"
+                | "anti_join"
+                | "anti_join_multiset"
+                | "difference"
+                | "difference_multiset"
+                | "cross_singleton"
+                | "zip" => HydroNodeType::Join,
+                "fold" | "fold_keyed" | "reduce" | "reduce_keyed" | "fold_no_replay"
+                | "reduce_no_replay" | "lattice_fold" | "lattice_reduce" | "sort"
+                | "sort_by_key" | "unique" => HydroNodeType::Aggregation,
+                _ => HydroNodeType::Transform,
+            },
+            GraphNode::Handoff { .. } | GraphNode::ModuleBoundary { .. } => {
+                HydroNodeType::Transform
+            }
+        };
+        node_ids.insert(
+            node_id,
+            structure.add_node(node.to_pretty_string().into_owned(), node_type, None),
+        );
+    }
+
+    for (edge_id, (src, dst)) in graph.edges() {
+        let (src_port, dst_port) = graph.edge_ports(edge_id);
+        let label = match (src_port.is_specified(), dst_port.is_specified()) {
+            (true, true) => Some(format!("{} -> {}", src_port, dst_port)),
+            (true, false) => Some(src_port.to_string()),
+            (false, true) => Some(dst_port.to_string()),
+            (false, false) => None,
+        };
+        structure.add_edge(node_ids[&src], node_ids[&dst], HydroEdgeType::Stream, label);
+    }
+
+    structure
+}
+
+/// Identifies an edge independently of its position in [`HydroGraphStructure::edges`].
+type EdgeKey = (usize, usize, HydroEdgeType, Option<String>);
+
+/// Adjacency information for one side of a diff.
+struct DiffSide<'a> {
+    graph: &'a HydroGraphStructure,
+    node_ids: Vec<usize>,
+    inputs: HashMap<usize, Vec<usize>>,
+    outputs: HashMap<usize, Vec<usize>>,
+}
+
+impl<'a> DiffSide<'a> {
+    fn new(graph: &'a HydroGraphStructure) -> Self {
+        let mut node_ids = graph.nodes.keys().copied().collect::<Vec<_>>();
+        node_ids.sort_unstable();
+
+        let mut inputs = HashMap::<usize, Vec<usize>>::new();
+        let mut outputs = HashMap::<usize, Vec<usize>>::new();
+        for (edge_idx, (src, dst, _, _)) in graph.edges.iter().enumerate() {
+            outputs.entry(*src).or_default().push(edge_idx);
+            inputs.entry(*dst).or_default().push(edge_idx);
+        }
+
+        DiffSide {
+            graph,
+            node_ids,
+            inputs,
+            outputs,
+        }
+    }
+
+    /// The label, type, and location of a node, which must be equal for two nodes to match.
+    fn signature(&self, node_id: usize) -> &(String, HydroNodeType, Option<usize>) {
+        &self.graph.nodes[&node_id]
+    }
+
+    fn input_nodes(&self, node_id: usize) -> impl Iterator<Item = usize> + '_ {
+        self.inputs
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .map(|&edge_idx| self.graph.edges[edge_idx].0)
+    }
+
+    fn output_nodes(&self, node_id: usize) -> impl Iterator<Item = usize> + '_ {
+        self.outputs
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .map(|&edge_idx| self.graph.edges[edge_idx].1)
+    }
+
+    /// Hashes the signature of a node along with everything upstream of it, so that nodes
+    /// computing the same thing in both graphs get the same hash.
+    fn upstream_hash(
+        &self,
+        node_id: usize,
+        memo: &mut HashMap<usize, u64>,
+        visiting: &mut HashSet<usize>,
+    ) -> u64 {
+        if let Some(&hash) = memo.get(&node_id) {
+            return hash;
+        }
+        if !visiting.insert(node_id) {
+            // Back edge of a cycle, which only contributes its own signature.
+            let mut hasher = DefaultHasher::new();
+            self.signature(node_id).hash(&mut hasher);
+            return hasher.finish();
+        }
+
+        let mut input_hashes = self
+            .inputs
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .map(|&edge_idx| {
+                let (src, _, edge_type, label) = &self.graph.edges[edge_idx];
+                let mut hasher = DefaultHasher::new();
+                (edge_type, label).hash(&mut hasher);
+                self.upstream_hash(*src, memo, visiting).hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect::<Vec<_>>();
+        input_hashes.sort_unstable();
+
+        let mut hasher = DefaultHasher::new();
+        self.signature(node_id).hash(&mut hasher);
+        input_hashes.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        visiting.remove(&node_id);
+        memo.insert(node_id, hash);
+        hash
+    }
+
+    fn upstream_hashes(&self) -> HashMap<usize, u64> {
+        let mut memo = HashMap::new();
+        let mut visiting = HashSet::new();
+        for &node_id in &self.node_ids {
+            self.upstream_hash(node_id, &mut memo, &mut visiting);
+        }
+        memo
+    }
+}
+
+impl HydroGraphStructure {
+    /// Diffs this graph structure against a newer version of it, matching nodes by their label,
+    /// type, and location.
+    ///
+    /// Nodes are matched in three passes. First, nodes with identical upstream subgraphs are
+    /// paired. Then, the remaining nodes are paired with a node of the same signature that
+    /// shares the most already-matched neighbors, until no more such pairs can be found. Finally,
+    /// any nodes that are still left over are paired with a node of the same signature in ID
+    /// order, and are reported as rewired if their inputs differ.
+    pub fn diff(&self, after: &HydroGraphStructure) -> HydroGraphDiff {
+        let before = DiffSide::new(self);
+        let after = DiffSide::new(after);
+
+        let mut before_to_after = HashMap::<usize, usize>::new();
+        let mut after_to_before = HashMap::<usize, usize>::new();
+
+        // Pass 1: identical upstream subgraphs.
+        let mut before_by_hash = HashMap::<u64, Vec<usize>>::new();
+        let before_hashes = before.upstream_hashes();
+        for &node_id in before.node_ids.iter().rev() {
+            before_by_hash
+                .entry(before_hashes[&node_id])
+                .or_default()
+                .push(node_id);
+        }
+        let after_hashes = after.upstream_hashes();
+        for &node_id in &after.node_ids {
+            if let Some(before_id) = before_by_hash
+                .get_mut(&after_hashes[&node_id])
+                .and_then(|candidates| candidates.pop())
+            {
+                before_to_after.insert(before_id, node_id);
+                after_to_before.insert(node_id, before_id);
+            }
+        }
+
+        // Pass 2: same signature, preferring nodes with matched neighbors.
+        loop {
+            let mut progress = false;
+            for &node_id in &after.node_ids {
+                if after_to_before.contains_key(&node_id) {
+                    continue;
+                }
+
+                let matched_inputs = after
+                    .input_nodes(node_id)
+                    .filter_map(|n| after_to_before.get(&n).copied())
+                    .collect::<Vec<_>>();
+                let matched_outputs = after
+                    .output_nodes(node_id)
+                    .filter_map(|n| after_to_before.get(&n).copied())
+                    .collect::<Vec<_>>();
+
+                let best = before
+                    .node_ids
+                    .iter()
+                    .filter(|before_id| {
+                        !before_to_after.contains_key(before_id)
+                            && before.signature(**before_id) == after.signature(node_id)
+                    })
+                    .map(|&before_id| {
+                        let score = before
+                            .input_nodes(before_id)
+                            .filter(|n| matched_inputs.contains(n))
+                            .count()
+                            + before
+                                .output_nodes(before_id)
+                                .filter(|n| matched_outputs.contains(n))
+                                .count();
+                        (score, std::cmp::Reverse(before_id))
+                    })
+                    .max();
+
+                if let Some((score, std::cmp::Reverse(before_id))) = best {
+                    if score == 0 {
+                        continue;
+                    }
+                    before_to_after.insert(before_id, node_id);
+                    after_to_before.insert(node_id, before_id);
+                    progress = true;
+                }
+            }
+
+            if !progress {
+                break;
+            }
+        }
+
+        // Pass 3: same signature, in ID order.
+        for &node_id in &after.node_ids {
+            if after_to_before.contains_key(&node_id) {
+                continue;
+            }
+            if let Some(&before_id) = before.node_ids.iter().find(|before_id| {
+                !before_to_after.contains_key(before_id)
+                    && before.signature(**before_id) == after.signature(node_id)
+            }) {
+                before_to_after.insert(before_id, node_id);
+                after_to_before.insert(node_id, before_id);
+            }
+        }
+
+        // Build the union of both graphs, reusing the new graph's nodes for matched pairs.
+        let mut diff = HydroGraphDiff::default();
+        let mut after_to_merged = HashMap::new();
+        let mut before_to_merged = HashMap::new();
+        for graph in [before.graph, after.graph] {
+            for (location_id, location_type) in &graph.locations {
+                diff.structure
+                    .add_location(*location_id, location_type.clone());
+            }
+        }
+        for &node_id in &after.node_ids {
+            let (label, node_type, location) = after.signature(node_id).clone();
+            let merged_id = diff.structure.add_node(label, node_type, location);
+            after_to_merged.insert(node_id, merged_id);
+            let status = if let Some(before_id) = after_to_before.get(&node_id) {
+                before_to_merged.insert(*before_id, merged_id);
+                DiffStatus::Unchanged
+            } else {
+                DiffStatus::Added
+            };
+            diff.node_status.insert(merged_id, status);
+        }
+        for &node_id in &before.node_ids {
+            if let Entry::Vacant(entry) = before_to_merged.entry(node_id) {
+                let (label, node_type, location) = before.signature(node_id).clone();
+                let merged_id = diff.structure.add_node(label, node_type, location);
+                entry.insert(merged_id);
+                diff.node_status.insert(merged_id, DiffStatus::Removed);
+            }
+        }
+
+        // Edges of the old graph that have not (yet) been found in the new graph.
+        let mut before_edges = HashMap::<EdgeKey, usize>::new();
+        for (src, dst, edge_type, label) in &before.graph.edges {
+            *before_edges
+                .entry((
+                    before_to_merged[src],
+                    before_to_merged[dst],
+                    *edge_type,
+                    label.clone(),
+                ))
+                .or_default() += 1;
+        }
+
+        let mut changed_inputs = HashSet::new();
+        for (src, dst, edge_type, label) in &after.graph.edges {
+            let key = (
+                after_to_merged[src],
+                after_to_merged[dst],
+                *edge_type,
+                label.clone(),
+            );
+            let status = match before_edges.get_mut(&key) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    DiffStatus::Unchanged
+                }
+                _ => {
+                    changed_inputs.insert(key.1);
+                    DiffStatus::Added
+                }
+            };
+            diff.structure.add_edge(key.0, key.1, key.2, key.3);
+            diff.edge_status.push(status);
+        }
+
+        for (src, dst, edge_type, label) in &before.graph.edges {
+            let key = (
+                before_to_merged[src],
+                before_to_merged[dst],
+                *edge_type,
+                label.clone(),
+            );
+            let count = before_edges.get_mut(&key).unwrap();
+            if *count > 0 {
+                *count -= 1;
+                changed_inputs.insert(key.1);
+                diff.structure.add_edge(key.0, key.1, key.2, key.3);
+                diff.edge_status.push(DiffStatus::Removed);
+            }
+        }
+
+        for node_id in changed_inputs {
+            let status = diff.node_status.get_mut(&node_id).unwrap();
+            if *status == DiffStatus::Unchanged {
+                *status = DiffStatus::Rewired;
+            }
+        }
+
+        diff
+    }
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use stageleft::*;
+
+    use super::*;
+    use crate::deploy::HydroDeploy;
+    use crate::ir::deep_clone;
+    use crate::location::Location;
+
+    fn tee_behind_persist_flow(extra_map: bool) -> crate::builder::built::BuiltFlow<'static> {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        let tick = process.tick();
+        let before_tee = unsafe { process.source_iter(q!(0..10)).tick_batch(&tick).persist() };
+
+        before_tee
+            .clone()
+            .map(q!(|v| v + 1))
+            .all_ticks()
+            .for_each(q!(|n| println!("{}", n)));
+
+        let filtered = before_tee.filter(q!(|v| *v > 5));
+        let filtered = if extra_map {
+            filtered.map(q!(|v| v * 2))
+        } else {
+            filtered
+        };
+        filtered.all_ticks().for_each(q!(|n| println!("{}", n)));
+
+        flow.finalize()
+    }
+
+    #[test]
+    fn identical_ir_is_unchanged() {
+        let built = tee_behind_persist_flow(false);
+        let diff = diff_hydro_ir(built.ir(), &deep_clone(built.ir()));
+        assert!(diff.is_unchanged(), "{}", diff);
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn persist_pullup_diff() {
+        let built = tee_behind_persist_flow(false);
+        let before = deep_clone(built.ir());
+        let optimized = built.optimize_with(crate::rewrites::persist_pullup::persist_pullup);
+
+        let diff = diff_hydro_ir(&before, optimized.ir());
+        assert!(!diff.is_unchanged());
+
+        // The persists after the tee are pulled up through it, so the persisted values are
+        // now shared and the operators downstream of the tee read from a different input.
+        assert_eq!(diff.added(), Vec::<&str>::new());
+        assert_eq!(diff.removed(), vec!["persist()", "persist()"]);
+        assert!(diff.rewired().contains(&"tee()"));
+
+        let mermaid = diff.to_mermaid(&HydroWriteConfig::default());
+        assert!(mermaid.contains("stroke:#d00"));
+    }
+
+    #[test]
+    fn dfir_diff_reports_added_operator() {
+        let before = tee_behind_persist_flow(false)
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile();
+        let after = tee_behind_persist_flow(true)
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile();
+
+        let diff = diff_dfir_graphs(&before.all_dfir()[&0], &after.all_dfir()[&0]);
+        assert_eq!(diff.removed(), Vec::<&str>::new());
+        assert_eq!(diff.added().len(), 1);
+        assert!(diff.added()[0].starts_with("map"));
+        assert!(
+            diff.to_dot(&HydroWriteConfig::default())
+                .contains("penwidth")
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::fmt::Write;
 
-use super::render::{HydroEdgeType, HydroGraphWrite, HydroNodeType};
+use super::render::{DiffStatus, HydroEdgeType, HydroGraphWrite, HydroNodeType};
 
 /// Escapes a string for use in a DOT graph label.
 pub fn escape_dot(string: &str, newline: &str) -> String {
@@ -20,47 +20,17 @@ impl<W> HydroDot<W> {
     }
 }
 
-impl<W> HydroGraphWrite for HydroDot<W>
+impl<W> HydroDot<W>
 where
     W: Write,
 {
-    type Err = std::fmt::Error;
-
-    fn write_prologue(&mut self) -> Result<(), Self::Err> {
-        writeln!(
-            self.write,
-            "{b:i$}digraph HydroIR {{",
-            b = "",
-            i = self.indent
-        )?;
-        self.indent += 4;
-
-        const FONTS: &str = "\"Monaco,Menlo,Consolas,&quot;Droid Sans Mono&quot;,Inconsolata,&quot;Courier New&quot;,monospace\"";
-        writeln!(
-            self.write,
-            "{b:i$}node [fontname={}, style=filled];",
-            FONTS,
-            b = "",
-            i = self.indent
-        )?;
-        writeln!(
-            self.write,
-            "{b:i$}edge [fontname={}];",
-            FONTS,
-            b = "",
-            i = self.indent
-        )?;
-        Ok(())
-    }
-
-    fn write_node_definition(
+    fn write_node_definition_styled(
         &mut self,
         node_id: usize,
         node_label: &str,
         node_type: HydroNodeType,
-        _location_id: Option<usize>,
-        _location_type: Option<&str>,
-    ) -> Result<(), Self::Err> {
+        diff: Option<DiffStatus>,
+    ) -> std::fmt::Result {
         let escaped_label = escape_dot(node_label, "\\l");
         let label = format!("n{}", node_id);
 
@@ -86,17 +56,24 @@ where
             i = self.indent,
         )?;
         write!(self.write, ", shape={shape_str}, fillcolor={color_str}")?;
+        if let Some((color, style)) = diff.and_then(diff_style) {
+            write!(
+                self.write,
+                ", color=\"{color}\", penwidth=4, style=\"filled,{style}\""
+            )?;
+        }
         writeln!(self.write, "]")?;
         Ok(())
     }
 
-    fn write_edge(
+    fn write_edge_styled(
         &mut self,
         src_id: usize,
         dst_id: usize,
         edge_type: HydroEdgeType,
         label: Option<&str>,
-    ) -> Result<(), Self::Err> {
+        diff: Option<DiffStatus>,
+    ) -> std::fmt::Result {
         let mut properties = Vec::<Cow<'static, str>>::new();
 
         if let Some(label) = label {
@@ -120,6 +97,13 @@ where
             HydroEdgeType::Stream => {}
         }
 
+        // Diff highlighting overrides the edge type styling
+        if let Some((color, style)) = diff.and_then(diff_style) {
+            properties.push(format!("color=\"{color}\"").into());
+            properties.push(format!("style=\"{style}\"").into());
+            properties.push("penwidth=3".into());
+        }
+
         write!(
             self.write,
             "{b:i$}n{} -> n{}",
@@ -139,6 +123,94 @@ where
         writeln!(self.write)?;
         Ok(())
     }
+}
+
+/// Color and style highlighting a changed node or edge in a diff.
+fn diff_style(status: DiffStatus) -> Option<(&'static str, &'static str)> {
+    match status {
+        DiffStatus::Unchanged => None,
+        DiffStatus::Added => Some(("#00aa00", "bold")),
+        DiffStatus::Removed => Some(("#dd0000", "dashed")),
+        DiffStatus::Rewired => Some(("#ee8800", "bold")),
+    }
+}
+
+impl<W> HydroGraphWrite for HydroDot<W>
+where
+    W: Write,
+{
+    type Err = std::fmt::Error;
+
+    fn write_prologue(&mut self) -> Result<(), Self::Err> {
+        writeln!(
+            self.write,
+            "{b:i$}digraph HydroIR {{",
+            b = "",
+            i = self.indent
+        )?;
+        self.indent += 4;
+
+        const FONTS: &str = "\"Monaco,Menlo,Consolas,&quot;Droid Sans Mono&quot;,Inconsolata,&quot;Courier New&quot;,monospace\"";
+        writeln!(
+            self.write,
+            "{b:i$}node [fontname={}, style=filled];",
+            FONTS,
+            b = "",
+            i = self.indent
+        )?;
+        writeln!(
+            self.write,
+            "{b:i$}edge [fontname={}];",
+            FONTS,
+            b = "",
+            i = self.indent
+        )?;
+        Ok(())
+    }
+
+    fn write_node_definition(
+        &mut self,
+        node_id: usize,
+        node_label: &str,
+        node_type: HydroNodeType,
+        _location_id: Option<usize>,
+        _location_type: Option<&str>,
+    ) -> Result<(), Self::Err> {
+        self.write_node_definition_styled(node_id, node_label, node_type, None)
+    }
+
+    fn write_edge(
+        &mut self,
+        src_id: usize,
+        dst_id: usize,
+        edge_type: HydroEdgeType,
+        label: Option<&str>,
+    ) -> Result<(), Self::Err> {
+        self.write_edge_styled(src_id, dst_id, edge_type, label, None)
+    }
+
+    fn write_diff_node_definition(
+        &mut self,
+        node_id: usize,
+        node_label: &str,
+        node_type: HydroNodeType,
+        _location_id: Option<usize>,
+        _location_type: Option<&str>,
+        status: DiffStatus,
+    ) -> Result<(), Self::Err> {
+        self.write_node_definition_styled(node_id, node_label, node_type, Some(status))
+    }
+
+    fn write_diff_edge(
+        &mut self,
+        src_id: usize,
+        dst_id: usize,
+        edge_type: HydroEdgeType,
+        label: Option<&str>,
+        status: DiffStatus,
+    ) -> Result<(), Self::Err> {
+        self.write_edge_styled(src_id, dst_id, edge_type, label, Some(status))
+    }
 
     fn write_location_start(
         &mut self,
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::fmt::Write;
 
-use super::render::{HydroEdgeType, HydroGraphWrite, HydroNodeType};
+use super::render::{DiffStatus, HydroEdgeType, HydroGraphWrite, HydroNodeType};
 
 /// Escapes a string for use in a mermaid graph label.
 pub fn escape_mermaid(string: &str) -> String {
@@ -150,6 +150,44 @@ where
         Ok(())
     }
 
+    fn write_diff_node_definition(
+        &mut self,
+        node_id: usize,
+        node_label: &str,
+        node_type: HydroNodeType,
+        location_id: Option<usize>,
+        location_type: Option<&str>,
+        status: DiffStatus,
+    ) -> Result<(), Self::Err> {
+        self.write_node_definition(node_id, node_label, node_type, location_id, location_type)?;
+        writeln!(
+            self.write,
+            "{b:i$}style n{node_id} {style}",
+            style = diff_style(status),
+            b = "",
+            i = self.indent,
+        )
+    }
+
+    fn write_diff_edge(
+        &mut self,
+        src_id: usize,
+        dst_id: usize,
+        edge_type: HydroEdgeType,
+        label: Option<&str>,
+        status: DiffStatus,
+    ) -> Result<(), Self::Err> {
+        self.write_edge(src_id, dst_id, edge_type, label)?;
+        writeln!(
+            self.write,
+            "{b:i$}linkStyle {} {}",
+            self.link_count - 1,
+            diff_style(status),
+            b = "",
+            i = self.indent,
+        )
+    }
+
     fn write_location_start(
         &mut self,
         location_id: usize,
@@ -179,3 +217,13 @@ where
         Ok(())
     }
 }
+
+/// Mermaid style overrides highlighting a node or edge in a diff.
+fn diff_style(status: DiffStatus) -> &'static str {
+    match status {
+        DiffStatus::Unchanged => "stroke-width:1px",
+        DiffStatus::Added => "stroke:#0a0,stroke-width:4px",
+        DiffStatus::Removed => "stroke:#d00,stroke-width:4px,stroke-dasharray:5 5",
+        DiffStatus::Rewired => "stroke:#e80,stroke-width:4px",
+    }
+}
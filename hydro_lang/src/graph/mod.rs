@@ -1,6 +1,7 @@
 //! Graph visualization utilities for Hydro IR
 
 pub mod debug;
+pub mod diff;
 pub mod graphviz;
 pub mod mermaid;
 pub mod reactflow;
@@ -1,13 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 
 use auto_impl::auto_impl;
 
+pub use super::diff::DiffStatus;
 pub use super::graphviz::{HydroDot, escape_dot};
 // Re-export specific implementations
 pub use super::mermaid::{HydroMermaid, escape_mermaid};
 pub use super::reactflow::HydroReactFlow;
-use crate::ir::{HydroLeaf, HydroNode, HydroSource};
+use crate::ir::{HydroLeaf, HydroNode, HydroSource, Monotonicity};
 
 /// Trait for writing textual representations of Hydro IR graphs, i.e. mermaid or dot graphs.
 #[auto_impl(&mut, Box)]
@@ -37,6 +38,39 @@ pub trait HydroGraphWrite {
         label: Option<&str>,
     ) -> Result<(), Self::Err>;
 
+    /// Write a node definition, highlighted according to how it changed in a
+    /// [graph diff](super::diff). By default, the label is prefixed with the status marker.
+    fn write_diff_node_definition(
+        &mut self,
+        node_id: usize,
+        node_label: &str,
+        node_type: HydroNodeType,
+        location_id: Option<usize>,
+        location_type: Option<&str>,
+        status: DiffStatus,
+    ) -> Result<(), Self::Err> {
+        self.write_node_definition(
+            node_id,
+            &format!("{}{}", status.marker(), node_label),
+            node_type,
+            location_id,
+            location_type,
+        )
+    }
+
+    /// Write an edge, highlighted according to how it changed in a [graph diff](super::diff).
+    /// By default, the status is ignored.
+    fn write_diff_edge(
+        &mut self,
+        src_id: usize,
+        dst_id: usize,
+        edge_type: HydroEdgeType,
+        label: Option<&str>,
+        _status: DiffStatus,
+    ) -> Result<(), Self::Err> {
+        self.write_edge(src_id, dst_id, edge_type, label)
+    }
+
     /// Begin writing a location grouping (process/cluster).
     fn write_location_start(
         &mut self,
@@ -55,7 +89,7 @@ pub trait HydroGraphWrite {
 }
 
 /// Types of nodes in Hydro IR for styling purposes.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HydroNodeType {
     Source,
     Transform,
@@ -67,7 +101,7 @@ pub enum HydroNodeType {
 }
 
 /// Types of edges in Hydro IR.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HydroEdgeType {
     Stream,
     Persistent,
@@ -132,6 +166,102 @@ impl HydroGraphStructure {
     pub fn add_location(&mut self, location_id: usize, location_type: String) {
         self.locations.insert(location_id, location_type);
     }
+
+    /// Write this graph structure with any [`HydroGraphWrite`] implementation.
+    pub fn write_graph<W>(&self, graph_write: W, config: &HydroWriteConfig) -> Result<(), W::Err>
+    where
+        W: HydroGraphWrite,
+    {
+        self.write_graph_with_status(graph_write, config, |_| None, |_| None)
+    }
+
+    /// Write this graph structure, highlighting the nodes and edges (by index into
+    /// [`Self::edges`]) for which a [`DiffStatus`] is given.
+    pub(crate) fn write_graph_with_status<W>(
+        &self,
+        mut graph_write: W,
+        config: &HydroWriteConfig,
+        node_status: impl Fn(usize) -> Option<DiffStatus>,
+        edge_status: impl Fn(usize) -> Option<DiffStatus>,
+    ) -> Result<(), W::Err>
+    where
+        W: HydroGraphWrite,
+    {
+        graph_write.write_prologue()?;
+
+        // Write node definitions
+        let mut node_ids = self.nodes.keys().copied().collect::<Vec<_>>();
+        node_ids.sort_unstable();
+        for &node_id in &node_ids {
+            let (label, node_type, location) = &self.nodes[&node_id];
+            let (location_id, location_type) = if let Some(loc_id) = location {
+                (
+                    Some(*loc_id),
+                    self.locations.get(loc_id).map(|s| s.as_str()),
+                )
+            } else {
+                (None, None)
+            };
+            if let Some(status) = node_status(node_id) {
+                graph_write.write_diff_node_definition(
+                    node_id,
+                    label,
+                    *node_type,
+                    location_id,
+                    location_type,
+                    status,
+                )?;
+            } else {
+                graph_write.write_node_definition(
+                    node_id,
+                    label,
+                    *node_type,
+                    location_id,
+                    location_type,
+                )?;
+            }
+        }
+
+        // Group nodes by location if requested
+        if config.show_location_groups {
+            let mut nodes_by_location: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for &node_id in &node_ids {
+                if let Some(location_id) = self.nodes[&node_id].2 {
+                    nodes_by_location
+                        .entry(location_id)
+                        .or_default()
+                        .push(node_id);
+                }
+            }
+
+            for (&location_id, node_ids) in &nodes_by_location {
+                if let Some(location_type) = self.locations.get(&location_id) {
+                    graph_write.write_location_start(location_id, location_type)?;
+                    for &node_id in node_ids {
+                        graph_write.write_node(node_id)?;
+                    }
+                    graph_write.write_location_end()?;
+                }
+            }
+        }
+
+        // Write edges
+        for (edge_idx, (src_id, dst_id, edge_type, label)) in self.edges.iter().enumerate() {
+            if let Some(status) = edge_status(edge_idx) {
+                graph_write.write_diff_edge(
+                    *src_id,
+                    *dst_id,
+                    *edge_type,
+                    label.as_deref(),
+                    status,
+                )?;
+            } else {
+                graph_write.write_edge(*src_id, *dst_id, *edge_type, label.as_deref())?;
+            }
+        }
+
+        graph_write.write_epilogue()
+    }
 }
 
 impl HydroLeaf {
@@ -187,11 +317,7 @@ impl HydroLeaf {
     }
 
     /// Core graph writing logic that works with any GraphWrite implementation.
-    pub fn write_graph<W>(
-        &self,
-        mut graph_write: W,
-        config: &HydroWriteConfig,
-    ) -> Result<(), W::Err>
+    pub fn write_graph<W>(&self, graph_write: W, config: &HydroWriteConfig) -> Result<(), W::Err>
     where
         W: HydroGraphWrite,
     {
@@ -201,58 +327,7 @@ impl HydroLeaf {
         // Build the graph structure by traversing the IR
         let _sink_id = self.build_graph_structure(&mut structure, &mut seen_tees, config);
 
-        // Write the graph
-        graph_write.write_prologue()?;
-
-        // Write node definitions
-        for (&node_id, (label, node_type, location)) in &structure.nodes {
-            let (location_id, location_type) = if let Some(loc_id) = location {
-                (
-                    Some(*loc_id),
-                    structure.locations.get(loc_id).map(|s| s.as_str()),
-                )
-            } else {
-                (None, None)
-            };
-            graph_write.write_node_definition(
-                node_id,
-                label,
-                *node_type,
-                location_id,
-                location_type,
-            )?;
-        }
-
-        // Group nodes by location if requested
-        if config.show_location_groups {
-            let mut nodes_by_location: HashMap<usize, Vec<usize>> = HashMap::new();
-            for (&node_id, (_, _, location)) in &structure.nodes {
-                if let Some(location_id) = location {
-                    nodes_by_location
-                        .entry(*location_id)
-                        .or_default()
-                        .push(node_id);
-                }
-            }
-
-            for (&location_id, node_ids) in &nodes_by_location {
-                if let Some(location_type) = structure.locations.get(&location_id) {
-                    graph_write.write_location_start(location_id, location_type)?;
-                    for &node_id in node_ids {
-                        graph_write.write_node(node_id)?;
-                    }
-                    graph_write.write_location_end()?;
-                }
-            }
-        }
-
-        // Write edges
-        for (src_id, dst_id, edge_type, label) in &structure.edges {
-            graph_write.write_edge(*src_id, *dst_id, *edge_type, label.as_deref())?;
-        }
-
-        graph_write.write_epilogue()?;
-        Ok(())
+        structure.write_graph(graph_write, config)
     }
 
     /// Build the graph structure by traversing the IR tree.
@@ -407,7 +482,7 @@ impl HydroNode {
             structure.add_node(label, HydroNodeType::Source, location_id)
         }
 
-        match self {
+        let node_id = match self {
             HydroNode::Placeholder => {
                 structure.add_node("PLACEHOLDER".to_string(), HydroNodeType::Transform, None)
             }
@@ -536,6 +611,7 @@ impl HydroNode {
                 acc,
                 input,
                 metadata,
+                ..
             } => build_single_input_transform(
                 structure,
                 seen_tees,
@@ -549,6 +625,23 @@ impl HydroNode {
                 },
             ),
 
+            HydroNode::LatticeFold {
+                init,
+                input,
+                metadata,
+            } => build_single_input_transform(
+                structure,
+                seen_tees,
+                config,
+                input,
+                metadata,
+                NodeParams {
+                    label: format!("lattice_fold({:?})", init),
+                    node_type: HydroNodeType::Aggregation,
+                    edge_type: HydroEdgeType::Stream,
+                },
+            ),
+
             HydroNode::Network {
                 to_location,
                 serialize_fn,
@@ -642,7 +735,20 @@ impl HydroNode {
                     None,
                 )
             }
+        };
+
+        // tees may be reached several times, and unpersists are rendered as their input
+        if !matches!(
+            self,
+            HydroNode::Placeholder | HydroNode::Tee { .. } | HydroNode::Unpersist { .. }
+        ) && self.metadata().monotonicity == Some(Monotonicity::NonMonotone)
+        {
+            if let Some((label, _, _)) = structure.nodes.get_mut(&node_id) {
+                label.push_str("\n[non-monotone]");
+            }
         }
+
+        node_id
     }
 }
 
@@ -693,13 +799,21 @@ pub fn write_hydro_ir_reactflow(
 }
 
 fn write_hydro_ir_graph<W>(
-    mut graph_write: W,
+    graph_write: W,
     leaves: &[HydroLeaf],
     config: &HydroWriteConfig,
 ) -> Result<(), W::Err>
 where
     W: HydroGraphWrite,
 {
+    hydro_ir_graph_structure(leaves, config).write_graph(graph_write, config)
+}
+
+/// Builds a single graph structure for multiple leaves, sharing nodes behind tees.
+pub fn hydro_ir_graph_structure(
+    leaves: &[HydroLeaf],
+    config: &HydroWriteConfig,
+) -> HydroGraphStructure {
     let mut structure = HydroGraphStructure::new();
     let mut seen_tees = HashMap::new();
 
@@ -708,53 +822,5 @@ where
         leaf.build_graph_structure(&mut structure, &mut seen_tees, config);
     }
 
-    // Write the graph using the same logic as individual leaves
-    graph_write.write_prologue()?;
-
-    for (&node_id, (label, node_type, location)) in &structure.nodes {
-        let (location_id, location_type) = if let Some(loc_id) = location {
-            (
-                Some(*loc_id),
-                structure.locations.get(loc_id).map(|s| s.as_str()),
-            )
-        } else {
-            (None, None)
-        };
-        graph_write.write_node_definition(
-            node_id,
-            label,
-            *node_type,
-            location_id,
-            location_type,
-        )?;
-    }
-
-    if config.show_location_groups {
-        let mut nodes_by_location: HashMap<usize, Vec<usize>> = HashMap::new();
-        for (&node_id, (_, _, location)) in &structure.nodes {
-            if let Some(location_id) = location {
-                nodes_by_location
-                    .entry(*location_id)
-                    .or_default()
-                    .push(node_id);
-            }
-        }
-
-        for (&location_id, node_ids) in &nodes_by_location {
-            if let Some(location_type) = structure.locations.get(&location_id) {
-                graph_write.write_location_start(location_id, location_type)?;
-                for &node_id in node_ids {
-                    graph_write.write_node(node_id)?;
-                }
-                graph_write.write_location_end()?;
-            }
-        }
-    }
-
-    for (src_id, dst_id, edge_type, label) in &structure.edges {
-        graph_write.write_edge(*src_id, *dst_id, *edge_type, label.as_deref())?;
-    }
-
-    graph_write.write_epilogue()?;
-    Ok(())
+    structure
 }
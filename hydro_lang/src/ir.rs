@@ -16,12 +16,16 @@ use proc_macro2::TokenStream;
 use quote::ToTokens;
 #[cfg(feature = "build")]
 use quote::quote;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 #[cfg(feature = "build")]
 use syn::parse_quote;
 
 #[cfg(feature = "build")]
 use crate::deploy::{Deploy, RegisterPort};
 use crate::location::LocationId;
+#[cfg(feature = "build")]
+use crate::staging_util::get_this_crate;
 
 /// Debug displays the type's tokens.
 ///
@@ -64,6 +68,22 @@ impl Debug for DebugExpr {
     }
 }
 
+/// Serializes the expression's tokens as a string, which is parsed again on deserialization.
+impl Serialize for DebugExpr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_token_stream().to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for DebugExpr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tokens = String::deserialize(deserializer)?;
+        syn::parse_str::<syn::Expr>(&tokens)
+            .map(Self::from)
+            .map_err(D::Error::custom)
+    }
+}
+
 /// Simplify expanded q! macro calls back to q!(...) syntax for better readability
 fn simplify_q_macro(token_str: &str) -> String {
     // Look for patterns that indicate a q! macro expansion
@@ -150,6 +170,37 @@ impl Debug for DebugType {
     }
 }
 
+/// Serializes the type's tokens as a string, which is parsed again on deserialization.
+impl Serialize for DebugType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_token_stream().to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for DebugType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tokens = String::deserialize(deserializer)?;
+        syn::parse_str::<syn::Type>(&tokens)
+            .map(Self::from)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// (De)serializes a [`syn::Ident`] as a string, for use with `#[serde(with = ...)]`.
+mod serde_ident {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(ident: &syn::Ident, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&ident.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<syn::Ident, D::Error> {
+        let ident = String::deserialize(deserializer)?;
+        syn::parse_str(&ident).map_err(D::Error::custom)
+    }
+}
+
 pub enum DebugInstantiate {
     Building,
     Finalized(Box<DebugInstantiateFinalized>),
@@ -197,8 +248,23 @@ impl Clone for DebugInstantiate {
     }
 }
 
+/// Network instantiation is specific to a deployment, so it is not persisted. A deserialized
+/// network is always [`DebugInstantiate::Building`], and is instantiated again when deployed.
+impl Serialize for DebugInstantiate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl<'de> Deserialize<'de> for DebugInstantiate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <()>::deserialize(deserializer)?;
+        Ok(DebugInstantiate::Building)
+    }
+}
+
 /// A source in a Hydro graph, where data enters the graph.
-#[derive(Debug, Hash, Clone)]
+#[derive(Debug, Hash, Clone, Serialize, Deserialize)]
 pub enum HydroSource {
     Stream(DebugExpr),
     ExternalNetwork(),
@@ -219,7 +285,7 @@ where
 /// An leaf in a Hydro graph, which is an pipeline that doesn't emit
 /// any downstream values. Traversals over the dataflow graph and
 /// generating DFIR IR start from leaves.
-#[derive(Debug, Hash)]
+#[derive(Debug, Hash, Serialize, Deserialize)]
 pub enum HydroLeaf {
     ForEach {
         f: DebugExpr,
@@ -232,6 +298,7 @@ pub enum HydroLeaf {
         metadata: HydroIrMetadata,
     },
     CycleSink {
+        #[serde(with = "serde_ident")]
         ident: syn::Ident,
         location_kind: LocationId,
         input: Box<HydroNode>,
@@ -604,16 +671,113 @@ impl Hash for TeeNode {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Default)]
+struct SerdeTees {
+    next_id: usize,
+    serialized: HashMap<*const RefCell<HydroNode>, usize>,
+    deserialized: HashMap<usize, Rc<RefCell<HydroNode>>>,
+}
+
+thread_local! {
+    static SERDE_TEES: RefCell<Option<SerdeTees>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, which (de)serializes some IR, such that a tee shared by several consumers is
+/// written out only once and restored as a single shared node.
+///
+/// The serializer must visit the IR exactly once, in the same order as it is deserialized.
+/// Outside of this scope, every reference to a tee serializes a full copy of its input, and
+/// deserializing a reference to an earlier tee fails.
+pub fn serde_dedup_tee<T>(f: impl FnOnce() -> T) -> T {
+    let prev = SERDE_TEES.with(|tees| tees.replace(Some(SerdeTees::default())));
+    let ret = f();
+    SERDE_TEES.with(|tees| tees.replace(prev));
+    ret
+}
+
+#[derive(Serialize, Deserialize)]
+enum SerializedTee<N> {
+    /// The first occurrence of a tee, along with its input.
+    Node { id: usize, node: N },
+    /// A later occurrence of a tee that was already serialized.
+    Ref(usize),
+}
+
+impl Serialize for TeeNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (id, seen) = SERDE_TEES.with(|tees| {
+            let mut tees = tees.borrow_mut();
+            let Some(tees) = tees.as_mut() else {
+                return (0, false);
+            };
+
+            if let Some(&id) = tees.serialized.get(&self.as_ptr()) {
+                (id, true)
+            } else {
+                let id = tees.next_id;
+                tees.next_id += 1;
+                tees.serialized.insert(self.as_ptr(), id);
+                (id, false)
+            }
+        });
+
+        if seen {
+            SerializedTee::<&HydroNode>::Ref(id).serialize(serializer)
+        } else {
+            SerializedTee::Node {
+                id,
+                node: &*self.0.borrow(),
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TeeNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match SerializedTee::<HydroNode>::deserialize(deserializer)? {
+            SerializedTee::Node { id, node } => {
+                let inner = Rc::new(RefCell::new(node));
+                SERDE_TEES.with(|tees| {
+                    if let Some(tees) = tees.borrow_mut().as_mut() {
+                        tees.deserialized.insert(id, inner.clone());
+                    }
+                });
+                Ok(TeeNode(inner))
+            }
+            SerializedTee::Ref(id) => SERDE_TEES
+                .with(|tees| {
+                    tees.borrow()
+                        .as_ref()
+                        .and_then(|tees| tees.deserialized.get(&id).cloned())
+                })
+                .map(TeeNode)
+                .ok_or_else(|| D::Error::custom(format!("reference to unknown tee {}", id))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HydroIrMetadata {
     pub location_kind: LocationId,
     pub output_type: Option<DebugType>,
     pub cardinality: Option<usize>,
     pub cpu_usage: Option<f64>,
     pub network_recv_cpu_usage: Option<f64>,
+    pub monotonicity: Option<Monotonicity>,
     pub id: Option<usize>,
 }
 
+/// Whether the output of a node only grows as its inputs grow, as labeled by
+/// [`monotonicity_analysis`](crate::monotonicity::monotonicity_analysis).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Monotonicity {
+    /// Outputs are never retracted, so the node can run without coordination.
+    Monotone,
+    /// Outputs may change as more inputs arrive, so the result depends on timing.
+    NonMonotone,
+}
+
 // HydroIrMetadata shouldn't be used to hash or compare
 impl Hash for HydroIrMetadata {
     fn hash<H: Hasher>(&self, _: &mut H) {}
@@ -627,9 +791,20 @@ impl PartialEq for HydroIrMetadata {
 
 impl Eq for HydroIrMetadata {}
 
+/// The algebraic properties that the method creating a [`HydroNode::Fold`],
+/// [`HydroNode::FoldKeyed`], [`HydroNode::Reduce`], or [`HydroNode::ReduceKeyed`] requires of
+/// its closure, such as [`Stream::fold_commutative`](crate::Stream::fold_commutative).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AggregationProperties {
+    /// The result does not depend on the order of the inputs.
+    pub commutative: bool,
+    /// The result does not depend on whether inputs are duplicated.
+    pub idempotent: bool,
+}
+
 /// An intermediate node in a Hydro graph, which consumes data
 /// from upstream nodes and emits data to downstream nodes.
-#[derive(Debug, Hash)]
+#[derive(Debug, Hash, Serialize, Deserialize)]
 pub enum HydroNode {
     Placeholder,
 
@@ -640,6 +815,7 @@ pub enum HydroNode {
     },
 
     CycleSource {
+        #[serde(with = "serde_ident")]
         ident: syn::Ident,
         location_kind: LocationId,
         metadata: HydroIrMetadata,
@@ -758,23 +934,37 @@ pub enum HydroNode {
     Fold {
         init: DebugExpr,
         acc: DebugExpr,
+        properties: AggregationProperties,
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
     FoldKeyed {
         init: DebugExpr,
         acc: DebugExpr,
+        properties: AggregationProperties,
+        input: Box<HydroNode>,
+        metadata: HydroIrMetadata,
+    },
+    LatticeFold {
+        init: DebugExpr,
+        input: Box<HydroNode>,
+        metadata: HydroIrMetadata,
+    },
+    LatticeFoldKeyed {
+        init: DebugExpr,
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
 
     Reduce {
         f: DebugExpr,
+        properties: AggregationProperties,
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
     ReduceKeyed {
         f: DebugExpr,
+        properties: AggregationProperties,
         input: Box<HydroNode>,
         metadata: HydroIrMetadata,
     },
@@ -964,6 +1154,8 @@ impl HydroNode {
             | HydroNode::Network { input, .. }
             | HydroNode::Fold { input, .. }
             | HydroNode::FoldKeyed { input, .. }
+            | HydroNode::LatticeFold { input, .. }
+            | HydroNode::LatticeFoldKeyed { input, .. }
             | HydroNode::Reduce { input, .. }
             | HydroNode::ReduceKeyed { input, .. }
             | HydroNode::Counter { input, .. } => {
@@ -1127,32 +1319,66 @@ impl HydroNode {
             HydroNode::Fold {
                 init,
                 acc,
+                properties,
                 input,
                 metadata,
             } => HydroNode::Fold {
                 init: init.clone(),
                 acc: acc.clone(),
+                properties: *properties,
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
             HydroNode::FoldKeyed {
                 init,
                 acc,
+                properties,
                 input,
                 metadata,
             } => HydroNode::FoldKeyed {
                 init: init.clone(),
                 acc: acc.clone(),
+                properties: *properties,
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
-            HydroNode::Reduce { f, input, metadata } => HydroNode::Reduce {
+            HydroNode::LatticeFold {
+                init,
+                input,
+                metadata,
+            } => HydroNode::LatticeFold {
+                init: init.clone(),
+                input: Box::new(input.deep_clone(seen_tees)),
+                metadata: metadata.clone(),
+            },
+            HydroNode::LatticeFoldKeyed {
+                init,
+                input,
+                metadata,
+            } => HydroNode::LatticeFoldKeyed {
+                init: init.clone(),
+                input: Box::new(input.deep_clone(seen_tees)),
+                metadata: metadata.clone(),
+            },
+            HydroNode::Reduce {
+                f,
+                properties,
+                input,
+                metadata,
+            } => HydroNode::Reduce {
                 f: f.clone(),
+                properties: *properties,
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
-            HydroNode::ReduceKeyed { f, input, metadata } => HydroNode::ReduceKeyed {
+            HydroNode::ReduceKeyed {
+                f,
+                properties,
+                input,
+                metadata,
+            } => HydroNode::ReduceKeyed {
                 f: f.clone(),
+                properties: *properties,
                 input: Box::new(input.deep_clone(seen_tees)),
                 metadata: metadata.clone(),
             },
@@ -1962,6 +2188,64 @@ impl HydroNode {
                 (fold_ident, input_location_id)
             }
 
+            HydroNode::LatticeFold { .. } | HydroNode::LatticeFoldKeyed { .. } => {
+                let is_keyed = matches!(self, HydroNode::LatticeFoldKeyed { .. });
+
+                let (HydroNode::LatticeFold { init, input, .. }
+                | HydroNode::LatticeFoldKeyed { init, input, .. }) = self
+                else {
+                    unreachable!()
+                };
+
+                let (input, lifetime) =
+                    if let HydroNode::Persist { inner: input, .. } = input.as_mut() {
+                        (input, quote!('static))
+                    } else {
+                        (input, quote!('tick))
+                    };
+
+                let (input_ident, input_location_id) =
+                    input.emit_core(builders_or_callback, built_tees, next_stmt_id);
+
+                let fold_ident =
+                    syn::Ident::new(&format!("stream_{}", *next_stmt_id), Span::call_site());
+
+                match builders_or_callback {
+                    BuildersOrCallback::Builders(graph_builders) => {
+                        let builder = graph_builders.entry(input_location_id).or_default();
+                        if is_keyed {
+                            // DFIR has no keyed lattice fold, so merge each value into its key's
+                            // accumulator with `fold_keyed`
+                            let root = get_this_crate();
+                            builder.add_dfir(
+                                parse_quote! {
+                                    #fold_ident = #input_ident -> fold_keyed::<#lifetime>(#init, |acc, item| {
+                                        #root::runtime_support::dfir_rs::lattices::Merge::merge(acc, item);
+                                    });
+                                },
+                                None,
+                                Some(&next_stmt_id.to_string()),
+                            );
+                        } else {
+                            builder.add_dfir(
+                                parse_quote! {
+                                    #fold_ident = #input_ident -> lattice_fold::<#lifetime>(#init);
+                                },
+                                None,
+                                Some(&next_stmt_id.to_string()),
+                            );
+                        }
+                    }
+                    BuildersOrCallback::Callback(_, node_callback) => {
+                        node_callback(self, next_stmt_id);
+                    }
+                }
+
+                *next_stmt_id += 1;
+
+                (fold_ident, input_location_id)
+            }
+
             HydroNode::Reduce { .. } | HydroNode::ReduceKeyed { .. } => {
                 let operator: syn::Ident = if matches!(self, HydroNode::Reduce { .. }) {
                     parse_quote!(reduce)
@@ -2164,6 +2448,9 @@ impl HydroNode {
                 transform(init);
                 transform(acc);
             }
+            HydroNode::LatticeFold { init, .. } | HydroNode::LatticeFoldKeyed { init, .. } => {
+                transform(init);
+            }
             HydroNode::Network {
                 serialize_fn,
                 deserialize_fn,
@@ -2212,6 +2499,8 @@ impl HydroNode {
             HydroNode::Sort { metadata, .. } => metadata,
             HydroNode::Fold { metadata, .. } => metadata,
             HydroNode::FoldKeyed { metadata, .. } => metadata,
+            HydroNode::LatticeFold { metadata, .. } => metadata,
+            HydroNode::LatticeFoldKeyed { metadata, .. } => metadata,
             HydroNode::Reduce { metadata, .. } => metadata,
             HydroNode::ReduceKeyed { metadata, .. } => metadata,
             HydroNode::Network { metadata, .. } => metadata,
@@ -2249,6 +2538,8 @@ impl HydroNode {
             HydroNode::Sort { metadata, .. } => metadata,
             HydroNode::Fold { metadata, .. } => metadata,
             HydroNode::FoldKeyed { metadata, .. } => metadata,
+            HydroNode::LatticeFold { metadata, .. } => metadata,
+            HydroNode::LatticeFoldKeyed { metadata, .. } => metadata,
             HydroNode::Reduce { metadata, .. } => metadata,
             HydroNode::ReduceKeyed { metadata, .. } => metadata,
             HydroNode::Network { metadata, .. } => metadata,
@@ -2299,6 +2590,8 @@ impl HydroNode {
             }
             HydroNode::Fold { input, .. }
             | HydroNode::FoldKeyed { input, .. }
+            | HydroNode::LatticeFold { input, .. }
+            | HydroNode::LatticeFoldKeyed { input, .. }
             | HydroNode::Reduce { input, .. }
             | HydroNode::ReduceKeyed { input, .. } => {
                 // Skip persist before fold/reduce
@@ -2361,6 +2654,8 @@ impl HydroNode {
             HydroNode::Sort { .. } => "Sort()".to_string(),
             HydroNode::Fold { init, acc, .. } => format!("Fold({:?}, {:?})", init, acc),
             HydroNode::FoldKeyed { init, acc, .. } => format!("FoldKeyed({:?}, {:?})", init, acc),
+            HydroNode::LatticeFold { init, .. } => format!("LatticeFold({:?})", init),
+            HydroNode::LatticeFoldKeyed { init, .. } => format!("LatticeFoldKeyed({:?})", init),
             HydroNode::Reduce { f, .. } => format!("Reduce({:?})", f),
             HydroNode::ReduceKeyed { f, .. } => format!("ReduceKeyed({:?})", f),
             HydroNode::Network { to_location, .. } => format!("Network(to {:?})", to_location),
@@ -2557,11 +2852,11 @@ mod test {
 
     #[test]
     fn hydro_node_size() {
-        insta::assert_snapshot!(size_of::<HydroNode>(), @"184");
+        insta::assert_snapshot!(size_of::<HydroNode>(), @"192");
     }
 
     #[test]
     fn hydro_leaf_size() {
-        insta::assert_snapshot!(size_of::<HydroLeaf>(), @"160");
+        insta::assert_snapshot!(size_of::<HydroLeaf>(), @"168");
     }
 }
@@ -0,0 +1,270 @@
+use std::hash::Hash;
+
+use stageleft::{IntoQuotedMut, QuotedWithContext, q};
+use syn::parse_quote;
+
+use crate::ir::{AggregationProperties, HydroNode};
+use crate::keyed_stream::KeyedStream;
+use crate::location::tick::NoAtomic;
+use crate::location::{Location, NoTick, Tick};
+use crate::singleton::bounded_top_level_once;
+use crate::stream::ExactlyOnce;
+use crate::{Bounded, NoOrder, Stream};
+
+/// A collection of independent singleton values, one for each key, such as the result of
+/// aggregating a [`KeyedStream`].
+///
+/// Like a [`Singleton`](crate::Singleton), an [`Unbounded`](crate::Unbounded) keyed
+/// singleton at the top level is continuously updated as more values are aggregated, so it
+/// can only be observed through a snapshot with [`KeyedSingleton::latest_tick`].
+pub struct KeyedSingleton<K, V, Loc, Bound> {
+    pub(crate) underlying: Stream<(K, V), Loc, Bound, NoOrder, ExactlyOnce>,
+}
+
+impl<'a, K, V, L, B> KeyedSingleton<K, V, L, B>
+where
+    L: Location<'a>,
+{
+    pub(crate) fn new(underlying: Stream<(K, V), L, B, NoOrder, ExactlyOnce>) -> Self {
+        KeyedSingleton { underlying }
+    }
+}
+
+impl<'a, K, V, L, B> Clone for KeyedSingleton<K, V, L, B>
+where
+    K: Clone,
+    V: Clone,
+    L: Location<'a>,
+{
+    fn clone(&self) -> Self {
+        KeyedSingleton::new(self.underlying.clone())
+    }
+}
+
+impl<'a, K, V, L, B> KeyedSingleton<K, V, L, B>
+where
+    L: Location<'a>,
+{
+    /// Transforms the value of each key with `f`.
+    pub fn map<U, F>(self, f: impl IntoQuotedMut<'a, F, L>) -> KeyedSingleton<K, U, L, B>
+    where
+        F: Fn(V) -> U + 'a,
+    {
+        let f = f.splice_fn1_ctx(&self.underlying.location);
+        let wrapped: syn::Expr = parse_quote!({
+            let f = #f;
+            move |(k, v)| (k, f(v))
+        });
+
+        let location = self.underlying.location.clone();
+        KeyedSingleton::new(Stream::new(
+            location.clone(),
+            HydroNode::Map {
+                f: wrapped.into(),
+                input: Box::new(self.underlying.ir_node.into_inner()),
+                metadata: location.new_node_metadata::<(K, U)>(),
+            },
+        ))
+    }
+}
+
+impl<'a, K, V, L, B> KeyedSingleton<K, V, L, B>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    /// Given a tick, returns a keyed singleton corresponding to a snapshot of the values as
+    /// of that tick. The snapshot at tick `t + 1` is guaranteed to include at least all
+    /// relevant data that contributed to the snapshot at tick `t`.
+    ///
+    /// # Safety
+    /// Because this picks a snapshot of values that are continuously changing, the output
+    /// has non-deterministic values since the snapshot can be at an arbitrary point in time.
+    pub unsafe fn latest_tick(self, tick: &Tick<L>) -> KeyedSingleton<K, V, Tick<L>, Bounded> {
+        KeyedSingleton::new(Stream::new(
+            tick.clone(),
+            HydroNode::Unpersist {
+                inner: Box::new(self.underlying.ir_node.into_inner()),
+                metadata: tick.new_node_metadata::<(K, V)>(),
+            },
+        ))
+    }
+}
+
+impl<'a, K, V, L> KeyedSingleton<K, V, L, Bounded>
+where
+    K: Eq + Hash,
+    L: Location<'a>,
+{
+    /// Returns a stream of the `(K, V)` entries, in a non-deterministic order.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3)]));
+    /// let batch = unsafe { numbers.into_keyed().tick_batch(&tick) };
+    /// batch.reduce(q!(|acc, x| *acc += x)).entries().all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 3) in any order
+    /// # let mut out = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # out.sort();
+    /// # assert_eq!(out, vec![(1, 5), (2, 3)]);
+    /// # }));
+    /// ```
+    pub fn entries(self) -> Stream<(K, V), L, Bounded, NoOrder, ExactlyOnce> {
+        if L::is_top_level() {
+            let location = self.underlying.location.clone();
+            let keep_first: syn::Expr = parse_quote!(|_, _| {});
+            Stream::new(
+                location.clone(),
+                bounded_top_level_once::<(K, V), L>(
+                    &location,
+                    self.underlying.ir_node.into_inner(),
+                    |copies| HydroNode::ReduceKeyed {
+                        f: keep_first.into(),
+                        properties: AggregationProperties {
+                            commutative: false,
+                            idempotent: true,
+                        },
+                        input: Box::new(copies),
+                        metadata: location.new_node_metadata::<(K, V)>(),
+                    },
+                ),
+            )
+        } else {
+            self.underlying
+        }
+    }
+
+    /// Returns a stream of the keys that have a value, in a non-deterministic order.
+    pub fn keys(self) -> Stream<K, L, Bounded, NoOrder, ExactlyOnce> {
+        self.entries().map(q!(|(k, _)| k))
+    }
+
+    /// Looks up the value of the key of each element in `requests`, producing the request
+    /// paired with the value. Requests for keys that have no value are dropped.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let totals = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec![(1, 2), (2, 3), (1, 3)]))
+    ///         .into_keyed()
+    ///         .tick_batch(&tick)
+    /// }
+    /// .fold(q!(|| 0), q!(|acc, x| *acc += x));
+    /// let requests = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec![(1, 'a'), (3, 'b')]))
+    ///         .into_keyed()
+    ///         .tick_batch(&tick)
+    /// };
+    /// totals.lookup(requests).entries().all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, ('a', 5))
+    /// # assert_eq!(stream.next().await.unwrap(), (1, ('a', 5)));
+    /// # }));
+    /// ```
+    pub fn lookup<Q, O, R>(
+        self,
+        requests: KeyedStream<K, Q, L, Bounded, O, R>,
+    ) -> KeyedStream<K, (Q, V), L, Bounded, NoOrder, R> {
+        let values: Stream<(K, V), L, Bounded, NoOrder, R> = unsafe {
+            // SAFETY: there is exactly one value for each key
+            self.entries().assume_retries()
+        };
+        KeyedStream::new(requests.underlying.join(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use crate::FlowBuilder;
+    use crate::location::Location;
+
+    struct P1 {}
+    struct P2 {}
+
+    #[test]
+    fn keyed_singleton_lookup_top_level_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let totals = node
+            .source_iter(q!(vec![(1, 2), (2, 3), (1, 3)]))
+            .into_keyed()
+            .fold(q!(|| 0), q!(|acc, x| *acc += x));
+        let requests = node.source_iter(q!(vec![(1, 'a'), (3, 'b')])).into_keyed();
+        let out_port = totals
+            .lookup(requests)
+            .entries()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        assert_eq!(
+            simulation
+                .run()
+                .bincode::<(i32, (char, i32))>(&nodes.raw_port_bincode(&out_port)),
+            vec![(1, ('a', 5))]
+        );
+    }
+
+    #[test]
+    fn keyed_singleton_entries_top_level_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let entries_port = receiver
+            .source_iter(q!(vec![(1, 2.0), (2, 3.5), (1, 3.0)]))
+            .into_keyed()
+            .reduce_commutative(q!(|acc: &mut f64, x| *acc += x))
+            .entries()
+            .send_bincode_external(&external);
+        // Messages from the sender keep the receiver ticking after the entries are final.
+        let pings_port = sender
+            .source_iter(q!(0..5))
+            .send_bincode(&receiver)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&sender, ())
+            .with_process(&receiver, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let entries_port = nodes.raw_port_bincode(&entries_port);
+        let pings_port = nodes.raw_port_bincode(&pings_port);
+        simulation
+            .explore(0..10, |output| {
+                assert_eq!(output.bincode::<i32>(&pings_port).len(), 5);
+
+                let mut entries = output.bincode::<(i32, f64)>(&entries_port);
+                entries.sort_by_key(|(k, _)| *k);
+                if entries == vec![(1, 5.0), (2, 3.5)] {
+                    Ok(())
+                } else {
+                    Err(format!("entries were {:?}", entries))
+                }
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,713 @@
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use lattices::Merge;
+use stageleft::{IntoQuotedMut, QuotedWithContext, q};
+use syn::parse_quote;
+
+use crate::ir::{AggregationProperties, HydroNode};
+use crate::keyed_singleton::KeyedSingleton;
+use crate::location::tick::NoAtomic;
+use crate::location::{Location, NoTick, Tick};
+use crate::stream::{AtLeastOnce, ExactlyOnce};
+use crate::{Bounded, NoOrder, Stream, TotalOrder, Unbounded};
+
+/// A collection of independent streams of values, one for each key.
+///
+/// Unlike a [`Stream`] of `(K, V)` pairs, the ordering and retry guarantees of a keyed
+/// stream apply to the values of each key separately, so aggregations only need to be
+/// insensitive to the interleaving of different keys. For example, the values of a key may
+/// be totally ordered even though there is no order between the values of different keys.
+///
+/// Type Parameters:
+/// - `K`: the type of the keys
+/// - `V`: the type of the values in each stream
+/// - `Loc`: the location where the streams are being materialized
+/// - `Bound`: the boundedness of the streams, which is either [`Bounded`]
+///   or [`Unbounded`]
+/// - `Order`: the ordering of the values within each key, which is either [`TotalOrder`]
+///   or [`NoOrder`] (default is [`TotalOrder`])
+/// - `Retries`: the retry guarantee of the values within each key, which is either
+///   [`ExactlyOnce`] or [`AtLeastOnce`] (default is [`ExactlyOnce`])
+pub struct KeyedStream<K, V, Loc, Bound, Order = TotalOrder, Retries = ExactlyOnce> {
+    pub(crate) underlying: Stream<(K, V), Loc, Bound, NoOrder, Retries>,
+    _phantom_order: PhantomData<Order>,
+}
+
+impl<'a, K, V, L, B, O, R> KeyedStream<K, V, L, B, O, R>
+where
+    L: Location<'a>,
+{
+    pub(crate) fn new(underlying: Stream<(K, V), L, B, NoOrder, R>) -> Self {
+        KeyedStream {
+            underlying,
+            _phantom_order: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, L, B, O, R> Clone for KeyedStream<K, V, L, B, O, R>
+where
+    K: Clone,
+    V: Clone,
+    L: Location<'a>,
+{
+    fn clone(&self) -> Self {
+        KeyedStream::new(self.underlying.clone())
+    }
+}
+
+impl<'a, K, V, L, B, O, R> KeyedStream<K, V, L, B, O, R>
+where
+    L: Location<'a>,
+{
+    /// Flattens the keyed stream into a stream of `(K, V)` pairs. The pairs of different keys
+    /// are interleaved in a non-deterministic order.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process
+    ///     .source_iter(q!(vec![(1, 2), (2, 3), (1, 3)]))
+    ///     .into_keyed()
+    ///     .map(q!(|v| v * 10))
+    ///     .entries()
+    /// # }, |mut stream| async move {
+    /// // (1, 20), (2, 30), (1, 30) in any order
+    /// # let mut out = vec![];
+    /// # for _ in 0..3 {
+    /// #     out.push(stream.next().await.unwrap());
+    /// # }
+    /// # out.sort();
+    /// # assert_eq!(out, vec![(1, 20), (1, 30), (2, 30)]);
+    /// # }));
+    /// ```
+    pub fn entries(self) -> Stream<(K, V), L, B, NoOrder, R> {
+        self.underlying
+    }
+
+    /// Transforms each value with `f`, keeping it under the same key.
+    pub fn map<U, F>(self, f: impl IntoQuotedMut<'a, F, L>) -> KeyedStream<K, U, L, B, O, R>
+    where
+        F: Fn(V) -> U + 'a,
+    {
+        let f = f.splice_fn1_ctx(&self.underlying.location);
+        let wrapped: syn::Expr = parse_quote!({
+            let f = #f;
+            move |(k, v)| (k, f(v))
+        });
+
+        let location = self.underlying.location.clone();
+        KeyedStream::new(Stream::new(
+            location.clone(),
+            HydroNode::Map {
+                f: wrapped.into(),
+                input: Box::new(self.underlying.ir_node.into_inner()),
+                metadata: location.new_node_metadata::<(K, U)>(),
+            },
+        ))
+    }
+
+    /// Keeps only the values for which `f` returns `true`.
+    pub fn filter<F>(self, f: impl IntoQuotedMut<'a, F, L>) -> KeyedStream<K, V, L, B, O, R>
+    where
+        F: Fn(&V) -> bool + 'a,
+    {
+        let f = f.splice_fn1_borrow_ctx(&self.underlying.location);
+        let wrapped: syn::Expr = parse_quote!({
+            let f = #f;
+            move |(_, v): &(_, _)| f(v)
+        });
+
+        let location = self.underlying.location.clone();
+        KeyedStream::new(Stream::new(
+            location.clone(),
+            HydroNode::Filter {
+                f: wrapped.into(),
+                input: Box::new(self.underlying.ir_node.into_inner()),
+                metadata: location.new_node_metadata::<(K, V)>(),
+            },
+        ))
+    }
+
+    /// Equi-joins the values of each key with the values of the same key in `other`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let names = process
+    ///     .source_iter(q!(vec![(1, 'a'), (2, 'b')]))
+    ///     .into_keyed();
+    /// let scores = process
+    ///     .source_iter(q!(vec![(1, 10), (1, 20), (3, 30)]))
+    ///     .into_keyed();
+    /// names.join(scores).entries()
+    /// # }, |mut stream| async move {
+    /// // (1, ('a', 10)), (1, ('a', 20)) in any order
+    /// # let mut out = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # out.sort();
+    /// # assert_eq!(out, vec![(1, ('a', 10)), (1, ('a', 20))]);
+    /// # }));
+    /// ```
+    pub fn join<V2, O2>(
+        self,
+        other: KeyedStream<K, V2, L, B, O2, R>,
+    ) -> KeyedStream<K, (V, V2), L, B, NoOrder, R>
+    where
+        K: Eq + Hash,
+    {
+        KeyedStream::new(self.underlying.join(other.underlying))
+    }
+
+    /// Explicitly "casts" the keyed stream to a type with a different ordering guarantee
+    /// for the values of each key.
+    ///
+    /// # Safety
+    /// This function is used as an escape hatch, and any mistakes in the
+    /// provided ordering guarantee will propagate into the guarantees
+    /// for the rest of the program.
+    pub unsafe fn assume_ordering<O2>(self) -> KeyedStream<K, V, L, B, O2, R> {
+        KeyedStream::new(self.underlying)
+    }
+
+    /// Explicitly "casts" the keyed stream to a type with a different retries guarantee.
+    ///
+    /// # Safety
+    /// This function is used as an escape hatch, and any mistakes in the
+    /// provided retries guarantee will propagate into the guarantees
+    /// for the rest of the program.
+    pub unsafe fn assume_retries<R2>(self) -> KeyedStream<K, V, L, B, O, R2> {
+        KeyedStream::new(unsafe { self.underlying.assume_retries() })
+    }
+
+    pub fn weakest_retries(self) -> KeyedStream<K, V, L, B, O, AtLeastOnce> {
+        KeyedStream::new(self.underlying.weakest_retries())
+    }
+}
+
+impl<'a, K, V, L, B> KeyedStream<K, V, L, B, TotalOrder, ExactlyOnce>
+where
+    K: Eq + Hash,
+    L: Location<'a>,
+{
+    /// Combines the values of each key into a [`KeyedSingleton`], by starting with an initial
+    /// value generated by the `init` closure, and then applying the `comb` closure to each
+    /// value in order.
+    ///
+    /// The values of each key must have a [`TotalOrder`] guarantee, which means that the
+    /// `comb` closure is allowed to depend on the order of values within a key.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let words = process.source_iter(q!(vec![(1, "a"), (2, "b"), (1, "c")]));
+    /// let joined = words
+    ///     .into_keyed()
+    ///     .fold(q!(|| String::new()), q!(|acc, w| acc.push_str(w)));
+    /// unsafe { joined.latest_tick(&tick) }.entries().all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, "ac"), (2, "b") in any order
+    /// # let mut out = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # out.sort();
+    /// # assert_eq!(out, vec![(1, "ac".to_string()), (2, "b".to_string())]);
+    /// # }));
+    /// ```
+    pub fn fold<A, I, F>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> KeyedSingleton<K, A, L, B>
+    where
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    {
+        self.fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: false,
+            },
+        )
+    }
+
+    fn fold_with_properties<A, I, F>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        comb: impl IntoQuotedMut<'a, F, L>,
+        properties: AggregationProperties,
+    ) -> KeyedSingleton<K, A, L, B>
+    where
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    {
+        let location = self.underlying.location.clone();
+        let init = init.splice_fn0_ctx(&location).into();
+        let comb = comb.splice_fn2_borrow_mut_ctx(&location).into();
+
+        let mut core = HydroNode::FoldKeyed {
+            init,
+            acc: comb,
+            properties,
+            input: Box::new(self.underlying.ir_node.into_inner()),
+            metadata: location.new_node_metadata::<(K, A)>(),
+        };
+
+        if L::is_top_level() {
+            // top-level (possibly unbounded) keyed singletons are represented as
+            // a stream which produces all entries from all ticks every tick
+            core = HydroNode::Persist {
+                inner: Box::new(core),
+                metadata: location.new_node_metadata::<(K, A)>(),
+            };
+        }
+
+        KeyedSingleton::new(Stream::new(location, core))
+    }
+
+    /// Combines the values of each key into a [`KeyedSingleton`], by starting with the first
+    /// value and then applying the `comb` closure to each later value in order.
+    ///
+    /// The values of each key must have a [`TotalOrder`] guarantee, which means that the
+    /// `comb` closure is allowed to depend on the order of values within a key.
+    pub fn reduce<F>(self, comb: impl IntoQuotedMut<'a, F, L>) -> KeyedSingleton<K, V, L, B>
+    where
+        F: Fn(&mut V, V) + 'a,
+    {
+        self.reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: false,
+            },
+        )
+    }
+
+    fn reduce_with_properties<F>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, L>,
+        properties: AggregationProperties,
+    ) -> KeyedSingleton<K, V, L, B>
+    where
+        F: Fn(&mut V, V) + 'a,
+    {
+        let location = self.underlying.location.clone();
+        let f = comb.splice_fn2_borrow_mut_ctx(&location).into();
+
+        let mut core = HydroNode::ReduceKeyed {
+            f,
+            properties,
+            input: Box::new(self.underlying.ir_node.into_inner()),
+            metadata: location.new_node_metadata::<(K, V)>(),
+        };
+
+        if L::is_top_level() {
+            core = HydroNode::Persist {
+                inner: Box::new(core),
+                metadata: location.new_node_metadata::<(K, V)>(),
+            };
+        }
+
+        KeyedSingleton::new(Stream::new(location, core))
+    }
+}
+
+impl<'a, K, V, L, B, O> KeyedStream<K, V, L, B, O, ExactlyOnce>
+where
+    K: Eq + Hash,
+    L: Location<'a>,
+{
+    /// Like [`KeyedStream::fold`], but the values of each key may be in any order, so the
+    /// `comb` closure must be **commutative**.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let counts = process
+    ///     .source_iter(q!(vec![(1, 2), (2, 3), (1, 3)]))
+    ///     .into_keyed()
+    ///     .fold_commutative(q!(|| 0), q!(|acc, x| *acc += x));
+    /// unsafe { counts.latest_tick(&tick) }.entries().all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 3) in any order
+    /// # let mut out = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # out.sort();
+    /// # assert_eq!(out, vec![(1, 5), (2, 3)]);
+    /// # }));
+    /// ```
+    pub fn fold_commutative<A, I, F>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> KeyedSingleton<K, A, L, B>
+    where
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    {
+        unsafe {
+            // SAFETY: aggregation is commutative
+            self.assume_ordering::<TotalOrder>()
+        }
+        .fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: false,
+            },
+        )
+    }
+
+    /// Like [`KeyedStream::reduce`], but the values of each key may be in any order, so the
+    /// `comb` closure must be **commutative**.
+    pub fn reduce_commutative<F>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> KeyedSingleton<K, V, L, B>
+    where
+        F: Fn(&mut V, V) + 'a,
+    {
+        unsafe {
+            // SAFETY: aggregation is commutative
+            self.assume_ordering::<TotalOrder>()
+        }
+        .reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: false,
+            },
+        )
+    }
+}
+
+impl<'a, K, V, L, B, R> KeyedStream<K, V, L, B, TotalOrder, R>
+where
+    K: Eq + Hash,
+    L: Location<'a>,
+{
+    /// Like [`KeyedStream::fold`], but values may be duplicated, so the `comb` closure must be
+    /// **idempotent**.
+    pub fn fold_idempotent<A, I, F>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> KeyedSingleton<K, A, L, B>
+    where
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    {
+        unsafe {
+            // SAFETY: aggregation is idempotent
+            self.assume_retries::<ExactlyOnce>()
+        }
+        .fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: true,
+            },
+        )
+    }
+
+    /// Like [`KeyedStream::reduce`], but values may be duplicated, so the `comb` closure must be
+    /// **idempotent**.
+    pub fn reduce_idempotent<F>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> KeyedSingleton<K, V, L, B>
+    where
+        F: Fn(&mut V, V) + 'a,
+    {
+        unsafe {
+            // SAFETY: aggregation is idempotent
+            self.assume_retries::<ExactlyOnce>()
+        }
+        .reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: true,
+            },
+        )
+    }
+}
+
+impl<'a, K, V, L, B, O, R> KeyedStream<K, V, L, B, O, R>
+where
+    K: Eq + Hash,
+    L: Location<'a>,
+{
+    /// Like [`KeyedStream::fold`], but the values of each key may be in any order and may be
+    /// duplicated, so the `comb` closure must be **commutative** and **idempotent**.
+    pub fn fold_commutative_idempotent<A, I, F>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> KeyedSingleton<K, A, L, B>
+    where
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    {
+        unsafe {
+            // SAFETY: aggregation is commutative and idempotent
+            self.assume_ordering::<TotalOrder>()
+                .assume_retries::<ExactlyOnce>()
+        }
+        .fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: true,
+            },
+        )
+    }
+
+    /// Like [`KeyedStream::reduce`], but the values of each key may be in any order and may
+    /// be duplicated, so the `comb` closure must be **commutative** and **idempotent**.
+    pub fn reduce_commutative_idempotent<F>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> KeyedSingleton<K, V, L, B>
+    where
+        F: Fn(&mut V, V) + 'a,
+    {
+        unsafe {
+            // SAFETY: aggregation is commutative and idempotent
+            self.assume_ordering::<TotalOrder>()
+                .assume_retries::<ExactlyOnce>()
+        }
+        .reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: true,
+            },
+        )
+    }
+
+    /// Merges the values of each key into a lattice of type `Lat`, starting from its
+    /// [`Default`] value. Because lattice merges are commutative and idempotent, the values
+    /// may be in any order and may be duplicated.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let maxes = process
+    ///     .source_iter(q!(vec![(1, 2), (2, 3), (1, 4)]))
+    ///     .map(q!(|(k, v)| (k, hydro_lang::lattices::Max::new(v))))
+    ///     .into_keyed()
+    ///     .fold_lattice::<lattices::Max<i32>>()
+    ///     .map(q!(|max| max.into_reveal()));
+    /// unsafe { maxes.latest_tick(&tick) }.entries().all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, 4), (2, 3) in any order
+    /// # let mut out = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # out.sort();
+    /// # assert_eq!(out, vec![(1, 4), (2, 3)]);
+    /// # }));
+    /// ```
+    pub fn fold_lattice<Lat>(self) -> KeyedSingleton<K, Lat, L, B>
+    where
+        Lat: Merge<V> + Default,
+    {
+        let location = self.underlying.location.clone();
+        let lat_type = stageleft::quote_type::<Lat>();
+        let init: syn::Expr = parse_quote!(<#lat_type as ::std::default::Default>::default);
+
+        let mut core = HydroNode::LatticeFoldKeyed {
+            init: init.into(),
+            input: Box::new(self.underlying.ir_node.into_inner()),
+            metadata: location.new_node_metadata::<(K, Lat)>(),
+        };
+
+        if L::is_top_level() {
+            core = HydroNode::Persist {
+                inner: Box::new(core),
+                metadata: location.new_node_metadata::<(K, Lat)>(),
+            };
+        }
+
+        KeyedSingleton::new(Stream::new(location, core))
+    }
+
+    /// Computes the set of keys that have at least one value.
+    pub fn keys(self) -> KeyedSingleton<K, (), L, B> {
+        self.fold_commutative_idempotent(q!(|| ()), q!(|_, _| {}))
+    }
+}
+
+impl<'a, K, V, L, B, O, R> KeyedStream<K, V, L, B, O, R>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    /// Given a tick, returns a keyed stream corresponding to a batch of values segmented by
+    /// that tick. The values of each key preserve their order across batches.
+    ///
+    /// # Safety
+    /// The batch boundaries are non-deterministic and may change across executions.
+    pub unsafe fn tick_batch(self, tick: &Tick<L>) -> KeyedStream<K, V, Tick<L>, Bounded, O, R> {
+        KeyedStream::new(unsafe { self.underlying.tick_batch(tick) })
+    }
+}
+
+impl<'a, K, V, L, O, R> KeyedStream<K, V, Tick<L>, Bounded, O, R>
+where
+    L: Location<'a>,
+{
+    pub fn all_ticks(self) -> KeyedStream<K, V, L, Unbounded, O, R> {
+        KeyedStream::new(self.underlying.all_ticks())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use stageleft::q;
+
+    use crate::location::Location;
+    use crate::{FlowBuilder, Stream, Unbounded};
+
+    struct P1 {}
+    struct P2 {}
+
+    #[test]
+    fn keyed_fold_and_reduce_top_level_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let words = node
+            .source_iter(q!(vec![(1, "a"), (2, "b"), (1, "c")]))
+            .into_keyed();
+        let folded_port = words
+            .clone()
+            .fold(q!(|| String::new()), q!(|acc, w| acc.push_str(w)))
+            .entries()
+            .send_bincode_external(&external);
+        let reduced_port = words
+            .map(q!(|w| w.to_string()))
+            .reduce(q!(|acc, w| acc.push_str(&w)))
+            .entries()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let output = simulation.run();
+        for port in [&folded_port, &reduced_port] {
+            let mut entries = output.bincode::<(i32, String)>(&nodes.raw_port_bincode(port));
+            entries.sort();
+            assert_eq!(entries, vec![(1, "ac".to_string()), (2, "b".to_string())]);
+        }
+    }
+
+    #[test]
+    fn keyed_join_top_level_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let names_node = flow.process::<P1>();
+        let scores_node = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let names = names_node
+            .source_iter(q!(vec![(1, 'a'), (2, 'b')]))
+            .send_bincode(&receiver)
+            .into_keyed();
+        let scores = scores_node
+            .source_iter(q!(vec![(1, 10), (1, 20), (3, 30)]))
+            .send_bincode(&receiver)
+            .into_keyed();
+        let out_port = names
+            .join(scores)
+            .entries()
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&names_node, ())
+            .with_process(&scores_node, ())
+            .with_process(&receiver, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        // However the deliveries are interleaved, each matching pair is joined exactly once.
+        let port = nodes.raw_port_bincode(&out_port);
+        simulation
+            .explore(0..10, |output| {
+                let mut joined = output.bincode::<(i32, (char, i32))>(&port);
+                joined.sort();
+                if joined == vec![(1, ('a', 10)), (1, ('a', 20))] {
+                    Ok(())
+                } else {
+                    Err(format!("joined {:?}", joined))
+                }
+            })
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn outer_and_semi_joins_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let (nodes, (left_port, semi_port)) =
+            crate::test_util::sim_deploy_test(&mut simulation, |flow, external| {
+                let sender = flow.process::<P1>();
+                let receiver = flow.process::<P2>();
+
+                // the left side arrives over the network, so it is unbounded
+                let orders = sender
+                    .source_iter(q!(vec![(1, 'a'), (2, 'b'), (3, 'c')]))
+                    .send_bincode(&receiver);
+                let prices = receiver.source_iter(q!(vec![(1, 10), (3, 30)]));
+
+                let left_port = orders
+                    .clone()
+                    .left_join(prices)
+                    .send_bincode_external(external);
+                let keys: Stream<_, _, Unbounded> = receiver.source_iter(q!(vec![2, 2, 3])).into();
+                let semi_port = orders.semi_join(keys).send_bincode_external(external);
+
+                (left_port, semi_port)
+            });
+
+        let mut left = nodes
+            .connect_source_bincode(left_port)
+            .await
+            .collect::<Vec<(i32, (char, Option<i32>))>>()
+            .await;
+        left.sort();
+        assert_eq!(
+            left,
+            vec![(1, ('a', Some(10))), (2, ('b', None)), (3, ('c', Some(30)))]
+        );
+
+        let mut semi = nodes
+            .connect_source_bincode(semi_port)
+            .await
+            .collect::<Vec<(i32, char)>>()
+            .await;
+        semi.sort();
+        assert_eq!(semi, vec![(2, 'b'), (3, 'c')]);
+    }
+}
@@ -3,6 +3,7 @@
 
 stageleft::stageleft_no_entry_crate!();
 
+pub use lattices;
 pub use stageleft::q;
 
 #[cfg(feature = "runtime_support")]
@@ -11,6 +12,7 @@ pub use stageleft::q;
 pub mod runtime_support {
     pub use {bincode, dfir_rs, stageleft, tokio};
     pub mod resource_measurement;
+    pub mod sim;
 }
 
 #[doc(hidden)]
@@ -27,6 +29,15 @@ pub mod runtime_context;
 #[cfg_attr(docsrs, doc(cfg(feature = "dfir_context")))]
 pub use runtime_context::RUNTIME_CONTEXT;
 
+pub mod runtime_clock;
+pub use runtime_clock::CLOCK_NOW;
+
+pub mod codec;
+
+pub mod window;
+
+pub mod watermark;
+
 pub mod boundedness;
 pub use boundedness::{Bounded, Unbounded};
 
@@ -36,9 +47,18 @@ pub use stream::{NoOrder, Stream, TotalOrder};
 pub mod singleton;
 pub use singleton::Singleton;
 
+pub mod keyed_stream;
+pub use keyed_stream::KeyedStream;
+
+pub mod keyed_singleton;
+pub use keyed_singleton::KeyedSingleton;
+
 pub mod optional;
 pub use optional::Optional;
 
+pub mod monotone_singleton;
+pub use monotone_singleton::MonotoneSingleton;
+
 pub mod location;
 pub use location::cluster::CLUSTER_SELF_ID;
 pub use location::{Atomic, Cluster, ClusterId, ExternalProcess, Location, Process, Tick};
@@ -49,6 +69,8 @@ pub mod deploy;
 
 pub mod deploy_runtime;
 
+pub mod sim_runtime;
+
 pub mod cycle;
 
 pub mod builder;
@@ -56,6 +78,10 @@ pub use builder::FlowBuilder;
 
 pub mod ir;
 
+pub mod nondet;
+
+pub mod monotonicity;
+
 pub mod graph;
 
 pub mod rewrites;
@@ -0,0 +1,177 @@
+use std::pin::Pin;
+
+use futures::StreamExt;
+use proc_macro2::Span;
+use quote::quote;
+use serde::{Deserialize, Serialize};
+use stageleft::QuotedWithContext;
+use stageleft::runtime_support::{FreeVariableWithContext, QuoteTokens};
+
+use crate::staging_util::Invariant;
+
+/// A change to the membership of a [`Cluster`](super::Cluster), as observed by
+/// [`Location::source_cluster_membership`](crate::Location::source_cluster_membership).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MembershipEvent {
+    Joined,
+    Left,
+}
+
+/// The runtime representation of a cluster's membership changes, identified by raw member IDs.
+pub type MembershipStream = Pin<Box<dyn futures::Stream<Item = (u32, MembershipEvent)>>>;
+
+/// A free variable that evaluates to a new [`MembershipStream`] for the cluster each time it
+/// is spliced, starting with a [`MembershipEvent::Joined`] for every initial member.
+pub(crate) struct ClusterMembership<'a, C> {
+    pub(crate) id: usize,
+    pub(crate) _phantom: Invariant<'a, C>,
+}
+
+impl<C> Clone for ClusterMembership<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for ClusterMembership<'_, C> {}
+
+impl<'a, C: 'a, Ctx> FreeVariableWithContext<Ctx> for ClusterMembership<'a, C> {
+    type O = MembershipStream;
+
+    fn to_tokens(self, _ctx: &Ctx) -> QuoteTokens
+    where
+        Self: Sized,
+    {
+        let ident = syn::Ident::new(
+            &format!("__hydro_lang_cluster_membership_{}", self.id),
+            Span::call_site(),
+        );
+
+        QuoteTokens {
+            prelude: None,
+            expr: Some(quote! { #ident() }),
+        }
+    }
+}
+
+impl<'a, C: 'a, Ctx> QuotedWithContext<'a, MembershipStream, Ctx> for ClusterMembership<'a, C> {}
+
+/// A free variable that evaluates to a [`MembershipStream`] for the cluster's deployed members,
+/// which never changes after they have joined. This is the default for deployments that do
+/// not support changing membership at runtime.
+#[cfg(feature = "build")]
+#[derive(Clone, Copy)]
+pub(crate) struct StaticClusterMembership {
+    pub(crate) id: usize,
+}
+
+#[cfg(feature = "build")]
+impl<Ctx> FreeVariableWithContext<Ctx> for StaticClusterMembership {
+    type O = MembershipStream;
+
+    fn to_tokens(self, _ctx: &Ctx) -> QuoteTokens
+    where
+        Self: Sized,
+    {
+        let ids = syn::Ident::new(
+            &format!("__hydro_lang_cluster_ids_{}", self.id),
+            Span::call_site(),
+        );
+        let root = crate::staging_util::get_this_crate();
+
+        QuoteTokens {
+            prelude: None,
+            expr: Some(
+                quote! { #root::location::cluster::membership::static_membership_stream(#ids) },
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "build")]
+impl<'a, Ctx> QuotedWithContext<'a, MembershipStream, Ctx> for StaticClusterMembership {}
+
+/// Starts with a [`MembershipEvent::Joined`] for each of `members`, and then never ends.
+pub fn static_membership_stream(members: &[u32]) -> MembershipStream {
+    let joined = members
+        .iter()
+        .map(|id| (*id, MembershipEvent::Joined))
+        .collect::<Vec<_>>();
+
+    Box::pin(futures::stream::iter(joined).chain(futures::stream::pending()))
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use super::MembershipEvent;
+    use crate::location::{ClusterId, Location};
+    use crate::{Stream, Unbounded};
+
+    struct P1 {}
+    struct C1 {}
+
+    #[test]
+    fn broadcast_bincode_dynamic_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let (nodes, (workers, events_port, out_port)) =
+            crate::test_util::sim_deploy_test(&mut simulation, |flow, external| {
+                let leader = flow.process::<P1>();
+                let workers = flow.cluster::<C1>();
+
+                let events_port = leader
+                    .source_cluster_membership(&workers)
+                    .entries()
+                    .map(q!(|(id, event)| (id.raw_id, event)))
+                    .send_bincode_external(external);
+
+                let membership = leader.cluster_membership(&workers);
+                let elements: Stream<u32, _, Unbounded> = leader.source_iter(q!(0..5u32)).into();
+                let out_port = unsafe {
+                    // SAFETY: the test checks every outcome of the race with the membership change
+                    elements.broadcast_bincode_dynamic(&workers, membership)
+                }
+                .send_bincode(&leader)
+                .map(q!(|(id, n)| (id.raw_id, n)))
+                .send_bincode_external(external);
+
+                (workers, events_port, out_port)
+            });
+
+        simulation.update_cluster_membership(
+            &workers,
+            ClusterId::from_raw(1),
+            MembershipEvent::Left,
+        );
+
+        // Members that stay receive every element, while the member that leaves receives
+        // the elements broadcast before the leader observed it leave.
+        let events_port = nodes.raw_port_bincode(&events_port);
+        let port = nodes.raw_port_bincode(&out_port);
+        simulation
+            .explore(0..20, |output| {
+                let events = output.bincode::<(u32, MembershipEvent)>(&events_port);
+                if events.last() != Some(&(1, MembershipEvent::Left)) {
+                    return Err(format!("observed membership events {:?}", events));
+                }
+
+                let received = output.bincode::<(u32, u32)>(&port);
+                for id in 0..3 {
+                    let mut from_leader = received
+                        .iter()
+                        .filter(|(member, _)| *member == id)
+                        .map(|(_, n)| *n)
+                        .collect::<Vec<_>>();
+                    from_leader.sort();
+                    let expected_len = if id == 1 { from_leader.len() } else { 5 };
+                    if from_leader != (0..expected_len as u32).collect::<Vec<_>>() {
+                        return Err(format!("member {} received {:?}", id, from_leader));
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+}
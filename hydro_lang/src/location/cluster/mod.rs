@@ -6,13 +6,20 @@ use quote::quote;
 use stageleft::runtime_support::{FreeVariableWithContext, QuoteTokens};
 use stageleft::{QuotedWithContext, quote_type};
 
-use super::{Location, LocationId};
+use super::{Location, LocationId, NoTick};
 use crate::builder::FlowState;
+use crate::ir::HydroNode;
 use crate::staging_util::{Invariant, get_this_crate};
+use crate::stream::ExactlyOnce;
+use crate::{Stream, TotalOrder, Unbounded};
 
 pub mod cluster_id;
 pub use cluster_id::ClusterId;
 
+pub mod membership;
+use membership::ClusterMembership;
+pub use membership::{MembershipEvent, MembershipStream};
+
 pub struct Cluster<'a, ClusterTag> {
     pub(crate) id: usize,
     pub(crate) flow_state: FlowState,
@@ -39,6 +46,41 @@ impl<'a, C> Cluster<'a, C> {
             _phantom: PhantomData,
         }
     }
+
+    pub(crate) fn membership(&self) -> ClusterMembership<'a, C> {
+        ClusterMembership {
+            id: self.id,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The membership events of this cluster observed at `location`, keyed by typed
+    /// [`ClusterId`]s. The tag type `C` cannot be named inside `q!`, so the conversion from
+    /// the raw IDs is spliced in with the quoted type instead.
+    pub(crate) fn membership_events<L>(
+        &self,
+        location: &L,
+    ) -> Stream<(ClusterId<C>, MembershipEvent), L, Unbounded, TotalOrder, ExactlyOnce>
+    where
+        C: 'a,
+        L: Location<'a> + NoTick,
+    {
+        let raw_events = location.source_stream(self.membership());
+
+        let root = get_this_crate();
+        let c_type = quote_type::<C>();
+        let to_cluster_id: syn::Expr =
+            syn::parse_quote!(|(id, event)| (#root::ClusterId::<#c_type>::from_raw(id), event));
+
+        Stream::new(
+            location.clone(),
+            HydroNode::Map {
+                f: to_cluster_id.into(),
+                input: Box::new(raw_events.ir_node.into_inner()),
+                metadata: location.new_node_metadata::<(ClusterId<C>, MembershipEvent)>(),
+            },
+        )
+    }
 }
 
 impl<C> Clone for Cluster<'_, C> {
@@ -73,7 +115,7 @@ impl<'a, C> Location<'a> for Cluster<'a, C> {
 
 pub struct ClusterIds<'a, C> {
     pub(crate) id: usize,
-    _phantom: Invariant<'a, C>,
+    pub(crate) _phantom: Invariant<'a, C>,
 }
 
 impl<C> Clone for ClusterIds<'_, C> {
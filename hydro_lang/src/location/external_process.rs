@@ -6,6 +6,7 @@ use serde::de::DeserializeOwned;
 
 use super::{Location, LocationId, NoTick};
 use crate::builder::FlowState;
+use crate::codec::{Bincode, Codec};
 use crate::ir::{DebugInstantiate, HydroNode, HydroSource};
 use crate::staging_util::Invariant;
 use crate::stream::ExactlyOnce;
@@ -24,7 +25,9 @@ pub struct ExternalBytesPort {
     pub(crate) port_id: usize,
 }
 
-pub struct ExternalBincodeSink<Type>
+/// An external port whose messages are encoded with the codec `C`, which defaults to
+/// [`Bincode`].
+pub struct ExternalSink<Type, C = Bincode>
 where
     Type: Serialize,
 {
@@ -38,10 +41,12 @@ where
         expect(unused, reason = "unused without feature")
     )]
     pub(crate) port_id: usize,
-    pub(crate) _phantom: PhantomData<Type>,
+    pub(crate) _phantom: PhantomData<(Type, C)>,
 }
 
-pub struct ExternalBincodeStream<Type>
+/// An external port whose messages are encoded with the codec `C`, which defaults to
+/// [`Bincode`].
+pub struct ExternalStream<Type, C = Bincode>
 where
     Type: DeserializeOwned,
 {
@@ -55,9 +60,17 @@ where
         expect(unused, reason = "unused without feature")
     )]
     pub(crate) port_id: usize,
-    pub(crate) _phantom: PhantomData<Type>,
+    pub(crate) _phantom: PhantomData<(Type, C)>,
 }
 
+/// An [`ExternalSink`] that uses [`Bincode`], under the name it had before codecs could be
+/// chosen.
+pub type ExternalBincodeSink<Type> = ExternalSink<Type, Bincode>;
+
+/// An [`ExternalStream`] that uses [`Bincode`], under the name it had before codecs could be
+/// chosen.
+pub type ExternalBincodeStream<Type> = ExternalStream<Type, Bincode>;
+
 pub struct ExternalProcess<'a, ProcessTag> {
     pub(crate) id: usize,
 
@@ -148,12 +161,31 @@ impl<'a, P> ExternalProcess<'a, P> {
         &self,
         to: &L,
     ) -> (
-        ExternalBincodeSink<T>,
+        ExternalSink<T>,
+        Stream<T, L, Unbounded, TotalOrder, ExactlyOnce>,
+    )
+    where
+        L: Location<'a> + NoTick,
+        T: Serialize + DeserializeOwned,
+    {
+        self.source_external_with_codec(to, Bincode)
+    }
+
+    /// Like [`ExternalProcess::source_external_bincode`], but the external process must
+    /// encode its messages with the given codec instead of bincode.
+    #[expect(clippy::type_complexity, reason = "stream markers")]
+    pub fn source_external_with_codec<L, T, C>(
+        &self,
+        to: &L,
+        _codec: C,
+    ) -> (
+        ExternalSink<T, C>,
         Stream<T, L, Unbounded, TotalOrder, ExactlyOnce>,
     )
     where
         L: Location<'a> + NoTick,
         T: Serialize + DeserializeOwned,
+        C: Codec,
     {
         let next_external_port_id = {
             let mut flow_state = self.flow_state.borrow_mut();
@@ -163,7 +195,7 @@ impl<'a, P> ExternalProcess<'a, P> {
         };
 
         (
-            ExternalBincodeSink {
+            ExternalSink {
                 process_id: self.id,
                 port_id: next_external_port_id,
                 _phantom: PhantomData,
@@ -177,7 +209,14 @@ impl<'a, P> ExternalProcess<'a, P> {
                         to_key: None,
                         serialize_fn: None,
                         instantiate_fn: DebugInstantiate::Building,
-                        deserialize_fn: Some(crate::stream::deserialize_bincode::<T>(None).into()),
+                        deserialize_fn: Some(
+                            crate::stream::deserialize_with_codec_type(
+                                &stageleft::quote_type::<C>(),
+                                None,
+                                &stageleft::quote_type::<T>(),
+                            )
+                            .into(),
+                        ),
                         input: Box::new(HydroNode::Source {
                             source: HydroSource::ExternalNetwork(),
                             location_kind: LocationId::ExternalProcess(self.id),
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::time::Duration;
@@ -10,8 +11,10 @@ use stageleft::{QuotedWithContext, q};
 use super::builder::FlowState;
 use crate::cycle::{CycleCollection, ForwardRef, ForwardRefMarker};
 use crate::ir::{HydroIrMetadata, HydroNode, HydroSource};
+use crate::keyed_stream::KeyedStream;
+use crate::runtime_clock::{CLOCK_INTERVAL, CLOCK_INTERVAL_DELAYED};
 use crate::stream::ExactlyOnce;
-use crate::{Singleton, Stream, TotalOrder, Unbounded};
+use crate::{Bounded, Singleton, Stream, TotalOrder, Unbounded};
 
 pub mod external_process;
 pub use external_process::ExternalProcess;
@@ -20,7 +23,7 @@ pub mod process;
 pub use process::Process;
 
 pub mod cluster;
-pub use cluster::{Cluster, ClusterId};
+pub use cluster::{Cluster, ClusterId, MembershipEvent};
 
 pub mod can_send;
 pub use can_send::CanSend;
@@ -107,6 +110,7 @@ pub trait Location<'a>: Clone {
             cardinality: None,
             cpu_usage: None,
             network_recv_cpu_usage: None,
+            monotonicity: None,
             id: None,
         }
     }
@@ -151,16 +155,20 @@ pub trait Location<'a>: Clone {
         )
     }
 
+    /// Creates a [`Bounded`] stream with the elements of the given iterator.
+    ///
+    /// A bounded top-level stream is complete by the end of the first tick, so it can be
+    /// sorted, chained, or aggregated into a final value without first batching it into a
+    /// tick. Call `.into()` to treat it as an [`Unbounded`] stream, for example to
+    /// [`Stream::union`] it with a stream received over the network.
     fn source_iter<T, E>(
         &self,
         e: impl QuotedWithContext<'a, E, Self>,
-    ) -> Stream<T, Self, Unbounded, TotalOrder, ExactlyOnce>
+    ) -> Stream<T, Self, Bounded, TotalOrder, ExactlyOnce>
     where
         E: IntoIterator<Item = T>,
         Self: Sized + NoTick,
     {
-        // TODO(shadaj): we mark this as unbounded because we do not yet have a representation
-        // for bounded top-level streams, and this is the only way to generate one
         let e = e.splice_untyped_ctx(self);
 
         Stream::new(
@@ -176,14 +184,13 @@ pub trait Location<'a>: Clone {
         )
     }
 
-    fn singleton<T>(&self, e: impl QuotedWithContext<'a, T, Self>) -> Singleton<T, Self, Unbounded>
+    /// Creates a [`Bounded`] singleton with the given value, which is available from the
+    /// first tick onwards and never changes.
+    fn singleton<T>(&self, e: impl QuotedWithContext<'a, T, Self>) -> Singleton<T, Self, Bounded>
     where
         T: Clone,
         Self: Sized + NoTick,
     {
-        // TODO(shadaj): we mark this as unbounded because we do not yet have a representation
-        // for bounded top-level singletons, and this is the only way to generate one
-
         let e_arr = q!([e]);
         let e = e_arr.splice_untyped_ctx(self);
 
@@ -206,12 +213,59 @@ pub trait Location<'a>: Clone {
         )
     }
 
+    /// Returns the changes to the membership of `cluster` as they are observed at this
+    /// location, keyed by the member that joined or left. The stream starts with a
+    /// [`MembershipEvent::Joined`] for every member the cluster was deployed with, followed
+    /// by any members that are added or removed while the flow is running.
+    ///
+    /// Different locations may observe the changes at different times, but the events of each
+    /// member are always observed in the order they happened.
+    fn source_cluster_membership<C>(
+        &self,
+        cluster: &Cluster<'a, C>,
+    ) -> KeyedStream<ClusterId<C>, MembershipEvent, Self, Unbounded, TotalOrder, ExactlyOnce>
+    where
+        C: 'a,
+        Self: Sized + NoTick,
+    {
+        KeyedStream::new(cluster.membership_events(self).into())
+    }
+
+    /// Returns the current members of `cluster`, as observed at this location. The value
+    /// changes over time as members join and leave, see
+    /// [`Location::source_cluster_membership`].
+    fn cluster_membership<C>(
+        &self,
+        cluster: &Cluster<'a, C>,
+    ) -> Singleton<HashSet<ClusterId<C>>, Self, Unbounded>
+    where
+        C: 'a,
+        Self: Sized + NoTick,
+    {
+        cluster.membership_events(self).fold(
+            q!(|| HashSet::new()),
+            q!(|members, (id, event)| {
+                match event {
+                    MembershipEvent::Joined => {
+                        members.insert(id);
+                    }
+                    MembershipEvent::Left => {
+                        members.remove(&id);
+                    }
+                }
+            }),
+        )
+    }
+
     /// Generates a stream with values emitted at a fixed interval, with
     /// each value being the current time (as an [`tokio::time::Instant`]).
     ///
     /// The clock source used is monotonic, so elements will be emitted in
     /// increasing order.
     ///
+    /// Time is read from the clock of the running DFIR instance, which is
+    /// the system clock unless the flow is being simulated.
+    ///
     /// # Safety
     /// Because this stream is generated by an OS timer, it will be
     /// non-deterministic because each timestamp will be arbitrary.
@@ -222,9 +276,7 @@ pub trait Location<'a>: Clone {
     where
         Self: Sized + NoTick,
     {
-        self.source_stream(q!(tokio_stream::wrappers::IntervalStream::new(
-            tokio::time::interval(interval)
-        )))
+        self.source_stream(q!(CLOCK_INTERVAL(interval)))
     }
 
     /// Generates a stream with values emitted at a fixed interval (with an
@@ -234,6 +286,9 @@ pub trait Location<'a>: Clone {
     /// The clock source used is monotonic, so elements will be emitted in
     /// increasing order.
     ///
+    /// Time is read from the clock of the running DFIR instance, which is
+    /// the system clock unless the flow is being simulated.
+    ///
     /// # Safety
     /// Because this stream is generated by an OS timer, it will be
     /// non-deterministic because each timestamp will be arbitrary.
@@ -245,9 +300,7 @@ pub trait Location<'a>: Clone {
     where
         Self: Sized + NoTick,
     {
-        self.source_stream(q!(tokio_stream::wrappers::IntervalStream::new(
-            tokio::time::interval_at(tokio::time::Instant::now() + delay, interval)
-        )))
+        self.source_stream(q!(CLOCK_INTERVAL_DELAYED(delay, interval)))
     }
 
     fn forward_ref<S>(&self) -> (ForwardRef<'a, S>, S)
@@ -0,0 +1,157 @@
+use stageleft::{IntoQuotedMut, q};
+
+use crate::ir::HydroNode;
+use crate::location::tick::NoAtomic;
+use crate::location::{Location, NoTick, Tick};
+use crate::stream::ExactlyOnce;
+use crate::{Bounded, Singleton, Stream, TotalOrder};
+
+/// A [`Singleton`] whose value only grows over time according to a lattice order, such as
+/// the result of [`Stream::fold_lattice`].
+///
+/// Because the value never moves backwards, a predicate that is monotone with respect to the
+/// lattice (one that stays `true` once it becomes `true`) has a deterministic answer even
+/// when the singleton is [`Unbounded`](crate::Unbounded). [`MonotoneSingleton::threshold`]
+/// uses this to observe the value at the top level, without taking snapshots in a [`Tick`].
+pub struct MonotoneSingleton<Type, Loc, Bound> {
+    pub(crate) underlying: Singleton<Type, Loc, Bound>,
+}
+
+impl<'a, T, L, B> MonotoneSingleton<T, L, B>
+where
+    L: Location<'a>,
+{
+    pub(crate) fn new(underlying: Singleton<T, L, B>) -> Self {
+        MonotoneSingleton { underlying }
+    }
+}
+
+impl<'a, T, L, B> Clone for MonotoneSingleton<T, L, B>
+where
+    T: Clone,
+    L: Location<'a>,
+{
+    fn clone(&self) -> Self {
+        MonotoneSingleton::new(self.underlying.clone())
+    }
+}
+
+impl<'a, T, L, B> From<MonotoneSingleton<T, L, B>> for Singleton<T, L, B>
+where
+    L: Location<'a>,
+{
+    fn from(singleton: MonotoneSingleton<T, L, B>) -> Self {
+        singleton.into_singleton()
+    }
+}
+
+impl<'a, T, L, B> MonotoneSingleton<T, L, B>
+where
+    L: Location<'a>,
+{
+    /// Forgets that the value is monotone, returning a regular [`Singleton`].
+    pub fn into_singleton(self) -> Singleton<T, L, B> {
+        self.underlying
+    }
+
+    /// Returns a stream that emits a single `()` once `pred` holds for the value.
+    ///
+    /// The `pred` closure must be **monotone** with respect to the lattice order: once it
+    /// returns `true` for some value, it must also return `true` for every larger value.
+    /// This ensures that the output is deterministic even though the singleton is
+    /// continuously changing, since the threshold cannot be "un-crossed" later.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let numbers: Stream<_, _, Unbounded> = process.source_iter(q!(vec![1, 5, 3, 8])).into();
+    /// numbers
+    ///     .map(q!(|x| hydro_lang::lattices::Max::new(x)))
+    ///     .fold_lattice::<lattices::Max<i32>>()
+    ///     .threshold(q!(|max| *max.as_reveal_ref() >= 5))
+    /// # }, |mut stream| async move {
+    /// // ()
+    /// # assert_eq!(stream.next().await.unwrap(), ());
+    /// # }));
+    /// ```
+    pub fn threshold<F>(
+        self,
+        pred: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<(), L, B, TotalOrder, ExactlyOnce>
+    where
+        F: Fn(&T) -> bool + 'a,
+    {
+        let reached = self.underlying.filter(pred).map(q!(|_| ()));
+        let location = reached.location.clone();
+
+        if L::is_top_level() {
+            // the `()` may be emitted several times in one tick (see `bounded_top_level_once`),
+            // so `Unique` keeps one per tick, and since a monotone predicate stays satisfied,
+            // `Delta` only lets through the one from the first tick that reaches it
+            Stream::new(
+                location.clone(),
+                HydroNode::Persist {
+                    inner: Box::new(HydroNode::Delta {
+                        inner: Box::new(HydroNode::Unique {
+                            input: Box::new(HydroNode::Unpersist {
+                                inner: Box::new(reached.ir_node.into_inner()),
+                                metadata: location.new_node_metadata::<()>(),
+                            }),
+                            metadata: location.new_node_metadata::<()>(),
+                        }),
+                        metadata: location.new_node_metadata::<()>(),
+                    }),
+                    metadata: location.new_node_metadata::<()>(),
+                },
+            )
+        } else {
+            Stream::new(location, reached.ir_node.into_inner())
+        }
+    }
+}
+
+impl<'a, T, L, B> MonotoneSingleton<T, L, B>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    /// Given a tick, returns a snapshot of the value as of that tick. The snapshot at tick
+    /// `t + 1` is guaranteed to be at least as large as the snapshot at tick `t`.
+    ///
+    /// # Safety
+    /// Because this picks a snapshot of a value that is continuously growing, the output
+    /// has a non-deterministic value since the snapshot can be at an arbitrary point in time.
+    pub unsafe fn latest_tick(self, tick: &Tick<L>) -> MonotoneSingleton<T, Tick<L>, Bounded> {
+        MonotoneSingleton::new(unsafe { self.underlying.latest_tick(tick) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use crate::location::Location;
+
+    struct P1 {}
+    struct P2 {}
+
+    #[tokio::test]
+    async fn lattice_threshold_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let out = crate::test_util::sim_collect_test(&mut simulation, |flow| {
+            let sender = flow.process::<P1>();
+            let receiver = flow.process::<P2>();
+
+            sender
+                .source_iter(q!(vec![1, 4, 2, 7, 3]))
+                .send_bincode(&receiver)
+                .map(q!(|x| crate::lattices::Max::new(x)))
+                .fold_lattice::<lattices::Max<i32>>()
+                .threshold(q!(|max| *max.as_reveal_ref() >= 4))
+        })
+        .await;
+        assert_eq!(out, vec![()]);
+    }
+}
@@ -0,0 +1,407 @@
+//! Monotonicity (CALM) analysis of Hydro IR.
+//!
+//! Following the CALM theorem, a computation composed only of monotone operators (such as
+//! `map`, `filter`, `join`, and lattice folds) can run without coordination, because its
+//! outputs never have to be retracted as more messages arrive. This module labels each node
+//! of the IR as monotone or not, and reports the network edges where a protocol needs
+//! coordination (such as Paxos) to produce deterministic results.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::ir::*;
+use crate::location::LocationId;
+
+/// Why a network edge needs coordination (such as consensus) for the program to produce
+/// deterministic results, following the CALM theorem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinationReason {
+    /// The data sent over the edge is the result of a non-monotone computation, so the
+    /// receiver may observe values that are later retracted.
+    NonMonotoneInput,
+    /// The received data flows into a non-monotone operator, so the result depends on how
+    /// far along the stream of messages the receiver is.
+    FeedsNonMonotone,
+}
+
+/// A network edge that needs coordination, as reported by [`monotonicity_analysis`].
+#[derive(Debug, Clone)]
+pub struct CoordinationEdge {
+    pub from: LocationId,
+    pub to: LocationId,
+    pub output_type: Option<DebugType>,
+    pub reason: CoordinationReason,
+}
+
+/// The result of [`monotonicity_analysis`].
+#[derive(Debug, Default)]
+pub struct MonotonicityReport {
+    /// The network edges that need coordination, in the order they appear in the IR.
+    pub coordination_edges: Vec<CoordinationEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NodeResult {
+    monotonicity: Monotonicity,
+    /// Indices of the network edges that (transitively) feed this node.
+    upstream_networks: BTreeSet<usize>,
+}
+
+// A free function rather than `impl Default`, since impls are not copied into the staged
+// version of this crate that simulations are compiled against.
+fn monotone_result() -> NodeResult {
+    NodeResult {
+        monotonicity: Monotonicity::Monotone,
+        upstream_networks: BTreeSet::new(),
+    }
+}
+
+#[derive(Default)]
+struct AnalysisState {
+    /// Results flowing into each cycle sink in the previous pass, keyed by cycle ident.
+    cycles: HashMap<String, NodeResult>,
+    seen_tees: HashMap<*const RefCell<HydroNode>, NodeResult>,
+    networks: Vec<(LocationId, LocationId, Option<DebugType>)>,
+    coordination: BTreeMap<usize, CoordinationReason>,
+}
+
+/// Whether the operator itself retracts or reorders outputs as its inputs grow, regardless
+/// of its inputs. An aggregation is monotone only if it is commutative and idempotent and
+/// runs over every input so far (`'static`), since it then merges its inputs like a lattice
+/// fold. Over a single tick's batch, its result depends on where the batch boundaries fall.
+fn is_non_monotone_operator(node: &HydroNode) -> bool {
+    match node {
+        HydroNode::Unpersist { inner, .. } => {
+            // `Unpersist(Persist(x))` is just `x`, anything else observes a snapshot
+            !matches!(inner.as_ref(), HydroNode::Persist { .. })
+        }
+        HydroNode::Fold {
+            input, properties, ..
+        }
+        | HydroNode::FoldKeyed {
+            input, properties, ..
+        }
+        | HydroNode::Reduce {
+            input, properties, ..
+        }
+        | HydroNode::ReduceKeyed {
+            input, properties, ..
+        } => {
+            !(properties.commutative
+                && properties.idempotent
+                && matches!(input.as_ref(), HydroNode::Persist { .. }))
+        }
+        HydroNode::Difference { .. }
+        | HydroNode::AntiJoin { .. }
+        | HydroNode::Sort { .. }
+        | HydroNode::Enumerate { .. } => true,
+        _ => false,
+    }
+}
+
+fn analyze_node(node: &mut HydroNode, state: &mut AnalysisState) -> NodeResult {
+    let non_monotone_operator = is_non_monotone_operator(node);
+
+    let inputs: Vec<&mut HydroNode> = match node {
+        HydroNode::Placeholder => {
+            panic!()
+        }
+
+        HydroNode::Source { .. } | HydroNode::CycleSource { .. } | HydroNode::Tee { .. } => {
+            vec![]
+        }
+
+        HydroNode::Persist { inner, .. }
+        | HydroNode::Unpersist { inner, .. }
+        | HydroNode::Delta { inner, .. } => vec![inner.as_mut()],
+
+        HydroNode::Chain { first, second, .. } => vec![first.as_mut(), second.as_mut()],
+
+        HydroNode::CrossSingleton { left, right, .. }
+        | HydroNode::CrossProduct { left, right, .. }
+        | HydroNode::Join { left, right, .. } => vec![left.as_mut(), right.as_mut()],
+
+        HydroNode::Difference { pos, neg, .. } | HydroNode::AntiJoin { pos, neg, .. } => {
+            vec![pos.as_mut(), neg.as_mut()]
+        }
+
+        HydroNode::ResolveFutures { input, .. }
+        | HydroNode::ResolveFuturesOrdered { input, .. }
+        | HydroNode::Map { input, .. }
+        | HydroNode::FlatMap { input, .. }
+        | HydroNode::Filter { input, .. }
+        | HydroNode::FilterMap { input, .. }
+        | HydroNode::DeferTick { input, .. }
+        | HydroNode::Enumerate { input, .. }
+        | HydroNode::Inspect { input, .. }
+        | HydroNode::Unique { input, .. }
+        | HydroNode::Sort { input, .. }
+        | HydroNode::Fold { input, .. }
+        | HydroNode::FoldKeyed { input, .. }
+        | HydroNode::LatticeFold { input, .. }
+        | HydroNode::LatticeFoldKeyed { input, .. }
+        | HydroNode::Reduce { input, .. }
+        | HydroNode::ReduceKeyed { input, .. }
+        | HydroNode::Network { input, .. }
+        | HydroNode::Counter { input, .. } => vec![input.as_mut()],
+    };
+
+    let mut result = monotone_result();
+    for input in inputs {
+        let input_result = analyze_node(input, state);
+        if input_result.monotonicity == Monotonicity::NonMonotone {
+            result.monotonicity = Monotonicity::NonMonotone;
+        }
+        result
+            .upstream_networks
+            .extend(input_result.upstream_networks);
+    }
+
+    match node {
+        HydroNode::CycleSource { ident, .. } => {
+            result = state
+                .cycles
+                .get(&ident.to_string())
+                .cloned()
+                .unwrap_or_else(monotone_result);
+        }
+
+        HydroNode::Tee { inner, .. } => {
+            if let Some(seen) = state.seen_tees.get(&inner.as_ptr()) {
+                result = seen.clone();
+            } else {
+                result = analyze_node(&mut inner.0.borrow_mut(), state);
+                state.seen_tees.insert(inner.as_ptr(), result.clone());
+            }
+        }
+
+        HydroNode::Network {
+            to_location,
+            input,
+            metadata,
+            ..
+        } => {
+            let network_id = state.networks.len();
+            state.networks.push((
+                input.metadata().location_kind.clone(),
+                to_location.clone(),
+                metadata.output_type.clone(),
+            ));
+
+            if result.monotonicity == Monotonicity::NonMonotone {
+                state
+                    .coordination
+                    .insert(network_id, CoordinationReason::NonMonotoneInput);
+            }
+
+            result.upstream_networks.insert(network_id);
+        }
+
+        _ if non_monotone_operator => {
+            for network_id in &result.upstream_networks {
+                state
+                    .coordination
+                    .entry(*network_id)
+                    .or_insert(CoordinationReason::FeedsNonMonotone);
+            }
+
+            result.monotonicity = Monotonicity::NonMonotone;
+        }
+
+        _ => {}
+    }
+
+    node.metadata_mut().monotonicity = Some(result.monotonicity);
+    result
+}
+
+/// Labels each node of the IR with whether its output is [`Monotonicity::Monotone`], and
+/// reports which network edges need coordination for the program to be deterministic.
+///
+/// Negation (`Difference`, `AntiJoin`), aggregation that is not both commutative and
+/// idempotent or that only covers a single tick, sorting, and snapshots of a collection
+/// (`Unpersist`) are non-monotone, and so is everything downstream of them. A network edge needs
+/// coordination if it carries non-monotone data, or if the messages it delivers are consumed
+/// by a non-monotone operator.
+///
+/// The annotations are stored in [`HydroIrMetadata::monotonicity`], and are displayed by the
+/// mermaid and DOT renderers. Cycles are analyzed by iterating to a fixpoint.
+pub fn monotonicity_analysis(ir: &mut [HydroLeaf]) -> MonotonicityReport {
+    let mut state = AnalysisState::default();
+
+    loop {
+        state.seen_tees.clear();
+        state.networks.clear();
+        state.coordination.clear();
+
+        let mut cycle_sinks = HashMap::new();
+        for leaf in ir.iter_mut() {
+            let (input, metadata) = match leaf {
+                HydroLeaf::ForEach {
+                    input, metadata, ..
+                }
+                | HydroLeaf::DestSink {
+                    input, metadata, ..
+                }
+                | HydroLeaf::CycleSink {
+                    input, metadata, ..
+                } => (input, metadata),
+            };
+
+            let result = analyze_node(input, &mut state);
+            metadata.monotonicity = Some(result.monotonicity);
+
+            if let HydroLeaf::CycleSink { ident, .. } = leaf {
+                cycle_sinks.insert(ident.to_string(), result);
+            }
+        }
+
+        // results only ever become less monotone, so this converges
+        if cycle_sinks == state.cycles {
+            break;
+        }
+        state.cycles = cycle_sinks;
+    }
+
+    MonotonicityReport {
+        coordination_edges: state
+            .coordination
+            .into_iter()
+            .map(|(network_id, reason)| {
+                let (from, to, output_type) = state.networks[network_id].clone();
+                CoordinationEdge {
+                    from,
+                    to,
+                    output_type,
+                    reason,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::*;
+
+    use super::*;
+    use crate::location::Location;
+    use crate::{FlowBuilder, Stream, Unbounded};
+
+    #[test]
+    fn monotone_pipeline_needs_no_coordination() {
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<()>();
+        let receiver = flow.process::<()>();
+
+        let numbers: Stream<_, _, Unbounded> = sender.source_iter(q!(0..10)).into();
+        numbers
+            .map(q!(|v| v + 1))
+            .send_bincode(&receiver)
+            .filter(q!(|v| v % 2 == 0))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let mut report = None;
+        let built = flow.optimize_with(|ir| {
+            crate::rewrites::persist_pullup::persist_pullup(ir);
+            report = Some(monotonicity_analysis(ir));
+        });
+
+        assert!(report.unwrap().coordination_edges.is_empty());
+        for leaf in built.ir() {
+            assert_eq!(leaf.metadata().monotonicity, Some(Monotonicity::Monotone));
+        }
+    }
+
+    #[test]
+    fn aggregation_after_network_needs_coordination() {
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<()>();
+        let receiver = flow.process::<()>();
+        let tick = receiver.tick();
+
+        let numbers: Stream<_, _, Unbounded> = sender.source_iter(q!(0..10)).into();
+        let received = numbers.send_bincode(&receiver);
+        unsafe { received.tick_batch(&tick) }
+            .count()
+            .all_ticks()
+            .for_each(q!(|n| println!("{}", n)));
+
+        let mut report = None;
+        let built = flow.optimize_with(|ir| {
+            crate::rewrites::persist_pullup::persist_pullup(ir);
+            report = Some(monotonicity_analysis(ir));
+        });
+
+        let edges = report.unwrap().coordination_edges;
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].reason, CoordinationReason::FeedsNonMonotone);
+        assert_eq!(edges[0].to, receiver.id());
+        for leaf in built.ir() {
+            assert_eq!(
+                leaf.metadata().monotonicity,
+                Some(Monotonicity::NonMonotone)
+            );
+        }
+    }
+
+    #[test]
+    fn per_tick_commutative_idempotent_aggregation_needs_coordination() {
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<()>();
+        let receiver = flow.process::<()>();
+        let tick = receiver.tick();
+
+        // the maximum of each batch depends on where the batch boundaries fall
+        let numbers: Stream<_, _, Unbounded> = sender.source_iter(q!(0..10)).into();
+        let received = numbers.send_bincode(&receiver);
+        unsafe { received.tick_batch(&tick) }
+            .max()
+            .all_ticks()
+            .for_each(q!(|n| println!("{}", n)));
+
+        let mut report = None;
+        let built = flow.optimize_with(|ir| {
+            crate::rewrites::persist_pullup::persist_pullup(ir);
+            report = Some(monotonicity_analysis(ir));
+        });
+
+        let edges = report.unwrap().coordination_edges;
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].reason, CoordinationReason::FeedsNonMonotone);
+        assert_eq!(edges[0].to, receiver.id());
+        for leaf in built.ir() {
+            assert_eq!(
+                leaf.metadata().monotonicity,
+                Some(Monotonicity::NonMonotone)
+            );
+        }
+    }
+
+    #[test]
+    fn static_commutative_idempotent_aggregation_needs_no_coordination() {
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<()>();
+        let receiver = flow.process::<()>();
+        let tick = receiver.tick();
+
+        let numbers: Stream<_, _, Unbounded> = sender.source_iter(q!(0..10)).into();
+        let received = numbers.send_bincode(&receiver);
+        unsafe { received.tick_batch(&tick) }
+            .persist()
+            .max()
+            .all_ticks()
+            .for_each(q!(|n| println!("{}", n)));
+
+        let mut report = None;
+        let built = flow.optimize_with(|ir| {
+            crate::rewrites::persist_pullup::persist_pullup(ir);
+            report = Some(monotonicity_analysis(ir));
+        });
+
+        assert!(report.unwrap().coordination_edges.is_empty());
+        for leaf in built.ir() {
+            assert_eq!(leaf.metadata().monotonicity, Some(Monotonicity::Monotone));
+        }
+    }
+}
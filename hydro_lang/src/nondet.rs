@@ -0,0 +1,97 @@
+//! Discovery of the points in a Hydro program where the runtime makes a non-deterministic choice.
+//!
+//! Operators like [`crate::Stream::tick_batch`] and [`crate::Singleton::latest_tick`] (and
+//! everything built on top of them, such as `sample_every` and `timeout`) are `unsafe` because
+//! the runtime is free to pick the batch boundaries. Network channels add a second source of
+//! non-determinism, since messages from different senders may be interleaved arbitrarily. This
+//! module locates all such choice points in the IR, so that testing tools (such as the
+//! simulator in `hydro_lang::deploy::sim`) can report what they are exploring.
+
+use crate::ir::{DebugType, HydroLeaf, HydroNode, deep_clone, transform_bottom_up};
+use crate::location::LocationId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChoicePointKind {
+    /// A stream or singleton is batched or snapshotted into a tick, with non-deterministic
+    /// boundaries (`tick_batch`, `latest_tick`).
+    Batch,
+    /// Messages arrive over the network, interleaved non-deterministically with other inputs.
+    NetworkDelivery,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChoicePoint {
+    pub kind: ChoicePointKind,
+    /// The location at which the choice is made (the receiver, for network deliveries).
+    pub location: LocationId,
+    pub output_type: Option<DebugType>,
+}
+
+/// Lists the non-deterministic choice points in a (pre-optimization) IR, in the order that
+/// they are reached by a bottom-up traversal.
+pub fn choice_points(ir: &[HydroLeaf]) -> Vec<ChoicePoint> {
+    let mut ir = deep_clone(ir);
+    let mut points = Vec::new();
+
+    transform_bottom_up(&mut ir, &mut |_| {}, &mut |node| match node {
+        // Top-level operators also use `Unpersist` internally, but only batching into a tick
+        // produces one whose output lives inside the tick.
+        HydroNode::Unpersist { metadata, .. }
+            if matches!(metadata.location_kind, LocationId::Tick(..)) =>
+        {
+            points.push(ChoicePoint {
+                kind: ChoicePointKind::Batch,
+                location: metadata.location_kind.root().clone(),
+                output_type: metadata.output_type.clone(),
+            })
+        }
+        // Outputs to an external process are observed in order, so only deliveries into
+        // the flow itself can be interleaved.
+        HydroNode::Network {
+            to_location,
+            metadata,
+            ..
+        } if !matches!(to_location, LocationId::ExternalProcess(_)) => points.push(ChoicePoint {
+            kind: ChoicePointKind::NetworkDelivery,
+            location: to_location.clone(),
+            output_type: metadata.output_type.clone(),
+        }),
+        _ => {}
+    });
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::*;
+
+    use super::*;
+    use crate::FlowBuilder;
+    use crate::location::Location;
+
+    #[test]
+    fn finds_batches_and_network_deliveries() {
+        let flow = FlowBuilder::new();
+        let p1 = flow.process::<()>();
+        let p2 = flow.process::<()>();
+
+        let tick = p2.tick();
+        unsafe {
+            p1.source_iter(q!(0..10))
+                .send_bincode(&p2)
+                .tick_batch(&tick)
+                .count()
+                .all_ticks()
+        }
+        .for_each(q!(|n| println!("{}", n)));
+
+        let built = flow.finalize();
+        let points = built.choice_points();
+        assert_eq!(
+            points.iter().map(|p| p.kind).collect::<Vec<_>>(),
+            vec![ChoicePointKind::NetworkDelivery, ChoicePointKind::Batch]
+        );
+        assert!(points.iter().all(|p| p.location == p2.id()));
+    }
+}
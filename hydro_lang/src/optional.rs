@@ -8,10 +8,10 @@ use syn::parse_quote;
 
 use crate::builder::FLOW_USED_MESSAGE;
 use crate::cycle::{CycleCollection, CycleComplete, DeferTick, ForwardRefMarker, TickCycleMarker};
-use crate::ir::{HydroLeaf, HydroNode, HydroSource, TeeNode};
+use crate::ir::{AggregationProperties, HydroLeaf, HydroNode, HydroSource, TeeNode};
 use crate::location::tick::{Atomic, NoAtomic};
 use crate::location::{LocationId, NoTick, check_matching_location};
-use crate::singleton::ZipResult;
+use crate::singleton::{ZipResult, bounded_top_level_once};
 use crate::stream::{AtLeastOnce, ExactlyOnce, NoOrder};
 use crate::{Bounded, Location, Singleton, Stream, Tick, TotalOrder, Unbounded};
 
@@ -428,7 +428,8 @@ where
         }
     }
 
-    pub fn unwrap_or(self, other: Singleton<T, L, B>) -> Singleton<T, L, B> {
+    pub fn unwrap_or(self, other: impl Into<Singleton<T, L, B>>) -> Singleton<T, L, B> {
+        let other: Singleton<T, L, B> = other.into();
         check_matching_location(&self.location, &other.location);
 
         if L::is_top_level() {
@@ -517,10 +518,25 @@ where
 
     pub fn into_stream(self) -> Stream<T, L, Bounded, TotalOrder, ExactlyOnce> {
         if L::is_top_level() {
-            panic!("Converting an optional to a stream is not yet supported at the top level");
+            let location = self.location.clone();
+            let keep_first: syn::Expr = parse_quote!(|_, _| {});
+            Stream::new(
+                self.location,
+                bounded_top_level_once::<T, L>(&location, self.ir_node.into_inner(), |copies| {
+                    HydroNode::Reduce {
+                        f: keep_first.into(),
+                        properties: AggregationProperties {
+                            commutative: false,
+                            idempotent: true,
+                        },
+                        input: Box::new(copies),
+                        metadata: location.new_node_metadata::<T>(),
+                    }
+                }),
+            )
+        } else {
+            Stream::new(self.location, self.ir_node.into_inner())
         }
-
-        Stream::new(self.location, self.ir_node.into_inner())
     }
 }
 
@@ -0,0 +1,473 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem::Discriminant;
+use std::rc::Rc;
+
+use quote::ToTokens;
+use syn::visit_mut::VisitMut;
+
+use crate::ir::*;
+use crate::location::LocationId;
+
+/// Identifies the computation performed by a node, given the equivalence classes of its inputs.
+///
+/// [`HydroNode`]'s `Hash` implementation ignores metadata, so the location and output type are
+/// tracked here explicitly to avoid merging, for example, the same batch taken in two different
+/// ticks, or the same closure inferred at two different types.
+#[derive(PartialEq, Eq, Hash)]
+struct CseKey {
+    kind: Discriminant<HydroNode>,
+    location: LocationId,
+    output_type: Option<String>,
+    args: Vec<String>,
+    inputs: Vec<usize>,
+}
+
+/// A consumer of an equivalence class, either an input slot of another class or a leaf.
+#[derive(PartialEq, Eq, Hash)]
+enum CseParent {
+    Node(usize, usize),
+    Leaf(usize),
+}
+
+struct CseClass {
+    /// A representative node for the class, with its inputs replaced by placeholders.
+    node: HydroNode,
+    inputs: Vec<usize>,
+    parents: HashSet<CseParent>,
+    shared: Option<Rc<RefCell<HydroNode>>>,
+}
+
+#[derive(Default)]
+struct CseState {
+    classes: Vec<CseClass>,
+    by_key: HashMap<CseKey, usize>,
+    seen_tees: HashMap<*const RefCell<HydroNode>, usize>,
+}
+
+fn expr_tokens(expr: &DebugExpr) -> String {
+    expr.to_token_stream().to_string()
+}
+
+/// Finds calls that may have side effects or read external state: free functions (such as
+/// `CLOCK_NOW()` or `rand::random()`) and macros. Calls to tuple struct and enum variant
+/// constructors (such as `Some(x)`), recognized by their capitalized name, and the type hints
+/// that stageleft wraps around every closure are allowed.
+///
+/// This is public so that the staged copy of this module re-exports it, since stageleft
+/// does not copy over the `VisitMut` implementation.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct ImpureCallFinder {
+    pub found: bool,
+}
+
+impl VisitMut for ImpureCallFinder {
+    fn visit_expr_call_mut(&mut self, call: &mut syn::ExprCall) {
+        let is_pure = matches!(
+            call.func.as_ref(),
+            syn::Expr::Path(path) if {
+                let mut segments = path.path.segments.iter().map(|s| s.ident.to_string());
+                segments.clone().take(2).eq(["stageleft", "runtime_support"])
+                    || segments
+                        .next_back()
+                        .is_some_and(|name| name.starts_with(|c: char| c.is_uppercase()))
+            }
+        );
+        if !is_pure {
+            self.found = true;
+        }
+        syn::visit_mut::visit_expr_call_mut(self, call);
+    }
+
+    fn visit_macro_mut(&mut self, _: &mut syn::Macro) {
+        self.found = true;
+    }
+}
+
+/// Like [`expr_tokens`], but returns `None` if the expression may be impure, in which case
+/// two occurrences of it can produce different results and must not be merged.
+fn pure_expr_tokens(expr: &DebugExpr) -> Option<String> {
+    let mut finder = ImpureCallFinder::default();
+    finder.visit_expr_mut(&mut expr.0.as_ref().clone());
+    if finder.found {
+        None
+    } else {
+        Some(expr_tokens(expr))
+    }
+}
+
+/// The arguments that distinguish two nodes of the same kind with the same inputs, or `None`
+/// if the node must never be merged with another one (because it has side effects or
+/// produces a stream that can only be consumed once).
+fn cse_args(node: &HydroNode) -> Option<Vec<String>> {
+    match node {
+        HydroNode::Placeholder | HydroNode::Tee { .. } => None,
+
+        // Iterators may be non-deterministic (e.g. random or time-based), so each
+        // `source_iter` produces its own stream.
+        HydroNode::Source {
+            source: HydroSource::Stream(_) | HydroSource::ExternalNetwork() | HydroSource::Iter(_),
+            ..
+        } => None,
+        HydroNode::Source {
+            source: HydroSource::Spin(),
+            location_kind,
+            ..
+        } => Some(vec!["spin".to_string(), format!("{:?}", location_kind)]),
+        HydroNode::CycleSource {
+            ident,
+            location_kind,
+            ..
+        } => Some(vec![ident.to_string(), format!("{:?}", location_kind)]),
+
+        HydroNode::Persist { .. }
+        | HydroNode::Unpersist { .. }
+        | HydroNode::Delta { .. }
+        | HydroNode::Chain { .. }
+        | HydroNode::CrossProduct { .. }
+        | HydroNode::CrossSingleton { .. }
+        | HydroNode::Join { .. }
+        | HydroNode::Difference { .. }
+        | HydroNode::AntiJoin { .. }
+        | HydroNode::ResolveFutures { .. }
+        | HydroNode::ResolveFuturesOrdered { .. }
+        | HydroNode::DeferTick { .. }
+        | HydroNode::Unique { .. }
+        | HydroNode::Sort { .. } => Some(vec![]),
+
+        HydroNode::Map { f, .. }
+        | HydroNode::FlatMap { f, .. }
+        | HydroNode::Filter { f, .. }
+        | HydroNode::FilterMap { f, .. }
+        | HydroNode::Reduce { f, .. }
+        | HydroNode::ReduceKeyed { f, .. } => Some(vec![pure_expr_tokens(f)?]),
+
+        HydroNode::Fold { init, acc, .. } | HydroNode::FoldKeyed { init, acc, .. } => {
+            Some(vec![pure_expr_tokens(init)?, pure_expr_tokens(acc)?])
+        }
+
+        HydroNode::LatticeFold { init, .. } | HydroNode::LatticeFoldKeyed { init, .. } => {
+            Some(vec![pure_expr_tokens(init)?])
+        }
+
+        HydroNode::Enumerate { is_static, .. } => Some(vec![is_static.to_string()]),
+
+        // Side effects must run once per occurrence.
+        HydroNode::Inspect { .. } | HydroNode::Counter { .. } => None,
+
+        HydroNode::Network {
+            from_key: None,
+            to_location,
+            to_key: None,
+            serialize_fn,
+            deserialize_fn,
+            ..
+        } => Some(vec![
+            format!("{:?}", to_location),
+            serialize_fn.as_ref().map(expr_tokens).unwrap_or_default(),
+            deserialize_fn.as_ref().map(expr_tokens).unwrap_or_default(),
+        ]),
+        // Connected to an external port, which expects its own channel.
+        HydroNode::Network { .. } => None,
+    }
+}
+
+/// Moves `node` into the equivalence class of nodes that compute the same thing, returning
+/// the index of that class.
+fn cse_intern(node: HydroNode, state: &mut CseState) -> usize {
+    if let HydroNode::Tee { inner, .. } = node {
+        // Tees are transparent, the shared node is interned only once.
+        if let Some(&class) = state.seen_tees.get(&inner.as_ptr()) {
+            return class;
+        }
+        let class = cse_intern(inner.0.replace(HydroNode::Placeholder), state);
+        state.seen_tees.insert(inner.as_ptr(), class);
+        return class;
+    }
+
+    let mut node = node;
+    let mut inputs = Vec::new();
+    node.transform_children(
+        |child, _| {
+            let child = std::mem::replace(child, HydroNode::Placeholder);
+            inputs.push(cse_intern(child, state));
+        },
+        &mut Default::default(),
+    );
+
+    let key = cse_args(&node).map(|args| CseKey {
+        kind: std::mem::discriminant(&node),
+        location: node.metadata().location_kind.clone(),
+        output_type: node
+            .metadata()
+            .output_type
+            .as_ref()
+            .map(|t| t.to_token_stream().to_string()),
+        args,
+        inputs: inputs.clone(),
+    });
+
+    if let Some(&class) = key.as_ref().and_then(|key| state.by_key.get(key)) {
+        return class;
+    }
+
+    let class = state.classes.len();
+    for (slot, &input) in inputs.iter().enumerate() {
+        state.classes[input]
+            .parents
+            .insert(CseParent::Node(class, slot));
+    }
+    state.classes.push(CseClass {
+        node,
+        inputs,
+        parents: HashSet::new(),
+        shared: None,
+    });
+    if let Some(key) = key {
+        state.by_key.insert(key, class);
+    }
+    class
+}
+
+/// Rebuilds the IR for an equivalence class, behind a shared [`HydroNode::Tee`] if the class
+/// has more than one consumer.
+fn cse_build(class: usize, state: &mut CseState) -> HydroNode {
+    if state.classes[class].parents.len() <= 1 {
+        return cse_build_node(class, state);
+    }
+
+    let shared = if let Some(shared) = &state.classes[class].shared {
+        shared.clone()
+    } else {
+        let node = cse_build_node(class, state);
+        let shared = Rc::new(RefCell::new(node));
+        state.classes[class].shared = Some(shared.clone());
+        shared
+    };
+
+    let metadata = shared.borrow().metadata().clone();
+    HydroNode::Tee {
+        inner: TeeNode(shared),
+        metadata,
+    }
+}
+
+fn cse_build_node(class: usize, state: &mut CseState) -> HydroNode {
+    let mut node = std::mem::replace(&mut state.classes[class].node, HydroNode::Placeholder);
+    let mut inputs = state.classes[class].inputs.clone().into_iter();
+    node.transform_children(
+        |child, _| *child = cse_build(inputs.next().unwrap(), state),
+        &mut Default::default(),
+    );
+    node
+}
+
+/// Common subexpression elimination: merges structurally identical subtrees of the IR (the
+/// same operators, with the same arguments, at the same location) so that they are only
+/// computed once, with the results shared behind a [`HydroNode::Tee`].
+///
+/// Tees that end up with a single consumer, such as a stream that was cloned and then had
+/// the same operators applied to both copies, are removed. Nodes with side effects
+/// (`inspect`), closures that call free functions or macros (which may have side effects or
+/// read external state, like `CLOCK_NOW()`), sources (which may be non-deterministic or
+/// consumable only once), and channels connected to external processes are never merged.
+pub fn cse(ir: &mut [HydroLeaf]) {
+    let mut state = CseState::default();
+
+    let mut leaf_inputs = Vec::new();
+    for (leaf_idx, leaf) in ir.iter_mut().enumerate() {
+        leaf.transform_children(
+            |input, _| {
+                let class =
+                    cse_intern(std::mem::replace(input, HydroNode::Placeholder), &mut state);
+                state.classes[class]
+                    .parents
+                    .insert(CseParent::Leaf(leaf_idx));
+                leaf_inputs.push(class);
+            },
+            &mut Default::default(),
+        );
+    }
+
+    let mut leaf_inputs = leaf_inputs.into_iter();
+    for leaf in ir.iter_mut() {
+        leaf.transform_children(
+            |input, _| *input = cse_build(leaf_inputs.next().unwrap(), &mut state),
+            &mut Default::default(),
+        );
+    }
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use stageleft::*;
+
+    use super::*;
+    use crate::deploy::HydroDeploy;
+    use crate::location::Location;
+
+    /// Counts the distinct nodes in the IR matching `pred`, visiting shared nodes once.
+    fn count_nodes(ir: &mut [HydroLeaf], pred: impl Fn(&HydroNode) -> bool) -> usize {
+        let mut count = 0;
+        transform_bottom_up(ir, &mut |_| {}, &mut |node| {
+            if pred(node) {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    #[test]
+    fn cse_merges_duplicate_chains() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        let numbers = process.source_iter(q!(0..10));
+        numbers
+            .clone()
+            .map(q!(|v| v + 1))
+            .filter(q!(|v| v % 2 == 0))
+            .for_each(q!(|n| println!("a {}", n)));
+        numbers
+            .map(q!(|v| v + 1))
+            .filter(q!(|v| v % 2 == 0))
+            .for_each(q!(|n| println!("b {}", n)));
+
+        // A separate source is not merged, since iterators may be non-deterministic.
+        process
+            .source_iter(q!(0..10))
+            .map(q!(|v| v + 1))
+            .for_each(q!(|n| println!("c {}", n)));
+
+        let built = flow.finalize().optimize_with(cse);
+        insta::assert_debug_snapshot!(built.ir());
+
+        let mut ir = deep_clone(built.ir());
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::Source { .. })),
+            2
+        );
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::Map { .. })),
+            2
+        );
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::Filter { .. })),
+            1
+        );
+
+        for (id, graph) in built
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile()
+            .all_dfir()
+        {
+            insta::with_settings!({snapshot_suffix => format!("surface_graph_{id}")}, {
+                insta::assert_snapshot!(graph.surface_syntax_string());
+            });
+        }
+    }
+
+    #[test]
+    fn cse_merges_identical_network_sends() {
+        let flow = crate::builder::FlowBuilder::new();
+        let p1 = flow.process::<()>();
+        let p2 = flow.process::<()>();
+
+        // Sending the same data to the same location twice only needs one channel.
+        let numbers = p1.source_iter(q!(0..10));
+        for _ in 0..2 {
+            numbers
+                .clone()
+                .send_bincode(&p2)
+                .for_each(q!(|n| println!("{}", n)));
+        }
+
+        let built = flow.finalize().optimize_with(cse);
+
+        let mut ir = deep_clone(built.ir());
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::Network { .. })),
+            1
+        );
+
+        built
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile();
+    }
+
+    #[test]
+    fn cse_keeps_ticks_and_side_effects_separate() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        let numbers = process.source_iter(q!(0..10));
+        for _ in 0..2 {
+            let tick = process.tick();
+            unsafe { numbers.clone().tick_batch(&tick) }
+                .count()
+                .all_ticks()
+                .for_each(q!(|n| println!("{}", n)));
+        }
+
+        for _ in 0..2 {
+            numbers
+                .clone()
+                .inspect(q!(|n| println!("{}", n)))
+                .for_each(q!(|_| {}));
+        }
+
+        let built = flow.finalize().optimize_with(cse);
+
+        let mut ir = deep_clone(built.ir());
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::Fold { .. })),
+            2
+        );
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::Inspect { .. })),
+            2
+        );
+
+        built
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile();
+    }
+
+    #[test]
+    fn cse_keeps_impure_closures_separate() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+
+        let numbers = process.source_iter(q!(0..10));
+        for _ in 0..2 {
+            numbers
+                .clone()
+                .map(q!(|n| (n, std::time::Instant::now())))
+                .for_each(q!(|n| println!("{:?}", n)));
+        }
+        for _ in 0..2 {
+            numbers
+                .clone()
+                .filter_map(q!(|n| if n > 5 { Some(n) } else { None }))
+                .for_each(q!(|n| println!("{}", n)));
+        }
+
+        let built = flow.finalize().optimize_with(cse);
+
+        let mut ir = deep_clone(built.ir());
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::Map { .. })),
+            2
+        );
+        assert_eq!(
+            count_nodes(&mut ir, |n| matches!(n, HydroNode::FilterMap { .. })),
+            1
+        );
+
+        built
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile();
+    }
+}
@@ -1,2 +1,4 @@
+pub mod cse;
+pub mod network_pushdown;
 pub mod persist_pullup;
 pub mod properties;
@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::parse_quote;
+
+use crate::ir::*;
+use crate::location::LocationId;
+use crate::stream::{deserialize_bincode_with_type, serialize_bincode_with_type};
+
+/// Whether the tokens refer to a runtime variable that is only defined at the receiving
+/// location, such as `CLUSTER_SELF_ID`. The IDs of cluster members are bound at every
+/// location, so they can be used on either side of the network.
+fn references_location_state(tokens: TokenStream) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => {
+            let name = ident.to_string();
+            name.starts_with("__hydro_lang_") && !name.starts_with("__hydro_lang_cluster_ids_")
+        }
+        TokenTree::Group(group) => references_location_state(group.stream()),
+        _ => false,
+    })
+}
+
+/// Collects the tokens of `ty` and of all the types nested inside it.
+fn collect_subtypes(ty: &syn::Type, out: &mut HashSet<String>) {
+    out.insert(ty.to_token_stream().to_string());
+    match ty {
+        syn::Type::Tuple(tuple) => tuple.elems.iter().for_each(|t| collect_subtypes(t, out)),
+        syn::Type::Paren(paren) => collect_subtypes(&paren.elem, out),
+        syn::Type::Array(array) => collect_subtypes(&array.elem, out),
+        syn::Type::Path(path) => {
+            for segment in &path.path.segments {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    for arg in &args.args {
+                        if let syn::GenericArgument::Type(t) = arg {
+                            collect_subtypes(t, out);
+                        }
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether values of type `ty` can be sent over the network, given the types that were
+/// already being sent. We cannot check trait bounds on the IR, so we only accept types
+/// built out of the existing payload (as produced by a projection) and primitives.
+fn is_sendable(ty: &syn::Type, sent: &HashSet<String>) -> bool {
+    if sent.contains(&ty.to_token_stream().to_string()) {
+        return true;
+    }
+
+    match ty {
+        syn::Type::Tuple(tuple) => tuple.elems.iter().all(|t| is_sendable(t, sent)),
+        syn::Type::Paren(paren) => is_sendable(&paren.elem, sent),
+        syn::Type::Path(path) => {
+            path.qself.is_none()
+                && path.path.segments.last().is_some_and(|segment| {
+                    segment.arguments.is_none()
+                        && [
+                            "bool", "char", "u8", "u16", "u32", "u64", "u128", "usize", "i8",
+                            "i16", "i32", "i64", "i128", "isize", "f32", "f64", "String",
+                        ]
+                        .iter()
+                        .any(|primitive| segment.ident == primitive)
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Splits the `(ClusterId<C>, T)` type sent to a cluster into its two halves.
+fn demux_types(ty: &syn::Type) -> Option<(&syn::Type, &syn::Type)> {
+    match ty {
+        syn::Type::Tuple(tuple) if tuple.elems.len() == 2 => {
+            Some((&tuple.elems[0], &tuple.elems[1]))
+        }
+        _ => None,
+    }
+}
+
+fn network_pushdown_node(node: &mut HydroNode) {
+    let (f, network, metadata) = match &*node {
+        HydroNode::Filter { f, input, metadata }
+        | HydroNode::Map { f, input, metadata }
+        | HydroNode::FilterMap { f, input, metadata } => (f, input, metadata),
+        _ => return,
+    };
+
+    let HydroNode::Network {
+        from_key: None,
+        to_location,
+        to_key: None,
+        serialize_fn: Some(serialize_fn),
+        deserialize_fn: Some(_),
+        input: network_input,
+        ..
+    } = network.as_ref()
+    else {
+        return;
+    };
+
+    // Messages from a cluster are tagged with the sender's ID on arrival, which the operator
+    // may depend on, so we only push operators across channels sent from a process.
+    let is_demux = match (network_input.metadata().location_kind.root(), to_location) {
+        (LocationId::Process(_), LocationId::Process(_)) => false,
+        (LocationId::Process(_), LocationId::Cluster(_)) => true,
+        _ => return,
+    };
+
+    if references_location_state(f.to_token_stream()) {
+        return;
+    }
+
+    let Some(sent_type) = network_input.metadata().output_type.clone() else {
+        return;
+    };
+    let (member_type, payload_type) = if is_demux {
+        let Some((member_type, payload_type)) = demux_types(&sent_type) else {
+            return;
+        };
+        (Some(member_type.clone()), payload_type.clone())
+    } else {
+        (None, (*sent_type.0).clone())
+    };
+
+    // The type sent over the network after the rewrite, if the operator changes it.
+    let new_payload_type = if let HydroNode::Filter { .. } = node {
+        None
+    } else {
+        // The serialization functions are regenerated for the new type, which we only know
+        // how to do for channels that use bincode rather than some other codec.
+        let bincode_fn = serialize_bincode_with_type(is_demux, &payload_type);
+        if serialize_fn.to_token_stream().to_string() != bincode_fn.to_token_stream().to_string() {
+            return;
+        }
+
+        let Some(output_type) = &metadata.output_type else {
+            return;
+        };
+        let mut sent = HashSet::new();
+        collect_subtypes(&payload_type, &mut sent);
+        if !is_sendable(output_type, &sent) {
+            return;
+        }
+        Some((*output_type.0).clone())
+    };
+
+    let receiver_output_type = metadata.output_type.clone();
+    let sender_location = network_input.metadata().location_kind.clone();
+    let op_output_type = match (&new_payload_type, member_type) {
+        (None, _) => sent_type,
+        (Some(payload), Some(member_type)) => {
+            let pair: syn::Type = parse_quote!((#member_type, #payload));
+            pair.into()
+        }
+        (Some(payload), None) => payload.clone().into(),
+    };
+
+    if is_demux {
+        // The operator now sees the `(ClusterId<C>, T)` pairs being sent, so it is wrapped to
+        // only apply to the payload.
+        let wrapped: syn::Expr = match &*node {
+            HydroNode::Filter { f, .. } => parse_quote!({
+                let f = #f;
+                move |(_, v): &(_, _)| f(v)
+            }),
+            HydroNode::Map { f, .. } => parse_quote!({
+                let f = #f;
+                move |(id, v)| (id, f(v))
+            }),
+            HydroNode::FilterMap { f, .. } => parse_quote!({
+                let f = #f;
+                move |(id, v)| f(v).map(|v| (id, v))
+            }),
+            _ => unreachable!(),
+        };
+
+        if let HydroNode::Filter { f, .. }
+        | HydroNode::Map { f, .. }
+        | HydroNode::FilterMap { f, .. } = node
+        {
+            *f = wrapped.into();
+        }
+    }
+
+    let (HydroNode::Filter {
+        input, metadata, ..
+    }
+    | HydroNode::Map {
+        input, metadata, ..
+    }
+    | HydroNode::FilterMap {
+        input, metadata, ..
+    }) = node
+    else {
+        unreachable!()
+    };
+
+    let mut network = std::mem::replace(input.as_mut(), HydroNode::Placeholder);
+    let HydroNode::Network {
+        serialize_fn,
+        deserialize_fn,
+        input: network_input,
+        metadata: network_metadata,
+        ..
+    } = &mut network
+    else {
+        unreachable!()
+    };
+
+    *input = std::mem::replace(network_input, Box::new(HydroNode::Placeholder));
+    metadata.location_kind = sender_location;
+    metadata.output_type = Some(op_output_type);
+
+    if let Some(payload) = new_payload_type {
+        *serialize_fn = Some(serialize_bincode_with_type(is_demux, &payload).into());
+        *deserialize_fn = Some(deserialize_bincode_with_type(None, &payload).into());
+        network_metadata.output_type = receiver_output_type;
+    }
+
+    **network_input = std::mem::replace(node, HydroNode::Placeholder);
+    *node = network;
+}
+
+/// Moves stateless `filter`, `map`, and `filter_map` operators that are applied right after
+/// a network channel to the sending side, so that data that would be immediately discarded
+/// is never sent.
+///
+/// Operators are only moved if they do not reference variables that are only available at
+/// the receiving location (such as `CLUSTER_SELF_ID`), and, if they change the type of the
+/// payload, only when the new type is a projection of the old one (or a primitive) so that
+/// it can still be serialized.
+///
+/// This rewrite is not applied by `with_default_optimize`, so it has to be enabled with
+/// `optimize_with(network_pushdown)`.
+pub fn network_pushdown(ir: &mut [HydroLeaf]) {
+    transform_bottom_up(ir, &mut |_| (), &mut network_pushdown_node);
+}
+
+#[cfg(stageleft_runtime)]
+#[cfg(test)]
+mod tests {
+    use stageleft::*;
+
+    use super::*;
+    use crate::CLUSTER_SELF_ID;
+    use crate::deploy::HydroDeploy;
+    use crate::location::Location;
+
+    /// Returns the kinds of the nodes sent over each network channel.
+    fn network_inputs(ir: &mut [HydroLeaf]) -> Vec<String> {
+        let mut inputs = Vec::new();
+        transform_bottom_up(ir, &mut |_| {}, &mut |node| {
+            if let HydroNode::Network { input, .. } = node {
+                let root = input.print_root();
+                inputs.push(root[..root.find('(').unwrap_or(root.len())].to_string());
+            }
+        });
+        inputs
+    }
+
+    #[test]
+    fn network_pushdown_process_to_process() {
+        let flow = crate::builder::FlowBuilder::new();
+        let p1 = flow.process::<()>();
+        let p2 = flow.process::<()>();
+
+        p1.source_iter(q!(0..10))
+            .map(q!(|v| (v, v.to_string())))
+            .send_bincode(&p2)
+            .filter(q!(|(v, _)| v % 2 == 0))
+            .map(q!(|(v, _)| v))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let built = flow.finalize().optimize_with(network_pushdown);
+        insta::assert_debug_snapshot!(built.ir());
+
+        for (id, graph) in built
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile()
+            .all_dfir()
+        {
+            insta::with_settings!({snapshot_suffix => format!("surface_graph_{id}")}, {
+                insta::assert_snapshot!(graph.surface_syntax_string());
+            });
+        }
+    }
+
+    #[test]
+    fn network_pushdown_process_to_cluster() {
+        let flow = crate::builder::FlowBuilder::new();
+        let process = flow.process::<()>();
+        let cluster = flow.cluster::<()>();
+
+        let ids = cluster.members();
+        let to_all = || {
+            process
+                .source_iter(q!(0..10))
+                .flat_map_ordered(q!(|v| ids.iter().map(move |id| (*id, v))))
+                .send_bincode(&cluster)
+        };
+
+        to_all()
+            .filter(q!(|v| v % 2 == 0))
+            .for_each(q!(|n| println!("{}", n)));
+
+        // Depends on the receiver's ID, so it has to stay on the cluster.
+        to_all()
+            .filter(q!(move |v| *v as u32 != CLUSTER_SELF_ID.raw_id))
+            .for_each(q!(|n| println!("{}", n)));
+
+        // Boxing produces a type that may not be serializable.
+        to_all()
+            .map(q!(|v| Box::new(v)))
+            .for_each(q!(|n| println!("{}", n)));
+
+        let built = flow.finalize().optimize_with(network_pushdown);
+        insta::assert_debug_snapshot!(built.ir());
+
+        let mut ir = deep_clone(built.ir());
+        assert_eq!(
+            network_inputs(&mut ir),
+            vec!["Filter", "FlatMap", "FlatMap"]
+        );
+
+        built
+            .with_default_optimize::<HydroDeploy>()
+            .preview_compile();
+    }
+}
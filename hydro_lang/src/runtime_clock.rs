@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use quote::quote;
+use stageleft::runtime_support::{FreeVariableWithContext, QuoteTokens};
+use tokio::time::Instant;
+
+use crate::staging_util::get_this_crate;
+
+/// A free variable which, when spliced into a quoted snippet, becomes a function returning the
+/// current time according to the clock of the running DFIR instance. Unlike
+/// [`Instant::now`], this follows the virtual clock when the flow is simulated.
+///
+/// ```rust,ignore
+/// stream.map(q!(|v| (v, CLOCK_NOW())))
+/// ```
+pub static CLOCK_NOW: ClockNow = ClockNow { _private: &() };
+
+#[derive(Clone, Copy)]
+pub struct ClockNow<'a> {
+    _private: &'a (),
+}
+
+impl<Ctx> FreeVariableWithContext<Ctx> for ClockNow<'_> {
+    type O = fn() -> Instant;
+
+    fn to_tokens(self, _ctx: &Ctx) -> QuoteTokens {
+        let root = get_this_crate();
+
+        QuoteTokens {
+            prelude: None,
+            expr: Some(quote!(#root::runtime_support::dfir_rs::scheduled::clock::now)),
+        }
+    }
+}
+
+/// A free variable which, when spliced into a quoted snippet, becomes a function creating a
+/// stream that yields the current time every `period`, according to the clock of the running
+/// DFIR instance. Used by [`Location::source_interval`](crate::Location::source_interval).
+pub(crate) static CLOCK_INTERVAL: ClockInterval = ClockInterval { _private: &() };
+
+/// The stream returned by [`CLOCK_INTERVAL`] and [`CLOCK_INTERVAL_DELAYED`].
+#[cfg(any(feature = "runtime_support", feature = "dfir_context"))]
+type ClockIntervalStream = dfir_rs::scheduled::clock::Interval;
+
+/// Without `dfir_rs`, snippets are only staged and never run, so they are type-checked against
+/// a Tokio [`IntervalStream`](tokio_stream::wrappers::IntervalStream), which yields the same
+/// items.
+#[cfg(not(any(feature = "runtime_support", feature = "dfir_context")))]
+type ClockIntervalStream = tokio_stream::wrappers::IntervalStream;
+
+#[derive(Clone, Copy)]
+pub(crate) struct ClockInterval<'a> {
+    _private: &'a (),
+}
+
+impl<Ctx> FreeVariableWithContext<Ctx> for ClockInterval<'_> {
+    type O = fn(Duration) -> ClockIntervalStream;
+
+    fn to_tokens(self, _ctx: &Ctx) -> QuoteTokens {
+        let root = get_this_crate();
+
+        QuoteTokens {
+            prelude: None,
+            expr: Some(quote!(#root::runtime_support::dfir_rs::scheduled::clock::interval)),
+        }
+    }
+}
+
+/// Like [`CLOCK_INTERVAL`], but the function takes an initial delay before the first element.
+pub(crate) static CLOCK_INTERVAL_DELAYED: ClockIntervalDelayed =
+    ClockIntervalDelayed { _private: &() };
+
+#[derive(Clone, Copy)]
+pub(crate) struct ClockIntervalDelayed<'a> {
+    _private: &'a (),
+}
+
+impl<Ctx> FreeVariableWithContext<Ctx> for ClockIntervalDelayed<'_> {
+    type O = fn(Duration, Duration) -> ClockIntervalStream;
+
+    fn to_tokens(self, _ctx: &Ctx) -> QuoteTokens {
+        let root = get_this_crate();
+
+        QuoteTokens {
+            prelude: None,
+            expr: Some(quote!(
+                #root::runtime_support::dfir_rs::scheduled::clock::interval_delayed
+            )),
+        }
+    }
+}
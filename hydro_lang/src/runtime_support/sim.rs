@@ -0,0 +1,343 @@
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::io::BufRead;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::Context;
+
+use dfir_rs::scheduled::clock::VirtualClock;
+use dfir_rs::scheduled::graph::Dfir;
+use dfir_rs::util::simulation::SimRng;
+use futures::FutureExt;
+
+use crate::sim_runtime::{
+    SIM_OUTPUT_PREFIX, SimChannel, SimConfig, SimEvent, SimNetwork, SimOutput, SimPorts,
+};
+
+struct SimInstance {
+    location_id: usize,
+    member: Option<u32>,
+    dfir: Dfir<'static>,
+    /// The tasks that feed the outputs of the DFIR graph into its sinks, which are polled by
+    /// the simulator rather than spawned, so that messages reach the network deterministically.
+    sink_tasks: Vec<Pin<Box<dyn Future<Output = ()>>>>,
+    /// Whether the instance has run its initial tick, after which it only runs when woken
+    /// up by an external event.
+    started: bool,
+}
+
+/// All the locations of a simulated flow, running on a single thread and communicating
+/// through a [`SimNetwork`].
+///
+/// Time-based operators read from a [`VirtualClock`] shared by all locations, which only
+/// advances once nothing else can happen, so timers fire instantly and deterministically.
+pub struct SimFleet {
+    config: SimConfig,
+    network: Rc<SimNetwork>,
+    clock: VirtualClock,
+    instances: Vec<SimInstance>,
+}
+
+impl SimFleet {
+    pub fn new(config: SimConfig) -> Self {
+        SimFleet {
+            config,
+            network: Rc::new(SimNetwork::default()),
+            clock: VirtualClock::new(),
+            instances: Vec::new(),
+        }
+    }
+
+    /// Instantiates a location, once for each member if the location is a cluster.
+    pub fn add_location(
+        &mut self,
+        location_id: usize,
+        build: impl Fn(&'static SimPorts) -> Dfir<'static>,
+    ) {
+        let members = self
+            .config
+            .clusters
+            .get(&location_id)
+            .map(|members| members.iter().copied().map(Some).collect::<Vec<_>>())
+            .unwrap_or_else(|| vec![None]);
+
+        for member in members {
+            // The ports must outlive the DFIR graph, which lives until the process exits.
+            let ports: &'static SimPorts = Box::leak(Box::new(SimPorts {
+                location_id,
+                instance: self.instances.len(),
+                cluster_id: member,
+                clusters: self.config.clusters.clone(),
+                network: self.network.clone(),
+            }));
+
+            let mut dfir = build(ports);
+            dfir.set_clock(self.clock.clone());
+            let sink_tasks = dfir.take_tasks();
+
+            self.instances.push(SimInstance {
+                location_id,
+                member,
+                dfir,
+                sink_tasks,
+                started: false,
+            });
+        }
+    }
+
+    /// Runs the given instance until it has processed all the messages delivered to it so far,
+    /// recording this in the trace if it did any work.
+    ///
+    /// Like [`Dfir::run_async`], an instance only starts new ticks in response to external
+    /// events, so a location with tick-scoped state (which reschedules itself every tick)
+    /// does not stay busy forever.
+    fn tick(&mut self, i: usize, trace: &mut Vec<SimEvent>) -> bool {
+        let instance = &mut self.instances[i];
+        if instance.started && instance.dfir.recv_events_async().now_or_never().is_none() {
+            return false;
+        }
+
+        instance.started = true;
+        let did_work = instance.dfir.run_available();
+
+        // The sinks of the simulator never block, so a single poll hands everything this
+        // tick produced to the network.
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        instance
+            .sink_tasks
+            .retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+
+        if did_work {
+            trace.push(SimEvent::Tick {
+                location: instance.location_id,
+                member: instance.member,
+            });
+        }
+        did_work
+    }
+
+    /// Delivers the oldest message on a channel, returning the instance that received it.
+    fn deliver(&mut self, channel: &SimChannel, trace: &mut Vec<SimEvent>) -> Option<usize> {
+        trace.push(SimEvent::Deliver(channel.clone()));
+        self.network.deliver(channel)
+    }
+
+    /// Drops the oldest message on a channel.
+    fn drop_message(&mut self, channel: &SimChannel, trace: &mut Vec<SimEvent>) {
+        trace.push(SimEvent::Drop(channel.clone()));
+        self.network.drop_message(channel);
+    }
+
+    /// Advances the simulated clock to the earliest pending timer, if there is one.
+    fn advance_time(&mut self, trace: &mut Vec<SimEvent>) -> bool {
+        let advanced = self.clock.advance_to_next_deadline();
+        if advanced {
+            trace.push(SimEvent::AdvanceTime);
+        }
+        advanced
+    }
+
+    /// Runs the simulation until no location has work to do, no messages are in flight and
+    /// no timers are pending, or until `max_steps` scheduling decisions have been made.
+    ///
+    /// At each step, the scheduler uses the seeded RNG to either run a tick on a location
+    /// that may have work, or deliver the oldest message on one of the non-empty channels.
+    /// If message loss is enabled, it may also drop the oldest message on a channel between
+    /// two locations, up to `max_drops` times. Inputs from external processes are never
+    /// dropped, and neither are cluster membership updates, which are delivered to each
+    /// observing location in order but at arbitrary points in the schedule. Time only
+    /// advances when nothing else is possible. If the config contains a schedule, that is
+    /// replayed instead and the run is completed with a canonical schedule that does not
+    /// depend on the seed.
+    pub fn run(&mut self) -> SimOutput {
+        let mut input_channels = BTreeSet::new();
+        for (channel, inputs) in std::mem::take(&mut self.config.inputs) {
+            for input in inputs {
+                self.network.send(channel.clone(), input);
+            }
+            input_channels.insert(channel);
+        }
+
+        for (cluster, member, event) in std::mem::take(&mut self.config.membership_updates) {
+            input_channels.extend(self.network.send_membership_update(cluster, member, event));
+        }
+
+        let (trace, quiescent) = if let Some(schedule) = self.config.schedule.take() {
+            self.run_schedule(schedule)
+        } else {
+            self.run_random(&input_channels)
+        };
+
+        SimOutput {
+            outputs: self.network.take_external_outputs(),
+            trace,
+            quiescent,
+        }
+    }
+
+    fn run_random(&mut self, input_channels: &BTreeSet<SimChannel>) -> (Vec<SimEvent>, bool) {
+        let mut rng = SimRng::new(self.config.seed);
+        let mut active = vec![true; self.instances.len()];
+        let mut trace = Vec::new();
+        let mut drops_left = self.config.max_drops;
+
+        let mut steps = 0;
+        while steps < self.config.max_steps {
+            let pending = self.network.pending_channels();
+            let active_instances = active
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| **a)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            if active_instances.is_empty() && pending.is_empty() {
+                let mut any_work = false;
+                for (i, active) in active.iter_mut().enumerate() {
+                    if self.tick(i, &mut trace) {
+                        any_work = true;
+                        *active = true;
+                    }
+                }
+
+                if !any_work && self.network.pending_channels().is_empty() {
+                    if self.advance_time(&mut trace) {
+                        steps += 1;
+                        continue;
+                    }
+
+                    return (trace, true);
+                }
+
+                continue;
+            }
+
+            let droppable = if drops_left > 0 {
+                pending
+                    .iter()
+                    .filter(|channel| !input_channels.contains(channel))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            } else {
+                Vec::new()
+            };
+
+            steps += 1;
+            let choice = rng.next_below(active_instances.len() + pending.len() + droppable.len());
+            if let Some(&i) = active_instances.get(choice) {
+                if !self.tick(i, &mut trace) {
+                    active[i] = false;
+                }
+            } else if let Some(channel) = pending.get(choice - active_instances.len()) {
+                if let Some(receiver) = self.deliver(channel, &mut trace) {
+                    active[receiver] = true;
+                }
+            } else {
+                drops_left -= 1;
+                self.drop_message(
+                    &droppable[choice - active_instances.len() - pending.len()],
+                    &mut trace,
+                );
+            }
+        }
+
+        (trace, false)
+    }
+
+    /// Replays a forced prefix of scheduling decisions, skipping any that are not enabled.
+    fn run_schedule(&mut self, schedule: Vec<SimEvent>) -> (Vec<SimEvent>, bool) {
+        let mut trace = Vec::new();
+
+        for event in schedule.into_iter().take(self.config.max_steps) {
+            match event {
+                SimEvent::Tick { location, member } => {
+                    if let Some(i) = self
+                        .instances
+                        .iter()
+                        .position(|i| i.location_id == location && i.member == member)
+                    {
+                        self.tick(i, &mut trace);
+                    }
+                }
+                SimEvent::Deliver(channel) => {
+                    if self.network.pending_channels().contains(&channel) {
+                        self.deliver(&channel, &mut trace);
+                    }
+                }
+                SimEvent::Drop(channel) => {
+                    if self.network.pending_channels().contains(&channel) {
+                        self.drop_message(&channel, &mut trace);
+                    }
+                }
+                SimEvent::AdvanceTime => {
+                    self.advance_time(&mut trace);
+                }
+            }
+        }
+
+        let quiescent = self.run_canonical(&mut trace);
+        (trace, quiescent)
+    }
+
+    /// Finishes a run with a deterministic schedule that does not depend on the seed: all
+    /// in-flight messages are delivered, then every location runs a tick, until quiescence
+    /// (advancing time whenever nothing else is possible).
+    fn run_canonical(&mut self, trace: &mut Vec<SimEvent>) -> bool {
+        let mut steps = trace.len();
+        while steps < self.config.max_steps {
+            let pending = self.network.pending_channels();
+            if !pending.is_empty() {
+                for channel in pending {
+                    self.deliver(&channel, trace);
+                    steps += 1;
+                }
+                continue;
+            }
+
+            let mut any_work = false;
+            for i in 0..self.instances.len() {
+                any_work |= self.tick(i, trace);
+                steps += 1;
+            }
+
+            if !any_work && self.network.pending_channels().is_empty() {
+                if self.advance_time(trace) {
+                    steps += 1;
+                    continue;
+                }
+
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Entrypoint for a compiled simulation binary. Reads a [`SimConfig`] from stdin, instantiates
+/// the locations using `register`, runs the simulation, and reports the [`SimOutput`] on stdout.
+pub async fn run_main(register: impl FnOnce(&mut SimFleet)) {
+    let mut config_line = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut config_line)
+        .expect("Failed to read simulation config");
+    let config: SimConfig =
+        serde_json::from_str(&config_line).expect("Failed to parse simulation config");
+
+    // The whole simulation runs in a single poll, so it opts out of Tokio's cooperative
+    // budget, which would otherwise make channels look empty once it is used up.
+    let output = tokio::task::LocalSet::new()
+        .run_until(tokio::task::unconstrained(async move {
+            let mut fleet = SimFleet::new(config);
+            register(&mut fleet);
+            fleet.run()
+        }))
+        .await;
+
+    println!(
+        "{}{}",
+        SIM_OUTPUT_PREFIX,
+        serde_json::to_string(&output).unwrap()
+    );
+}
@@ -0,0 +1,443 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Error;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use futures::{Sink, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use stageleft::{QuotedWithContext, RuntimeData, q};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::location::cluster::{MembershipEvent, MembershipStream};
+
+/// A unidirectional, FIFO link between a sender and a port on a receiving location.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SimChannel {
+    pub from_location: usize,
+    pub from_member: Option<u32>,
+    pub port: String,
+    pub to_member: Option<u32>,
+}
+
+/// A single scheduling decision made by the simulator.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SimEvent {
+    /// A location (or cluster member) ran until it processed all the messages delivered to
+    /// it so far, and did some work in the process.
+    Tick {
+        location: usize,
+        member: Option<u32>,
+    },
+    /// The oldest in-flight message on a channel was delivered.
+    Deliver(SimChannel),
+    /// The oldest in-flight message on a channel was lost, as if the connection carrying it
+    /// had dropped and been re-established. Only happens if message loss is enabled.
+    Drop(SimChannel),
+    /// The simulated clock, shared by all locations, jumped forward to the earliest pending
+    /// timer (such as the next tick of a `source_interval`).
+    AdvanceTime,
+}
+
+/// Configuration passed to a compiled simulation binary on startup.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SimConfig {
+    pub seed: u64,
+    pub max_steps: usize,
+    pub clusters: HashMap<usize, Vec<u32>>,
+    pub inputs: Vec<(SimChannel, Vec<Bytes>)>,
+    /// If set, the simulator replays these events (skipping any that are not enabled when
+    /// reached) and then finishes the run with a fixed, canonical schedule instead of a
+    /// randomized one.
+    #[serde(default)]
+    pub schedule: Option<Vec<SimEvent>>,
+    /// The maximum number of messages between locations that a randomized schedule may drop.
+    #[serde(default)]
+    pub max_drops: usize,
+    /// Changes to the membership of clusters, which are delivered to every location that
+    /// observes the cluster's membership at times chosen by the scheduler.
+    #[serde(default)]
+    pub membership_updates: Vec<(usize, u32, MembershipEvent)>,
+}
+
+/// The result of running a simulation to quiescence (or until the step limit).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SimOutput {
+    pub outputs: BTreeMap<String, Vec<Bytes>>,
+    pub trace: Vec<SimEvent>,
+    pub quiescent: bool,
+}
+
+impl SimOutput {
+    /// Decodes the bincode-serialized messages that were sent to the given external port.
+    pub fn bincode<T: DeserializeOwned>(&self, port: &str) -> Vec<T> {
+        self.outputs
+            .get(port)
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|item| bincode::deserialize(item).unwrap())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Prefix of the line on which a simulation binary reports its [`SimOutput`].
+pub const SIM_OUTPUT_PREFIX: &str = "__hydro_sim_output ";
+
+type SimReceiver = (usize, UnboundedSender<(Option<u32>, Bytes)>);
+
+/// The in-memory network shared by all locations in a simulation. Messages are buffered
+/// per [`SimChannel`] until the scheduler decides to deliver them.
+#[derive(Default)]
+pub struct SimNetwork {
+    in_flight: RefCell<BTreeMap<SimChannel, VecDeque<Bytes>>>,
+    receivers: RefCell<HashMap<(String, Option<u32>), SimReceiver>>,
+    external_outputs: RefCell<BTreeMap<String, Vec<Bytes>>>,
+    /// The channels that carry membership updates for each cluster, one per subscription.
+    membership_channels: RefCell<Vec<(usize, SimChannel)>>,
+}
+
+impl SimNetwork {
+    pub fn send(&self, channel: SimChannel, data: Bytes) {
+        self.in_flight
+            .borrow_mut()
+            .entry(channel)
+            .or_default()
+            .push_back(data);
+    }
+
+    /// Lists all channels that have at least one message in flight, in a deterministic order.
+    pub fn pending_channels(&self) -> Vec<SimChannel> {
+        self.in_flight
+            .borrow()
+            .iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    /// Delivers the oldest message on `channel`, returning the index of the instance that
+    /// received it (if there is one listening on that port).
+    pub fn deliver(&self, channel: &SimChannel) -> Option<usize> {
+        let data = self
+            .in_flight
+            .borrow_mut()
+            .get_mut(channel)
+            .and_then(|queue| queue.pop_front())?;
+
+        let receivers = self.receivers.borrow();
+        let (instance, sender) = receivers.get(&(channel.port.clone(), channel.to_member))?;
+        sender.send((channel.from_member, data)).ok()?;
+        Some(*instance)
+    }
+
+    /// Discards the oldest message on `channel`, returning whether there was one.
+    pub fn drop_message(&self, channel: &SimChannel) -> bool {
+        self.in_flight
+            .borrow_mut()
+            .get_mut(channel)
+            .and_then(|queue| queue.pop_front())
+            .is_some()
+    }
+
+    /// Enqueues a membership update on every channel subscribed to the cluster's membership,
+    /// returning those channels.
+    pub fn send_membership_update(
+        &self,
+        cluster: usize,
+        member: u32,
+        event: MembershipEvent,
+    ) -> Vec<SimChannel> {
+        let channels = self
+            .membership_channels
+            .borrow()
+            .iter()
+            .filter(|(of_cluster, _)| *of_cluster == cluster)
+            .map(|(_, channel)| channel.clone())
+            .collect::<Vec<_>>();
+
+        for channel in &channels {
+            self.send(
+                channel.clone(),
+                bincode::serialize(&(member, event)).unwrap().into(),
+            );
+        }
+
+        channels
+    }
+
+    pub fn take_external_outputs(&self) -> BTreeMap<String, Vec<Bytes>> {
+        std::mem::take(&mut self.external_outputs.borrow_mut())
+    }
+}
+
+/// A sink that hands every item to a callback, used to feed the simulated network.
+pub struct SimSink<T> {
+    send: Box<dyn FnMut(T)>,
+}
+
+impl<T> SimSink<T> {
+    pub fn new(send: impl FnMut(T) + 'static) -> Self {
+        SimSink {
+            send: Box::new(send),
+        }
+    }
+}
+
+impl<T> Sink<T> for SimSink<T> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        (self.get_mut().send)(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Runtime handle given to each simulated location, playing the role of
+/// [`hydro_deploy_integration::DeployPorts`] in a real deployment.
+pub struct SimPorts {
+    pub location_id: usize,
+    pub instance: usize,
+    pub cluster_id: Option<u32>,
+    pub clusters: HashMap<usize, Vec<u32>>,
+    pub network: Rc<SimNetwork>,
+}
+
+impl SimPorts {
+    pub fn cluster_members(&self, of_cluster: usize) -> &[u32] {
+        self.clusters
+            .get(&of_cluster)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn sink(&self, port: &str) -> SimSink<Bytes> {
+        let network = self.network.clone();
+        let from_location = self.location_id;
+        let from_member = self.cluster_id;
+        let port = port.to_string();
+        SimSink::new(move |data| {
+            network.send(
+                SimChannel {
+                    from_location,
+                    from_member,
+                    port: port.clone(),
+                    to_member: None,
+                },
+                data,
+            )
+        })
+    }
+
+    pub fn demux_sink(&self, port: &str) -> SimSink<(u32, Bytes)> {
+        let network = self.network.clone();
+        let from_location = self.location_id;
+        let from_member = self.cluster_id;
+        let port = port.to_string();
+        SimSink::new(move |(to_member, data)| {
+            network.send(
+                SimChannel {
+                    from_location,
+                    from_member,
+                    port: port.clone(),
+                    to_member: Some(to_member),
+                },
+                data,
+            )
+        })
+    }
+
+    /// A sink for messages leaving the simulation, which are delivered immediately.
+    pub fn external_sink(&self, port: &str) -> SimSink<Bytes> {
+        let network = self.network.clone();
+        let port = port.to_string();
+        SimSink::new(move |data| {
+            network
+                .external_outputs
+                .borrow_mut()
+                .entry(port.clone())
+                .or_default()
+                .push(data)
+        })
+    }
+
+    fn register_receiver(&self, port: &str) -> UnboundedReceiverStream<(Option<u32>, Bytes)> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let existing = self
+            .network
+            .receivers
+            .borrow_mut()
+            .insert((port.to_string(), self.cluster_id), (self.instance, sender));
+        assert!(existing.is_none(), "Port {} was connected twice", port);
+        UnboundedReceiverStream::new(receiver)
+    }
+
+    pub fn source(&self, port: &str) -> Pin<Box<dyn Stream<Item = Result<BytesMut, Error>>>> {
+        Box::pin(
+            self.register_receiver(port)
+                .map(|(_, data)| Ok(BytesMut::from(data.as_ref()))),
+        )
+    }
+
+    /// A stream of the members of the cluster, which starts with the initial members and then
+    /// follows the membership updates of the simulation.
+    pub fn cluster_membership(&self, of_cluster: usize) -> MembershipStream {
+        let port = format!(
+            "location_{}_membership_{}_{}",
+            self.location_id,
+            of_cluster,
+            self.network.membership_channels.borrow().len()
+        );
+        self.network.membership_channels.borrow_mut().push((
+            of_cluster,
+            SimChannel {
+                from_location: of_cluster,
+                from_member: None,
+                port: port.clone(),
+                to_member: self.cluster_id,
+            },
+        ));
+
+        let initial = self
+            .cluster_members(of_cluster)
+            .iter()
+            .map(|id| (*id, MembershipEvent::Joined))
+            .collect::<Vec<_>>();
+        let updates = self
+            .register_receiver(&port)
+            .map(|(_, data)| bincode::deserialize::<(u32, MembershipEvent)>(&data).unwrap());
+
+        Box::pin(futures::stream::iter(initial).chain(updates))
+    }
+
+    #[expect(clippy::type_complexity, reason = "stream of tagged bytes")]
+    pub fn tagged_source(
+        &self,
+        port: &str,
+    ) -> Pin<Box<dyn Stream<Item = Result<(u32, BytesMut), Error>>>> {
+        Box::pin(self.register_receiver(port).map(|(from_member, data)| {
+            Ok((
+                from_member.expect("Tagged source received a message from a non-cluster sender"),
+                BytesMut::from(data.as_ref()),
+            ))
+        }))
+    }
+}
+
+pub fn sim_cluster_members(
+    ports: RuntimeData<&SimPorts>,
+    of_cluster: usize,
+) -> impl QuotedWithContext<&[u32], ()> + Copy {
+    q!(ports.cluster_members(of_cluster))
+}
+
+pub fn sim_cluster_self_id(
+    ports: RuntimeData<&SimPorts>,
+) -> impl QuotedWithContext<u32, ()> + Copy {
+    q!(ports
+        .cluster_id
+        .expect("Tried to read Cluster ID on a non-cluster node"))
+}
+
+pub fn sim_cluster_membership(
+    ports: RuntimeData<&SimPorts>,
+    of_cluster: usize,
+) -> impl QuotedWithContext<MembershipStream, ()> + Copy {
+    q!(ports.cluster_membership(of_cluster))
+}
+
+pub fn sim_o2o(ports: RuntimeData<&SimPorts>, p2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(ports.sink(p2_port)).splice_untyped_ctx(&()),
+        q!(ports.source(p2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+pub fn sim_o2m(ports: RuntimeData<&SimPorts>, c2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(ports.demux_sink(c2_port)).splice_untyped_ctx(&()),
+        q!(ports.source(c2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+pub fn sim_m2o(ports: RuntimeData<&SimPorts>, p2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(ports.sink(p2_port)).splice_untyped_ctx(&()),
+        q!(ports.tagged_source(p2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+pub fn sim_m2m(ports: RuntimeData<&SimPorts>, c2_port: &str) -> (syn::Expr, syn::Expr) {
+    (
+        q!(ports.demux_sink(c2_port)).splice_untyped_ctx(&()),
+        q!(ports.tagged_source(c2_port)).splice_untyped_ctx(&()),
+    )
+}
+
+pub fn sim_e2o(ports: RuntimeData<&SimPorts>, p2_port: &str) -> syn::Expr {
+    q!(ports.source(p2_port)).splice_untyped_ctx(&())
+}
+
+pub fn sim_o2e(ports: RuntimeData<&SimPorts>, e2_port: &str) -> syn::Expr {
+    q!(ports.external_sink(e2_port)).splice_untyped_ctx(&())
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use super::SimEvent;
+    use crate::location::Location;
+
+    struct P1 {}
+    struct P2 {}
+
+    #[test]
+    fn sim_message_loss_drops_plain_messages() {
+        let mut simulation = crate::deploy::Simulation::new().with_message_loss(1);
+
+        let counterexample = crate::test_util::sim_explore_test(
+            &mut simulation,
+            0..20,
+            |flow| {
+                let sender = flow.process::<P1>();
+                let receiver = flow.process::<P2>();
+
+                sender.source_iter(q!(0..5u32)).send_bincode(&receiver)
+            },
+            |received| {
+                if received.len() == 5 {
+                    Ok(())
+                } else {
+                    Err(format!("only received {:?}", received))
+                }
+            },
+        )
+        .expect_err("some schedule should drop a message");
+
+        // The sender must run before there is a message to drop.
+        assert_eq!(counterexample.schedule.len(), 2);
+        assert!(matches!(
+            counterexample.schedule.last(),
+            Some(SimEvent::Drop(_))
+        ));
+    }
+}
@@ -4,13 +4,14 @@ use std::ops::Deref;
 use std::rc::Rc;
 
 use stageleft::{IntoQuotedMut, QuotedWithContext, q};
+use syn::parse_quote;
 
 use crate::builder::FLOW_USED_MESSAGE;
 use crate::cycle::{
     CycleCollection, CycleCollectionWithInitial, CycleComplete, DeferTick, ForwardRefMarker,
     TickCycleMarker,
 };
-use crate::ir::{HydroLeaf, HydroNode, TeeNode};
+use crate::ir::{HydroLeaf, HydroNode, HydroSource, TeeNode};
 use crate::location::tick::{Atomic, NoAtomic};
 use crate::location::{Location, LocationId, NoTick, Tick, check_matching_location};
 use crate::stream::{AtLeastOnce, ExactlyOnce};
@@ -390,6 +391,15 @@ where
     }
 }
 
+impl<'a, T, L> Singleton<T, L, Bounded>
+where
+    L: Location<'a>,
+{
+    pub fn into_stream(self) -> Stream<T, L, Bounded, TotalOrder, ExactlyOnce> {
+        Optional::from(self).into_stream()
+    }
+}
+
 impl<'a, T, L, B> Singleton<T, Atomic<L>, B>
 where
     L: Location<'a> + NoTick,
@@ -562,9 +572,98 @@ where
             },
         )
     }
+}
 
-    pub fn into_stream(self) -> Stream<T, Tick<L>, Bounded, TotalOrder, ExactlyOnce> {
-        Stream::new(self.location, self.ir_node.into_inner())
+/// Lowers the IR of a [`Bounded`] top-level collection into the IR of a top-level stream that
+/// emits its contents exactly once.
+///
+/// The operators behind the `Persist` of a top-level collection re-emit all of its contents
+/// every time their subgraph runs, which can happen several times in one tick when a later
+/// subgraph in the same stratum feeds into it. `one_per_tick` must reduce those runs to a
+/// single copy per tick without hashing the values. A bounded collection is final by the end
+/// of the first tick, so only the copy from the first tick is let through.
+///
+/// There is no end-of-stream signal in the IR, so this relies on every input of the
+/// collection being a `source_iter` or `singleton`, which is asserted here.
+pub(crate) fn bounded_top_level_once<'a, T, L: Location<'a>>(
+    location: &L,
+    persisted: HydroNode,
+    one_per_tick: impl FnOnce(HydroNode) -> HydroNode,
+) -> HydroNode {
+    assert!(
+        settles_in_first_tick(&persisted),
+        "bounded top-level collections must only be computed from `source_iter` and `singleton`, \
+         so that they are final by the end of the first tick"
+    );
+
+    let first_tick: syn::Expr = parse_quote!([()]);
+    let drop_signal: syn::Expr = parse_quote!(|(v, ())| v);
+
+    HydroNode::Persist {
+        inner: Box::new(HydroNode::Map {
+            f: drop_signal.into(),
+            input: Box::new(HydroNode::CrossSingleton {
+                left: Box::new(one_per_tick(HydroNode::Unpersist {
+                    inner: Box::new(persisted),
+                    metadata: location.new_node_metadata::<T>(),
+                })),
+                right: Box::new(HydroNode::Source {
+                    source: HydroSource::Iter(first_tick.into()),
+                    location_kind: location.id(),
+                    metadata: location.new_node_metadata::<()>(),
+                }),
+                metadata: location.new_node_metadata::<(T, ())>(),
+            }),
+            metadata: location.new_node_metadata::<T>(),
+        }),
+        metadata: location.new_node_metadata::<T>(),
+    }
+}
+
+/// Whether every input of `node` is a `source_iter` or `singleton` (rather than a network,
+/// a cycle, or a value deferred to a later tick), so that it has all of its contents by the
+/// end of the first tick.
+fn settles_in_first_tick(node: &HydroNode) -> bool {
+    match node {
+        HydroNode::Source { source, .. } => matches!(source, HydroSource::Iter(_)),
+
+        HydroNode::Tee { inner, .. } => settles_in_first_tick(&inner.0.borrow()),
+
+        HydroNode::Persist { inner, .. }
+        | HydroNode::Unpersist { inner, .. }
+        | HydroNode::Delta { inner, .. } => settles_in_first_tick(inner),
+
+        HydroNode::Chain { first, second, .. } => {
+            settles_in_first_tick(first) && settles_in_first_tick(second)
+        }
+
+        HydroNode::CrossSingleton { left, right, .. }
+        | HydroNode::CrossProduct { left, right, .. }
+        | HydroNode::Join { left, right, .. } => {
+            settles_in_first_tick(left) && settles_in_first_tick(right)
+        }
+
+        HydroNode::Difference { pos, neg, .. } | HydroNode::AntiJoin { pos, neg, .. } => {
+            settles_in_first_tick(pos) && settles_in_first_tick(neg)
+        }
+
+        HydroNode::Map { input, .. }
+        | HydroNode::FlatMap { input, .. }
+        | HydroNode::Filter { input, .. }
+        | HydroNode::FilterMap { input, .. }
+        | HydroNode::Enumerate { input, .. }
+        | HydroNode::Inspect { input, .. }
+        | HydroNode::Unique { input, .. }
+        | HydroNode::Sort { input, .. }
+        | HydroNode::Fold { input, .. }
+        | HydroNode::FoldKeyed { input, .. }
+        | HydroNode::LatticeFold { input, .. }
+        | HydroNode::LatticeFoldKeyed { input, .. }
+        | HydroNode::Reduce { input, .. }
+        | HydroNode::ReduceKeyed { input, .. }
+        | HydroNode::Counter { input, .. } => settles_in_first_tick(input),
+
+        _ => false,
     }
 }
 
@@ -622,3 +721,128 @@ where
         Optional::new(location, ir_node)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use stageleft::q;
+
+    use crate::FlowBuilder;
+    use crate::location::Location;
+
+    struct P1 {}
+    struct P2 {}
+
+    #[test]
+    fn singleton_into_stream_top_level_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        // `fallback` is used twice, so it is teed into the `unwrap_or` below from a later
+        // subgraph in the same stratum, which runs the subgraph of `total` twice in the first
+        // tick.
+        let fallback = node
+            .source_iter(q!(vec![10.0f64]))
+            .reduce(q!(|acc, x| *acc += x))
+            .unwrap_or(node.singleton(q!(0.0)));
+        let total_port = node
+            .source_iter(q!(vec![1.5f64, 2.5]))
+            .reduce(q!(|acc, x| *acc += x))
+            .unwrap_or(fallback.clone())
+            .into_stream()
+            .send_bincode_external(&external);
+        let fallback_port = fallback.into_stream().send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let output = simulation.run();
+        assert_eq!(
+            output.bincode::<f64>(&nodes.raw_port_bincode(&total_port)),
+            vec![4.0]
+        );
+        assert_eq!(
+            output.bincode::<f64>(&nodes.raw_port_bincode(&fallback_port)),
+            vec![10.0]
+        );
+    }
+
+    #[tokio::test]
+    async fn bounded_top_level_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let (nodes, (poke_port, sorted_port, totals_port, parity_port)) =
+            crate::test_util::sim_deploy_test(&mut simulation, |flow, external| {
+                let process = flow.process::<P1>();
+
+                // external inputs make the process run for more ticks, which must not repeat
+                // the outputs of the bounded streams
+                let (poke_port, pokes) = external.source_external_bincode::<_, u32>(&process);
+                pokes.for_each(q!(|_| {}));
+
+                let numbers = process.source_iter(q!(vec![4, 2, 3, 1]));
+
+                let sorted_port = numbers
+                    .clone()
+                    .sort()
+                    .chain(process.source_iter(q!(vec![10, 20])))
+                    .filter_not_in(process.source_iter(q!(vec![3])))
+                    .send_bincode_external(external);
+
+                let totals_port = numbers
+                    .clone()
+                    .count()
+                    .into_stream()
+                    .chain(
+                        numbers
+                            .clone()
+                            .fold(q!(|| 0), q!(|acc, x| *acc += x))
+                            .into_stream(),
+                    )
+                    .chain(process.singleton(q!(100)).into_stream())
+                    .send_bincode_external(external);
+
+                let parity_port = numbers
+                    .map(q!(|x| (x % 2, x)))
+                    .into_keyed()
+                    .fold(q!(|| 0), q!(|acc, x| *acc += x))
+                    .entries()
+                    .send_bincode_external(external);
+
+                (poke_port, sorted_port, totals_port, parity_port)
+            });
+
+        let mut poke_sink = nodes.connect_sink_bincode(poke_port).await;
+        for n in 0..3 {
+            poke_sink.send(n).await.unwrap();
+        }
+        drop(poke_sink);
+
+        let sorted = nodes
+            .connect_source_bincode(sorted_port)
+            .await
+            .collect::<Vec<usize>>()
+            .await;
+        assert_eq!(sorted, vec![1, 2, 4, 10, 20]);
+
+        let totals = nodes
+            .connect_source_bincode(totals_port)
+            .await
+            .collect::<Vec<usize>>()
+            .await;
+        assert_eq!(totals, vec![4, 10, 100]);
+
+        let mut parity = nodes
+            .connect_source_bincode(parity_port)
+            .await
+            .collect::<Vec<(usize, usize)>>()
+            .await;
+        parity.sort();
+        assert_eq!(parity, vec![(0, 6), (1, 4)]);
+    }
+}
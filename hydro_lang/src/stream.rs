@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::future::Future;
 use std::hash::Hash;
 use std::marker::PhantomData;
@@ -6,22 +7,29 @@ use std::ops::Deref;
 use std::rc::Rc;
 
 use bytes::Bytes;
+use lattices::Merge;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 use stageleft::{IntoQuotedMut, QuotedWithContext, q};
 use syn::parse_quote;
-use tokio::time::Instant;
 
 use crate::builder::FLOW_USED_MESSAGE;
+use crate::codec::{Bincode, Codec};
 use crate::cycle::{CycleCollection, CycleComplete, DeferTick, ForwardRefMarker, TickCycleMarker};
-use crate::ir::{DebugInstantiate, HydroLeaf, HydroNode, TeeNode};
-use crate::location::external_process::{ExternalBincodeStream, ExternalBytesPort};
+use crate::ir::{AggregationProperties, DebugInstantiate, HydroLeaf, HydroNode, TeeNode};
+use crate::keyed_stream::KeyedStream;
+use crate::location::cluster::ClusterIds;
+use crate::location::external_process::{ExternalBytesPort, ExternalStream};
 use crate::location::tick::{Atomic, NoAtomic};
 use crate::location::{
     CanSend, ExternalProcess, Location, LocationId, NoTick, Tick, check_matching_location,
 };
 use crate::staging_util::get_this_crate;
-use crate::{Bounded, Cluster, ClusterId, Optional, Singleton, Unbounded};
+use crate::watermark::{LateRecords, Watermarked};
+use crate::{
+    Bounded, CLOCK_NOW, Cluster, ClusterId, MonotoneSingleton, Optional, Singleton, Unbounded,
+    watermark, window,
+};
 
 /// Marks the stream as being totally ordered, which means that there are
 /// no sources of non-determinism (other than intentional ones) that will
@@ -97,7 +105,7 @@ impl MinRetries<AtLeastOnce> for ExactlyOnce {
 /// - `Order`: the ordering of the stream, which is either [`TotalOrder`]
 ///   or [`NoOrder`] (default is [`TotalOrder`])
 pub struct Stream<Type, Loc, Bound, Order = TotalOrder, Retries = ExactlyOnce> {
-    location: Loc,
+    pub(crate) location: Loc,
     pub(crate) ir_node: RefCell<HydroNode>,
 
     _phantom: PhantomData<(Type, Loc, Bound, Order, Retries)>,
@@ -380,7 +388,7 @@ where
     /// ```rust
     /// # use hydro_lang::{*, stream::ExactlyOnce};
     /// # use futures::StreamExt;
-    /// # tokio_test::block_on(test_util::stream_transform_test::<_, _, NoOrder, ExactlyOnce>(|process| {
+    /// # tokio_test::block_on(test_util::stream_transform_test::<_, _, _, NoOrder, ExactlyOnce>(|process| {
     /// process
     ///     .source_iter(q!(vec![
     ///         std::collections::HashSet::<i32>::from_iter(vec![1, 2]),
@@ -450,7 +458,7 @@ where
     /// ```rust
     /// # use hydro_lang::{*, stream::ExactlyOnce};
     /// # use futures::StreamExt;
-    /// # tokio_test::block_on(test_util::stream_transform_test::<_, _, NoOrder, ExactlyOnce>(|process| {
+    /// # tokio_test::block_on(test_util::stream_transform_test::<_, _, _, NoOrder, ExactlyOnce>(|process| {
     /// process
     ///     .source_iter(q!(vec![
     ///         std::collections::HashSet::<i32>::from_iter(vec![1, 2]),
@@ -573,14 +581,34 @@ where
         let other: Optional<O2, L, Bounded> = other.into();
         check_matching_location(&self.location, &other.location);
 
-        Stream::new(
-            self.location.clone(),
-            HydroNode::CrossSingleton {
-                left: Box::new(self.ir_node.into_inner()),
-                right: Box::new(other.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<(T, O2)>(),
-            },
-        )
+        if L::is_top_level() {
+            Stream::new(
+                self.location.clone(),
+                HydroNode::Persist {
+                    inner: Box::new(HydroNode::CrossSingleton {
+                        left: Box::new(HydroNode::Unpersist {
+                            inner: Box::new(self.ir_node.into_inner()),
+                            metadata: self.location.new_node_metadata::<T>(),
+                        }),
+                        right: Box::new(HydroNode::Unpersist {
+                            inner: Box::new(other.ir_node.into_inner()),
+                            metadata: self.location.new_node_metadata::<O2>(),
+                        }),
+                        metadata: self.location.new_node_metadata::<(T, O2)>(),
+                    }),
+                    metadata: self.location.new_node_metadata::<(T, O2)>(),
+                },
+            )
+        } else {
+            Stream::new(
+                self.location.clone(),
+                HydroNode::CrossSingleton {
+                    left: Box::new(self.ir_node.into_inner()),
+                    right: Box::new(other.ir_node.into_inner()),
+                    metadata: self.location.new_node_metadata::<(T, O2)>(),
+                },
+            )
+        }
     }
 
     /// Allow this stream through if the argument (a Bounded Optional) is non-empty, otherwise the output is empty.
@@ -685,23 +713,39 @@ where
     /// #     assert_eq!(stream.next().await.unwrap(), w);
     /// # }
     /// # }));
-    pub fn filter_not_in<O2>(
-        self,
-        other: Stream<T, L, Bounded, O2, R>,
-    ) -> Stream<T, L, Bounded, O, R>
+    pub fn filter_not_in<O2>(self, other: Stream<T, L, Bounded, O2, R>) -> Stream<T, L, B, O, R>
     where
         T: Eq + Hash,
     {
         check_matching_location(&self.location, &other.location);
 
-        Stream::new(
-            self.location.clone(),
-            HydroNode::Difference {
-                pos: Box::new(self.ir_node.into_inner()),
-                neg: Box::new(other.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<T>(),
-            },
-        )
+        if L::is_top_level() {
+            // the negative side is complete by the end of the first tick, so each batch of
+            // the positive side can be checked against all of it
+            Stream::new(
+                self.location.clone(),
+                HydroNode::Persist {
+                    inner: Box::new(HydroNode::Difference {
+                        pos: Box::new(HydroNode::Unpersist {
+                            inner: Box::new(self.ir_node.into_inner()),
+                            metadata: self.location.new_node_metadata::<T>(),
+                        }),
+                        neg: Box::new(other.ir_node.into_inner()),
+                        metadata: self.location.new_node_metadata::<T>(),
+                    }),
+                    metadata: self.location.new_node_metadata::<T>(),
+                },
+            )
+        } else {
+            Stream::new(
+                self.location.clone(),
+                HydroNode::Difference {
+                    pos: Box::new(self.ir_node.into_inner()),
+                    neg: Box::new(other.ir_node.into_inner()),
+                    metadata: self.location.new_node_metadata::<T>(),
+                },
+            )
+        }
     }
 
     /// An operator which allows you to "inspect" each element of a stream without
@@ -810,6 +854,42 @@ where
             self.assume_retries::<AtLeastOnce>()
         }
     }
+
+    /// Builds the IR that emits the elements of `self` followed by those of `other`.
+    fn chain_ir<B2, O2>(self, other: Stream<T, L, B2, O2, R>) -> HydroNode {
+        if L::is_top_level() {
+            HydroNode::Persist {
+                inner: Box::new(HydroNode::Chain {
+                    first: Box::new(HydroNode::Unpersist {
+                        inner: Box::new(self.ir_node.into_inner()),
+                        metadata: self.location.new_node_metadata::<T>(),
+                    }),
+                    second: Box::new(HydroNode::Unpersist {
+                        inner: Box::new(other.ir_node.into_inner()),
+                        metadata: self.location.new_node_metadata::<T>(),
+                    }),
+                    metadata: self.location.new_node_metadata::<T>(),
+                }),
+                metadata: self.location.new_node_metadata::<T>(),
+            }
+        } else {
+            HydroNode::Chain {
+                first: Box::new(self.ir_node.into_inner()),
+                second: Box::new(other.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<T>(),
+            }
+        }
+    }
+
+    /// Emits the elements of both streams with no guarantee on how they are interleaved.
+    /// Unlike [`Stream::union`], the inputs are not batched into a tick, so this preserves
+    /// the boundedness of the inputs.
+    fn merge_unordered<O2>(self, other: Stream<T, L, B, O2, R>) -> Stream<T, L, B, NoOrder, R> {
+        check_matching_location(&self.location, &other.location);
+
+        let location = self.location.clone();
+        Stream::new(location, self.chain_ir(other))
+    }
 }
 
 impl<'a, T, L, B, O, R> Stream<&T, L, B, O, R>
@@ -879,7 +959,14 @@ where
             // SAFETY: the combinator function is commutative and idempotent
             self.assume_ordering().assume_retries()
         }
-        .fold(init, comb)
+        .fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: true,
+            },
+        )
     }
 
     /// Combines elements of the stream into an [`Optional`], by starting with the first element in the stream,
@@ -917,7 +1004,63 @@ where
             // SAFETY: the combinator function is commutative and idempotent
             self.assume_ordering().assume_retries()
         }
-        .reduce(comb)
+        .reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: true,
+            },
+        )
+    }
+
+    /// Merges elements of the stream into a lattice of type `Lat`, starting from its
+    /// [`Default`] value, and returns the result as a [`MonotoneSingleton`].
+    ///
+    /// Because lattice merges are associative, commutative, and idempotent, this can be
+    /// applied to any stream regardless of its ordering and retry guarantees. The result only
+    /// grows over time, so it can be checked against monotone thresholds with
+    /// [`MonotoneSingleton::threshold`] without batching it into a [`Tick`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![1, 5, 3]));
+    /// let batch = unsafe { numbers.tick_batch(&tick) };
+    /// batch
+    ///     .map(q!(|x| hydro_lang::lattices::Max::new(x)))
+    ///     .fold_lattice::<lattices::Max<i32>>()
+    ///     .into_singleton()
+    ///     .map(q!(|max| max.into_reveal()))
+    ///     .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // 5
+    /// # assert_eq!(stream.next().await.unwrap(), 5);
+    /// # }));
+    /// ```
+    pub fn fold_lattice<Lat>(self) -> MonotoneSingleton<Lat, L, B>
+    where
+        Lat: Merge<T> + Default,
+    {
+        let lat_type = stageleft::quote_type::<Lat>();
+        let init: syn::Expr = parse_quote!(<#lat_type as ::std::default::Default>::default);
+
+        let mut core = HydroNode::LatticeFold {
+            init: init.into(),
+            input: Box::new(self.ir_node.into_inner()),
+            metadata: self.location.new_node_metadata::<Lat>(),
+        };
+
+        if L::is_top_level() {
+            core = HydroNode::Persist {
+                inner: Box::new(core),
+                metadata: self.location.new_node_metadata::<Lat>(),
+            };
+        }
+
+        MonotoneSingleton::new(Singleton::new(self.location, core))
     }
 
     /// Computes the maximum element in the stream as an [`Optional`], which
@@ -984,6 +1127,11 @@ where
 
         let mut core = HydroNode::Reduce {
             f: wrapped.into(),
+            // ties between equal keys are broken by arrival order
+            properties: AggregationProperties {
+                commutative: false,
+                idempotent: true,
+            },
             input: Box::new(self.ir_node.into_inner()),
             metadata: self.location.new_node_metadata::<T>(),
         };
@@ -1066,7 +1214,14 @@ where
             // SAFETY: the combinator function is commutative
             self.assume_ordering()
         }
-        .fold(init, comb)
+        .fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: false,
+            },
+        )
     }
 
     /// Combines elements of the stream into a [`Optional`], by starting with the first element in the stream,
@@ -1100,7 +1255,13 @@ where
             // SAFETY: the combinator function is commutative
             self.assume_ordering()
         }
-        .reduce(comb)
+        .reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: false,
+            },
+        )
     }
 
     /// Computes the number of elements in the stream as a [`Singleton`].
@@ -1163,7 +1324,14 @@ where
             // SAFETY: the combinator function is idempotent
             self.assume_retries()
         }
-        .fold(init, comb)
+        .fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: true,
+            },
+        )
     }
 
     /// Combines elements of the stream into an [`Optional`], by starting with the first element in the stream,
@@ -1195,7 +1363,13 @@ where
             // SAFETY: the combinator function is idempotent
             self.assume_retries()
         }
-        .reduce(comb)
+        .reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: true,
+            },
+        )
     }
 }
 
@@ -1209,7 +1383,7 @@ where
     /// ```rust
     /// # use hydro_lang::{*, stream::ExactlyOnce};
     /// # use futures::StreamExt;
-    /// # tokio_test::block_on(test_util::stream_transform_test::<_, _, TotalOrder, ExactlyOnce>(|process| {
+    /// # tokio_test::block_on(test_util::stream_transform_test::<_, _, _, TotalOrder, ExactlyOnce>(|process| {
     /// let tick = process.tick();
     /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
     /// numbers.enumerate()
@@ -1323,6 +1497,22 @@ where
         self,
         init: impl IntoQuotedMut<'a, I, L>,
         comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> Singleton<A, L, B> {
+        self.fold_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: false,
+            },
+        )
+    }
+
+    fn fold_with_properties<A, I: Fn() -> A + 'a, F: Fn(&mut A, T)>(
+        self,
+        init: impl IntoQuotedMut<'a, I, L>,
+        comb: impl IntoQuotedMut<'a, F, L>,
+        properties: AggregationProperties,
     ) -> Singleton<A, L, B> {
         let init = init.splice_fn0_ctx(&self.location).into();
         let comb = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
@@ -1330,6 +1520,7 @@ where
         let mut core = HydroNode::Fold {
             init,
             acc: comb,
+            properties,
             input: Box::new(self.ir_node.into_inner()),
             metadata: self.location.new_node_metadata::<A>(),
         };
@@ -1374,10 +1565,25 @@ where
     pub fn reduce<F: Fn(&mut T, T) + 'a>(
         self,
         comb: impl IntoQuotedMut<'a, F, L>,
+    ) -> Optional<T, L, B> {
+        self.reduce_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: false,
+            },
+        )
+    }
+
+    fn reduce_with_properties<F: Fn(&mut T, T) + 'a>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, L>,
+        properties: AggregationProperties,
     ) -> Optional<T, L, B> {
         let f = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
         let mut core = HydroNode::Reduce {
             f,
+            properties,
             input: Box::new(self.ir_node.into_inner()),
             metadata: self.location.new_node_metadata::<T>(),
         };
@@ -1405,7 +1611,7 @@ impl<'a, T, L: Location<'a> + NoTick + NoAtomic, O, R> Stream<T, L, Unbounded, O
     /// # use hydro_lang::*;
     /// # use futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let numbers = process.source_iter(q!(vec![1, 2, 3, 4]));
+    /// let numbers: Stream<i32, _, Unbounded> = process.source_iter(q!(vec![1, 2, 3, 4])).into();
     /// numbers.clone().map(q!(|x| x + 1)).union(numbers)
     /// # }, |mut stream| async move {
     /// // 2, 3, 4, 5, and 1, 2, 3, 4 interleaved in unknown order
@@ -1468,13 +1674,31 @@ where
     where
         T: Ord,
     {
-        Stream::new(
-            self.location.clone(),
-            HydroNode::Sort {
-                input: Box::new(self.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<T>(),
-            },
-        )
+        if L::is_top_level() {
+            // a bounded top-level stream is complete by the end of the first tick, so sorting
+            // the first batch sorts the whole stream
+            Stream::new(
+                self.location.clone(),
+                HydroNode::Persist {
+                    inner: Box::new(HydroNode::Sort {
+                        input: Box::new(HydroNode::Unpersist {
+                            inner: Box::new(self.ir_node.into_inner()),
+                            metadata: self.location.new_node_metadata::<T>(),
+                        }),
+                        metadata: self.location.new_node_metadata::<T>(),
+                    }),
+                    metadata: self.location.new_node_metadata::<T>(),
+                },
+            )
+        } else {
+            Stream::new(
+                self.location.clone(),
+                HydroNode::Sort {
+                    input: Box::new(self.ir_node.into_inner()),
+                    metadata: self.location.new_node_metadata::<T>(),
+                },
+            )
+        }
     }
 
     /// Produces a new stream that first emits the elements of the `self` stream,
@@ -1508,14 +1732,8 @@ where
     {
         check_matching_location(&self.location, &other.location);
 
-        Stream::new(
-            self.location.clone(),
-            HydroNode::Chain {
-                first: Box::new(self.ir_node.into_inner()),
-                second: Box::new(other.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<T>(),
-            },
-        )
+        let location = self.location.clone();
+        Stream::new(location, self.chain_ir(other))
     }
 }
 
@@ -1523,6 +1741,12 @@ impl<'a, K, V1, L, B, O, R> Stream<(K, V1), L, B, O, R>
 where
     L: Location<'a>,
 {
+    /// Treats the first element of each pair as a key, producing a [`KeyedStream`] whose
+    /// values inherit the ordering and retry guarantees of this stream.
+    pub fn into_keyed(self) -> KeyedStream<K, V1, L, B, O, R> {
+        KeyedStream::new(Stream::new(self.location, self.ir_node.into_inner()))
+    }
+
     /// Given two streams of pairs `(K, V1)` and `(K, V2)`, produces a new stream of nested pairs `(K, (V1, V2))`
     /// by equi-joining the two streams on the key attribute `K`.
     ///
@@ -1593,100 +1817,313 @@ where
     {
         check_matching_location(&self.location, &n.location);
 
-        Stream::new(
-            self.location.clone(),
-            HydroNode::AntiJoin {
-                pos: Box::new(self.ir_node.into_inner()),
-                neg: Box::new(n.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<(K, V1)>(),
-            },
-        )
+        if L::is_top_level() {
+            Stream::new(
+                self.location.clone(),
+                HydroNode::Persist {
+                    inner: Box::new(HydroNode::AntiJoin {
+                        pos: Box::new(HydroNode::Unpersist {
+                            inner: Box::new(self.ir_node.into_inner()),
+                            metadata: self.location.new_node_metadata::<(K, V1)>(),
+                        }),
+                        neg: Box::new(n.ir_node.into_inner()),
+                        metadata: self.location.new_node_metadata::<(K, V1)>(),
+                    }),
+                    metadata: self.location.new_node_metadata::<(K, V1)>(),
+                },
+            )
+        } else {
+            Stream::new(
+                self.location.clone(),
+                HydroNode::AntiJoin {
+                    pos: Box::new(self.ir_node.into_inner()),
+                    neg: Box::new(n.ir_node.into_inner()),
+                    metadata: self.location.new_node_metadata::<(K, V1)>(),
+                },
+            )
+        }
     }
-}
 
-impl<'a, K, V, L> Stream<(K, V), Tick<L>, Bounded, TotalOrder, ExactlyOnce>
-where
-    K: Eq + Hash,
-    L: Location<'a>,
-{
-    /// A special case of [`Stream::fold`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
-    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
-    /// in the second element are accumulated via the `comb` closure.
-    ///
-    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
-    /// to depend on the order of elements in the stream.
+    /// Given a stream of pairs `(K, V1)` and a bounded stream of pairs `(K, V2)`, produces a
+    /// new stream of nested pairs `(K, (V1, Option<V2>))` by left outer joining the two streams
+    /// on the key attribute `K`. Elements of the first stream whose key does not appear in the
+    /// second stream are emitted once, paired with `None`.
     ///
-    /// If the input and output value types are the same and do not require initialization then use
-    /// [`Stream::reduce_keyed`].
+    /// The `other` stream must be [`Bounded`], since this function will wait until all its
+    /// elements are available before deciding that a key has no match.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
+    /// # use std::collections::HashSet;
     /// # use futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
     /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
-    /// let batch = unsafe { numbers.tick_batch(&tick) };
-    /// batch
-    ///     .fold_keyed(q!(|| 0), q!(|acc, x| *acc += x))
-    ///     .all_ticks()
+    /// let stream1 = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec![(1, 'a'), (2, 'b'), (3, 'c')]))
+    ///         .tick_batch(&tick)
+    /// };
+    /// let stream2 = unsafe {
+    ///     process
+    ///         .source_iter(q!(vec![(1, 'x'), (2, 'y'), (2, 'z')]))
+    ///         .tick_batch(&tick)
+    /// };
+    /// stream1.left_join(stream2).all_ticks()
     /// # }, |mut stream| async move {
-    /// // (1, 5), (2, 7)
-    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
-    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// // (1, ('a', Some('x'))), (2, ('b', Some('y'))), (2, ('b', Some('z'))), (3, ('c', None)) in any order
+    /// # let mut results = HashSet::new();
+    /// # for _ in 0..4 {
+    /// #     results.insert(stream.next().await.unwrap());
+    /// # }
+    /// # assert_eq!(results, HashSet::from([(1, ('a', Some('x'))), (2, ('b', Some('y'))), (2, ('b', Some('z'))), (3, ('c', None))]));
     /// # }));
     /// ```
-    pub fn fold_keyed<A, I, F>(
+    #[expect(clippy::type_complexity, reason = "optional values of an outer join")]
+    pub fn left_join<V2, O2>(
         self,
-        init: impl IntoQuotedMut<'a, I, Tick<L>>,
-        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
-    ) -> Stream<(K, A), Tick<L>, Bounded, NoOrder, ExactlyOnce>
+        other: Stream<(K, V2), L, Bounded, O2, R>,
+    ) -> Stream<(K, (V1, Option<V2>)), L, B, NoOrder, R>
     where
-        I: Fn() -> A + 'a,
-        F: Fn(&mut A, V) + 'a,
+        K: Eq + Hash + Clone,
+        V1: Clone,
+        V2: Clone,
     {
-        let init = init.splice_fn0_ctx(&self.location).into();
-        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+        check_matching_location(&self.location, &other.location);
 
-        Stream::new(
-            self.location.clone(),
-            HydroNode::FoldKeyed {
-                init,
-                acc: comb,
-                input: Box::new(self.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<(K, A)>(),
-            },
-        )
+        let other_keys = other.clone().map(q!(|(k, _)| k));
+        // a bounded stream can always be treated as having the boundedness of `self`
+        let other = Stream::<(K, V2), L, B, O2, R>::new(other.location, other.ir_node.into_inner());
+
+        let matched = self
+            .clone()
+            .join(other)
+            .map(q!(|(k, (v1, v2))| (k, (v1, Some(v2)))));
+        let unmatched = self
+            .anti_join(other_keys)
+            .map(q!(|(k, v1)| (k, (v1, None))));
+
+        matched.merge_unordered(unmatched)
     }
 
-    /// A special case of [`Stream::reduce`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
-    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
-    /// in the second element are accumulated via the `comb` closure.
-    ///
-    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
-    /// to depend on the order of elements in the stream.
+    /// Given a stream of pairs `(K, V1)` and a stream of keys `K`, produces the elements of
+    /// the first stream whose key appears in the second stream. Each element is emitted at most
+    /// once, even if its key appears several times in `keys`.
     ///
-    /// If you need the accumulated value to have a different type than the input, use [`Stream::fold_keyed`].
+    /// Unlike [`Stream::anti_join`], `keys` may be [`Unbounded`], since an element can be
+    /// emitted as soon as its key is seen.
     ///
     /// # Example
     /// ```rust
     /// # use hydro_lang::*;
+    /// # use std::collections::HashSet;
     /// # use futures::StreamExt;
     /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// let tick = process.tick();
-    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
-    /// let batch = unsafe { numbers.tick_batch(&tick) };
-    /// batch.reduce_keyed(q!(|acc, x| *acc += x)).all_ticks()
+    /// let stream = process.source_iter(q!(vec![(1, 'a'), (2, 'b'), (3, 'c')]));
+    /// let keys = process.source_iter(q!(vec![1, 3, 3]));
+    /// stream.semi_join(keys)
     /// # }, |mut stream| async move {
-    /// // (1, 5), (2, 7)
-    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
-    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// // (1, 'a'), (3, 'c') in any order
+    /// # let mut results = HashSet::new();
+    /// # for _ in 0..2 {
+    /// #     results.insert(stream.next().await.unwrap());
+    /// # }
+    /// # assert_eq!(results, HashSet::from([(1, 'a'), (3, 'c')]));
+    /// # }));
+    /// ```
+    pub fn semi_join<O2, R2>(
+        self,
+        keys: Stream<K, L, B, O2, R2>,
+    ) -> Stream<(K, V1), L, B, NoOrder, R>
+    where
+        K: Eq + Hash,
+    {
+        let keys = unsafe {
+            // SAFETY: the output only depends on the set of keys, so duplicates in `keys`
+            // do not affect it
+            keys.unique().map(q!(|k| (k, ()))).assume_retries::<R>()
+        };
+
+        self.join(keys).map(q!(|(k, (v1, ()))| (k, v1)))
+    }
+}
+
+impl<'a, K, V1, L, O, R> Stream<(K, V1), L, Bounded, O, R>
+where
+    L: Location<'a>,
+{
+    /// Given two bounded streams of pairs `(K, V1)` and `(K, V2)`, produces a new stream of
+    /// nested pairs `(K, (Option<V1>, Option<V2>))` by full outer joining the two streams on the
+    /// key attribute `K`. Elements of either stream whose key does not appear in the other
+    /// stream are emitted once, paired with `None`.
+    ///
+    /// Both streams must be [`Bounded`], since this function must wait until all elements of
+    /// each side are available before deciding that a key has no match.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use std::collections::HashSet;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let stream1 = process.source_iter(q!(vec![(1, 'a'), (2, 'b')]));
+    /// let stream2 = process.source_iter(q!(vec![(2, 'y'), (3, 'z')]));
+    /// stream1.full_outer_join(stream2)
+    /// # }, |mut stream| async move {
+    /// // (1, (Some('a'), None)), (2, (Some('b'), Some('y'))), (3, (None, Some('z'))) in any order
+    /// # let mut results = HashSet::new();
+    /// # for _ in 0..3 {
+    /// #     results.insert(stream.next().await.unwrap());
+    /// # }
+    /// # assert_eq!(results, HashSet::from([(1, (Some('a'), None)), (2, (Some('b'), Some('y'))), (3, (None, Some('z')))]));
+    /// # }));
+    /// ```
+    #[expect(clippy::type_complexity, reason = "optional values of an outer join")]
+    pub fn full_outer_join<V2, O2>(
+        self,
+        other: Stream<(K, V2), L, Bounded, O2, R>,
+    ) -> Stream<(K, (Option<V1>, Option<V2>)), L, Bounded, NoOrder, R>
+    where
+        K: Eq + Hash + Clone,
+        V1: Clone,
+        V2: Clone,
+    {
+        check_matching_location(&self.location, &other.location);
+
+        let self_keys = self.clone().map(q!(|(k, _)| k));
+
+        let left = self
+            .left_join(other.clone())
+            .map(q!(|(k, (v1, v2))| (k, (Some(v1), v2))));
+        let right_only = other
+            .anti_join(self_keys)
+            .map(q!(|(k, v2)| (k, (None, Some(v2)))));
+
+        left.merge_unordered(right_only)
+    }
+}
+
+impl<'a, K, V, L> Stream<(K, V), Tick<L>, Bounded, TotalOrder, ExactlyOnce>
+where
+    K: Eq + Hash,
+    L: Location<'a>,
+{
+    /// A special case of [`Stream::fold`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
+    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
+    /// in the second element are accumulated via the `comb` closure.
+    ///
+    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
+    /// to depend on the order of elements in the stream.
+    ///
+    /// If the input and output value types are the same and do not require initialization then use
+    /// [`Stream::reduce_keyed`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.tick_batch(&tick) };
+    /// batch
+    ///     .fold_keyed(q!(|| 0), q!(|acc, x| *acc += x))
+    ///     .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 7)
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
+    /// # }));
+    /// ```
+    pub fn fold_keyed<A, I, F>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+    ) -> Stream<(K, A), Tick<L>, Bounded, NoOrder, ExactlyOnce>
+    where
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    {
+        self.fold_keyed_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: false,
+            },
+        )
+    }
+
+    fn fold_keyed_with_properties<A, I, F>(
+        self,
+        init: impl IntoQuotedMut<'a, I, Tick<L>>,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+        properties: AggregationProperties,
+    ) -> Stream<(K, A), Tick<L>, Bounded, NoOrder, ExactlyOnce>
+    where
+        I: Fn() -> A + 'a,
+        F: Fn(&mut A, V) + 'a,
+    {
+        let init = init.splice_fn0_ctx(&self.location).into();
+        let comb = comb.splice_fn2_borrow_mut_ctx(&self.location).into();
+
+        Stream::new(
+            self.location.clone(),
+            HydroNode::FoldKeyed {
+                init,
+                acc: comb,
+                properties,
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<(K, A)>(),
+            },
+        )
+    }
+
+    /// A special case of [`Stream::reduce`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
+    /// tuples are partitioned into groups by the first element ("keys"), and for each group the values
+    /// in the second element are accumulated via the `comb` closure.
+    ///
+    /// The input stream must have a [`TotalOrder`] guarantee, which means that the `comb` closure is allowed
+    /// to depend on the order of elements in the stream.
+    ///
+    /// If you need the accumulated value to have a different type than the input, use [`Stream::fold_keyed`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 3), (2, 4)]));
+    /// let batch = unsafe { numbers.tick_batch(&tick) };
+    /// batch.reduce_keyed(q!(|acc, x| *acc += x)).all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, 5), (2, 7)
+    /// # assert_eq!(stream.next().await.unwrap(), (1, 5));
+    /// # assert_eq!(stream.next().await.unwrap(), (2, 7));
     /// # }));
     /// ```
     pub fn reduce_keyed<F>(
         self,
         comb: impl IntoQuotedMut<'a, F, Tick<L>>,
     ) -> Stream<(K, V), Tick<L>, Bounded, NoOrder, ExactlyOnce>
+    where
+        F: Fn(&mut V, V) + 'a,
+    {
+        self.reduce_keyed_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: false,
+            },
+        )
+    }
+
+    fn reduce_keyed_with_properties<F>(
+        self,
+        comb: impl IntoQuotedMut<'a, F, Tick<L>>,
+        properties: AggregationProperties,
+    ) -> Stream<(K, V), Tick<L>, Bounded, NoOrder, ExactlyOnce>
     where
         F: Fn(&mut V, V) + 'a,
     {
@@ -1696,6 +2133,7 @@ where
             self.location.clone(),
             HydroNode::ReduceKeyed {
                 f,
+                properties,
                 input: Box::new(self.ir_node.into_inner()),
                 metadata: self.location.new_node_metadata::<(K, V)>(),
             },
@@ -1748,7 +2186,55 @@ where
             // SAFETY: aggregation is commutative and idempotent
             self.assume_ordering().assume_retries()
         }
-        .fold_keyed(init, comb)
+        .fold_keyed_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: true,
+            },
+        )
+    }
+
+    /// A special case of [`Stream::fold_lattice`], in the spirit of SQL's GROUP BY and aggregation constructs.
+    /// The input tuples are partitioned into groups by the first element ("keys"), and for each group the values
+    /// in the second element are merged into a lattice of type `Lat`, starting from its [`Default`] value.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let numbers = process.source_iter(q!(vec![(1, 2), (2, 3), (1, 4), (2, 1)]));
+    /// let batch = unsafe { numbers.tick_batch(&tick) };
+    /// batch
+    ///     .map(q!(|(k, v)| (k, hydro_lang::lattices::Max::new(v))))
+    ///     .fold_keyed_lattice::<lattices::Max<i32>>()
+    ///     .map(q!(|(k, max)| (k, max.into_reveal())))
+    ///     .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (1, 4), (2, 3) in any order
+    /// # let mut out = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # out.sort();
+    /// # assert_eq!(out, vec![(1, 4), (2, 3)]);
+    /// # }));
+    /// ```
+    pub fn fold_keyed_lattice<Lat>(self) -> Stream<(K, Lat), Tick<L>, Bounded, NoOrder, ExactlyOnce>
+    where
+        Lat: Merge<V> + Default,
+    {
+        let lat_type = stageleft::quote_type::<Lat>();
+        let init: syn::Expr = parse_quote!(<#lat_type as ::std::default::Default>::default);
+
+        Stream::new(
+            self.location.clone(),
+            HydroNode::LatticeFoldKeyed {
+                init: init.into(),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<(K, Lat)>(),
+            },
+        )
     }
 
     /// Given a stream of pairs `(K, V)`, produces a new stream of unique keys `K`.
@@ -1809,7 +2295,13 @@ where
             // SAFETY: aggregation is commutative and idempotent
             self.assume_ordering().assume_retries()
         }
-        .reduce_keyed(comb)
+        .reduce_keyed_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: true,
+            },
+        )
     }
 }
 
@@ -1857,7 +2349,14 @@ where
             // SAFETY: aggregation is commutative
             self.assume_ordering()
         }
-        .fold_keyed(init, comb)
+        .fold_keyed_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: false,
+            },
+        )
     }
 
     /// A special case of [`Stream::reduce_commutative`], in the spirit of SQL's GROUP BY and aggregation constructs. The input
@@ -1896,7 +2395,13 @@ where
             // SAFETY: aggregation is commutative
             self.assume_ordering()
         }
-        .reduce_keyed(comb)
+        .reduce_keyed_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: true,
+                idempotent: false,
+            },
+        )
     }
 }
 
@@ -1944,7 +2449,14 @@ where
             // SAFETY: aggregation is idempotent
             self.assume_retries()
         }
-        .fold_keyed(init, comb)
+        .fold_keyed_with_properties(
+            init,
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: true,
+            },
+        )
     }
 
     /// A special case of [`Stream::reduce_idempotent`], in the spirit of SQL's GROUP BY and aggregation constructs.
@@ -1983,7 +2495,13 @@ where
             // SAFETY: aggregation is idempotent
             self.assume_retries()
         }
-        .reduce_keyed(comb)
+        .reduce_keyed_with_properties(
+            comb,
+            AggregationProperties {
+                commutative: false,
+                idempotent: true,
+            },
+        )
     }
 }
 
@@ -2052,7 +2570,7 @@ where
     /// #       );
     /// #   },
     /// # ));
-    pub fn resolve_futures<T2>(self) -> Stream<T2, L, B, NoOrder, R>
+    pub fn resolve_futures<T2>(self) -> Stream<T2, L, Unbounded, NoOrder, R>
     where
         T: Future<Output = T2>,
     {
@@ -2119,8 +2637,8 @@ where
 
         let latest_received = unsafe { self.assume_retries() }.fold_commutative(
             q!(|| None),
-            q!(|latest, _| {
-                *latest = Some(Instant::now());
+            q!(move |latest, _| {
+                *latest = Some(CLOCK_NOW());
             }),
         );
 
@@ -2130,7 +2648,7 @@ where
         }
         .filter_map(q!(move |latest_received| {
             if let Some(latest_received) = latest_received {
-                if Instant::now().duration_since(latest_received) > duration {
+                if CLOCK_NOW().duration_since(latest_received) > duration {
                     Some(())
                 } else {
                     None
@@ -2143,87 +2661,445 @@ where
     }
 }
 
-impl<'a, F, T, L, B, O, R> Stream<F, L, B, O, R>
+impl<'a, T, L> Stream<T, L, Unbounded, TotalOrder, ExactlyOnce>
 where
     L: Location<'a> + NoTick + NoAtomic,
-    F: Future<Output = T>,
+    T: Clone,
 {
-    /// Consumes a stream of `Future<T>`, produces a new stream of the resulting `T` outputs.
-    /// Future outputs are produced in the same order as the input stream.
+    /// Attaches the event timestamp extracted by `timestamp` to each element, and batches the
+    /// result into `tick`.
     ///
-    /// # Example
-    /// ```rust
-    /// # use std::collections::HashSet;
-    /// # use futures::StreamExt;
-    /// # use hydro_lang::*;
-    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
-    /// process.source_iter(q!([2, 3, 1, 9, 6, 5, 4, 7, 8]))
-    ///     .map(q!(|x| async move {
-    ///         tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-    ///         x
-    ///     }))
-    ///     .resolve_futures_ordered()
-    /// #   },
-    /// #   |mut stream| async move {
-    /// // 2, 3, 1, 9, 6, 5, 4, 7, 8
-    /// #       let mut output = Vec::new();
-    /// #       for _ in 1..10 {
-    /// #           output.push(stream.next().await.unwrap());
-    /// #       }
-    /// #       assert_eq!(
-    /// #           output,
-    /// #           vec![2, 3, 1, 9, 6, 5, 4, 7, 8]
-    /// #       );
-    /// #   },
-    /// # ));
-    pub fn resolve_futures_ordered(self) -> Stream<T, L, B, O, R> {
-        Stream::new(
+    /// # Safety
+    /// The batch boundaries are non-deterministic, as in [`Stream::tick_batch`].
+    unsafe fn timestamped_batch<F>(
+        self,
+        tick: &Tick<L>,
+        timestamp: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<(u64, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>
+    where
+        F: Fn(&T) -> u64 + 'a,
+    {
+        let f = timestamp.splice_fn1_borrow_ctx(&self.location);
+        let wrapped: syn::Expr = parse_quote!({
+            let timestamp_fn = #f;
+            move |v| (timestamp_fn(&v), v)
+        });
+
+        let timestamped: Stream<(u64, T), L, Unbounded, TotalOrder, ExactlyOnce> = Stream::new(
             self.location.clone(),
-            HydroNode::ResolveFuturesOrdered {
+            HydroNode::Map {
+                f: wrapped.into(),
                 input: Box::new(self.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<T>(),
+                metadata: self.location.new_node_metadata::<(u64, T)>(),
             },
-        )
+        );
+
+        unsafe { timestamped.tick_batch(tick) }
     }
-}
 
-impl<'a, T, L, B, O, R> Stream<T, L, B, O, R>
-where
-    L: Location<'a> + NoTick,
-{
-    pub fn for_each<F: Fn(T) + 'a>(self, f: impl IntoQuotedMut<'a, F, L>) {
-        let f = f.splice_fn1_ctx(&self.location).into();
-        let metadata = self.location.new_node_metadata::<T>();
-        self.location
-            .flow_state()
-            .borrow_mut()
-            .leaves
-            .as_mut()
-            .expect(FLOW_USED_MESSAGE)
-            .push(HydroLeaf::ForEach {
-                input: Box::new(HydroNode::Unpersist {
-                    inner: Box::new(self.ir_node.into_inner()),
-                    metadata: metadata.clone(),
-                }),
-                f,
-                metadata,
-            });
+    /// Tracks the largest timestamp seen in `batch` across ticks, returning the watermark as of
+    /// the previous tick along with the one including the current batch.
+    fn watermarks(
+        tick: &Tick<L>,
+        batch: Stream<(u64, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>,
+    ) -> Singleton<(Option<u64>, Option<u64>), Tick<L>, Bounded> {
+        let (complete_watermark, prev_watermark) =
+            tick.cycle_with_initial(tick.singleton(q!(None::<u64>)));
+        let watermark = prev_watermark
+            .clone()
+            .into_stream()
+            .flatten_ordered()
+            .chain(batch.map(q!(|(t, _)| t)))
+            .max()
+            .into_singleton();
+        complete_watermark.complete_next_tick(watermark.clone());
+
+        prev_watermark.zip(watermark)
     }
 
-    pub fn dest_sink<S>(self, sink: impl QuotedWithContext<'a, S, L>)
-    where
-        S: 'a + futures::Sink<T> + Unpin,
-    {
-        self.location
-            .flow_state()
-            .borrow_mut()
-            .leaves
-            .as_mut()
-            .expect(FLOW_USED_MESSAGE)
-            .push(HydroLeaf::DestSink {
-                sink: sink.splice_typed_ctx(&self.location).into(),
-                input: Box::new(self.ir_node.into_inner()),
-                metadata: self.location.new_node_metadata::<T>(),
+    /// Groups the elements of the stream into fixed-size, non-overlapping windows of event
+    /// time, based on the timestamp extracted by the `timestamp` closure. Window `i` covers
+    /// the timestamps `[i * size, (i + 1) * size)`.
+    ///
+    /// A window is emitted, all at once in a single tick, as soon as an element with a
+    /// timestamp at or after the end of the window arrives. So the timestamps are expected
+    /// to be non-decreasing in the order of the stream, and elements whose window has already
+    /// been emitted are dropped. Because each window is emitted in a single tick, it can be
+    /// aggregated with [`Stream::fold_keyed`] to compute one result per window.
+    ///
+    /// # Safety
+    /// Which windows are emitted together in the same tick depends on when the elements
+    /// arrive, so operators that combine the windows of a tick (such as a
+    /// [`Stream::count`] of the whole batch) are non-deterministic. If the timestamps are
+    /// not non-decreasing, the output is non-deterministic as well, because an element that
+    /// arrives after one with a larger timestamp is only kept if both land in the same tick.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use hydro_lang::*;
+    /// # use futures::StreamExt;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// let tick = process.tick();
+    /// let readings: Stream<_, _, Unbounded> = process
+    ///     .source_iter(q!(vec![(1, 10), (4, 20), (6, 5), (12, 1)]))
+    ///     .into();
+    /// // SAFETY: the timestamps are non-decreasing, and the windows are aggregated separately
+    /// unsafe { readings.tumbling_window(&tick, 5, q!(|(t, _)| *t)) }
+    ///     .map(q!(|(window, (_, v))| (window.start, v)))
+    ///     .fold_keyed(q!(|| 0), q!(|acc, v| *acc += v))
+    ///     .all_ticks()
+    /// # }, |mut stream| async move {
+    /// // (0, 30), (5, 5)
+    /// # let mut windows = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+    /// # windows.sort();
+    /// # assert_eq!(windows, vec![(0, 30), (5, 5)]);
+    /// # }));
+    /// ```
+    pub unsafe fn tumbling_window<F>(
+        self,
+        tick: &Tick<L>,
+        size: u64,
+        timestamp: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<(window::Window, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>
+    where
+        F: Fn(&T) -> u64 + 'a,
+    {
+        unsafe { self.sliding_window(tick, size, size, timestamp) }
+    }
+
+    /// Groups the elements of the stream into windows of event time of length `size`, with a
+    /// new window starting every `slide` units of time, based on the timestamp extracted by
+    /// the `timestamp` closure. When `slide` is smaller than `size`, the windows overlap and
+    /// each element is emitted once for every window that contains it.
+    ///
+    /// Windows are emitted in the same way as in [`Stream::tumbling_window`].
+    ///
+    /// # Safety
+    /// As in [`Stream::tumbling_window`], the grouping of windows into ticks is
+    /// non-deterministic, and so is the output if the timestamps are not non-decreasing.
+    pub unsafe fn sliding_window<F>(
+        self,
+        tick: &Tick<L>,
+        size: u64,
+        slide: u64,
+        timestamp: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<(window::Window, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>
+    where
+        F: Fn(&T) -> u64 + 'a,
+    {
+        assert!(
+            size > 0 && slide > 0,
+            "Windows must have a positive size and slide"
+        );
+
+        let batch = unsafe { self.timestamped_batch(tick, timestamp) };
+        let watermarks = Self::watermarks(tick, batch.clone());
+
+        let (complete_pending, pending) =
+            tick.cycle::<Stream<(u64, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>>();
+        let with_watermarks = pending.chain(batch).cross_singleton(watermarks);
+
+        // Elements stay buffered until the last window containing them has been emitted.
+        complete_pending.complete_next_tick(with_watermarks.clone().filter_map(q!(move |(
+            (t, v),
+            (_, watermark),
+        )| {
+            let last_end = window::Window::sliding(t, size, slide).last()?.end;
+            if watermark.is_some_and(|w| w >= last_end) {
+                None
+            } else {
+                Some((t, v))
+            }
+        })));
+
+        with_watermarks.flat_map_ordered(q!(move |((t, v), (prev, watermark))| {
+            window::Window::sliding(t, size, slide)
+                .into_iter()
+                .filter(|w| prev.is_none_or(|p| w.end > p) && watermark.is_some_and(|c| w.end <= c))
+                .map(|w| (w, v.clone()))
+                .collect::<Vec<_>>()
+        }))
+    }
+
+    /// Groups the elements of the stream into sessions of activity, based on the event
+    /// timestamp extracted by the `timestamp` closure. A session ends once there is a gap of
+    /// at least `gap` units of time after an element, and its window covers
+    /// `[first timestamp, last timestamp + gap)`.
+    ///
+    /// Windows are emitted in the same way as in [`Stream::tumbling_window`], so a session
+    /// is emitted once an element arrives that is at least `gap` after the end of it.
+    ///
+    /// # Safety
+    /// As in [`Stream::tumbling_window`], the grouping of sessions into ticks is
+    /// non-deterministic, and so is the output if the timestamps are not non-decreasing.
+    pub unsafe fn session_window<F>(
+        self,
+        tick: &Tick<L>,
+        gap: u64,
+        timestamp: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<(window::Window, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>
+    where
+        F: Fn(&T) -> u64 + 'a,
+    {
+        assert!(gap > 0, "Sessions must have a positive gap");
+
+        let batch = unsafe { self.timestamped_batch(tick, timestamp) };
+        let watermarks = Self::watermarks(tick, batch.clone());
+
+        let (complete_pending, pending) =
+            tick.cycle::<Stream<(u64, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>>();
+        let sessions = pending
+            .chain(batch)
+            .fold(
+                q!(|| Vec::new()),
+                q!(
+                    move |sessions: &mut Vec<(window::Window, Vec<_>)>, (t, v)| {
+                        match sessions.last_mut() {
+                            Some((window, elements)) if t < window.end => {
+                                window.start = window.start.min(t);
+                                window.end = window.end.max(t.saturating_add(gap));
+                                elements.push((t, v));
+                            }
+                            _ => sessions.push((
+                                window::Window {
+                                    start: t,
+                                    end: t.saturating_add(gap),
+                                },
+                                vec![(t, v)],
+                            )),
+                        }
+                    }
+                ),
+            )
+            .zip(watermarks)
+            .into_stream()
+            .flat_map_ordered(q!(|(sessions, watermarks)| sessions
+                .into_iter()
+                .map(move |session| (session, watermarks))));
+
+        complete_pending.complete_next_tick(sessions.clone().flat_map_ordered(q!(|(
+            (window, elements),
+            (_, watermark),
+        )| {
+            if watermark.is_some_and(|w| w >= window.end) {
+                vec![]
+            } else {
+                elements
+            }
+        })));
+
+        sessions.flat_map_ordered(q!(|((window, elements), (prev, watermark))| {
+            if prev.is_none_or(|p| window.end > p) && watermark.is_some_and(|w| w >= window.end) {
+                elements.into_iter().map(|(_, v)| (window, v)).collect()
+            } else {
+                vec![]
+            }
+        }))
+    }
+}
+
+impl<'a, T, L, O, R> Stream<Watermarked<T>, L, Unbounded, O, R>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+    T: Clone,
+{
+    /// Buffers the data in a stream that carries watermarks (such as one produced by
+    /// [`Stream::send_bincode_watermarked`]), and releases each record into `tick` once the
+    /// watermark reaches the time returned by `release_at` for it.
+    ///
+    /// To release records one by one once no more records with the same timestamp can
+    /// arrive, `release_at` should return the timestamp plus one. To aggregate event-time
+    /// windows, it should instead return the end of the record's window, so that all the
+    /// records in a window are released together in a single tick.
+    ///
+    /// A record is late if the watermark had already reached its release time in an
+    /// earlier tick. Late records are handled according to `late`.
+    ///
+    /// # Safety
+    /// Whether a record is late depends on when it arrives relative to the watermarks, which
+    /// is non-deterministic. If no records are late, each record is released as soon as the
+    /// watermark reaches its release time.
+    pub unsafe fn release_by_watermark<F>(
+        self,
+        tick: &Tick<L>,
+        release_at: impl IntoQuotedMut<'a, F, L>,
+        late: LateRecords,
+    ) -> Stream<T, Tick<L>, Bounded, NoOrder, R>
+    where
+        F: Fn(&T) -> u64 + 'a,
+    {
+        let f = release_at.splice_fn1_borrow_ctx(&self.location);
+        let with_release_time: syn::Expr = parse_quote!({
+            let release_fn = #f;
+            move |m| match m {
+                watermark::Watermarked::Data(data) => watermark::Watermarked::Data((release_fn(&data), data)),
+                watermark::Watermarked::Watermark(w) => watermark::Watermarked::Watermark(w),
+            }
+        });
+
+        let timed: Stream<Watermarked<(u64, T)>, L, Unbounded, NoOrder, R> = Stream::new(
+            self.location.clone(),
+            HydroNode::Map {
+                f: with_release_time.into(),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<Watermarked<(u64, T)>>(),
+            },
+        );
+
+        let batch = unsafe {
+            // SAFETY: records are held back until the watermark passes them, so batch
+            // boundaries only affect which records are late
+            timed.tick_batch(tick)
+        };
+
+        let (complete_watermark, prev_watermark) =
+            tick.cycle_with_initial(tick.singleton(q!(None::<u64>)));
+        let watermark = prev_watermark
+            .clone()
+            .zip(
+                batch
+                    .clone()
+                    .filter_map(q!(|m| match m {
+                        watermark::Watermarked::Watermark(w) => Some(w),
+                        watermark::Watermarked::Data(_) => None,
+                    }))
+                    .max()
+                    .into_singleton(),
+            )
+            .map(q!(|(prev, new)| prev.max(new)));
+        complete_watermark.complete_next_tick(watermark.clone());
+
+        let incoming = batch
+            .filter_map(q!(|m| match m {
+                watermark::Watermarked::Data(data) => Some(data),
+                watermark::Watermarked::Watermark(_) => None,
+            }))
+            .cross_singleton(prev_watermark);
+        let late_records = incoming.clone().filter_map(q!(|((t, data), prev)| {
+            if prev.is_some_and(|p| p >= t) {
+                Some(data)
+            } else {
+                None
+            }
+        }));
+        let on_time = incoming.filter_map(q!(|((t, data), prev)| {
+            if prev.is_some_and(|p| p >= t) {
+                None
+            } else {
+                Some((t, data))
+            }
+        }));
+
+        let (complete_pending, pending) =
+            tick.cycle::<Stream<(u64, T), Tick<L>, Bounded, NoOrder, R>>();
+        let with_watermark = pending.chain(on_time).cross_singleton(watermark);
+        complete_pending.complete_next_tick(with_watermark.clone().filter_map(q!(|(
+            (t, data),
+            watermark,
+        )| {
+            if watermark.is_some_and(|w| w >= t) {
+                None
+            } else {
+                Some((t, data))
+            }
+        })));
+
+        let released = with_watermark.filter_map(q!(|((t, data), watermark)| {
+            if watermark.is_some_and(|w| w >= t) {
+                Some(data)
+            } else {
+                None
+            }
+        }));
+
+        match late {
+            LateRecords::Drop => released,
+            LateRecords::Release => released.chain(late_records),
+        }
+    }
+}
+
+impl<'a, F, T, L, B, O, R> Stream<F, L, B, O, R>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+    F: Future<Output = T>,
+{
+    /// Consumes a stream of `Future<T>`, produces a new stream of the resulting `T` outputs.
+    /// Future outputs are produced in the same order as the input stream.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::collections::HashSet;
+    /// # use futures::StreamExt;
+    /// # use hydro_lang::*;
+    /// # tokio_test::block_on(test_util::stream_transform_test(|process| {
+    /// process.source_iter(q!([2, 3, 1, 9, 6, 5, 4, 7, 8]))
+    ///     .map(q!(|x| async move {
+    ///         tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    ///         x
+    ///     }))
+    ///     .resolve_futures_ordered()
+    /// #   },
+    /// #   |mut stream| async move {
+    /// // 2, 3, 1, 9, 6, 5, 4, 7, 8
+    /// #       let mut output = Vec::new();
+    /// #       for _ in 1..10 {
+    /// #           output.push(stream.next().await.unwrap());
+    /// #       }
+    /// #       assert_eq!(
+    /// #           output,
+    /// #           vec![2, 3, 1, 9, 6, 5, 4, 7, 8]
+    /// #       );
+    /// #   },
+    /// # ));
+    pub fn resolve_futures_ordered(self) -> Stream<T, L, Unbounded, O, R> {
+        Stream::new(
+            self.location.clone(),
+            HydroNode::ResolveFuturesOrdered {
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<T>(),
+            },
+        )
+    }
+}
+
+impl<'a, T, L, B, O, R> Stream<T, L, B, O, R>
+where
+    L: Location<'a> + NoTick,
+{
+    pub fn for_each<F: Fn(T) + 'a>(self, f: impl IntoQuotedMut<'a, F, L>) {
+        let f = f.splice_fn1_ctx(&self.location).into();
+        let metadata = self.location.new_node_metadata::<T>();
+        self.location
+            .flow_state()
+            .borrow_mut()
+            .leaves
+            .as_mut()
+            .expect(FLOW_USED_MESSAGE)
+            .push(HydroLeaf::ForEach {
+                input: Box::new(HydroNode::Unpersist {
+                    inner: Box::new(self.ir_node.into_inner()),
+                    metadata: metadata.clone(),
+                }),
+                f,
+                metadata,
+            });
+    }
+
+    pub fn dest_sink<S>(self, sink: impl QuotedWithContext<'a, S, L>)
+    where
+        S: 'a + futures::Sink<T> + Unpin,
+    {
+        self.location
+            .flow_state()
+            .borrow_mut()
+            .leaves
+            .as_mut()
+            .expect(FLOW_USED_MESSAGE)
+            .push(HydroLeaf::DestSink {
+                sink: sink.splice_typed_ctx(&self.location).into(),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<T>(),
             });
     }
 }
@@ -2337,6 +3213,95 @@ pub(super) fn deserialize_bincode<T: DeserializeOwned>(tagged: Option<&syn::Type
     deserialize_bincode_with_type(tagged, &stageleft::quote_type::<T>())
 }
 
+pub fn serialize_with_codec_type(
+    codec_type: &syn::Type,
+    is_demux: bool,
+    t_type: &syn::Type,
+) -> syn::Expr {
+    let root = get_this_crate();
+
+    if is_demux {
+        parse_quote! {
+            ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<(#root::ClusterId<_>, #t_type), _>(
+                |(id, data)| {
+                    (id.raw_id, <#codec_type as #root::codec::Codec>::encode(&data).unwrap().into())
+                }
+            )
+        }
+    } else {
+        parse_quote! {
+            ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#t_type, _>(
+                |data| {
+                    <#codec_type as #root::codec::Codec>::encode(&data).unwrap().into()
+                }
+            )
+        }
+    }
+}
+
+pub fn deserialize_with_codec_type(
+    codec_type: &syn::Type,
+    tagged: Option<&syn::Type>,
+    t_type: &syn::Type,
+) -> syn::Expr {
+    let root = get_this_crate();
+
+    if let Some(c_type) = tagged {
+        parse_quote! {
+            |res| {
+                let (id, b) = res.unwrap();
+                (#root::ClusterId::<#c_type>::from_raw(id), <#codec_type as #root::codec::Codec>::decode::<#t_type>(&b).unwrap())
+            }
+        }
+    } else {
+        parse_quote! {
+            |res| {
+                <#codec_type as #root::codec::Codec>::decode::<#t_type>(&res.unwrap()).unwrap()
+            }
+        }
+    }
+}
+
+/// Controls how [`Stream::send_bincode_batched`] groups elements into network messages.
+///
+/// By default, all the elements sent to a destination in a single tick are sent together
+/// in one message.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchConfig {
+    max_size: usize,
+    max_delay: Option<std::time::Duration>,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            max_size: 1024,
+            max_delay: None,
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Sets the maximum number of elements in a single message. Larger batches are split
+    /// into several messages.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        assert!(max_size > 0, "Batches must contain at least one element");
+        self.max_size = max_size;
+        self
+    }
+
+    /// Allows elements to be held back across ticks to form larger batches. Buffered elements
+    /// are sent once there are `max_size` of them, or at the latest after `max_delay`.
+    pub fn with_max_delay(mut self, max_delay: std::time::Duration) -> Self {
+        assert!(
+            !max_delay.is_zero(),
+            "The maximum delay of a batch must be non-zero"
+        );
+        self.max_delay = Some(max_delay);
+        self
+    }
+}
+
 impl<'a, T, L, B, O, R> Stream<T, L, B, O, R>
 where
     L: Location<'a> + NoTick,
@@ -2373,20 +3338,773 @@ where
                 input: Box::new(self.ir_node.into_inner()),
                 metadata: other.new_node_metadata::<CoreType>(),
             },
-        )
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but encodes the elements with the given [`Codec`]
+    /// instead of bincode.
+    ///
+    /// ```rust,ignore
+    /// stream.send_with_codec(&other, hydro_lang::codec::Json)
+    /// ```
+    #[expect(
+        clippy::type_complexity,
+        reason = "Complex signatures for CanSend trait"
+    )]
+    pub fn send_with_codec<L2, CoreType, C>(
+        self,
+        other: &L2,
+        _codec: C,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, O::Min, R>
+    where
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        L2: Location<'a>,
+        CoreType: Serialize + DeserializeOwned,
+        C: Codec,
+        O: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<O>>,
+    {
+        let codec_type = stageleft::quote_type::<C>();
+        let core_type = stageleft::quote_type::<CoreType>();
+
+        let serialize_pipeline =
+            serialize_with_codec_type(&codec_type, L::Root::is_demux(), &core_type);
+
+        let deserialize_pipeline =
+            deserialize_with_codec_type(&codec_type, L::Root::tagged_type().as_ref(), &core_type);
+
+        Stream::new(
+            other.clone(),
+            HydroNode::Network {
+                from_key: None,
+                to_location: other.id(),
+                to_key: None,
+                serialize_fn: Some(serialize_pipeline.into()),
+                instantiate_fn: DebugInstantiate::Building,
+                deserialize_fn: Some(deserialize_pipeline.into()),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: other.new_node_metadata::<CoreType>(),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but coalesces the elements sent to each destination in
+    /// a tick into a single message, which is unpacked transparently by the receiver. This
+    /// trades a little latency for much less per-message overhead in high-throughput flows.
+    ///
+    /// See [`BatchConfig`] for the limits on the size of batches and on how long elements
+    /// may be held back.
+    #[expect(
+        clippy::type_complexity,
+        reason = "Complex signatures for CanSend trait"
+    )]
+    pub fn send_bincode_batched<L2, CoreType>(
+        self,
+        other: &L2,
+        config: BatchConfig,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, O::Min, R>
+    where
+        L: NoAtomic,
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        L2: Location<'a>,
+        CoreType: Serialize + DeserializeOwned,
+        O: MinOrder<<L::Root as CanSend<'a, L2>>::OutStrongestOrder<O>>,
+    {
+        let root = get_this_crate();
+        let core_type = stageleft::quote_type::<CoreType>();
+        let location = self.location.clone();
+
+        // Elements are serialized up front and keyed by their destination (or `0` if there is
+        // only one), so that batches can be assembled regardless of the kind of channel.
+        let serialize_keyed: syn::Expr = if L::Root::is_demux() {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<(#root::ClusterId<_>, #core_type), (u32, ::std::vec::Vec<u8>)>(
+                    |(id, data)| (id.raw_id, #root::runtime_support::bincode::serialize(&data).unwrap())
+                )
+            }
+        } else {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#core_type, (u32, ::std::vec::Vec<u8>)>(
+                    |data| (0, #root::runtime_support::bincode::serialize(&data).unwrap())
+                )
+            }
+        };
+
+        // The order and retries of the keyed stream are never observed by the receiver, which
+        // sees the original guarantees of `self` (weakened by the network).
+        let serialized: Stream<(u32, Vec<u8>), L, B, TotalOrder, ExactlyOnce> = Stream::new(
+            location.clone(),
+            HydroNode::Map {
+                f: serialize_keyed.into(),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: location.new_node_metadata::<(u32, Vec<u8>)>(),
+            },
+        );
+
+        let tick = location.tick();
+        let batch = unsafe {
+            // SAFETY: batch boundaries only determine how elements are split into messages,
+            // which is not visible to the receiver
+            serialized.tick_batch(&tick)
+        };
+
+        let max_size = config.max_size;
+        let to_send = if let Some(max_delay) = config.max_delay {
+            let (complete_buffered, buffered) =
+                tick.cycle::<Stream<(u32, Vec<u8>), Tick<L>, Bounded, TotalOrder, ExactlyOnce>>();
+            let pending = buffered.chain(batch);
+
+            let max_delay_nanos = max_delay.as_nanos() as u64;
+            let timer = unsafe {
+                // SAFETY: the timer only determines when buffered elements are sent
+                location
+                    .source_interval(q!(std::time::Duration::from_nanos(max_delay_nanos)))
+                    .tick_batch(&tick)
+            };
+
+            let flush = pending
+                .clone()
+                .count()
+                .filter(q!(move |n| *n >= max_size))
+                .map(q!(|_| ()))
+                .union(timer.first().map(q!(|_| ())));
+
+            complete_buffered.complete_next_tick(pending.clone().continue_unless(flush.clone()));
+            pending.continue_if(flush)
+        } else {
+            batch
+        };
+
+        let frames = to_send
+            .fold_keyed(
+                q!(|| Vec::new()),
+                q!(move |frames: &mut Vec<(u64, Vec<u8>)>, data: Vec<u8>| {
+                    match frames.last_mut() {
+                        Some((count, frame)) if (*count as usize) < max_size => {
+                            *count += 1;
+                            frame.extend_from_slice(&data);
+                        }
+                        _ => {
+                            // The first 8 bytes are filled in with the number of elements.
+                            let mut frame = vec![0; 8];
+                            frame.extend_from_slice(&data);
+                            frames.push((1, frame));
+                        }
+                    }
+                }),
+            )
+            .flat_map_ordered(q!(|(key, frames)| {
+                frames.into_iter().map(move |(count, mut frame)| {
+                    // A frame has the same encoding as a bincode `Vec` of its elements.
+                    frame[..8].copy_from_slice(&count.to_le_bytes());
+                    (key, Bytes::from(frame))
+                })
+            }))
+            .all_ticks();
+
+        let network_input = if L::Root::is_demux() {
+            frames.ir_node.into_inner()
+        } else {
+            frames.map(q!(|(_, frame)| frame)).ir_node.into_inner()
+        };
+
+        let tagged_type = L::Root::tagged_type();
+        let deserialize_pipeline = deserialize_bincode_with_type(
+            tagged_type.as_ref(),
+            &stageleft::quote_type::<Vec<CoreType>>(),
+        );
+
+        let batch_type =
+            stageleft::quote_type::<<L::Root as CanSend<'a, L2>>::Out<Vec<CoreType>>>();
+        let unpack: syn::Expr = if tagged_type.is_some() {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#batch_type, _>(
+                    |(id, batch)| batch.into_iter().map(move |data| (id, data))
+                )
+            }
+        } else {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#batch_type, _>(
+                    |batch| batch
+                )
+            }
+        };
+
+        Stream::new(
+            other.clone(),
+            HydroNode::FlatMap {
+                f: unpack.into(),
+                input: Box::new(HydroNode::Network {
+                    from_key: None,
+                    to_location: other.id(),
+                    to_key: None,
+                    serialize_fn: None,
+                    instantiate_fn: DebugInstantiate::Building,
+                    deserialize_fn: Some(deserialize_pipeline.into()),
+                    input: Box::new(network_input),
+                    metadata: other
+                        .new_node_metadata::<<L::Root as CanSend<'a, L2>>::Out<Vec<CoreType>>>(),
+                }),
+                metadata: other.new_node_metadata::<<L::Root as CanSend<'a, L2>>::Out<CoreType>>(),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but also tracks the progress of event time, based on the
+    /// timestamp extracted from each element by `timestamp`. Each sender's watermark is the
+    /// largest timestamp it has sent, so the elements of each sender must be sent in order of
+    /// their timestamps.
+    ///
+    /// The receiver merges the watermarks of all senders into a low-watermark, which is
+    /// interleaved with the data as [`Watermarked::Watermark`] elements. A watermark `w`
+    /// guarantees that all elements with a timestamp below `w` have already been received.
+    /// Use [`Stream::release_by_watermark`] to process the elements once they are complete.
+    ///
+    /// When sending from a cluster, the watermark does not advance until every member has
+    /// sent at least one element, and is held back by members that stop sending.
+    #[expect(
+        clippy::type_complexity,
+        reason = "Complex signatures for CanSend trait"
+    )]
+    pub fn send_bincode_watermarked<L2, CoreType, F>(
+        self,
+        other: &L2,
+        timestamp: impl IntoQuotedMut<'a, F, L>,
+    ) -> Stream<Watermarked<<L::Root as CanSend<'a, L2>>::Out<CoreType>>, L2, Unbounded, NoOrder, R>
+    where
+        L: NoAtomic,
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        L2: Location<'a> + NoTick + NoAtomic,
+        CoreType: Serialize + DeserializeOwned + Clone,
+        <L::Root as CanSend<'a, L2>>::Out<CoreType>: Clone,
+        F: Fn(&CoreType) -> u64 + 'a,
+    {
+        let root = get_this_crate();
+        let core_type = stageleft::quote_type::<CoreType>();
+        let location = self.location.clone();
+        let f = timestamp.splice_fn1_borrow_ctx(&location);
+
+        // Elements are keyed by their destination (or `0` if there is only one), so that a
+        // watermark can be sent to every destination.
+        let with_timestamp: syn::Expr = parse_quote!({
+            let timestamp_fn = #f;
+            move |data: #core_type| (timestamp_fn(&data), data)
+        });
+        let keyed = self.key_by_destination::<CoreType, (u64, CoreType)>(
+            L::Root::is_demux(),
+            Some(with_timestamp),
+        );
+
+        let tick = location.tick();
+        let batch = unsafe {
+            // SAFETY: batch boundaries only determine how often watermarks are sent
+            keyed.tick_batch(&tick)
+        };
+
+        let (complete_watermark, prev_watermark) =
+            tick.cycle_with_initial(tick.singleton(q!(None::<u64>)));
+        let watermark = prev_watermark
+            .clone()
+            .into_stream()
+            .flatten_ordered()
+            .chain(batch.clone().map(q!(|(_, (t, _))| t)))
+            .max()
+            .into_singleton();
+        complete_watermark.complete_next_tick(watermark.clone());
+
+        let advanced = prev_watermark
+            .zip(watermark)
+            .filter_map(q!(|(prev, curr)| match curr {
+                Some(c) if prev.is_none_or(|p| c > p) => Some(c),
+                _ => None,
+            }));
+
+        let destinations = if let LocationId::Cluster(cluster_id) = other.id() {
+            let ids = ClusterIds::<()> {
+                id: cluster_id,
+                _phantom: PhantomData,
+            };
+            tick.singleton(q!(ids.iter().map(|id| id.raw_id).collect::<Vec<u32>>()))
+        } else {
+            tick.singleton(q!(vec![0u32]))
+        };
+
+        // Watermarks are sent after the data in the same tick, so they never overtake it.
+        let messages = batch
+            .map(q!(|(key, (_, data))| (
+                key,
+                watermark::Watermarked::Data(data)
+            )))
+            .chain(
+                destinations
+                    .into_stream()
+                    .flatten_ordered()
+                    .cross_singleton(advanced)
+                    .map(q!(|(key, w)| (key, watermark::Watermarked::Watermark(w)))),
+            )
+            .all_ticks();
+
+        let network_input = if L::Root::is_demux() {
+            messages
+                .map(q!(|(key, m)| (ClusterId::<()>::from_raw(key), m)))
+                .ir_node
+                .into_inner()
+        } else {
+            messages.map(q!(|(_, m)| m)).ir_node.into_inner()
+        };
+
+        let tagged_type = L::Root::tagged_type();
+        let message_type = stageleft::quote_type::<Watermarked<CoreType>>();
+        let received = HydroNode::Network {
+            from_key: None,
+            to_location: other.id(),
+            to_key: None,
+            serialize_fn: Some(
+                serialize_bincode_with_type(L::Root::is_demux(), &message_type).into(),
+            ),
+            instantiate_fn: DebugInstantiate::Building,
+            deserialize_fn: Some(
+                deserialize_bincode_with_type(tagged_type.as_ref(), &message_type).into(),
+            ),
+            input: Box::new(network_input),
+            metadata: other
+                .new_node_metadata::<<L::Root as CanSend<'a, L2>>::Out<Watermarked<CoreType>>>(),
+        };
+
+        // Messages are keyed by their sender (or `0` if there is only one), and the sender's ID
+        // is moved inside the data so that it is still tagged.
+        let received_type =
+            stageleft::quote_type::<<L::Root as CanSend<'a, L2>>::Out<Watermarked<CoreType>>>();
+        let key_by_sender: syn::Expr = if tagged_type.is_some() {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#received_type, _>(
+                    |(id, m)| (id.raw_id, match m {
+                        #root::watermark::Watermarked::Data(data) => #root::watermark::Watermarked::Data((id, data)),
+                        #root::watermark::Watermarked::Watermark(w) => #root::watermark::Watermarked::Watermark(w),
+                    })
+                )
+            }
+        } else {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#received_type, _>(
+                    |m| (0u32, m)
+                )
+            }
+        };
+
+        let by_sender: Stream<
+            (
+                u32,
+                Watermarked<<L::Root as CanSend<'a, L2>>::Out<CoreType>>,
+            ),
+            L2,
+            Unbounded,
+            NoOrder,
+            ExactlyOnce,
+        > = Stream::new(
+            other.clone(),
+            HydroNode::Map {
+                f: key_by_sender.into(),
+                input: Box::new(received),
+                metadata: other.new_node_metadata::<(
+                    u32,
+                    Watermarked<<L::Root as CanSend<'a, L2>>::Out<CoreType>>,
+                )>(),
+            },
+        );
+
+        let receiver_tick = other.tick();
+        let received_batch = unsafe {
+            // SAFETY: each sender's watermarks arrive after the data they cover, so batch
+            // boundaries only determine how often the merged watermark is forwarded
+            by_sender.tick_batch(&receiver_tick)
+        };
+
+        let new_marks = received_batch.clone().filter_map(q!(|(key, m)| match m {
+            watermark::Watermarked::Watermark(w) => Some((key, w)),
+            watermark::Watermarked::Data(_) => None,
+        }));
+
+        let (complete_marks, prev_marks) =
+            receiver_tick.cycle::<Stream<(u32, u64), Tick<L2>, Bounded, NoOrder, ExactlyOnce>>();
+        let marks = prev_marks
+            .chain(new_marks.clone())
+            .reduce_keyed_commutative_idempotent(q!(|acc, w| *acc = (*acc).max(w)));
+        complete_marks.complete_next_tick(marks.clone());
+
+        let num_senders = if let LocationId::Cluster(cluster_id) = location.root().id() {
+            let ids = ClusterIds::<()> {
+                id: cluster_id,
+                _phantom: PhantomData,
+            };
+            receiver_tick.singleton(q!(ids.len()))
+        } else {
+            receiver_tick.singleton(q!(1usize))
+        };
+
+        let all_reported =
+            marks
+                .clone()
+                .count()
+                .zip(num_senders)
+                .filter(q!(|(reported, senders)| reported == senders));
+        let low_watermark = marks
+            .map(q!(|(_, w)| w))
+            .min()
+            .continue_if(all_reported)
+            .continue_if(new_marks.count().filter(q!(|c| *c > 0)));
+
+        let merged = received_batch
+            .filter_map(q!(|(_, m)| match m {
+                watermark::Watermarked::Data(data) => Some(watermark::Watermarked::Data(data)),
+                watermark::Watermarked::Watermark(_) => None,
+            }))
+            .chain(
+                low_watermark
+                    .into_stream()
+                    .map(q!(|w| watermark::Watermarked::Watermark(w))),
+            )
+            .all_ticks();
+
+        Stream::new(other.clone(), merged.ir_node.into_inner())
+    }
+
+    /// Keys each element by the raw ID of its destination (or `0` if there is only one), for
+    /// the sends that keep state for each destination. If given, `value_fn` (a spliced
+    /// `Fn(CoreType) -> V`) is applied to the data.
+    ///
+    /// The result is always [`TotalOrder`] and [`ExactlyOnce`], so callers must make sure that
+    /// the receiver cannot observe the order or retries of `self` through it.
+    fn key_by_destination<CoreType, V>(
+        self,
+        is_demux: bool,
+        value_fn: Option<syn::Expr>,
+    ) -> Stream<(u32, V), L, B, TotalOrder, ExactlyOnce> {
+        let root = get_this_crate();
+        let core_type = stageleft::quote_type::<CoreType>();
+        let value_fn = value_fn.unwrap_or_else(|| parse_quote!(|data| data));
+
+        let keyed_fn: syn::Expr = if is_demux {
+            parse_quote!({
+                let value_fn = #value_fn;
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<(#root::ClusterId<_>, #core_type), _>(
+                    move |(id, data)| (id.raw_id, value_fn(data))
+                )
+            })
+        } else {
+            parse_quote!({
+                let value_fn = #value_fn;
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#core_type, _>(
+                    move |data| (0u32, value_fn(data))
+                )
+            })
+        };
+
+        Stream::new(
+            self.location.clone(),
+            HydroNode::Map {
+                f: keyed_fn.into(),
+                input: Box::new(self.ir_node.into_inner()),
+                metadata: self.location.new_node_metadata::<(u32, V)>(),
+            },
+        )
+    }
+
+    /// Like [`Stream::send_bincode`], but tolerates messages that are lost in the network,
+    /// for example when a connection drops and is re-established.
+    ///
+    /// Each element is tagged with a per-destination sequence number and kept by the sender
+    /// until the receiver acknowledges it. Unacknowledged elements are retransmitted every
+    /// `retransmit_interval`, so the network delivers them at least once, and the receiver
+    /// discards any retransmission it has already seen. The result therefore has the same
+    /// retry guarantees as `self` (for example [`ExactlyOnce`]) even if messages are lost,
+    /// but retransmissions may reorder elements, so it has [`NoOrder`].
+    ///
+    /// The receiver only remembers the sequence numbers above the lowest one it has not yet
+    /// received from each sender, so its state stays small as long as gaps are filled in.
+    ///
+    /// # Panics
+    /// Panics if `retransmit_interval` is zero.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// stream.send_bincode_reliable(&other, std::time::Duration::from_millis(100))
+    /// ```
+    #[expect(
+        clippy::type_complexity,
+        reason = "Complex signatures for CanSend trait"
+    )]
+    pub fn send_bincode_reliable<L2, CoreType>(
+        self,
+        other: &L2,
+        retransmit_interval: std::time::Duration,
+    ) -> Stream<<L::Root as CanSend<'a, L2>>::Out<CoreType>, L2, Unbounded, NoOrder, R>
+    where
+        L: NoAtomic,
+        L::Root: CanSend<'a, L2, In<CoreType> = T>,
+        L2: Location<'a> + NoTick + NoAtomic,
+        CoreType: Serialize + DeserializeOwned + Clone,
+        <L::Root as CanSend<'a, L2>>::Out<CoreType>: Clone,
+    {
+        assert!(
+            !retransmit_interval.is_zero(),
+            "The retransmit interval must be non-zero"
+        );
+
+        let root = get_this_crate();
+        let location = self.location.clone();
+
+        // The order and retries of the keyed stream are never observed by the receiver, since
+        // every element (including duplicates) gets its own sequence number.
+        let keyed = self.key_by_destination::<CoreType, CoreType>(L::Root::is_demux(), None);
+
+        let tick = location.tick();
+        let batch = unsafe {
+            // SAFETY: batch boundaries only determine the sequence numbers, which are not
+            // visible to the receiver
+            keyed.tick_batch(&tick)
+        };
+
+        let new_messages = batch
+            .sequence_per_destination()
+            .map(q!(|(key, (seq, data))| ((key, seq), data)));
+
+        let (complete_acks, acks) =
+            location.forward_ref::<Stream<(u32, u64), L, Unbounded, NoOrder, ExactlyOnce>>();
+        let acked = unsafe {
+            // SAFETY: an acknowledgement that is processed late only causes an extra
+            // retransmission, which the receiver discards
+            acks.tick_batch(&tick)
+        };
+
+        let (complete_unacked, prev_unacked) =
+            tick.cycle::<Stream<((u32, u64), CoreType), Tick<L>, Bounded, NoOrder, ExactlyOnce>>();
+        let still_unacked = prev_unacked.anti_join(acked);
+        complete_unacked.complete_next_tick(still_unacked.clone().chain(new_messages.clone()));
+
+        let retransmit_nanos = retransmit_interval.as_nanos() as u64;
+        let timer = unsafe {
+            // SAFETY: the timer only determines when unacknowledged elements are resent
+            location
+                .source_interval(q!(std::time::Duration::from_nanos(retransmit_nanos)))
+                .tick_batch(&tick)
+        };
+
+        let messages = new_messages
+            .chain(still_unacked.continue_if(timer.first()))
+            .all_ticks();
+
+        let network_input = if L::Root::is_demux() {
+            messages
+                .map(q!(|((key, seq), data)| (
+                    ClusterId::<()>::from_raw(key),
+                    (seq, data)
+                )))
+                .ir_node
+                .into_inner()
+        } else {
+            messages
+                .map(q!(|((_, seq), data)| (seq, data)))
+                .ir_node
+                .into_inner()
+        };
+
+        let tagged_type = L::Root::tagged_type();
+        let message_type = stageleft::quote_type::<(u64, CoreType)>();
+        let received = HydroNode::Network {
+            from_key: None,
+            to_location: other.id(),
+            to_key: None,
+            serialize_fn: Some(
+                serialize_bincode_with_type(L::Root::is_demux(), &message_type).into(),
+            ),
+            instantiate_fn: DebugInstantiate::Building,
+            deserialize_fn: Some(
+                deserialize_bincode_with_type(tagged_type.as_ref(), &message_type).into(),
+            ),
+            input: Box::new(network_input),
+            metadata: other
+                .new_node_metadata::<<L::Root as CanSend<'a, L2>>::Out<(u64, CoreType)>>(),
+        };
+
+        // Messages are keyed by their sender (or `0` if there is only one), and the sender's ID
+        // is kept alongside the data so that it is still tagged.
+        let received_type =
+            stageleft::quote_type::<<L::Root as CanSend<'a, L2>>::Out<(u64, CoreType)>>();
+        let key_by_sender: syn::Expr = if tagged_type.is_some() {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#received_type, _>(
+                    |(id, (seq, data))| (id.raw_id, (seq, (id, data)))
+                )
+            }
+        } else {
+            parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<#received_type, _>(
+                    |(seq, data)| (0u32, (seq, data))
+                )
+            }
+        };
+
+        let by_sender: Stream<
+            (u32, (u64, <L::Root as CanSend<'a, L2>>::Out<CoreType>)),
+            L2,
+            Unbounded,
+            NoOrder,
+            ExactlyOnce,
+        > = Stream::new(
+            other.clone(),
+            HydroNode::Map {
+                f: key_by_sender.into(),
+                input: Box::new(received),
+                metadata: other
+                    .new_node_metadata::<(u32, (u64, <L::Root as CanSend<'a, L2>>::Out<CoreType>))>(
+                    ),
+            },
+        );
+
+        let receiver_tick = other.tick();
+        let received_batch = unsafe {
+            // SAFETY: retransmissions are discarded regardless of the batch they arrive in
+            by_sender.tick_batch(&receiver_tick)
+        };
+
+        // Every message is acknowledged, including retransmissions, since the earlier
+        // acknowledgement may have been lost.
+        let ack_messages = received_batch
+            .clone()
+            .map(q!(|(sender, (seq, _))| (sender, seq)))
+            .all_ticks();
+        let ack_input = if tagged_type.is_some() {
+            ack_messages
+                .map(q!(|(sender, seq)| (ClusterId::<()>::from_raw(sender), seq)))
+                .ir_node
+                .into_inner()
+        } else {
+            ack_messages.map(q!(|(_, seq)| seq)).ir_node.into_inner()
+        };
+
+        let ack_type = stageleft::quote_type::<u64>();
+        let (ack_tagged, key_ack, ack_metadata) = if L::Root::is_demux() {
+            let tagged: syn::Type = parse_quote!(());
+            let key_ack: syn::Expr = parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<(#root::ClusterId<()>, u64), _>(
+                    |(id, seq)| (id.raw_id, seq)
+                )
+            };
+            (
+                Some(tagged),
+                key_ack,
+                location.new_node_metadata::<(ClusterId<()>, u64)>(),
+            )
+        } else {
+            let key_ack: syn::Expr = parse_quote! {
+                ::#root::runtime_support::stageleft::runtime_support::fn1_type_hint::<u64, _>(
+                    |seq| (0u32, seq)
+                )
+            };
+            (None, key_ack, location.new_node_metadata::<u64>())
+        };
+
+        complete_acks.complete(Stream::new(
+            location.clone(),
+            HydroNode::Map {
+                f: key_ack.into(),
+                input: Box::new(HydroNode::Network {
+                    from_key: None,
+                    to_location: location.id(),
+                    to_key: None,
+                    serialize_fn: Some(
+                        serialize_bincode_with_type(tagged_type.is_some(), &ack_type).into(),
+                    ),
+                    instantiate_fn: DebugInstantiate::Building,
+                    deserialize_fn: Some(
+                        deserialize_bincode_with_type(ack_tagged.as_ref(), &ack_type).into(),
+                    ),
+                    input: Box::new(ack_input),
+                    metadata: ack_metadata,
+                }),
+                metadata: location.new_node_metadata::<(u32, u64)>(),
+            },
+        ));
+
+        // For each sender, the receiver tracks the lowest sequence number it has not yet
+        // received, along with the sequence numbers above it that it has.
+        let (complete_seen, seen) = receiver_tick.cycle::<Stream<
+            (u32, (u64, std::collections::BTreeSet<u64>)),
+            Tick<L2>,
+            Bounded,
+            NoOrder,
+            ExactlyOnce,
+        >>();
+
+        let progress = received_batch
+            // the output has no order, so the order in which elements are pushed is irrelevant
+            .fold_keyed_commutative(q!(|| Vec::new()), q!(|acc, m| acc.push(m)))
+            .left_join(seen.clone())
+            .map(q!(|(sender, (items, seen))| {
+                let (mut low, mut above) = seen.unwrap_or_default();
+                let mut fresh = Vec::new();
+                for (seq, data) in items {
+                    if seq >= low && above.insert(seq) {
+                        fresh.push(data);
+                    }
+                }
+                while above.remove(&low) {
+                    low += 1;
+                }
+                (sender, (low, above, fresh))
+            }));
+
+        complete_seen.complete_next_tick(
+            seen.anti_join(progress.clone().map(q!(|(sender, _)| sender)))
+                .chain(
+                    progress
+                        .clone()
+                        .map(q!(|(sender, (low, above, _))| (sender, (low, above)))),
+                ),
+        );
+
+        let released = progress
+            .flat_map_unordered(q!(|(_, (_, _, fresh))| fresh))
+            .all_ticks();
+
+        unsafe {
+            // SAFETY: retransmissions are discarded, so each element of `self` is delivered
+            // as many times as it was sent
+            released.assume_retries::<R>()
+        }
     }
 
     pub fn send_bincode_external<L2, CoreType>(
         self,
         other: &ExternalProcess<L2>,
-    ) -> ExternalBincodeStream<L::Out<CoreType>>
+    ) -> ExternalStream<L::Out<CoreType>>
+    where
+        L: CanSend<'a, ExternalProcess<'a, L2>, In<CoreType> = T, Out<CoreType> = CoreType>,
+        L2: 'a,
+        CoreType: Serialize + DeserializeOwned,
+    {
+        self.send_external_with_codec::<L2, CoreType, _>(other, Bincode)
+    }
+
+    /// Like [`Stream::send_bincode_external`], but encodes the elements with the given
+    /// [`Codec`], which the external process must use to decode them.
+    pub fn send_external_with_codec<L2, CoreType, C>(
+        self,
+        other: &ExternalProcess<L2>,
+        _codec: C,
+    ) -> ExternalStream<L::Out<CoreType>, C>
     where
         L: CanSend<'a, ExternalProcess<'a, L2>, In<CoreType> = T, Out<CoreType> = CoreType>,
         L2: 'a,
         CoreType: Serialize + DeserializeOwned,
-        // for now, we restirct Out<CoreType> to be CoreType, which means no tagged cluster -> external
+        C: Codec,
+        // for now, we restrict Out<CoreType> to be CoreType, which means no tagged cluster -> external
     {
-        let serialize_pipeline = Some(serialize_bincode::<CoreType>(L::is_demux()));
+        let serialize_pipeline = Some(serialize_with_codec_type(
+            &stageleft::quote_type::<C>(),
+            L::is_demux(),
+            &stageleft::quote_type::<CoreType>(),
+        ));
 
         let metadata = other.new_node_metadata::<CoreType>();
 
@@ -2414,7 +4132,7 @@ where
             metadata,
         });
 
-        ExternalBincodeStream {
+        ExternalStream {
             process_id: other.id,
             port_id: external_key,
             _phantom: PhantomData,
@@ -2517,6 +4235,12 @@ where
         self.send_bytes::<L2>(other).map(q!(|(_, b)| b))
     }
 
+    /// Sends each element to every member of `other` that the cluster was deployed with,
+    /// tagged with the ID of the sender.
+    ///
+    /// The recipients are fixed, so the result is deterministic. Following membership changes
+    /// at runtime would make the recipients of each element depend on when the change is
+    /// observed, so that is only offered by [`Stream::broadcast_bincode_dynamic`].
     #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
     pub fn broadcast_bincode<C2>(
         self,
@@ -2611,11 +4335,52 @@ where
     }
 }
 
+impl<'a, V, L> Stream<(u32, V), Tick<L>, Bounded, TotalOrder, ExactlyOnce>
+where
+    V: Clone,
+    L: Location<'a> + NoTick,
+{
+    /// Tags the elements sent to each destination with consecutive sequence numbers,
+    /// continuing from the numbers used in earlier ticks.
+    #[expect(clippy::type_complexity, reason = "stream markers")]
+    fn sequence_per_destination(
+        self,
+    ) -> Stream<(u32, (u64, V)), Tick<L>, Bounded, NoOrder, ExactlyOnce> {
+        let (complete_next_seq, prev_next_seq) =
+            self.location
+                .cycle::<Stream<(u32, u64), Tick<L>, Bounded, NoOrder, ExactlyOnce>>();
+
+        let numbered = self
+            .fold_keyed(q!(|| Vec::new()), q!(|acc, data| acc.push(data)))
+            .left_join(prev_next_seq.clone())
+            .map(q!(|(key, (items, next))| (key, (next.unwrap_or(0), items))));
+
+        complete_next_seq.complete_next_tick(
+            prev_next_seq
+                .chain(numbered.clone().map(q!(|(key, (start, items))| (
+                    key,
+                    start + items.len() as u64
+                ))))
+                .reduce_keyed_commutative_idempotent(q!(|acc, next| *acc = (*acc).max(next))),
+        );
+
+        numbered.flat_map_unordered(q!(|(key, (start, items))| items
+            .into_iter()
+            .enumerate()
+            .map(move |(i, data)| (key, (start + i as u64, data)))))
+    }
+}
+
 #[expect(clippy::type_complexity, reason = "ordering semantics for round-robin")]
 impl<'a, T, L, B> Stream<T, L, B, TotalOrder, ExactlyOnce>
 where
     L: Location<'a> + NoTick,
 {
+    /// Distributes the elements across the members of `other` that the cluster was deployed
+    /// with, in turn.
+    ///
+    /// Like [`Stream::broadcast_bincode`], the recipients are fixed so that the result is
+    /// deterministic; see [`Stream::round_robin_bincode_dynamic`] to follow membership changes.
     pub fn round_robin_bincode<C2>(
         self,
         other: &Cluster<'a, C2>,
@@ -2714,18 +4479,269 @@ where
     }
 }
 
+impl<'a, T, L, O, R> Stream<T, L, Unbounded, O, R>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    /// Like [`Stream::broadcast_bincode`], but sends each element to the current members of
+    /// `other` as given by `membership` (usually from [`Location::cluster_membership`]) rather
+    /// than to the members the cluster was deployed with.
+    ///
+    /// # Safety
+    /// Each element is sent to the members in a snapshot of `membership` taken when the element
+    /// is batched, so members that join or leave concurrently may or may not receive it.
+    #[expect(clippy::type_complexity, reason = "ordering semantics for broadcast")]
+    pub unsafe fn broadcast_bincode_dynamic<C2>(
+        self,
+        other: &Cluster<'a, C2>,
+        membership: Singleton<HashSet<ClusterId<C2>>, L, Unbounded>,
+    ) -> Stream<
+        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
+        Cluster<'a, C2>,
+        Unbounded,
+        O::Min,
+        R,
+    >
+    where
+        C2: 'a,
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
+        T: Clone + Serialize + DeserializeOwned,
+        O: MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<O>>,
+    {
+        let tick = self.location.tick();
+
+        // SAFETY: pairing each batch with the latest membership is the documented
+        // non-determinism of this method
+        unsafe { self.tick_batch(&tick) }
+            .cross_singleton(unsafe { membership.latest_tick(&tick) })
+            .flat_map_ordered(q!(|(v, members)| members
+                .into_iter()
+                .map(move |id| (id, v.clone()))))
+            .all_ticks()
+            .send_bincode(other)
+    }
+}
+
+#[expect(clippy::type_complexity, reason = "ordering semantics for round-robin")]
+impl<'a, T, L> Stream<T, L, Unbounded, TotalOrder, ExactlyOnce>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    /// Like [`Stream::round_robin_bincode`], but distributes the elements across the current
+    /// members of `other` as given by `membership` (usually from
+    /// [`Location::cluster_membership`]). While the cluster has no members, elements are
+    /// buffered until one joins.
+    ///
+    /// # Safety
+    /// The member that receives each element depends on the snapshot of `membership` taken
+    /// when the element is batched, so which member receives it is non-deterministic when
+    /// members join or leave concurrently.
+    pub unsafe fn round_robin_bincode_dynamic<C2>(
+        self,
+        other: &Cluster<'a, C2>,
+        membership: Singleton<HashSet<ClusterId<C2>>, L, Unbounded>,
+    ) -> Stream<
+        <L::Root as CanSend<'a, Cluster<'a, C2>>>::Out<T>,
+        Cluster<'a, C2>,
+        Unbounded,
+        <TotalOrder as MinOrder<
+            <L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>,
+        >>::Min,
+        ExactlyOnce,
+    >
+    where
+        C2: 'a,
+        L::Root: CanSend<'a, Cluster<'a, C2>, In<T> = (ClusterId<C2>, T)>,
+        T: Clone + Serialize + DeserializeOwned,
+        TotalOrder:
+            MinOrder<<L::Root as CanSend<'a, Cluster<'a, C2>>>::OutStrongestOrder<TotalOrder>>,
+    {
+        let tick = self.location.tick();
+
+        // SAFETY: pairing each batch with the latest membership is the documented
+        // non-determinism of this method
+        let batch = unsafe { self.enumerate().tick_batch(&tick) };
+        let members = unsafe { membership.latest_tick(&tick) }
+            .map(q!(|members| {
+                let mut members = members.into_iter().collect::<Vec<_>>();
+                members.sort_by_key(|id| id.raw_id);
+                members
+            }))
+            .filter(q!(|members| !members.is_empty()));
+
+        let (complete_pending, pending) =
+            tick.cycle::<Stream<(usize, T), Tick<L>, Bounded, TotalOrder, ExactlyOnce>>();
+        let all = pending.chain::<TotalOrder>(batch);
+        complete_pending.complete_next_tick(all.clone().continue_unless(members.clone()));
+
+        all.cross_singleton(members)
+            .map(q!(|((i, v), members)| (members[i % members.len()], v)))
+            .all_ticks()
+            .send_bincode(other)
+    }
+}
+
+impl<'a, T, C, B, R> Stream<T, Cluster<'a, C>, B, TotalOrder, R>
+where
+    C: 'a,
+{
+    /// Like [`Stream::send_bincode`], but preserves the order in which each member of the
+    /// cluster sent its elements, as if every sender had a FIFO channel to each destination.
+    /// The result is a [`KeyedStream`] keyed by the sender, with a [`TotalOrder`] over the
+    /// elements of each sender (but no order between different senders).
+    ///
+    /// Each element is tagged with a per-destination sequence number, and the receiver holds
+    /// back elements until all earlier elements from the same sender have arrived.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let ordered = cluster
+    ///     .source_iter(q!(0..10))
+    ///     .send_bincode_ordered(&process);
+    /// // each member's elements arrive in the order 0, 1, ..., 9
+    /// ordered.fold(q!(|| Vec::new()), q!(|acc, x| acc.push(x)))
+    /// ```
+    pub fn send_bincode_ordered<L2, CoreType>(
+        self,
+        other: &L2,
+    ) -> KeyedStream<ClusterId<C>, CoreType, L2, Unbounded, TotalOrder, R>
+    where
+        Cluster<'a, C>: CanSend<'a, L2, In<CoreType> = T, Out<CoreType> = (ClusterId<C>, CoreType)>,
+        L2: Location<'a> + NoTick + NoAtomic,
+        CoreType: Serialize + DeserializeOwned + Clone,
+    {
+        let location = self.location.clone();
+        let is_demux = <Cluster<'a, C> as CanSend<'a, L2>>::is_demux();
+
+        // Duplicated elements are simply numbered separately, so the retries of the keyed
+        // stream are never observed by the receiver.
+        let keyed = self.key_by_destination::<CoreType, CoreType>(is_demux, None);
+
+        let tick = location.tick();
+        let batch = unsafe {
+            // SAFETY: batch boundaries do not affect the sequence numbers
+            keyed.tick_batch(&tick)
+        };
+
+        let messages = batch.sequence_per_destination().all_ticks();
+
+        let network_input = if is_demux {
+            messages
+                .map(q!(|(key, m)| (ClusterId::<()>::from_raw(key), m)))
+                .ir_node
+                .into_inner()
+        } else {
+            messages.map(q!(|(_, m)| m)).ir_node.into_inner()
+        };
+
+        // Each received element is tagged with its sender and sequence number.
+        type Sequenced<C, T> = (ClusterId<C>, (u64, T));
+
+        let message_type = stageleft::quote_type::<(u64, CoreType)>();
+        let received: Stream<Sequenced<C, CoreType>, L2, Unbounded, NoOrder, ExactlyOnce> =
+            Stream::new(
+                other.clone(),
+                HydroNode::Network {
+                    from_key: None,
+                    to_location: other.id(),
+                    to_key: None,
+                    serialize_fn: Some(serialize_bincode_with_type(is_demux, &message_type).into()),
+                    instantiate_fn: DebugInstantiate::Building,
+                    deserialize_fn: Some(
+                        deserialize_bincode_with_type(
+                            <Cluster<'a, C> as CanSend<'a, L2>>::tagged_type().as_ref(),
+                            &message_type,
+                        )
+                        .into(),
+                    ),
+                    input: Box::new(network_input),
+                    metadata: other.new_node_metadata::<Sequenced<C, CoreType>>(),
+                },
+            );
+
+        let receiver_tick = other.tick();
+        let received_batch = unsafe {
+            // SAFETY: elements are held back until all earlier elements from the same sender
+            // have arrived, so batch boundaries only determine when they are released
+            received.tick_batch(&receiver_tick)
+        };
+
+        let (complete_held, held) =
+            receiver_tick
+                .cycle::<Stream<Sequenced<C, CoreType>, Tick<L2>, Bounded, NoOrder, ExactlyOnce>>();
+        let (complete_expected, expected) =
+            receiver_tick
+                .cycle::<Stream<(ClusterId<C>, u64), Tick<L2>, Bounded, NoOrder, ExactlyOnce>>();
+
+        let progress = held
+            .chain(received_batch)
+            // the pending elements are sorted below, so the order they are pushed in is irrelevant
+            .fold_keyed_commutative(q!(|| Vec::new()), q!(|acc, m| acc.push(m)))
+            .left_join(expected.clone())
+            .map(q!(|(sender, (mut pending, expected))| {
+                pending.sort_unstable_by_key(|(seq, _)| *seq);
+                let mut next = expected.unwrap_or(0);
+                let mut ready = Vec::new();
+                let mut still_held = Vec::new();
+                for (seq, data) in pending {
+                    if seq == next {
+                        ready.push(data);
+                        next += 1;
+                    } else if seq > next {
+                        still_held.push((seq, data));
+                    }
+                }
+                (sender, (next, ready, still_held))
+            }));
+
+        complete_held.complete_next_tick(progress.clone().flat_map_unordered(q!(|(
+            sender,
+            (_, _, still_held),
+        )| {
+            still_held.into_iter().map(move |m| (sender, m))
+        })));
+        complete_expected.complete_next_tick(
+            expected
+                .chain(
+                    progress
+                        .clone()
+                        .map(q!(|(sender, (next, _, _))| (sender, next))),
+                )
+                .reduce_keyed_commutative_idempotent(q!(|acc, next| *acc = (*acc).max(next))),
+        );
+
+        let released = progress
+            .flat_map_unordered(q!(|(sender, (_, ready, _))| ready
+                .into_iter()
+                .map(move |data| (sender, data))))
+            .all_ticks();
+
+        KeyedStream::new(unsafe {
+            // SAFETY: duplicates of an element are numbered separately, so each copy is
+            // delivered just as it would be by `send_bincode`
+            released.assume_retries::<R>()
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use futures::StreamExt;
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
     use hydro_deploy::Deployment;
     use serde::{Deserialize, Serialize};
     use stageleft::q;
 
+    use super::BatchConfig;
     use crate::FlowBuilder;
+    use crate::codec::{Bincode, Json};
     use crate::location::Location;
+    use crate::sim_runtime::SimEvent;
 
     struct P1 {}
     struct P2 {}
+    struct C1 {}
 
     #[derive(Serialize, Deserialize, Debug)]
     struct SendOverNetwork {
@@ -2763,4 +4779,458 @@ mod tests {
             assert_eq!(external_out.next().await.unwrap().n, i);
         }
     }
+
+    #[tokio::test]
+    async fn first_ten_distributed_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let numbers = first_node.source_iter(q!(0..10));
+        let out_port = numbers
+            .map(q!(|n| SendOverNetwork { n }))
+            .send_bincode(&second_node)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&first_node, ())
+            .with_process(&second_node, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let external_out = nodes.connect_source_bincode(out_port).await;
+        assert_eq!(
+            external_out.map(|m| m.n).collect::<Vec<_>>().await,
+            (0..10).collect::<Vec<_>>()
+        );
+        assert!(simulation.is_quiescent());
+    }
+
+    #[tokio::test]
+    async fn cluster_round_trip_sim_is_deterministic() {
+        let mut simulation = crate::deploy::Simulation::new().with_seed(42);
+
+        let flow = FlowBuilder::new();
+        let leader = flow.process::<P1>();
+        let workers = flow.cluster::<C1>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = leader
+            .source_iter(q!(0..5))
+            .broadcast_bincode(&workers)
+            .map(q!(|n| n * 10))
+            .send_bincode(&leader)
+            .map(q!(|(id, n)| (id.raw_id, n)))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&leader, ())
+            .with_cluster(&workers, 3)
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let mut received = nodes
+            .connect_source_bincode(out_port)
+            .await
+            .collect::<Vec<(u32, i32)>>()
+            .await;
+        received.sort();
+
+        let mut expected = (0..3)
+            .flat_map(|id| (0..5).map(move |n| (id, n * 10)))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(received, expected);
+
+        assert_eq!(simulation.trace(), simulation.run_with_seed(42).trace);
+    }
+
+    #[tokio::test]
+    async fn send_bincode_batched_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let leader = flow.process::<P1>();
+        let workers = flow.cluster::<C1>();
+        let external = flow.external_process::<P2>();
+
+        let ids = workers.members();
+        let out_port = leader
+            .source_iter(q!(0..9))
+            .map(q!(|n| (ids[n % ids.len()], n)))
+            .send_bincode_batched(&workers, BatchConfig::default().with_max_size(2))
+            .map(q!(|n| n * 10))
+            .send_bincode_batched(&leader, BatchConfig::default())
+            .map(q!(|(id, n)| (id.raw_id, n)))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&leader, ())
+            .with_cluster(&workers, 3)
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let mut received = nodes
+            .connect_source_bincode(out_port)
+            .await
+            .collect::<Vec<(u32, usize)>>()
+            .await;
+        received.sort();
+
+        let mut expected = (0..9).map(|n| ((n % 3) as u32, n * 10)).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(received, expected);
+
+        // Each worker is sent its three elements in two messages, instead of three.
+        let from_leader = simulation
+            .trace()
+            .into_iter()
+            .filter(|event| {
+                matches!(event, SimEvent::Deliver(channel) if channel.from_member.is_none())
+            })
+            .count();
+        assert_eq!(from_leader, 6);
+    }
+
+    #[tokio::test]
+    async fn send_with_codec_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let leader = flow.process::<P1>();
+        let workers = flow.cluster::<C1>();
+        let external = flow.external_process::<P2>();
+
+        let (in_port, input) =
+            external.source_external_with_codec::<_, (u32, String), _>(&leader, Json);
+
+        let ids = workers.members();
+        let out_port = input
+            .map(q!(|(n, s)| (ids[n as usize % ids.len()], (n, s))))
+            .send_with_codec(&workers, Json)
+            .map(q!(|(n, s)| format!("{}{}", s, n)))
+            .send_with_codec(&leader, Bincode)
+            .map(q!(|(id, s)| (id.raw_id, s)))
+            .send_external_with_codec(&external, Json);
+
+        let nodes = flow
+            .with_process(&leader, ())
+            .with_cluster(&workers, 2)
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let mut in_sink = nodes.connect_sink_codec(in_port).await;
+        for n in 0..4 {
+            in_sink.send((n, "v".to_string())).await.unwrap();
+        }
+        drop(in_sink);
+
+        let mut received = nodes
+            .connect_source_codec(out_port)
+            .await
+            .collect::<Vec<(u32, String)>>()
+            .await;
+        received.sort();
+
+        assert_eq!(
+            received,
+            vec![
+                (0, "v0".to_string()),
+                (0, "v2".to_string()),
+                (1, "v1".to_string()),
+                (1, "v3".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_bincode_batched_max_delay_sim() {
+        let mut simulation = crate::deploy::Simulation::new().with_max_steps(100);
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = first_node
+            .source_iter(q!(0..5))
+            .send_bincode_batched(
+                &second_node,
+                BatchConfig::default().with_max_delay(Duration::from_millis(100)),
+            )
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&first_node, ())
+            .with_process(&second_node, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let received = nodes
+            .connect_source_bincode(out_port)
+            .await
+            .collect::<Vec<i32>>()
+            .await;
+        assert_eq!(received, (0..5).collect::<Vec<_>>());
+
+        // The elements are held back until the timer fires, and then sent together.
+        let trace = simulation.trace();
+        let deliveries = trace
+            .iter()
+            .filter(|event| matches!(event, SimEvent::Deliver(_)))
+            .count();
+        assert_eq!(deliveries, 1);
+        assert!(trace.contains(&SimEvent::AdvanceTime));
+    }
+
+    #[tokio::test]
+    async fn send_bincode_batched_sub_millisecond_delay_sim() {
+        let mut simulation = crate::deploy::Simulation::new().with_max_steps(100);
+
+        let flow = FlowBuilder::new();
+        let first_node = flow.process::<P1>();
+        let second_node = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = first_node
+            .source_iter(q!(0..5))
+            .send_bincode_batched(
+                &second_node,
+                BatchConfig::default().with_max_delay(Duration::from_micros(500)),
+            )
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&first_node, ())
+            .with_process(&second_node, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let received = nodes
+            .connect_source_bincode(out_port)
+            .await
+            .collect::<Vec<i32>>()
+            .await;
+        assert_eq!(received, (0..5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "must be non-zero")]
+    fn batch_config_rejects_zero_max_delay() {
+        let _ = BatchConfig::default().with_max_delay(Duration::ZERO);
+    }
+
+    #[test]
+    fn sim_explore_finds_minimal_batching_counterexample() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let sender1 = flow.process::<P1>();
+        let sender2 = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let tick = receiver.tick();
+        let out_port = unsafe {
+            sender1
+                .source_iter(q!([1]))
+                .send_bincode(&receiver)
+                .union(sender2.source_iter(q!([2])).send_bincode(&receiver))
+                .tick_batch(&tick)
+        }
+        .count()
+        .filter(q!(|count| *count > 0))
+        .all_ticks()
+        .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&sender1, ())
+            .with_process(&sender2, ())
+            .with_process(&receiver, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        // `union` batches both of its inputs into a tick, on top of the explicit `tick_batch`
+        assert_eq!(
+            simulation
+                .choice_points()
+                .iter()
+                .map(|p| p.kind)
+                .collect::<Vec<_>>(),
+            vec![
+                crate::nondet::ChoicePointKind::NetworkDelivery,
+                crate::nondet::ChoicePointKind::Batch,
+                crate::nondet::ChoicePointKind::NetworkDelivery,
+                crate::nondet::ChoicePointKind::Batch,
+                crate::nondet::ChoicePointKind::Batch,
+            ]
+        );
+
+        let port = nodes.raw_port_bincode(&out_port);
+        let both_in_one_batch = |output: &crate::sim_runtime::SimOutput| {
+            let batch_sizes = output.bincode::<usize>(&port);
+            if batch_sizes == vec![2] {
+                Ok(())
+            } else {
+                Err(format!("batch sizes were {:?}", batch_sizes))
+            }
+        };
+
+        let counterexample = simulation
+            .explore(0..20, both_in_one_batch)
+            .expect_err("some schedule should split the batch");
+
+        // Running either sender ahead of the canonical schedule lets the receiver tick
+        // after only one of the messages has been delivered.
+        assert_eq!(counterexample.schedule.len(), 1);
+        assert!(both_in_one_batch(&simulation.run_schedule(&counterexample.schedule)).is_err());
+    }
+
+    #[test]
+    fn sim_explore_checks_deterministic_flow_once() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let node = flow.process::<P1>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = node
+            .source_iter(q!(0..3))
+            .map(q!(|x| x * 2))
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&node, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+        assert!(simulation.choice_points().is_empty());
+
+        let port = nodes.raw_port_bincode(&out_port);
+        let checks = std::cell::Cell::new(0);
+        simulation
+            .explore(0..20, |output| {
+                checks.set(checks.get() + 1);
+                assert_eq!(output.bincode::<i32>(&port), vec![0, 2, 4]);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(checks.get(), 1);
+    }
+
+    #[test]
+    fn sim_timers_use_virtual_clock() {
+        let mut simulation = crate::deploy::Simulation::new().with_max_steps(200);
+
+        let flow = FlowBuilder::new();
+        let sender = flow.process::<P1>();
+        let receiver = flow.process::<P2>();
+        let external = flow.external_process::<P2>();
+
+        let out_port = unsafe {
+            sender
+                .source_iter(q!([1]))
+                .send_bincode(&receiver)
+                .timeout(q!(Duration::from_secs(60)))
+                .sample_every(q!(Duration::from_secs(3600)))
+        }
+        .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&sender, ())
+            .with_process(&receiver, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        // Hours of simulated time pass without sleeping, sampling the timeout on every tick
+        // of the interval until the step limit is reached.
+        let output = simulation.run();
+        assert!(!output.quiescent);
+        assert!(
+            output
+                .trace
+                .iter()
+                .filter(|e| **e == SimEvent::AdvanceTime)
+                .count()
+                > 1
+        );
+        assert!(
+            output
+                .bincode::<()>(&nodes.raw_port_bincode(&out_port))
+                .len()
+                > 1
+        );
+    }
+
+    #[test]
+    fn send_bincode_ordered_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        // Every schedule must deliver the elements of each worker in the order they were sent.
+        crate::test_util::sim_explore_test(
+            &mut simulation,
+            0..20,
+            |flow| {
+                let leader = flow.process::<P1>();
+                let workers = flow.cluster::<C1>();
+
+                workers
+                    .source_iter(q!(0..10u32))
+                    .send_bincode_ordered(&leader)
+                    .entries()
+                    .map(q!(|(id, n)| (id.raw_id, n)))
+            },
+            |received: Vec<(u32, u32)>| {
+                for id in 0..3 {
+                    let from_worker = received
+                        .iter()
+                        .filter(|(sender, _)| *sender == id)
+                        .map(|(_, n)| *n)
+                        .collect::<Vec<_>>();
+                    if from_worker != (0..10).collect::<Vec<_>>() {
+                        return Err(format!("worker {} delivered {:?}", id, from_worker));
+                    }
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn send_bincode_reliable_sim() {
+        let mut simulation = crate::deploy::Simulation::new()
+            .with_message_loss(5)
+            .with_max_steps(5000);
+
+        // Lost messages and acknowledgements are retransmitted, and duplicates are discarded,
+        // so every element arrives exactly once.
+        let expected = (0..3)
+            .flat_map(|id| (0..5).map(move |n| (id, n)))
+            .collect::<Vec<_>>();
+        crate::test_util::sim_explore_test(
+            &mut simulation,
+            0..20,
+            |flow| {
+                let leader = flow.process::<P1>();
+                let workers = flow.cluster::<C1>();
+
+                workers
+                    .source_iter(q!(0..5u32))
+                    .send_bincode_reliable(&leader, Duration::from_millis(100))
+                    .map(q!(|(id, n)| (id.raw_id, n)))
+            },
+            |mut received: Vec<(u32, u32)>| {
+                received.sort();
+                if received == expected {
+                    Ok(())
+                } else {
+                    Err(format!("received {:?}", received))
+                }
+            },
+        )
+        .unwrap();
+    }
 }
@@ -5,10 +5,15 @@ use std::pin::Pin;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 
-use crate::{FlowBuilder, Process, Stream, Unbounded};
+#[cfg(stageleft_runtime)]
+pub use crate::deploy::sim::{
+    sim_collect_test, sim_deploy_test, sim_explore_test, sim_multi_location_test,
+    sim_stream_transform_test,
+};
+use crate::{FlowBuilder, Process, Stream};
 
-pub async fn multi_location_test<'a, T, C, O, R>(
-    thunk: impl FnOnce(&FlowBuilder<'a>, &Process<'a, ()>) -> Stream<T, Process<'a>, Unbounded, O, R>,
+pub async fn multi_location_test<'a, T, C, B, O, R>(
+    thunk: impl FnOnce(&FlowBuilder<'a>, &Process<'a, ()>) -> Stream<T, Process<'a>, B, O, R>,
     check: impl FnOnce(Pin<Box<dyn futures::Stream<Item = T>>>) -> C,
 ) where
     T: Serialize + DeserializeOwned + 'static,
@@ -34,8 +39,8 @@ pub async fn multi_location_test<'a, T, C, O, R>(
     check(external_out).await;
 }
 
-pub async fn stream_transform_test<'a, T, C, O, R>(
-    thunk: impl FnOnce(&Process<'a>) -> Stream<T, Process<'a>, Unbounded, O, R>,
+pub async fn stream_transform_test<'a, T, C, B, O, R>(
+    thunk: impl FnOnce(&Process<'a>) -> Stream<T, Process<'a>, B, O, R>,
     check: impl FnOnce(Pin<Box<dyn futures::Stream<Item = T>>>) -> C,
 ) where
     T: Serialize + DeserializeOwned + 'static,
@@ -0,0 +1,98 @@
+//! Watermarks, which track the progress of event time on streams that are not totally
+//! ordered, such as the messages sent by the members of a cluster.
+//!
+//! [`Stream::send_bincode_watermarked`] sends a stream along with the progress of each
+//! sender, and merges those into a single low-watermark at the receiver.
+//! [`Stream::release_by_watermark`] then holds back records until the watermark shows that
+//! everything they depend on has arrived.
+//!
+//! [`Stream::send_bincode_watermarked`]: crate::Stream::send_bincode_watermarked
+//! [`Stream::release_by_watermark`]: crate::Stream::release_by_watermark
+
+use serde::{Deserialize, Serialize};
+
+/// An element of a stream that carries watermarks alongside its data.
+///
+/// A `Watermark(w)` promises that no more records with an event timestamp below `w` will
+/// follow it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Watermarked<T> {
+    Data(T),
+    Watermark(u64),
+}
+
+/// What to do with records that arrive after the watermark has already passed them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LateRecords {
+    /// Discard late records.
+    #[default]
+    Drop,
+    /// Release late records immediately, in the tick they arrive.
+    Release,
+}
+
+#[cfg(test)]
+mod tests {
+    use stageleft::q;
+
+    use super::LateRecords;
+    use crate::location::Location;
+
+    struct P1 {}
+    struct C1 {}
+
+    #[tokio::test]
+    async fn release_by_watermark_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        // The watermark stops at 9, so the second window is never complete.
+        let received = crate::test_util::sim_collect_test(&mut simulation, |flow| {
+            let leader = flow.process::<P1>();
+            let workers = flow.cluster::<C1>();
+
+            let tick = leader.tick();
+            unsafe {
+                workers
+                    .source_iter(q!(0..10u64))
+                    .send_bincode_watermarked(&leader, q!(|t| *t))
+                    .release_by_watermark(&tick, q!(|(_, t)| (t / 5 + 1) * 5), LateRecords::Drop)
+            }
+            .map(q!(|(_, t)| (t / 5 * 5, t)))
+            .fold_keyed_commutative(q!(|| 0), q!(|acc, t| *acc += t))
+            .all_ticks()
+        })
+        .await;
+        assert_eq!(received, vec![(0, 30)]);
+    }
+
+    #[tokio::test]
+    async fn send_bincode_watermarked_to_cluster_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        // Every worker is sent the watermark, and releases everything below it.
+        let mut received = crate::test_util::sim_collect_test(&mut simulation, |flow| {
+            let leader = flow.process::<P1>();
+            let workers = flow.cluster::<C1>();
+
+            let ids = workers.members();
+            let worker_tick = workers.tick();
+            unsafe {
+                leader
+                    .source_iter(q!(0..10u64))
+                    .flat_map_ordered(q!(|t| ids.iter().map(move |id| (*id, t))))
+                    .send_bincode_watermarked(&workers, q!(|t| *t))
+                    .release_by_watermark(&worker_tick, q!(|t| t + 1), LateRecords::Drop)
+            }
+            .all_ticks()
+            .send_bincode(&leader)
+            .map(q!(|(id, t)| (id.raw_id, t)))
+        })
+        .await;
+        received.sort();
+
+        let expected = (0..3)
+            .flat_map(|id| (0..9).map(move |t| (id, t)))
+            .collect::<Vec<_>>();
+        assert_eq!(received, expected);
+    }
+}
@@ -0,0 +1,155 @@
+//! Windows over the event time of stream elements, used by [`Stream::tumbling_window`],
+//! [`Stream::sliding_window`], and [`Stream::session_window`].
+//!
+//! Event timestamps are plain `u64`s in whatever unit the application chooses (for example
+//! milliseconds since the UNIX epoch), and window sizes are measured in the same unit.
+//!
+//! [`Stream::tumbling_window`]: crate::Stream::tumbling_window
+//! [`Stream::sliding_window`]: crate::Stream::sliding_window
+//! [`Stream::session_window`]: crate::Stream::session_window
+
+use serde::{Deserialize, Serialize};
+
+/// A half-open interval `[start, end)` of event time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Window {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Window {
+    /// Whether the given timestamp falls inside this window.
+    pub fn contains(&self, timestamp: u64) -> bool {
+        self.start <= timestamp && timestamp < self.end
+    }
+
+    /// Returns the windows of length `size`, starting every `slide` units of time from 0,
+    /// that contain the given timestamp, in increasing order.
+    ///
+    /// # Panics
+    /// If `slide` is 0.
+    pub fn sliding(timestamp: u64, size: u64, slide: u64) -> Vec<Window> {
+        assert!(slide > 0, "Windows must have a positive slide");
+
+        let mut windows = Vec::new();
+        let mut start = Some(timestamp - timestamp % slide);
+        while let Some(s) = start {
+            if s.saturating_add(size) <= timestamp {
+                break;
+            }
+
+            windows.push(Window {
+                start: s,
+                end: s.saturating_add(size),
+            });
+            start = s.checked_sub(slide);
+        }
+
+        windows.reverse();
+        windows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use stageleft::q;
+
+    use super::Window;
+    use crate::location::Location;
+
+    struct P1 {}
+    struct P2 {}
+
+    #[test]
+    fn sliding_windows_contain_timestamp() {
+        assert_eq!(
+            Window::sliding(7, 10, 5),
+            vec![Window { start: 0, end: 10 }, Window { start: 5, end: 15 }]
+        );
+        assert_eq!(
+            Window::sliding(3, 10, 5),
+            vec![Window { start: 0, end: 10 }]
+        );
+        assert_eq!(
+            Window::sliding(12, 4, 4),
+            vec![Window { start: 12, end: 16 }]
+        );
+
+        // Gaps between the windows do not contain any timestamps.
+        assert_eq!(Window::sliding(8, 2, 5), vec![]);
+
+        for timestamp in 0..50 {
+            for window in Window::sliding(timestamp, 10, 3) {
+                assert!(window.contains(timestamp));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Windows must have a positive slide")]
+    fn sliding_windows_reject_zero_slide() {
+        Window::sliding(7, 10, 0);
+    }
+
+    #[tokio::test]
+    async fn event_time_windows_sim() {
+        let mut simulation = crate::deploy::Simulation::new();
+
+        let (nodes, (sliding_port, session_port)) =
+            crate::test_util::sim_deploy_test(&mut simulation, |flow, external| {
+                let sender = flow.process::<P1>();
+                let receiver = flow.process::<P2>();
+
+                let tick = receiver.tick();
+                let readings = sender
+                    .source_iter(q!(vec![1u64, 2, 6, 7, 8, 15, 30]))
+                    .send_bincode(&receiver);
+
+                let sliding_port =
+                    unsafe { readings.clone().sliding_window(&tick, 10, 5, q!(|t| *t)) }
+                        .fold_keyed(q!(|| 0), q!(|acc, t| *acc += t))
+                        .all_ticks()
+                        .send_bincode_external(external);
+
+                let session_port = unsafe { readings.session_window(&tick, 3, q!(|t| *t)) }
+                    .fold_keyed(q!(|| 0), q!(|acc, t| *acc += t))
+                    .all_ticks()
+                    .send_bincode_external(external);
+
+                (sliding_port, session_port)
+            });
+
+        let mut sliding = nodes
+            .connect_source_bincode(sliding_port)
+            .await
+            .collect::<Vec<(Window, u64)>>()
+            .await;
+        sliding.sort();
+        assert_eq!(
+            sliding,
+            vec![
+                (Window { start: 0, end: 10 }, 24),
+                (Window { start: 5, end: 15 }, 21),
+                (Window { start: 10, end: 20 }, 15),
+                (Window { start: 15, end: 25 }, 15),
+            ]
+        );
+
+        // The last session is still open, since nothing has arrived after it.
+        let mut sessions = nodes
+            .connect_source_bincode(session_port)
+            .await
+            .collect::<Vec<(Window, u64)>>()
+            .await;
+        sessions.sort();
+        assert_eq!(
+            sessions,
+            vec![
+                (Window { start: 1, end: 5 }, 3),
+                (Window { start: 6, end: 11 }, 21),
+                (Window { start: 15, end: 18 }, 15),
+            ]
+        );
+    }
+}
@@ -53,6 +53,7 @@ fn add_network(node: &mut HydroNode, new_location: &LocationId) {
             cardinality: None,
             cpu_usage: None,
             network_recv_cpu_usage: None,
+            monotonicity: None,
             id: None,
         },
     };
@@ -77,6 +78,7 @@ fn add_network(node: &mut HydroNode, new_location: &LocationId) {
             cardinality: None,
             cpu_usage: None,
             network_recv_cpu_usage: None,
+            monotonicity: None,
             id: None,
         },
     };
@@ -92,6 +94,7 @@ fn add_network(node: &mut HydroNode, new_location: &LocationId) {
             cardinality: None,
             cpu_usage: None,
             network_recv_cpu_usage: None,
+            monotonicity: None,
             id: None,
         },
     };
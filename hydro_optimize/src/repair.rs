@@ -138,6 +138,8 @@ fn inject_location_node(
                     // Update Persist's location as well (we won't see it during traversal)
                     HydroNode::Fold { input, .. }
                     | HydroNode::FoldKeyed { input, .. }
+                    | HydroNode::LatticeFold { input, .. }
+                    | HydroNode::LatticeFoldKeyed { input, .. }
                     | HydroNode::Reduce { input, .. }
                     | HydroNode::ReduceKeyed { input, .. } => {
                         if let HydroNode::Persist {
@@ -22,8 +22,8 @@ pub trait PartitionStream<'a, T, C1, C2, Order> {
             >;
 }
 
-impl<'a, T, C1, C2, Order> PartitionStream<'a, T, C1, C2, Order>
-    for Stream<(ClusterId<C2>, T), Cluster<'a, C1>, Unbounded, Order>
+impl<'a, T, C1, C2, B, Order> PartitionStream<'a, T, C1, C2, Order>
+    for Stream<(ClusterId<C2>, T), Cluster<'a, C1>, B, Order>
 {
     fn send_partitioned<Tag, F: Fn((ClusterId<C2>, T)) -> (ClusterId<C2>, T) + 'a>(
         self,
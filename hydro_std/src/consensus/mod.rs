@@ -0,0 +1,292 @@
+//! Replicated logs built on consensus protocols.
+//!
+//! A [`PaxosLike`] protocol sequences payloads sent to one cluster into a log that is stored
+//! on another, tolerating up to `f` failures. [`paxos::CorePaxos`] implements the classic
+//! Paxos protocol, with the leader lease configured through [`paxos::PaxosConfig`] and the
+//! acceptor log kept in a pluggable [`paxos::AcceptorStorage`]. The acceptors that store the
+//! log can be replaced while it runs, through reconfiguration entries in the log itself.
+//!
+//! The simplest entry point is [`PaxosLike::replicated_log`], which takes payloads at the
+//! proposers and returns the committed payloads with their slots, in slot order:
+//!
+//! ```rust,ignore
+//! let paxos = CorePaxos {
+//!     proposers: proposers.clone(),
+//!     acceptors: acceptors.clone(),
+//!     paxos_config: PaxosConfig { f: 1, ... },
+//!     acceptor_storage: InMemoryStorage,
+//! };
+//! let log = unsafe { paxos.replicated_log(payloads, checkpoints) };
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use hydro_lang::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+pub mod paxos;
+use paxos::PaxosPayload;
+
+pub trait PaxosLike<'a>: Sized {
+    /// The nodes that receive inputs in Paxos. Usually the proposers.
+    type PaxosIn: 'a;
+
+    /// The nodes that store logs in Paxos. Usually the acceptors.
+    type PaxosLog: 'a;
+
+    /// The nodes that output the results of Paxos. Proposers in Paxos, Proxy leaders in Compartmentalized Paxos.
+    type PaxosOut: 'a;
+    type Ballot: Clone + Ord + Debug + Serialize + DeserializeOwned;
+
+    fn payload_recipients(&self) -> &Cluster<'a, Self::PaxosIn>;
+    fn log_stores(&self) -> &Cluster<'a, Self::PaxosLog>;
+    fn output_recipients(&self) -> &Cluster<'a, Self::PaxosOut>;
+
+    fn get_recipient_from_ballot<L: Location<'a>>(
+        ballot: Optional<Self::Ballot, L, Unbounded>,
+    ) -> Optional<ClusterId<Self::PaxosIn>, L, Unbounded>;
+
+    /// # Safety
+    /// During leader-reelection, the latest known leader may be stale, which may
+    /// result in non-deterministic dropping of payloads.
+    #[expect(clippy::type_complexity, reason = "internal paxos code // TODO")]
+    unsafe fn build<P: PaxosPayload>(
+        self,
+        payload_generator: impl FnOnce(
+            Stream<Self::Ballot, Cluster<'a, Self::PaxosIn>, Unbounded>,
+        ) -> Stream<P, Cluster<'a, Self::PaxosIn>, Unbounded>,
+        checkpoints: Optional<usize, Cluster<'a, Self::PaxosLog>, Unbounded>,
+    ) -> Stream<(usize, Option<P>), Cluster<'a, Self::PaxosOut>, Unbounded, NoOrder>;
+
+    /// # Safety
+    /// During leader-reelection, the latest known leader may be stale, which may
+    /// result in non-deterministic dropping of payloads.
+    #[expect(clippy::type_complexity, reason = "internal paxos code // TODO")]
+    unsafe fn with_client<C: 'a, P: PaxosPayload>(
+        self,
+        clients: &Cluster<'a, C>,
+        payloads: Stream<P, Cluster<'a, C>, Unbounded>,
+        checkpoints: Optional<usize, Cluster<'a, Self::PaxosLog>, Unbounded>,
+    ) -> Stream<(usize, Option<P>), Cluster<'a, Self::PaxosOut>, Unbounded, NoOrder> {
+        unsafe {
+            // SAFETY: Non-deterministic leader notifications are handled in `cur_leader_id`. We do not
+            // care about the order in which key writes are processed, which is the non-determinism in
+            // `sequenced_payloads`.
+            let leaders = self.payload_recipients().clone();
+
+            self.build(
+                move |new_leader_elected| {
+                    let cur_leader_id = Self::get_recipient_from_ballot(
+                        new_leader_elected
+                            .broadcast_bincode_anonymous(clients)
+                            .inspect(q!(|ballot| println!(
+                                "Client notified that leader was elected: {:?}",
+                                ballot
+                            )))
+                            .max(),
+                    );
+
+                    let payloads_at_proposer = {
+                        // SAFETY: the risk here is that we send a batch of requests
+                        // with a stale leader ID, but because the leader ID comes from the
+                        // network there is no way to guarantee that it is up to date. This
+                        // is documented non-determinism.
+
+                        let client_tick = clients.tick();
+                        let payload_batch = payloads.tick_batch(&client_tick);
+
+                        let latest_leader = cur_leader_id.latest_tick(&client_tick);
+
+                        let (unsent_payloads_complete, unsent_payloads) =
+                            client_tick.cycle::<Stream<_, _, _, TotalOrder>>();
+
+                        let all_payloads = unsent_payloads.chain(payload_batch);
+
+                        unsent_payloads_complete.complete_next_tick(
+                            all_payloads.clone().continue_unless(latest_leader.clone()),
+                        );
+
+                        all_payloads.cross_singleton(latest_leader).all_ticks()
+                    }
+                    .map(q!(move |(payload, leader_id)| (leader_id, payload)))
+                    .send_bincode_anonymous(&leaders);
+
+                    let payloads_at_proposer = {
+                        // SAFETY: documented non-determinism in interleaving of client payloads
+                        payloads_at_proposer.assume_ordering()
+                    };
+
+                    payloads_at_proposer
+                },
+                checkpoints,
+            )
+        }
+    }
+
+    /// Sequences `payloads` into the replicated log, returning the committed payloads at each
+    /// member of [`PaxosLike::PaxosOut`] in slot order, see [`sequence_log`].
+    ///
+    /// # Safety
+    /// Only the current leader sequences payloads, so payloads sent to any other member of
+    /// [`PaxosLike::PaxosIn`], or sent while the leader is changing, may be
+    /// non-deterministically dropped. Use [`PaxosLike::with_client`] to route payloads to
+    /// the latest known leader.
+    unsafe fn replicated_log<P: PaxosPayload>(
+        self,
+        payloads: Stream<P, Cluster<'a, Self::PaxosIn>, Unbounded>,
+        checkpoints: Optional<usize, Cluster<'a, Self::PaxosLog>, Unbounded>,
+    ) -> Stream<(usize, P), Cluster<'a, Self::PaxosOut>, Unbounded> {
+        let outputs = self.output_recipients().clone();
+        let committed = unsafe {
+            // SAFETY: dropped payloads are documented non-determinism
+            self.build(|_| payloads, checkpoints)
+        };
+
+        sequence_log(&outputs, committed)
+    }
+}
+
+/// Orders the slots committed by a [`PaxosLike`] protocol, which may arrive out of order and
+/// more than once, into the log. Each slot is emitted once, after all slots before it, and
+/// slots that were filled with holes are skipped.
+pub fn sequence_log<'a, L, P: PaxosPayload>(
+    location: &Cluster<'a, L>,
+    committed: Stream<(usize, Option<P>), Cluster<'a, L>, Unbounded, NoOrder>,
+) -> Stream<(usize, P), Cluster<'a, L>, Unbounded> {
+    let tick = location.tick();
+
+    let (pending_complete_cycle, pending) = tick.cycle::<Stream<_, _, _, NoOrder>>();
+    let (next_slot_complete_cycle, next_slot) = tick.cycle_with_initial(tick.singleton(q!(0usize)));
+
+    let processed = pending
+        .chain(unsafe {
+            // SAFETY: slots are buffered until all slots before them have been emitted, so
+            // batching boundaries do not affect the output
+            committed.tick_batch(&tick)
+        })
+        .fold_commutative(
+            q!(|| BTreeMap::new()),
+            q!(|slots, (slot, payload)| {
+                slots.insert(slot, payload);
+            }),
+        )
+        .zip(next_slot)
+        .map(q!(|(mut slots, mut next_slot)| {
+            let mut ready = vec![];
+            while let Some(payload) = slots.remove(&next_slot) {
+                ready.push((next_slot, payload));
+                next_slot += 1;
+            }
+
+            // slots before `next_slot` are duplicates of slots that were already emitted
+            (ready, slots.split_off(&next_slot), next_slot)
+        }));
+
+    next_slot_complete_cycle
+        .complete_next_tick(processed.clone().map(q!(|(_, _, next_slot)| next_slot)));
+    pending_complete_cycle.complete_next_tick(
+        processed
+            .clone()
+            .flat_map_unordered(q!(|(_, pending, _)| pending))
+            .into(),
+    );
+
+    processed
+        .flat_map_ordered(q!(|(ready, _, _)| ready))
+        .filter_map(q!(|(slot, payload)| payload.map(|p| (slot, p))))
+        .all_ticks()
+}
+
+/// Computes the slot below which the acceptors may truncate their logs, given the
+/// checkpoints that replicas send after applying the log up to some slot. A slot is stable
+/// once at least `quorum` replicas have checkpointed it, since replicas that fall behind a
+/// stable slot can no longer replay the log and must catch up from the snapshot of a peer.
+///
+/// The result can be passed as the checkpoints of [`PaxosLike::build`].
+///
+/// # Panics
+/// Panics if `quorum` is zero.
+pub fn stable_checkpoint<'a, R: 'a, A: 'a>(
+    acceptors: &Cluster<'a, A>,
+    replica_checkpoints: Stream<usize, Cluster<'a, R>, Unbounded>,
+    quorum: usize,
+) -> Optional<usize, Cluster<'a, A>, Unbounded> {
+    assert!(quorum > 0, "stable_checkpoint requires a quorum of at least one replica");
+
+    let checkpoint_tick = acceptors.tick();
+
+    unsafe {
+        // SAFETY: even though we batch the checkpoint messages, because we reduce over the entire history,
+        // the final stable checkpoint is deterministic
+        // TODO(shadaj): once we can reduce keyed over unbounded streams, this should be safe
+        replica_checkpoints
+            .broadcast_bincode(acceptors)
+            .tick_batch(&checkpoint_tick)
+    }
+    .persist()
+    .reduce_keyed_commutative(q!(|curr_seq, seq| {
+        if seq > *curr_seq {
+            *curr_seq = seq;
+        }
+    }))
+    .map(q!(|(_sender, seq)| seq))
+    .fold_commutative(
+        q!(|| vec![]),
+        q!(|seqs, seq| {
+            // even though this is non-commutative, we sort the checkpoints before using them
+            seqs.push(seq);
+        }),
+    )
+    .filter_map(q!(move |mut seqs| {
+        // the largest slot that at least `quorum` replicas have checkpointed
+        seqs.sort_unstable_by(|a, b| b.cmp(a));
+        seqs.get(quorum - 1).copied()
+    }))
+    .latest()
+}
+
+#[cfg(test)]
+mod tests {
+    use hydro_lang::deploy::Simulation;
+    use hydro_lang::*;
+
+    struct Replica {}
+    struct Leader {}
+    struct External {}
+
+    #[test]
+    fn sequence_log_skips_holes_and_duplicates() {
+        let mut simulation = Simulation::new();
+
+        let flow = FlowBuilder::new();
+        let replicas = flow.cluster::<Replica>();
+        let leader = flow.process::<Leader>();
+        let external = flow.external_process::<External>();
+
+        let committed: Stream<_, _, Unbounded> = replicas
+            .source_iter(q!([
+                (2, Some(20u32)),
+                (0, Some(0)),
+                (1, None),
+                (0, Some(0)),
+                (4, Some(40)),
+            ]))
+            .into();
+        let out_port = super::sequence_log(&replicas, committed.into())
+            .send_bincode_anonymous(&leader)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&leader, ())
+            .with_cluster(&replicas, 1)
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        // Slot 4 is held back until slot 3 is committed.
+        let port = nodes.raw_port_bincode(&out_port);
+        let output = simulation.run();
+        assert_eq!(output.bincode::<(usize, u32)>(&port), vec![(0, 0), (2, 20)]);
+    }
+}
@@ -5,24 +5,32 @@ use std::time::Duration;
 
 use hydro_lang::stream::AtLeastOnce;
 use hydro_lang::*;
-use hydro_std::quorum::{collect_quorum, collect_quorum_with_response};
-use hydro_std::request_response::join_responses;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-use super::paxos_with_client::PaxosLike;
+use super::PaxosLike;
+use crate::quorum::{collect_quorum, collect_quorum_with_response};
+use crate::request_response::join_responses;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Proposer {}
 pub struct Acceptor {}
 
+/// Configures the fault tolerance and leader lease of a Paxos deployment.
+///
+/// The elected leader holds a lease that it renews by broadcasting "I am leader" heartbeats
+/// to the other proposers. Once a proposer has not heard a heartbeat for
+/// `i_am_leader_check_timeout` seconds, the lease has expired and it tries to become the
+/// leader itself.
 #[derive(Clone, Copy)]
 pub struct PaxosConfig {
-    /// Maximum number of faulty nodes
+    /// Maximum number of faulty nodes, which requires `f + 1` proposers and at least `2f + 1`
+    /// acceptors, of which `2f + 1` store the log at a time
     pub f: usize,
-    /// How often to send "I am leader" heartbeats
+    /// How often the leader renews its lease by sending "I am leader" heartbeats, in seconds
     pub i_am_leader_send_timeout: u64,
-    /// How often to check if the leader has expired
+    /// How long a lease lasts without a heartbeat before proposers consider the leader expired,
+    /// in seconds
     pub i_am_leader_check_timeout: u64,
     /// Initial delay, multiplied by proposer pid, to stagger proposers checking for timeouts
     pub i_am_leader_check_timeout_delay_multiplier: usize,
@@ -65,13 +73,118 @@ pub struct P2a<P, S> {
     pub value: Option<P>, // might be a re-committed hole
 }
 
-pub struct CorePaxos<'a> {
+/// An entry of the replicated log, which either holds a payload or replaces the acceptors that
+/// store the log for every slot after it.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub enum LogEntry<P> {
+    Payload(P),
+    Reconfigure(Vec<ClusterId<Acceptor>>),
+}
+
+impl<P> LogEntry<P> {
+    pub fn into_payload(self) -> Option<P> {
+        match self {
+            LogEntry::Payload(payload) => Some(payload),
+            LogEntry::Reconfigure(_) => None,
+        }
+    }
+}
+
+/// The acceptors that store the log from `start_slot` onwards.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct AcceptorConfig {
+    pub start_slot: usize,
+    pub members: Vec<ClusterId<Acceptor>>,
+}
+
+/// Determines how each acceptor keeps its log of accepted values, which proposers read back
+/// during leader election.
+pub trait AcceptorStorage<'a> {
+    /// Folds the accepted p2as and the checkpoints of the acceptor into its log, returning the
+    /// latest checkpoint together with the accepted value of each slot after it.
+    ///
+    /// A p2a must only replace the accepted value of its slot if it has a larger ballot, and
+    /// the entries for slots before a checkpoint may be discarded.
+    #[expect(clippy::type_complexity, reason = "internal paxos code // TODO")]
+    fn build_log<P: PaxosPayload, S>(
+        &self,
+        updates: Stream<CheckpointOrP2a<P, S>, Atomic<Cluster<'a, Acceptor>>, Unbounded, NoOrder>,
+    ) -> Singleton<
+        (Option<usize>, HashMap<usize, LogValue<P>>),
+        Atomic<Cluster<'a, Acceptor>>,
+        Unbounded,
+    >;
+}
+
+/// Keeps the acceptor log in memory, so it is lost if the acceptor restarts.
+#[derive(Clone, Copy, Default)]
+pub struct InMemoryStorage;
+
+impl<'a> AcceptorStorage<'a> for InMemoryStorage {
+    fn build_log<P: PaxosPayload, S>(
+        &self,
+        updates: Stream<CheckpointOrP2a<P, S>, Atomic<Cluster<'a, Acceptor>>, Unbounded, NoOrder>,
+    ) -> Singleton<
+        (Option<usize>, HashMap<usize, LogValue<P>>),
+        Atomic<Cluster<'a, Acceptor>>,
+        Unbounded,
+    > {
+        updates.fold_commutative(
+            q!(|| (None, HashMap::new())),
+            q!(|(prev_checkpoint, log), checkpoint_or_p2a| {
+                match checkpoint_or_p2a {
+                    CheckpointOrP2a::Checkpoint(new_checkpoint) => {
+                        if prev_checkpoint
+                            .map(|prev| new_checkpoint > prev)
+                            .unwrap_or(true)
+                        {
+                            for slot in (prev_checkpoint.unwrap_or(0))..new_checkpoint {
+                                log.remove(&slot);
+                            }
+
+                            *prev_checkpoint = Some(new_checkpoint);
+                        }
+                    }
+                    CheckpointOrP2a::P2a(p2a) => {
+                        // This is a regular p2a message. Insert it into the log if it is not checkpointed and has a higher ballot than what was there before
+                        if prev_checkpoint.map(|prev| p2a.slot > prev).unwrap_or(true)
+                            && log
+                                .get(&p2a.slot)
+                                .map(|prev_p2a: &LogValue<_>| p2a.ballot > prev_p2a.ballot)
+                                .unwrap_or(true)
+                        {
+                            log.insert(
+                                p2a.slot,
+                                LogValue {
+                                    ballot: p2a.ballot,
+                                    value: p2a.value,
+                                },
+                            );
+                        }
+                    }
+                }
+            }),
+        )
+    }
+}
+
+/// Paxos with a cluster of proposers that sequence payloads and a cluster of acceptors that
+/// store the log.
+///
+/// The log is stored on `2f + 1` of the acceptors, initially the ones with the smallest IDs, and
+/// the remaining acceptors are spares. When the leader observes that an acceptor storing the log
+/// has left the cluster (see [`Location::cluster_membership`]), it replaces the acceptor with a
+/// spare by sequencing a [`LogEntry::Reconfigure`]. This entry acts as a stop-sign: the leader
+/// sequences no further payloads until every slot up to and including the reconfiguration has
+/// been committed, and all later slots are stored on the new acceptors.
+pub struct CorePaxos<'a, S = InMemoryStorage> {
     pub proposers: Cluster<'a, Proposer>,
     pub acceptors: Cluster<'a, Acceptor>,
     pub paxos_config: PaxosConfig,
+    pub acceptor_storage: S,
 }
 
-impl<'a> PaxosLike<'a> for CorePaxos<'a> {
+impl<'a, S: AcceptorStorage<'a>> PaxosLike<'a> for CorePaxos<'a, S> {
     type PaxosIn = Proposer;
     type PaxosLog = Acceptor;
     type PaxosOut = Proposer;
@@ -85,6 +198,10 @@ impl<'a> PaxosLike<'a> for CorePaxos<'a> {
         &self.acceptors
     }
 
+    fn output_recipients(&self) -> &Cluster<'a, Self::PaxosOut> {
+        &self.proposers
+    }
+
     fn get_recipient_from_ballot<L: Location<'a>>(
         ballot: Optional<Self::Ballot, L, Unbounded>,
     ) -> Optional<ClusterId<Self::PaxosIn>, L, Unbounded> {
@@ -105,6 +222,7 @@ impl<'a> PaxosLike<'a> for CorePaxos<'a> {
                 a_checkpoint,
                 with_ballot,
                 self.paxos_config,
+                &self.acceptor_storage,
             )
             .1
         }
@@ -120,7 +238,8 @@ impl<'a> PaxosLike<'a> for CorePaxos<'a> {
 ///
 /// Returns a stream of ballots, where new values are emitted when a new leader is elected,
 /// and a stream of sequenced payloads with an index and optional payload (in the case of
-/// holes in the log).
+/// holes in the log, or of slots that reconfigured the acceptors). Each acceptor keeps its
+/// log in `acceptor_storage`.
 ///
 /// # Safety
 /// When the leader is stable, the algorithm will commit incoming payloads to the leader
@@ -136,6 +255,7 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
         Stream<Ballot, Cluster<'a, Proposer>, Unbounded>,
     ) -> Stream<P, Cluster<'a, Proposer>, Unbounded>,
     config: PaxosConfig,
+    acceptor_storage: &impl AcceptorStorage<'a>,
 ) -> (
     Stream<Ballot, Cluster<'a, Proposer>, Unbounded>,
     Stream<(usize, Option<P>), Cluster<'a, Proposer>, Unbounded, NoOrder>,
@@ -158,6 +278,14 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
     let (a_log_complete_cycle, a_log_forward_reference) =
         acceptor_tick.forward_ref::<Singleton<_, _, _>>();
 
+    // The acceptors that store the log, which start out as the acceptors with the smallest IDs
+    let acceptor_ids = acceptors.members();
+    let (p_config_complete_cycle, p_config) =
+        proposer_tick.cycle_with_initial(proposer_tick.singleton(q!(AcceptorConfig {
+            start_slot: 0,
+            members: acceptor_ids.iter().take(2 * f + 1).copied().collect(),
+        })));
+
     let (p_ballot, p_is_leader, p_relevant_p1bs, a_max_ballot) = unsafe {
         // SAFETY: The primary non-determinism exposed by leader election algorithm lies in which leader
         // is elected, which affects both the ballot at each proposer and the leader flag. But using a stale ballot
@@ -172,6 +300,7 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
             f + 1,
             2 * f + 1,
             config,
+            p_config.clone().map(q!(|config| config.members)),
             sequencing_max_ballot_forward_reference,
             a_log_forward_reference,
         )
@@ -188,7 +317,40 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
             .all_ticks(),
     );
 
-    let (p_to_replicas, a_log, sequencing_max_ballots) = unsafe {
+    let p_acceptor_membership = unsafe {
+        // SAFETY: A stale view of the acceptor membership only delays or skips a reconfiguration,
+        // which is sequenced through the log and therefore agreed upon by all proposers.
+        proposers
+            .cluster_membership(acceptors)
+            .latest_tick(&proposer_tick)
+    };
+    let p_reconfiguration = p_acceptor_membership
+        .zip(p_config.clone())
+        .filter_map(q!(move |(membership, config)| {
+            // Keep the current acceptors that are still members and replace the others with
+            // the spares with the smallest IDs
+            let mut members = config
+                .members
+                .iter()
+                .filter(|id| membership.contains(*id))
+                .copied()
+                .collect::<Vec<_>>();
+            let mut spares = membership
+                .into_iter()
+                .filter(|id| !config.members.contains(id))
+                .collect::<Vec<_>>();
+            spares.sort_by_key(|id| id.raw_id);
+            let num_missing = (2 * f + 1).saturating_sub(members.len());
+            members.extend(spares.into_iter().take(num_missing));
+
+            if members.len() == 2 * f + 1 && members != config.members {
+                Some(members)
+            } else {
+                None
+            }
+        }));
+
+    let (p_to_replicas, a_log, sequencing_max_ballots, p_new_config) = unsafe {
         // SAFETY: The relevant p1bs are non-deterministic because they come from a arbitrary quorum, but because
         // we use a quorum, if we remain the leader there are no missing committed values when we combine the logs.
         // The remaining non-determinism is in when incoming payloads are batched versus the leader flag and state
@@ -203,18 +365,58 @@ pub unsafe fn paxos_core<'a, P: PaxosPayload>(
             p_ballot.clone(),
             p_is_leader,
             p_relevant_p1bs,
+            p_config.clone(),
+            p_reconfiguration,
             f,
             a_max_ballot,
+            acceptor_storage,
         )
     };
 
+    // Tell the other proposers about the new acceptors, so that they contact them when they try
+    // to become the leader
+    let p_configs_from_peers = unsafe {
+        // SAFETY: A proposer that has not yet learned about a reconfiguration will contact the
+        // old acceptors, which report the reconfiguration in their p1bs.
+        p_new_config
+            .clone()
+            .map(q!(|(config, _step_down)| config))
+            .all_ticks()
+            .inspect(q!(|config| println!(
+                "Proposer reconfigured acceptors: {:?}",
+                config
+            )))
+            .broadcast_bincode_anonymous(proposers)
+            .tick_batch(&proposer_tick)
+    };
+    p_config_complete_cycle.complete_next_tick(
+        p_configs_from_peers
+            .chain(p_config.clone().into_stream())
+            .max_by_key(q!(|config| config.start_slot))
+            .unwrap_or(p_config),
+    );
+
+    // A leader that learned about a reconfiguration from the p1bs has not yet contacted the new
+    // acceptors, so it gives up its ballot (in the next tick, since the ballot it gives up
+    // determines the new configuration) to start a new leader election with them
+    let p_step_down_ballots = p_new_config
+        .filter(q!(|(_config, step_down)| *step_down))
+        .then(p_ballot.clone())
+        .map(q!(|ballot| Ballot {
+            num: ballot.num + 1,
+            proposer_id: ballot.proposer_id
+        }))
+        .defer_tick()
+        .all_ticks();
+
     a_log_complete_cycle.complete(unsafe {
         // SAFETY: We will always write payloads to the log before acknowledging them to the proposers,
         // which guarantees that if the leader changes the quorum overlap between sequencing and leader
         // election will include the committed value.
         a_log.latest_tick()
     });
-    sequencing_max_ballot_complete_cycle.complete(sequencing_max_ballots);
+    sequencing_max_ballot_complete_cycle
+        .complete(sequencing_max_ballots.union(p_step_down_ballots));
 
     (
         // Only tell the clients once when leader election concludes
@@ -237,6 +439,7 @@ pub unsafe fn leader_election<'a, L: Clone + Debug + Serialize + DeserializeOwne
     quorum_size: usize,
     num_quorum_participants: usize,
     paxos_config: PaxosConfig,
+    p_acceptors: Singleton<Vec<ClusterId<Acceptor>>, Tick<Cluster<'a, Proposer>>, Bounded>,
     p_received_p2b_ballots: Stream<Ballot, Cluster<'a, Proposer>, Unbounded, NoOrder>,
     a_log: Singleton<(Option<usize>, L), Tick<Cluster<'a, Acceptor>>, Bounded>,
 ) -> (
@@ -286,9 +489,14 @@ pub unsafe fn leader_election<'a, L: Clone + Debug + Serialize + DeserializeOwne
 
     let p_to_acceptors_p1a = p_trigger_election
         .then(p_ballot.clone())
-        .all_ticks()
+        .into_stream()
         .inspect(q!(|_| println!("Proposer leader expired, sending P1a")))
-        .broadcast_bincode_anonymous(acceptors);
+        .cross_singleton(p_acceptors)
+        .flat_map_ordered(q!(|(ballot, members)| members
+            .into_iter()
+            .map(move |id| (id, ballot))))
+        .all_ticks()
+        .send_bincode_anonymous(acceptors);
 
     let (a_max_ballot, a_to_proposers_p1b) = acceptor_p1(
         acceptor_tick,
@@ -624,62 +832,179 @@ unsafe fn sequence_payload<'a, P: PaxosPayload>(
     p_is_leader: Optional<(), Tick<Cluster<'a, Proposer>>, Bounded>,
 
     p_relevant_p1bs: Stream<
-        (Option<usize>, HashMap<usize, LogValue<P>>),
+        (Option<usize>, HashMap<usize, LogValue<LogEntry<P>>>),
         Tick<Cluster<'a, Proposer>>,
         Bounded,
         NoOrder,
     >,
+    p_config: Singleton<AcceptorConfig, Tick<Cluster<'a, Proposer>>, Bounded>,
+    p_reconfiguration: Optional<Vec<ClusterId<Acceptor>>, Tick<Cluster<'a, Proposer>>, Bounded>,
     f: usize,
 
     a_max_ballot: Singleton<Ballot, Tick<Cluster<'a, Acceptor>>, Bounded>,
+    acceptor_storage: &impl AcceptorStorage<'a>,
 ) -> (
     Stream<(usize, Option<P>), Cluster<'a, Proposer>, Unbounded, NoOrder>,
     Singleton<
-        (Option<usize>, HashMap<usize, LogValue<P>>),
+        (Option<usize>, HashMap<usize, LogValue<LogEntry<P>>>),
         Atomic<Cluster<'a, Acceptor>>,
         Unbounded,
     >,
     Stream<Ballot, Cluster<'a, Proposer>, Unbounded, NoOrder>,
+    Optional<(AcceptorConfig, bool), Tick<Cluster<'a, Proposer>>, Bounded>,
 ) {
+    let p_just_became_leader = p_is_leader
+        .clone()
+        .continue_unless(p_is_leader.clone().defer_tick());
+
+    // The p1bs are only reconciled once, when we become the leader. The slots before the current
+    // configuration were committed by the previous acceptors, so we treat them as checkpointed.
+    let p_relevant_p1bs = p_relevant_p1bs
+        .chain(
+            p_config
+                .clone()
+                .filter_map(q!(|config| config
+                    .start_slot
+                    .checked_sub(1)
+                    .map(|last_slot| (Some(last_slot), HashMap::new()))))
+                .into_stream(),
+        )
+        .continue_if(p_just_became_leader.clone());
+
     let (p_log_to_recommit, p_max_slot) =
-        recommit_after_leader_election(p_relevant_p1bs, p_ballot.clone(), f);
-
-    let indexed_payloads = index_payloads(proposer_tick, p_max_slot, unsafe {
-        // SAFETY: We batch payloads so that we can compute the correct slot based on
-        // base slot. In the case of a leader re-election, the base slot is updated which
-        // affects the computed payload slots. This non-determinism can lead to non-determinism
-        // in which payloads are committed when the leader is changing, which is documented at
-        // the function level.
-        c_to_proposers
-            .tick_batch(proposer_tick)
-            .continue_if(p_is_leader.clone())
-    });
+        recommit_after_leader_election(p_relevant_p1bs.clone(), p_ballot.clone(), f);
+    // If the acceptors have not accepted any slots yet, continue after the reconfiguration
+    let p_max_slot = p_max_slot
+        .into_singleton()
+        .zip(p_config.clone())
+        .filter_map(q!(
+            |(max_slot, config)| max_slot.max(config.start_slot.checked_sub(1))
+        ))
+        .continue_if(p_just_became_leader);
+
+    // A reconfiguration that we are waiting on to take effect, during which we do not sequence
+    // any new slots. Reconfigurations that were accepted before we became the leader must take
+    // effect before we can use our ballot with the new acceptors, so we give up our ballot.
+    let (p_stop_sign_complete_cycle, p_prev_stop_sign) =
+        proposer_tick.cycle::<Optional<(Ballot, usize, Vec<ClusterId<Acceptor>>, bool), _, _>>();
+    let p_accepted_reconfigurations = p_relevant_p1bs
+        .flat_map_unordered(q!(|(_checkpoint, log)| log.into_iter().filter_map(
+            |(slot, entry)| match entry.value {
+                Some(LogEntry::Reconfigure(members)) => Some((slot, members)),
+                _ => None,
+            }
+        )))
+        .cross_singleton(p_ballot.clone())
+        .cross_singleton(p_config.clone())
+        // Reconfigurations before the current configuration have already taken effect
+        .filter_map(q!(|(((slot, members), ballot), config)| {
+            if slot >= config.start_slot {
+                Some((ballot, slot, members, true))
+            } else {
+                None
+            }
+        }));
+    let p_stop_sign = p_prev_stop_sign
+        .into_stream()
+        .chain(p_accepted_reconfigurations)
+        .cross_singleton(p_ballot.clone())
+        .cross_singleton(p_config.clone())
+        .filter_map(q!(|(
+            ((ballot, slot, members, from_p1b), cur_ballot),
+            config,
+        )| {
+            // A reconfiguration that we sequenced ends once it takes effect, while one
+            // from the p1bs lasts until we give up our ballot
+            if ballot == cur_ballot && (from_p1b || slot >= config.start_slot) {
+                Some((ballot, slot, members, from_p1b))
+            } else {
+                None
+            }
+        }))
+        .max_by_key(q!(|(_ballot, slot, _members, _from_p1b)| *slot));
+
+    let (p_unsequenced_payloads_complete_cycle, p_unsequenced_payloads) =
+        proposer_tick.cycle::<Stream<P, _, _, TotalOrder>>();
+    let p_payloads = p_unsequenced_payloads
+        .chain(unsafe {
+            // SAFETY: We batch payloads so that we can compute the correct slot based on
+            // base slot. In the case of a leader re-election, the base slot is updated which
+            // affects the computed payload slots. This non-determinism can lead to non-determinism
+            // in which payloads are committed when the leader is changing, which is documented at
+            // the function level.
+            c_to_proposers.tick_batch(proposer_tick)
+        })
+        .continue_if(p_is_leader.clone());
+    // Hold back payloads until the reconfiguration takes effect
+    p_unsequenced_payloads_complete_cycle
+        .complete_next_tick(p_payloads.clone().continue_if(p_stop_sign.clone()));
+
+    let p_new_reconfiguration = p_reconfiguration
+        .continue_if(p_is_leader.clone())
+        .continue_unless(p_stop_sign.clone());
+    let indexed_payloads = index_payloads(
+        proposer_tick,
+        p_max_slot,
+        p_payloads
+            .continue_unless(p_stop_sign.clone())
+            .map(q!(|payload| LogEntry::Payload(payload)))
+            // The reconfiguration goes last, since we stop sequencing slots after it
+            .chain(
+                p_new_reconfiguration
+                    .map(q!(|members| LogEntry::Reconfigure(members)))
+                    .into_stream(),
+            ),
+    );
 
-    let payloads_to_send = indexed_payloads
+    let p_sequenced_reconfiguration = indexed_payloads
+        .clone()
+        .filter_map(q!(|(slot, entry)| match entry {
+            LogEntry::Reconfigure(members) => Some((slot, members)),
+            LogEntry::Payload(_) => None,
+        }))
         .cross_singleton(p_ballot.clone())
-        .map(q!(|((slot, payload), ballot)| (
-            (slot, ballot),
-            Some(payload)
-        )))
+        .map(q!(|((slot, members), ballot)| (
+            ballot, slot, members, false
+        )));
+    p_stop_sign_complete_cycle.complete_next_tick(
+        p_stop_sign
+            .clone()
+            .into_stream()
+            .chain(p_sequenced_reconfiguration)
+            .max_by_key(q!(|(_ballot, slot, _members, _from_p1b)| *slot)),
+    );
+
+    let p_payloads_to_send = indexed_payloads
+        .cross_singleton(p_ballot.clone())
+        .map(q!(|((slot, entry), ballot)| ((slot, ballot), Some(entry))))
         .chain(p_log_to_recommit)
-        .continue_if(p_is_leader)
-        .all_ticks_atomic();
+        .continue_if(p_is_leader);
+    let payloads_to_send = p_payloads_to_send.clone().all_ticks_atomic();
 
     let (a_log, a_to_proposers_p2b) = acceptor_p2(
         acceptor_tick,
         a_max_ballot.clone(),
-        payloads_to_send
+        p_payloads_to_send
             .clone()
+            .cross_singleton(p_config)
+            .flat_map_unordered(q!(move |(((slot, ballot), value), config)| config
+                .members
+                .into_iter()
+                .map(move |id| (
+                    id,
+                    P2a {
+                        sender: CLUSTER_SELF_ID,
+                        ballot,
+                        slot,
+                        value: value.clone()
+                    }
+                ))))
+            .all_ticks_atomic()
             .end_atomic()
-            .map(q!(move |((slot, ballot), value)| P2a {
-                sender: CLUSTER_SELF_ID,
-                ballot,
-                slot,
-                value
-            }))
-            .broadcast_bincode_anonymous(acceptors),
+            .send_bincode_anonymous(acceptors),
         a_checkpoint,
         proposers,
+        acceptor_storage,
     );
 
     // TOOD: only persist if we are the leader
@@ -692,12 +1017,58 @@ unsafe fn sequence_payload<'a, P: PaxosPayload>(
         payloads_to_send.tick_batch()
     });
 
+    // Count the slots sent with our ballot that are not committed yet, so that a reconfiguration
+    // only takes effect once every slot before it is committed
+    let (p_in_flight_complete_cycle, p_prev_in_flight) =
+        proposer_tick.cycle_with_initial(proposer_tick.singleton(q!((None, 0usize))));
+    let p_num_committed = unsafe {
+        // SAFETY: Observing a commit in a later tick only delays the reconfiguration.
+        p_to_replicas.clone().tick_batch()
+    }
+    .cross_singleton(p_ballot.clone())
+    .filter(q!(|(((_slot, ballot), _), cur_ballot)| ballot == cur_ballot))
+    .count();
+    let p_in_flight = p_prev_in_flight
+        .zip(p_ballot)
+        .zip(p_payloads_to_send.count())
+        .zip(p_num_committed)
+        .map(q!(|(
+            (((prev_ballot, prev_in_flight), ballot), num_sent),
+            num_committed,
+        )| {
+            let in_flight = if prev_ballot == Some(ballot) {
+                prev_in_flight
+            } else {
+                0
+            };
+            (
+                Some(ballot),
+                (in_flight + num_sent).saturating_sub(num_committed),
+            )
+        }));
+    p_in_flight_complete_cycle.complete_next_tick(p_in_flight.clone());
+
+    // Once every slot up to the reconfiguration is committed, the new acceptors take over
+    let p_new_config = p_stop_sign
+        .zip(p_in_flight.filter(q!(|(_ballot, in_flight)| *in_flight == 0)))
+        .map(q!(|((_ballot, slot, members, from_p1b), _)| (
+            AcceptorConfig {
+                start_slot: slot + 1,
+                members
+            },
+            from_p1b
+        )));
+
     (
         p_to_replicas
             .end_atomic()
-            .map(q!(|((slot, _ballot), (value, _))| (slot, value))),
+            .map(q!(|((slot, _ballot), (value, _))| (
+                slot,
+                value.and_then(|entry| entry.into_payload())
+            ))),
         a_log,
         fails.end_atomic().map(q!(|(_, ballot)| ballot)),
+        p_new_config,
     )
 }
 
@@ -745,6 +1116,7 @@ pub fn acceptor_p2<'a, P: PaxosPayload, S: Clone>(
     p_to_acceptors_p2a: Stream<P2a<P, S>, Cluster<'a, Acceptor>, Unbounded, NoOrder>,
     a_checkpoint: Optional<usize, Cluster<'a, Acceptor>, Unbounded>,
     proposers: &Cluster<'a, S>,
+    acceptor_storage: &impl AcceptorStorage<'a>,
 ) -> (
     Singleton<
         (Option<usize>, HashMap<usize, LogValue<P>>),
@@ -780,45 +1152,11 @@ pub fn acceptor_p2<'a, P: PaxosPayload, S: Clone>(
                 None
             }
         ));
-    let a_log = a_p2as_to_place_in_log
-        .chain(a_new_checkpoint.into_stream())
-        .all_ticks_atomic()
-        .fold_commutative(
-            q!(|| (None, HashMap::new())),
-            q!(|(prev_checkpoint, log), checkpoint_or_p2a| {
-                match checkpoint_or_p2a {
-                    CheckpointOrP2a::Checkpoint(new_checkpoint) => {
-                        if prev_checkpoint
-                            .map(|prev| new_checkpoint > prev)
-                            .unwrap_or(true)
-                        {
-                            for slot in (prev_checkpoint.unwrap_or(0))..new_checkpoint {
-                                log.remove(&slot);
-                            }
-
-                            *prev_checkpoint = Some(new_checkpoint);
-                        }
-                    }
-                    CheckpointOrP2a::P2a(p2a) => {
-                        // This is a regular p2a message. Insert it into the log if it is not checkpointed and has a higher ballot than what was there before
-                        if prev_checkpoint.map(|prev| p2a.slot > prev).unwrap_or(true)
-                            && log
-                                .get(&p2a.slot)
-                                .map(|prev_p2a: &LogValue<_>| p2a.ballot > prev_p2a.ballot)
-                                .unwrap_or(true)
-                        {
-                            log.insert(
-                                p2a.slot,
-                                LogValue {
-                                    ballot: p2a.ballot,
-                                    value: p2a.value,
-                                },
-                            );
-                        }
-                    }
-                }
-            }),
-        );
+    let a_log = acceptor_storage.build_log(
+        a_p2as_to_place_in_log
+            .chain(a_new_checkpoint.into_stream())
+            .all_ticks_atomic(),
+    );
 
     let a_to_proposers_p2b = p_to_acceptors_p2a_batch
         .cross_singleton(a_max_ballot)
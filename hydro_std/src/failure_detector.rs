@@ -0,0 +1,390 @@
+//! Heartbeat-based failure detectors, which let any [`Process`] or [`Cluster`] suspect the
+//! members of a cluster that have stopped sending heartbeats.
+//!
+//! Members emit heartbeats with [`heartbeats`] and send them to the monitoring location with
+//! the usual networking operators, such as `send_bincode` or `broadcast_bincode`. The monitor
+//! then passes the received stream to [`fixed_timeout_detector`] or [`phi_accrual_detector`],
+//! which track the members of the monitored cluster as they join and leave.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use hydro_lang::*;
+use location::NoTick;
+use location::tick::NoAtomic;
+use serde::{Deserialize, Serialize};
+use stream::ExactlyOnce;
+use tokio::time::Instant;
+
+/// Configuration for [`fixed_timeout_detector`].
+#[derive(Clone, Copy)]
+pub struct FixedTimeoutConfig {
+    /// How often the detector re-evaluates which members are suspected, in milliseconds
+    pub check_interval_ms: u64,
+    /// How long a member can go without a heartbeat before it is suspected, in milliseconds
+    pub timeout_ms: u64,
+}
+
+/// Configuration for [`phi_accrual_detector`].
+#[derive(Clone, Copy)]
+pub struct PhiAccrualConfig {
+    /// How often the detector re-evaluates which members are suspected, in milliseconds
+    pub check_interval_ms: u64,
+    /// Suspicion level above which a member is suspected; a threshold of `t` means that the
+    /// detector is wrong with a probability of about `10^-t`
+    pub threshold: u32,
+    /// Number of recent heartbeat inter-arrival times used to estimate the distribution
+    pub window_size: usize,
+    /// Lower bound on the standard deviation of inter-arrival times, in milliseconds, so that
+    /// perfectly regular heartbeats do not make the detector overly sensitive to jitter
+    pub min_std_dev_ms: u64,
+    /// Inter-arrival time assumed for members that have sent fewer than two heartbeats, in
+    /// milliseconds, typically the interval at which heartbeats are sent
+    pub first_heartbeat_estimate_ms: u64,
+}
+
+/// A sliding window of heartbeat inter-arrival times for a single member, used by
+/// [`phi_accrual_detector`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArrivalWindow {
+    intervals_ms: VecDeque<f64>,
+    window_size: usize,
+}
+
+impl ArrivalWindow {
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            intervals_ms: VecDeque::with_capacity(window_size),
+            window_size,
+        }
+    }
+
+    /// Records the time between two consecutive heartbeats, evicting the oldest sample if
+    /// the window is full.
+    pub fn add_interval(&mut self, interval: Duration) {
+        if self.intervals_ms.len() == self.window_size {
+            self.intervals_ms.pop_front();
+        }
+        self.intervals_ms.push_back(interval.as_secs_f64() * 1000.0);
+    }
+
+    /// Returns the suspicion level after `elapsed` time without a heartbeat, which is
+    /// `-log10` of the probability that a heartbeat arrives even later than that.
+    ///
+    /// Inter-arrival times are assumed to be normally distributed, with the mean and standard
+    /// deviation estimated from the window. If the window is empty, `first_estimate_ms` is used
+    /// as the mean, with a standard deviation of a quarter of it.
+    pub fn phi(&self, elapsed: Duration, first_estimate_ms: f64, min_std_dev_ms: f64) -> f64 {
+        let (mean, std_dev) = if self.intervals_ms.is_empty() {
+            (first_estimate_ms, first_estimate_ms / 4.0)
+        } else {
+            let count = self.intervals_ms.len() as f64;
+            let mean = self.intervals_ms.iter().sum::<f64>() / count;
+            let variance = self
+                .intervals_ms
+                .iter()
+                .map(|interval| (interval - mean) * (interval - mean))
+                .sum::<f64>()
+                / count;
+            (mean, variance.sqrt())
+        };
+
+        // Logistic approximation of the normal CDF, as used by Akka and Cassandra.
+        let y = (elapsed.as_secs_f64() * 1000.0 - mean) / std_dev.max(min_std_dev_ms);
+        let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+        if y > 0.0 {
+            -(e / (1.0 + e)).log10()
+        } else {
+            -(1.0 - 1.0 / (1.0 + e)).log10()
+        }
+    }
+}
+
+/// Emits a heartbeat at `location` every `interval_ms` milliseconds, to be sent to the
+/// locations monitoring it.
+///
+/// # Safety
+/// Heartbeats are generated by a timer, so they are emitted at non-deterministic times.
+pub unsafe fn heartbeats<'a, L: Location<'a> + NoTick>(
+    location: &L,
+    interval_ms: u64,
+) -> Stream<(), L, Unbounded> {
+    unsafe {
+        // SAFETY: source of intentional non-determinism
+        location.source_interval(q!(Duration::from_millis(interval_ms)))
+    }
+    .map(q!(|_| ()))
+}
+
+/// Samples `state` along with the current members of `monitored` every `check_interval_ms`
+/// milliseconds, which is when the detectors re-evaluate which members are suspected. Each
+/// sample also includes the time of the first check, after which members that never sent a
+/// heartbeat start to be suspected.
+///
+/// # Safety
+/// The samples are taken at non-deterministic times.
+#[expect(clippy::type_complexity, reason = "stream markers")]
+unsafe fn sample_on_check<'a, C: 'a, S, L>(
+    monitor: &L,
+    monitored: &Cluster<'a, C>,
+    state: Singleton<S, L, Unbounded>,
+    check_interval_ms: u64,
+) -> Singleton<((S, HashSet<ClusterId<C>>), Option<Instant>), Tick<L>, Bounded>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    let started = unsafe {
+        // SAFETY: source of intentional non-determinism
+        monitor.source_interval(q!(Duration::from_millis(check_interval_ms)))
+    }
+    .fold(
+        q!(|| None),
+        q!(|started, now| {
+            if started.is_none() {
+                *started = Some(now);
+            }
+        }),
+    );
+
+    let tick = monitor.tick();
+    unsafe {
+        // SAFETY: each check samples the latest state and membership
+        state
+            .latest_tick(&tick)
+            .zip(monitor.cluster_membership(monitored).latest_tick(&tick))
+            .zip(started.latest_tick(&tick))
+    }
+}
+
+/// Returns the current members of `monitored` that have not sent a heartbeat to `monitor` in
+/// the last `timeout_ms` milliseconds. Members that have never sent a heartbeat are suspected
+/// once the detector has been running for that long.
+///
+/// Heartbeats can carry any payload, which is ignored, so `heartbeats` can be the output of
+/// sending to `monitor` directly.
+///
+/// # Safety
+/// Suspicion depends on when heartbeats arrive and when the detector samples the clock, so
+/// a live member may be suspected if its heartbeats are delayed, and a failed member is only
+/// suspected after a non-deterministic delay.
+pub unsafe fn fixed_timeout_detector<'a, C: 'a, T, L, O, R>(
+    monitor: &L,
+    monitored: &Cluster<'a, C>,
+    heartbeats: Stream<(ClusterId<C>, T), L, Unbounded, O, R>,
+    config: FixedTimeoutConfig,
+) -> Singleton<HashSet<ClusterId<C>>, L, Unbounded>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    let check_interval_ms = config.check_interval_ms;
+    let timeout_ms = config.timeout_ms;
+
+    let last_heard = unsafe {
+        // SAFETY: heartbeats are timestamped on arrival, so only the time of the latest
+        // heartbeat from each member matters, not the order or retries of the input stream.
+        heartbeats
+            .map(q!(|(id, _)| id))
+            .assume_retries::<ExactlyOnce>()
+            .assume_ordering::<TotalOrder>()
+    }
+    .fold(
+        q!(|| HashMap::new()),
+        q!(move |last_heard, id| {
+            last_heard.insert(id, CLOCK_NOW());
+        }),
+    );
+
+    unsafe {
+        // SAFETY: see the safety section above
+        sample_on_check(monitor, monitored, last_heard, check_interval_ms)
+    }
+    .map(q!(move |((last_heard, members), started)| {
+        let now = CLOCK_NOW();
+        members
+            .into_iter()
+            .filter(|id| {
+                last_heard
+                    .get(id)
+                    .or(started.as_ref())
+                    .is_some_and(|since| {
+                        now.duration_since(*since) > Duration::from_millis(timeout_ms)
+                    })
+            })
+            .collect::<HashSet<_>>()
+    }))
+    .latest()
+}
+
+/// Returns the current members of `monitored` whose suspicion level, computed by the phi
+/// accrual failure detector from the history of heartbeats received at `monitor`, exceeds
+/// `config.threshold`. Unlike a fixed timeout, this adapts to the rate and jitter at which
+/// each member's heartbeats actually arrive.
+///
+/// Heartbeats can carry any payload, which is ignored, so `heartbeats` can be the output of
+/// sending to `monitor` directly.
+///
+/// # Safety
+/// Suspicion depends on when heartbeats arrive and when the detector samples the clock, so
+/// a live member may be suspected if its heartbeats are delayed, and a failed member is only
+/// suspected after a non-deterministic delay.
+pub unsafe fn phi_accrual_detector<'a, C: 'a, T, L, O, R>(
+    monitor: &L,
+    monitored: &Cluster<'a, C>,
+    heartbeats: Stream<(ClusterId<C>, T), L, Unbounded, O, R>,
+    config: PhiAccrualConfig,
+) -> Singleton<HashSet<ClusterId<C>>, L, Unbounded>
+where
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    let check_interval_ms = config.check_interval_ms;
+    let threshold = config.threshold;
+    let window_size = config.window_size;
+    let min_std_dev_ms = config.min_std_dev_ms;
+    let first_heartbeat_estimate_ms = config.first_heartbeat_estimate_ms;
+
+    let history = unsafe {
+        // SAFETY: heartbeats are timestamped on arrival, so the history only depends on
+        // when they are received and not on the order or retries of the input stream.
+        heartbeats
+            .map(q!(|(id, _)| id))
+            .assume_retries::<ExactlyOnce>()
+            .assume_ordering::<TotalOrder>()
+    }
+    .fold(
+        q!(|| HashMap::new()),
+        q!(move |history, id| {
+            let now = CLOCK_NOW();
+            let (last, window) = history
+                .entry(id)
+                .or_insert_with(|| (now, ArrivalWindow::new(window_size)));
+            if *last != now {
+                window.add_interval(now.duration_since(*last));
+                *last = now;
+            }
+        }),
+    );
+
+    unsafe {
+        // SAFETY: see the safety section above
+        sample_on_check(monitor, monitored, history, check_interval_ms)
+    }
+    .map(q!(move |((history, members), started)| {
+        let now = CLOCK_NOW();
+        let empty_window = ArrivalWindow::new(window_size);
+        members
+            .into_iter()
+            .filter(|id| {
+                let (since, window) = match history.get(id) {
+                    Some((last, window)) => (*last, window),
+                    None => match started {
+                        Some(started) => (started, &empty_window),
+                        None => return false,
+                    },
+                };
+                window.phi(
+                    now.duration_since(since),
+                    first_heartbeat_estimate_ms as f64,
+                    min_std_dev_ms as f64,
+                ) > threshold as f64
+            })
+            .collect::<HashSet<_>>()
+    }))
+    .latest()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use hydro_lang::deploy::Simulation;
+    use hydro_lang::*;
+
+    use super::{FixedTimeoutConfig, PhiAccrualConfig};
+
+    struct Node {}
+    struct Monitor {}
+    struct External {}
+
+    /// Runs a detector on a cluster of two nodes, where node 1 stops sending heartbeats
+    /// after its first three, and returns every non-empty suspected set.
+    fn suspected_after_heartbeats_stop(phi_accrual: bool) -> Vec<HashSet<ClusterId<Node>>> {
+        let mut simulation = Simulation::new().with_max_steps(2000);
+
+        let flow = FlowBuilder::new();
+        let nodes = flow.cluster::<Node>();
+        let monitor = flow.process::<Monitor>();
+        let external = flow.external_process::<External>();
+
+        let heartbeats = unsafe { super::heartbeats(&nodes, 100) }
+            .enumerate()
+            .filter(q!(move |(i, _)| CLUSTER_SELF_ID.raw_id == 0 || *i < 3))
+            .map(q!(|_| ()))
+            .send_bincode(&monitor);
+
+        let suspected = if phi_accrual {
+            unsafe {
+                super::phi_accrual_detector(
+                    &monitor,
+                    &nodes,
+                    heartbeats,
+                    PhiAccrualConfig {
+                        check_interval_ms: 100,
+                        threshold: 8,
+                        window_size: 100,
+                        min_std_dev_ms: 50,
+                        first_heartbeat_estimate_ms: 100,
+                    },
+                )
+            }
+        } else {
+            unsafe {
+                super::fixed_timeout_detector(
+                    &monitor,
+                    &nodes,
+                    heartbeats,
+                    FixedTimeoutConfig {
+                        check_interval_ms: 100,
+                        timeout_ms: 1000,
+                    },
+                )
+            }
+        };
+
+        let tick = monitor.tick();
+        let out_port = unsafe { suspected.latest_tick(&tick) }
+            .filter(q!(|suspected| !suspected.is_empty()))
+            .all_ticks()
+            .send_bincode_external(&external);
+
+        let deployed = flow
+            .with_process(&monitor, ())
+            .with_cluster(&nodes, 2)
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let port = deployed.raw_port_bincode(&out_port);
+        simulation.run().bincode::<HashSet<ClusterId<Node>>>(&port)
+    }
+
+    #[test]
+    fn fixed_timeout_suspects_silent_member() {
+        let suspected = suspected_after_heartbeats_stop(false);
+        assert!(!suspected.is_empty());
+        assert!(
+            suspected
+                .iter()
+                .all(|s| *s == HashSet::from([ClusterId::from_raw(1)]))
+        );
+    }
+
+    #[test]
+    fn phi_accrual_suspects_silent_member() {
+        let suspected = suspected_after_heartbeats_stop(true);
+        assert!(!suspected.is_empty());
+        assert!(
+            suspected
+                .iter()
+                .all(|s| *s == HashSet::from([ClusterId::from_raw(1)]))
+        );
+    }
+}
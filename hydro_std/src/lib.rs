@@ -2,6 +2,8 @@ stageleft::stageleft_no_entry_crate!();
 
 pub mod bench_client;
 pub mod compartmentalize;
+pub mod consensus;
+pub mod failure_detector;
 pub mod quorum;
 pub mod request_response;
 
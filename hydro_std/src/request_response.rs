@@ -1,7 +1,9 @@
 use std::hash::Hash;
+use std::time::Duration;
 
 use hydro_lang::*;
 use location::NoTick;
+use location::tick::NoAtomic;
 
 type JoinResponses<K, M, V, L> = Stream<(K, (M, V)), Atomic<L>, Unbounded, NoOrder>;
 
@@ -40,3 +42,202 @@ pub fn join_responses<'a, K: Clone + Eq + Hash, M: Clone, V: Clone, L: Location<
 
     joined_this_tick.all_ticks_atomic()
 }
+
+/// Configuration for [`reliable_request_response`].
+#[derive(Clone, Copy)]
+pub struct RetryConfig {
+    /// How long to wait for a response before retrying a request, in milliseconds
+    pub timeout_ms: u64,
+    /// How many times a request is sent, including the first attempt, before it times out
+    pub max_attempts: usize,
+}
+
+/// The outputs of [`reliable_request_response`].
+pub struct RequestResults<M, Req, Resp, L> {
+    /// The response to each request that was answered in time, with its metadata
+    pub completed: Stream<(M, Resp), L, Unbounded, NoOrder>,
+    /// The requests that were not answered after `max_attempts` attempts, with their metadata
+    pub timed_out: Stream<(M, Req), L, Unbounded, NoOrder>,
+}
+
+/// Sends each request through `round_trip` and matches it with its response, retrying
+/// requests that are not answered within `config.timeout_ms`.
+///
+/// Each request is assigned an id that is unique at this location, and `round_trip` must
+/// respond to a request with the same id, typically by sending it to a server and back.
+/// The metadata of a request is stored in-memory until it either receives a response or
+/// runs out of attempts, at which point it is emitted in [`RequestResults::timed_out`], so
+/// state does not grow with the number of requests that have finished. Responses that
+/// arrive after that, such as responses to earlier attempts, are discarded.
+///
+/// Because requests may be retried, `round_trip` must tolerate duplicates, and any response
+/// to a request may be picked if there are several.
+///
+/// # Safety
+/// Whether a request times out depends on when its responses arrive relative to a clock,
+/// so a request may be retried or time out even if a response is on its way.
+pub unsafe fn reliable_request_response<'a, M, Req, Resp, L>(
+    location: &L,
+    requests: Stream<(M, Req), L, Unbounded, NoOrder>,
+    round_trip: impl FnOnce(
+        Stream<(u64, Req), L, Unbounded, NoOrder>,
+    ) -> Stream<(u64, Resp), L, Unbounded, NoOrder>,
+    config: RetryConfig,
+) -> RequestResults<M, Req, Resp, L>
+where
+    M: Clone,
+    Req: Clone,
+    Resp: Clone,
+    L: Location<'a> + NoTick + NoAtomic,
+{
+    let timeout_ms = config.timeout_ms;
+    let max_attempts = config.max_attempts;
+    let tick = location.tick();
+
+    let new_requests = unsafe {
+        // SAFETY: ids only need to be unique, so the order in which they are assigned and
+        // the batching of requests do not matter.
+        requests
+            .assume_ordering::<TotalOrder>()
+            .enumerate()
+            .tick_batch(&tick)
+    }
+    .map(q!(move |(id, (meta, req))| (
+        id as u64,
+        (meta, req, CLOCK_NOW(), 1)
+    )));
+
+    let (responses_complete_cycle, responses) =
+        location.forward_ref::<Stream<(u64, Resp), L, Unbounded, NoOrder>>();
+    let responses = unsafe {
+        // SAFETY: a response that is processed late may cause an extra retry or a timeout,
+        // which is covered by the safety section above.
+        responses.tick_batch(&tick)
+    }
+    // Responses to different attempts of a request are interchangeable, so keep any one.
+    .reduce_keyed_commutative(q!(|_, _| {}));
+
+    let (pending_complete_cycle, pending) = tick.cycle::<Stream<_, _, _, NoOrder>>();
+    let pending = pending.chain(new_requests.clone());
+
+    let completed = pending
+        .clone()
+        .join(responses.clone())
+        .map(q!(|(_, ((meta, _, _, _), resp))| (meta, resp)));
+
+    let waiting = pending.anti_join(responses.map(q!(|(id, _)| id)));
+
+    let timer = unsafe {
+        // SAFETY: the timer only determines when expired requests are retried
+        location
+            .source_interval(q!(Duration::from_millis(timeout_ms)))
+            .tick_batch(&tick)
+    };
+    let expired = waiting
+        .clone()
+        .filter(q!(move |(_, (_, _, sent_at, _))| {
+            CLOCK_NOW().duration_since(*sent_at) >= Duration::from_millis(timeout_ms)
+        }))
+        .continue_if(timer.first());
+
+    let retries = expired
+        .clone()
+        .filter(q!(move |(_, (_, _, _, attempts))| *attempts < max_attempts))
+        .map(q!(move |(id, (meta, req, _, attempts))| (
+            id,
+            (meta, req, CLOCK_NOW(), attempts + 1)
+        )));
+    let timed_out = expired
+        .clone()
+        .filter(q!(move |(_, (_, _, _, attempts))| *attempts >= max_attempts))
+        .map(q!(|(_, (meta, req, _, _))| (meta, req)));
+
+    pending_complete_cycle.complete_next_tick(
+        waiting
+            .anti_join(expired.map(q!(|(id, _)| id)))
+            .chain(retries.clone()),
+    );
+
+    let to_send = new_requests
+        .chain(retries)
+        .map(q!(|(id, (_, req, _, _))| (id, req)))
+        .all_ticks();
+    responses_complete_cycle.complete(round_trip(to_send));
+
+    RequestResults {
+        completed: completed.all_ticks(),
+        timed_out: timed_out.all_ticks(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hydro_lang::deploy::Simulation;
+    use hydro_lang::*;
+
+    use super::RetryConfig;
+
+    struct Client {}
+    struct Server {}
+    struct External {}
+
+    #[test]
+    fn reliable_request_response_retries_and_times_out() {
+        let mut simulation = Simulation::new().with_message_loss(2).with_max_steps(3000);
+
+        let flow = FlowBuilder::new();
+        let client = flow.process::<Client>();
+        let server = flow.process::<Server>();
+        let external = flow.external_process::<External>();
+
+        // The server never answers odd requests, so they time out.
+        let requests: Stream<_, _, Unbounded> =
+            client.source_iter(q!(0..6u32)).map(q!(|n| (n, n))).into();
+        let results = unsafe {
+            super::reliable_request_response(
+                &client,
+                requests.into(),
+                |requests| {
+                    requests
+                        .send_bincode(&server)
+                        .filter(q!(|(_, n)| n % 2 == 0))
+                        .map(q!(|(id, n)| (id, n * 10)))
+                        .send_bincode(&client)
+                },
+                RetryConfig {
+                    timeout_ms: 100,
+                    max_attempts: 5,
+                },
+            )
+        };
+        let completed_port = results.completed.send_bincode_external(&external);
+        let timed_out_port = results.timed_out.send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&client, ())
+            .with_process(&server, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let completed_port = nodes.raw_port_bincode(&completed_port);
+        let timed_out_port = nodes.raw_port_bincode(&timed_out_port);
+        simulation
+            .explore(0..10, |output| {
+                let mut completed = output.bincode::<(u32, u32)>(&completed_port);
+                completed.sort();
+                let mut timed_out = output.bincode::<(u32, u32)>(&timed_out_port);
+                timed_out.sort();
+                if completed == vec![(0, 0), (2, 20), (4, 40)]
+                    && timed_out == vec![(1, 1), (3, 3), (5, 5)]
+                {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "completed {:?}, timed out {:?}",
+                        completed, timed_out
+                    ))
+                }
+            })
+            .unwrap();
+    }
+}
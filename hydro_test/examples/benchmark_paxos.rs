@@ -15,8 +15,10 @@ async fn main() {
     use hydro_lang::Location;
     use hydro_optimize::deploy::ReusableHosts;
     use hydro_optimize::deploy_and_analyze::deploy_and_analyze;
+    use hydro_std::consensus::paxos::{
+        Acceptor, CorePaxos, InMemoryStorage, PaxosConfig, Proposer,
+    };
     use hydro_test::cluster::kv_replica::Replica;
-    use hydro_test::cluster::paxos::{Acceptor, CorePaxos, PaxosConfig, Proposer};
     use hydro_test::cluster::paxos_bench::{Aggregator, Client};
     use tokio::sync::RwLock;
 
@@ -83,6 +85,7 @@ async fn main() {
                         i_am_leader_check_timeout,
                         i_am_leader_check_timeout_delay_multiplier,
                     },
+                    acceptor_storage: InMemoryStorage,
                 },
                 &clients,
                 &client_aggregator,
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use hydro_deploy::gcp::GcpNetwork;
 use hydro_deploy::{Deployment, Host};
 use hydro_lang::deploy::TrybuildHost;
-use hydro_test::cluster::paxos::{CorePaxos, PaxosConfig};
+use hydro_std::consensus::paxos::{CorePaxos, InMemoryStorage, PaxosConfig};
 use tokio::sync::RwLock;
 
 type HostCreator = Box<dyn Fn(&mut Deployment) -> Arc<dyn Host>>;
@@ -61,6 +61,7 @@ async fn main() {
                 i_am_leader_check_timeout,
                 i_am_leader_check_timeout_delay_multiplier,
             },
+            acceptor_storage: InMemoryStorage,
         },
         &clients,
         &client_aggregator,
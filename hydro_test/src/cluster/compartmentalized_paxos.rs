@@ -1,16 +1,15 @@
 use std::collections::HashMap;
 
 use hydro_lang::*;
+use hydro_std::consensus::PaxosLike;
+use hydro_std::consensus::paxos::{
+    Acceptor, Ballot, InMemoryStorage, LogValue, P2a, PaxosConfig, PaxosPayload, Proposer,
+    acceptor_p2, index_payloads, leader_election, recommit_after_leader_election,
+};
 use hydro_std::quorum::collect_quorum;
 use hydro_std::request_response::join_responses;
 use serde::{Deserialize, Serialize};
 
-use super::paxos::{
-    Acceptor, Ballot, LogValue, P2a, PaxosConfig, PaxosPayload, Proposer, acceptor_p2,
-    index_payloads, leader_election, recommit_after_leader_election,
-};
-use super::paxos_with_client::PaxosLike;
-
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ProxyLeader {}
 
@@ -48,6 +47,10 @@ impl<'a> PaxosLike<'a> for CoreCompartmentalizedPaxos<'a> {
         &self.acceptors
     }
 
+    fn output_recipients(&self) -> &Cluster<'a, Self::PaxosOut> {
+        &self.proxy_leaders
+    }
+
     fn get_recipient_from_ballot<L: Location<'a>>(
         ballot: Optional<Self::Ballot, L, Unbounded>,
     ) -> Optional<ClusterId<Self::PaxosIn>, L, Unbounded> {
@@ -129,6 +132,7 @@ pub unsafe fn compartmentalized_paxos_core<'a, P: PaxosPayload>(
     let (a_log_complete_cycle, a_log_forward_reference) =
         acceptor_tick.forward_ref::<Singleton<_, _, _>>();
 
+    let acceptor_ids = acceptors.members();
     let (p_ballot, p_is_leader, p_relevant_p1bs, a_max_ballot) = unsafe {
         // SAFETY: The primary non-determinism exposed by leader election algorithm lies in which leader
         // is elected, which affects both the ballot at each proposer and the leader flag. But using a stale ballot
@@ -143,6 +147,7 @@ pub unsafe fn compartmentalized_paxos_core<'a, P: PaxosPayload>(
             config.acceptor_grid_rows,
             config.acceptor_grid_rows * config.acceptor_grid_cols,
             config.paxos_config,
+            proposer_tick.singleton(q!(acceptor_ids.to_vec())),
             sequencing_max_ballot_forward_reference,
             a_log_forward_reference,
         )
@@ -290,6 +295,7 @@ unsafe fn sequence_payload<'a, P: PaxosPayload>(
         pl_to_acceptors_p2a_thrifty,
         a_checkpoint,
         proxy_leaders,
+        &InMemoryStorage,
     );
 
     // TODO: This is a liveness problem if any node in the thrifty quorum fails
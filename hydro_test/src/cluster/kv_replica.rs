@@ -39,7 +39,25 @@ impl<K: KvKey, V: KvValue> PartialOrd for SequencedKv<K, V> {
     }
 }
 
+/// The state of a replica after applying every slot before `next_slot`, which is sent to
+/// replicas that have fallen too far behind to replay the log.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "")]
+pub struct KvSnapshot<K: KvKey, V: KvValue> {
+    pub kv_store: HashMap<K, V>,
+    pub next_slot: usize,
+}
+
+/// An input to a replica's state machine: either the next sequenced payload or a snapshot to skip ahead to.
+#[derive(Clone, Debug)]
+pub enum PayloadOrSnapshot<K: KvKey, V: KvValue> {
+    Payload(SequencedKv<K, V>),
+    Snapshot(KvSnapshot<K, V>),
+}
+
 // Replicas. All relations for replicas will be prefixed with r. Expects ReplicaPayload on p_to_replicas, outputs a stream of (client address, ReplicaPayload) after processing.
+// A replica that learns that a peer has checkpointed at least `checkpoint_frequency` slots past its own progress
+// requests a snapshot from that peer and skips ahead, without outputting the payloads covered by the snapshot.
 #[expect(clippy::type_complexity, reason = "internal paxos code // TODO")]
 pub fn kv_replica<'a, K: KvKey, V: KvValue>(
     replicas: &Cluster<'a, Replica>,
@@ -58,6 +76,9 @@ pub fn kv_replica<'a, K: KvKey, V: KvValue>(
 
     let replica_tick = replicas.tick();
 
+    // Create a cycle since we'll use this seq before we define it
+    let (r_next_slot_complete_cycle, r_next_slot) =
+        replica_tick.cycle_with_initial(replica_tick.singleton(q!(0)));
     let (r_buffered_payloads_complete_cycle, r_buffered_payloads) = replica_tick.cycle();
     // p_to_replicas.inspect(q!(|payload: ReplicaPayload| println!("Replica received payload: {:?}", payload)));
     let r_sorted_payloads = unsafe {
@@ -66,10 +87,14 @@ pub fn kv_replica<'a, K: KvKey, V: KvValue>(
         p_to_replicas.tick_batch(&replica_tick)
     }
         .chain(r_buffered_payloads) // Combine with all payloads that we've received and not processed yet
+        .cross_singleton(r_next_slot.clone())
+        // Drop payloads that were already processed or are covered by an installed snapshot
+        .filter_map(q!(|(payload, next_slot)| if payload.seq >= next_slot {
+            Some(payload)
+        } else {
+            None
+        }))
         .sort();
-    // Create a cycle since we'll use this seq before we define it
-    let (r_next_slot_complete_cycle, r_next_slot) =
-        replica_tick.cycle_with_initial(replica_tick.singleton(q!(0)));
     // Find highest the sequence number of any payload that can be processed in this tick. This is the payload right before a hole.
     let r_next_slot_after_processing_payloads = r_sorted_payloads
         .clone()
@@ -100,25 +125,50 @@ pub fn kv_replica<'a, K: KvKey, V: KvValue>(
     // Save these, we can process them once the hole has been filled
     r_buffered_payloads_complete_cycle.complete_next_tick(r_new_non_processable_payloads);
 
+    // Snapshots from peers, of which we install the most recent one
+    let (r_snapshots_complete_cycle, r_snapshots) =
+        replicas.forward_ref::<Stream<KvSnapshot<K, V>, _, _, NoOrder>>();
+    let r_snapshot = unsafe {
+        // SAFETY: a snapshot is only installed if it is ahead of the replica, so the tick in
+        // which it is installed does not affect the final state of the store
+        r_snapshots.tick_batch(&replica_tick)
+    }
+    .max_by_key(q!(|snapshot| snapshot.next_slot));
+
     let r_kv_store = r_processable_payloads
         .clone()
+        .map(q!(|payload| PayloadOrSnapshot::Payload(payload)))
+        .chain(r_snapshot.map(q!(|snapshot| PayloadOrSnapshot::Snapshot(snapshot))).into_stream())
         .persist() // Optimization: all_ticks() + fold() = fold<static>, where the state of the previous fold is saved and persisted values are deleted.
-        .fold(q!(|| (HashMap::new(), 0)), q!(|(kv_store, next_slot), payload| {
-            if let Some(kv) = payload.kv {
-                kv_store.insert(kv.key, kv.value);
+        .fold(q!(|| (HashMap::new(), 0)), q!(|(kv_store, next_slot), update| {
+            match update {
+                PayloadOrSnapshot::Payload(payload) => {
+                    if let Some(kv) = payload.kv {
+                        kv_store.insert(kv.key, kv.value);
+                    }
+                    *next_slot = payload.seq + 1;
+                }
+                PayloadOrSnapshot::Snapshot(snapshot) => {
+                    if snapshot.next_slot > *next_slot {
+                        *kv_store = snapshot.kv_store;
+                        *next_slot = snapshot.next_slot;
+                    }
+                }
             }
-            *next_slot = payload.seq + 1;
         }));
     // Update the highest seq for the next tick
-    r_next_slot_complete_cycle
-        .complete_next_tick(r_kv_store.map(q!(|(_kv_store, next_slot)| next_slot)));
+    r_next_slot_complete_cycle.complete_next_tick(
+        r_kv_store
+            .clone()
+            .map(q!(|(_kv_store, next_slot)| next_slot)),
+    );
 
     // Send checkpoints to the acceptors when we've processed enough payloads
     let (r_checkpointed_seqs_complete_cycle, r_checkpointed_seqs) =
         replica_tick.cycle::<Optional<usize, _, _>>();
     let r_max_checkpointed_seq = r_checkpointed_seqs.persist().max().into_singleton();
     let r_checkpoint_seq_new = r_max_checkpointed_seq
-        .zip(r_next_slot)
+        .zip(r_next_slot.clone())
         .filter_map(q!(
             move |(max_checkpointed_seq, next_slot)| if max_checkpointed_seq
                 .map(|m| next_slot - m >= checkpoint_frequency)
@@ -131,9 +181,101 @@ pub fn kv_replica<'a, K: KvKey, V: KvValue>(
         ));
     r_checkpointed_seqs_complete_cycle.complete_next_tick(r_checkpoint_seq_new.clone());
 
+    // Share checkpoints with peers, so that replicas which fall too far behind request a snapshot
+    let r_snapshot_requests = unsafe {
+        // SAFETY: comparing a peer's checkpoint against our progress at an arbitrary tick only
+        // affects whether we request a snapshot, which is ignored if we have caught up by then
+        r_checkpoint_seq_new
+            .clone()
+            .all_ticks()
+            .broadcast_bincode(replicas)
+            .tick_batch(&replica_tick)
+    }
+    .cross_singleton(r_next_slot.clone())
+    .filter(q!(
+        move |((_peer, checkpoint), next_slot)| *checkpoint >= next_slot + checkpoint_frequency
+    ))
+    .max_by_key(q!(|((_peer, checkpoint), _)| *checkpoint))
+    .map(q!(|((peer, _checkpoint), _)| (peer, ())))
+    .all_ticks()
+    .send_bincode(replicas);
+
+    let r_snapshot_responses = unsafe {
+        // SAFETY: the snapshot is taken at an arbitrary tick, but any snapshot that is ahead of
+        // the requester lets it catch up
+        r_snapshot_requests.tick_batch(&replica_tick)
+    }
+    .cross_singleton(r_kv_store)
+    .map(q!(|((requester, ()), (kv_store, next_slot))| (
+        requester,
+        KvSnapshot {
+            kv_store,
+            next_slot
+        }
+    )))
+    .all_ticks()
+    .send_bincode_anonymous(replicas);
+    r_snapshots_complete_cycle.complete(r_snapshot_responses);
+
     // Tell clients that the payload has been committed. All ReplicaPayloads contain the client's machine ID (to string) as value.
     let r_to_clients = r_processable_payloads
         .filter_map(q!(|payload| payload.kv))
         .all_ticks();
     (r_checkpoint_seq_new.all_ticks(), r_to_clients)
 }
+
+#[cfg(test)]
+mod tests {
+    use hydro_lang::deploy::Simulation;
+    use hydro_lang::*;
+
+    use super::{KvPayload, Replica, kv_replica};
+
+    struct Leader {}
+    struct External {}
+
+    #[test]
+    fn lagging_replica_catches_up_from_snapshot() {
+        let mut simulation = Simulation::new().with_max_steps(2000);
+
+        let flow = FlowBuilder::new();
+        let replicas = flow.cluster::<Replica>();
+        let leader = flow.process::<Leader>();
+        let external = flow.external_process::<External>();
+
+        // Replica 1 never receives the first five slots, so it can only make progress by
+        // installing a snapshot from replica 0.
+        let committed: Stream<_, _, Unbounded> = replicas
+            .source_iter(q!((0..10usize).map(|slot| (
+                slot,
+                Some(KvPayload {
+                    key: slot as u32 % 3,
+                    value: slot as u32
+                })
+            ))))
+            .filter(q!(move |(slot, _)| CLUSTER_SELF_ID.raw_id == 0 || *slot >= 5))
+            .into();
+        let (checkpoints, _) = kv_replica(&replicas, committed, 2);
+        let out_port = checkpoints
+            .send_bincode(&leader)
+            .send_bincode_external(&external);
+
+        let nodes = flow
+            .with_process(&leader, ())
+            .with_cluster(&replicas, 2)
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+
+        let port = nodes.raw_port_bincode(&out_port);
+        let output = simulation.run();
+        let checkpoints = output.bincode::<(ClusterId<Replica>, usize)>(&port);
+        for replica in 0..2 {
+            let latest = checkpoints
+                .iter()
+                .filter(|(id, _)| id.raw_id == replica)
+                .map(|(_, slot)| *slot)
+                .max();
+            assert_eq!(latest, Some(10), "checkpoints: {:?}", checkpoints);
+        }
+    }
+}
@@ -1,9 +1,9 @@
 use hydro_lang::*;
 use hydro_std::bench_client::{bench_client, print_bench_results};
+use hydro_std::consensus::{PaxosLike, stable_checkpoint};
 use hydro_std::quorum::collect_quorum;
 
 use super::kv_replica::{KvPayload, Replica, kv_replica};
-use super::paxos_with_client::PaxosLike;
 
 pub struct Client;
 pub struct Aggregator;
@@ -44,41 +44,13 @@ pub fn paxos_bench<'a>(
         let (replica_checkpoint, processed_payloads) =
             kv_replica(replicas, sequenced_to_replicas, checkpoint_frequency);
 
-        // Get the latest checkpoint sequence per replica
-        let checkpoint_tick = acceptors.tick();
-        let a_checkpoint = unsafe {
-            // SAFETY: even though we batch the checkpoint messages, because we reduce over the entire history,
-            // the final min checkpoint is deterministic
-            // TODO(shadaj): once we can reduce keyed over unbounded streams, this should be safe
-
-            let a_checkpoint_largest_seqs = replica_checkpoint
-                .broadcast_bincode(&acceptors)
-                .tick_batch(&checkpoint_tick)
-                .persist()
-                .reduce_keyed_commutative(q!(|curr_seq, seq| {
-                    if seq > *curr_seq {
-                        *curr_seq = seq;
-                    }
-                }));
-
-            let a_checkpoints_quorum_reached = a_checkpoint_largest_seqs
-                .clone()
-                .count()
-                .filter_map(q!(move |num_received| if num_received == f + 1 {
-                    Some(true)
-                } else {
-                    None
-                }));
-
-            // Find the smallest checkpoint seq that everyone agrees to
-            a_checkpoint_largest_seqs
-                .continue_if(a_checkpoints_quorum_reached)
-                .map(q!(|(_sender, seq)| seq))
-                .min()
-                .latest()
-        };
-
-        acceptor_checkpoint_complete.complete(a_checkpoint);
+        // Acceptors truncate their logs below the slot that a quorum of replicas has checkpointed,
+        // and replicas that fall behind it catch up from the snapshot of a peer
+        acceptor_checkpoint_complete.complete(stable_checkpoint(
+            &acceptors,
+            replica_checkpoint,
+            f + 1,
+        ));
 
         let c_received_payloads = processed_payloads
             .map(q!(|payload| (
@@ -104,21 +76,30 @@ pub fn paxos_bench<'a>(
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use dfir_lang::graph::WriteConfig;
     use hydro_deploy::Deployment;
-    use hydro_lang::deploy::{DeployCrateWrapper, HydroDeploy, TrybuildHost};
+    use hydro_lang::deploy::{DeployCrateWrapper, HydroDeploy, Simulation, TrybuildHost};
+    use hydro_lang::*;
+    use hydro_std::consensus::paxos::{
+        Acceptor, CorePaxos, InMemoryStorage, PaxosConfig, Proposer,
+    };
+    use hydro_std::consensus::{PaxosLike, stable_checkpoint};
+    use regex::Regex;
+    use tokio::sync::mpsc::UnboundedReceiver;
 
-    use crate::cluster::paxos::{CorePaxos, PaxosConfig};
+    use crate::cluster::kv_replica::{KvPayload, Replica, kv_replica};
 
     const PAXOS_F: usize = 1;
 
     #[cfg(stageleft_runtime)]
     fn create_paxos<'a>(
-        proposers: &hydro_lang::Cluster<'a, crate::cluster::paxos::Proposer>,
-        acceptors: &hydro_lang::Cluster<'a, crate::cluster::paxos::Acceptor>,
-        clients: &hydro_lang::Cluster<'a, super::Client>,
-        client_aggregator: &hydro_lang::Process<'a, super::Aggregator>,
-        replicas: &hydro_lang::Cluster<'a, crate::cluster::kv_replica::Replica>,
+        proposers: &Cluster<'a, Proposer>,
+        acceptors: &Cluster<'a, Acceptor>,
+        clients: &Cluster<'a, super::Client>,
+        client_aggregator: &Process<'a, super::Aggregator>,
+        replicas: &Cluster<'a, Replica>,
     ) {
         super::paxos_bench(
             100,
@@ -134,6 +115,7 @@ mod tests {
                     i_am_leader_check_timeout: 10,
                     i_am_leader_check_timeout_delay_multiplier: 15,
                 },
+                acceptor_storage: InMemoryStorage,
             },
             clients,
             client_aggregator,
@@ -143,7 +125,7 @@ mod tests {
 
     #[test]
     fn paxos_ir() {
-        let builder = hydro_lang::FlowBuilder::new();
+        let builder = FlowBuilder::new();
         let proposers = builder.cluster();
         let acceptors = builder.cluster();
         let clients = builder.cluster();
@@ -159,7 +141,7 @@ mod tests {
         );
         let built = builder.with_default_optimize::<HydroDeploy>();
 
-        hydro_lang::ir::dbg_dedup_tee(|| {
+        ir::dbg_dedup_tee(|| {
             insta::assert_debug_snapshot!(built.ir());
         });
 
@@ -188,9 +170,114 @@ mod tests {
         });
     }
 
+    #[test]
+    fn paxos_sim_replicas_agree() {
+        let mut simulation = Simulation::new().with_max_steps(5000);
+
+        let builder = FlowBuilder::new();
+        let proposers = builder.cluster::<Proposer>();
+        let acceptors = builder.cluster::<Acceptor>();
+        let clients = builder.cluster::<super::Client>();
+        let replicas = builder.cluster::<Replica>();
+        let aggregator = builder.process::<super::Aggregator>();
+        let external = builder.external_process::<()>();
+
+        let paxos = CorePaxos {
+            proposers: proposers.clone(),
+            acceptors: acceptors.clone(),
+            paxos_config: PaxosConfig {
+                f: PAXOS_F,
+                i_am_leader_send_timeout: 5,
+                i_am_leader_check_timeout: 10,
+                i_am_leader_check_timeout_delay_multiplier: 15,
+            },
+            acceptor_storage: InMemoryStorage,
+        };
+
+        let payloads = clients
+            .source_iter(q!((0..4u32).map(|i| KvPayload { key: i, value: i })))
+            .into();
+        let (acceptor_checkpoint_complete, acceptor_checkpoint) =
+            acceptors.forward_ref::<Optional<_, _, _>>();
+        let sequenced = unsafe { paxos.with_client(&clients, payloads, acceptor_checkpoint) };
+        let (replica_checkpoint, processed) =
+            kv_replica(&replicas, sequenced.broadcast_bincode_anonymous(&replicas), 2);
+        acceptor_checkpoint_complete.complete(stable_checkpoint(
+            &acceptors,
+            replica_checkpoint,
+            PAXOS_F + 1,
+        ));
+        let out_port = processed
+            .send_bincode(&aggregator)
+            .send_bincode_external(&external);
+
+        let nodes = builder
+            .with_cluster(&proposers, PAXOS_F + 1)
+            .with_cluster(&acceptors, 2 * PAXOS_F + 1)
+            .with_cluster(&clients, 1)
+            .with_cluster(&replicas, PAXOS_F + 1)
+            .with_process(&aggregator, ())
+            .with_external(&external, ())
+            .deploy(&mut simulation);
+        assert!(!simulation.choice_points().is_empty());
+
+        let port = nodes.raw_port_bincode(&out_port);
+        let replica_logs = |output: &sim_runtime::SimOutput| {
+            let processed = output.bincode::<(ClusterId<Replica>, KvPayload<u32, u32>)>(&port);
+            (0..(PAXOS_F + 1) as u32)
+                .map(|replica| {
+                    processed
+                        .iter()
+                        .filter(|(id, _)| id.raw_id == replica)
+                        .map(|(_, payload)| payload.clone())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let canonical = replica_logs(&simulation.run_schedule(&[]));
+        assert!(canonical.iter().all(|log| log.len() == 4), "{:?}", canonical);
+
+        // Every batching and interleaving must leave the replicas with consistent logs, where
+        // the shorter log is a prefix of the longer one.
+        simulation
+            .explore(0..10, |output| {
+                let logs = replica_logs(output);
+                let shortest = logs.iter().map(|l| l.len()).min().unwrap_or(0);
+                for log in &logs {
+                    if log[..shortest] != logs[0][..shortest] {
+                        return Err(format!("replica logs diverged: {:?}", logs));
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    /// Waits until the client aggregator has reported a positive throughput `count` times.
+    async fn wait_for_throughput(client_out: &mut UnboundedReceiver<String>, count: usize) {
+        let re = Regex::new(r"Throughput: ([^ ]+) - ([^ ]+) - ([^ ]+) requests/s").unwrap();
+        let mut found = 0;
+        while let Some(line) = client_out.recv().await {
+            if let Some(caps) = re.captures(&line) {
+                if let Ok(lower) = f64::from_str(&caps[1]) {
+                    if lower > 0.0 {
+                        println!("Found throughput lower-bound: {}", lower);
+                        found += 1;
+                        if found == count {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+
+        panic!("client aggregator exited before reporting throughput");
+    }
+
     #[tokio::test]
     async fn paxos_some_throughput() {
-        let builder = hydro_lang::FlowBuilder::new();
+        let builder = FlowBuilder::new();
         let proposers = builder.cluster();
         let acceptors = builder.cluster();
         let clients = builder.cluster();
@@ -229,29 +316,89 @@ mod tests {
         deployment.deploy().await.unwrap();
 
         let client_node = &nodes.get_process(&client_aggregator);
-        let client_out = client_node.stdout_filter("Throughput:").await;
+        let mut client_out = client_node.stdout_filter("Throughput:").await;
 
         deployment.start().await.unwrap();
 
-        use std::str::FromStr;
+        wait_for_throughput(&mut client_out, 2).await;
+    }
 
-        use regex::Regex;
+    #[tokio::test]
+    async fn paxos_replaces_removed_acceptor() {
+        let builder = FlowBuilder::new();
+        let proposers = builder.cluster();
+        let acceptors = builder.cluster();
+        let clients = builder.cluster();
+        let client_aggregator = builder.process();
+        let replicas = builder.cluster();
 
-        let re = Regex::new(r"Throughput: ([^ ]+) - ([^ ]+) - ([^ ]+) requests/s").unwrap();
-        let mut found = 0;
-        let mut client_out = client_out;
-        while let Some(line) = client_out.recv().await {
-            if let Some(caps) = re.captures(&line) {
-                if let Ok(lower) = f64::from_str(&caps[1]) {
-                    if lower > 0.0 {
-                        println!("Found throughput lower-bound: {}", lower);
-                        found += 1;
-                        if found == 2 {
-                            break;
-                        }
-                    }
-                }
-            }
-        }
+        create_paxos(
+            &proposers,
+            &acceptors,
+            &clients,
+            &client_aggregator,
+            &replicas,
+        );
+        let mut deployment = Deployment::new();
+
+        // the last acceptor is a spare that does not store the log initially
+        let nodes = builder
+            .with_cluster(
+                &proposers,
+                (0..PAXOS_F + 1).map(|_| TrybuildHost::new(deployment.Localhost())),
+            )
+            .with_cluster(
+                &acceptors,
+                (0..2 * PAXOS_F + 2).map(|_| TrybuildHost::new(deployment.Localhost())),
+            )
+            .with_cluster(&clients, vec![TrybuildHost::new(deployment.Localhost())])
+            .with_process(
+                &client_aggregator,
+                TrybuildHost::new(deployment.Localhost()),
+            )
+            .with_cluster(
+                &replicas,
+                (0..PAXOS_F + 1).map(|_| TrybuildHost::new(deployment.Localhost())),
+            )
+            .deploy(&mut deployment);
+
+        deployment.deploy().await.unwrap();
+
+        let client_node = &nodes.get_process(&client_aggregator);
+        let mut client_out = client_node.stdout_filter("Throughput:").await;
+        let proposer_outs = futures::future::join_all(
+            nodes
+                .get_cluster(&proposers)
+                .members()
+                .iter()
+                .map(|node| node.stdout_filter("Proposer reconfigured acceptors:")),
+        )
+        .await;
+
+        deployment.start().await.unwrap();
+
+        wait_for_throughput(&mut client_out, 1).await;
+
+        // replace the last acceptor that stores the log with the spare
+        let replaced = 2 * PAXOS_F;
+        nodes
+            .remove_cluster_member(&acceptors, ClusterId::from_raw(replaced as u32))
+            .await;
+
+        let (reconfigured, _, _) = futures::future::select_all(
+            proposer_outs
+                .into_iter()
+                .map(|mut out| Box::pin(async move { out.recv().await })),
+        )
+        .await;
+        let reconfigured = reconfigured.unwrap();
+        assert!(
+            reconfigured.contains(&format!("({})", 2 * PAXOS_F + 1)),
+            "spare acceptor did not replace the removed one: {}",
+            reconfigured
+        );
+
+        // the spare stores the log in place of the stopped acceptor, so sequencing continues
+        wait_for_throughput(&mut client_out, 2).await;
     }
 }